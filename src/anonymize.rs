@@ -0,0 +1,65 @@
+//! Stable pseudonyms for interface names in shared output.
+
+use indexmap::IndexMap;
+
+/// Replaces interface names with pseudonyms (`iface1`, `iface2`, ...)
+///
+/// Each interface is assigned the next pseudonym the first time it is
+/// seen, so a name maps to the same pseudonym for the rest of the run.
+/// When disabled, names are passed through unchanged.
+pub struct Pseudonyms {
+    enabled: bool,
+    names: IndexMap<String, String>,
+}
+
+impl Pseudonyms {
+    pub fn new(enabled: bool) -> Pseudonyms {
+        Pseudonyms {
+            enabled,
+            names: IndexMap::new(),
+        }
+    }
+
+    /// Return the name to display for an interface
+    pub fn name(&mut self, iface: &str) -> String {
+        if !self.enabled {
+            return iface.to_string();
+        }
+        let next = format!("iface{}", self.names.len() + 1);
+        self.names.entry(iface.to_string()).or_insert(next).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_interface_same_pseudonym() {
+        let mut names = Pseudonyms::new(true);
+        assert_eq!(names.name("wlan0"), "iface1");
+        assert_eq!(names.name("eth0"), "iface2");
+        assert_eq!(names.name("wlan0"), "iface1");
+        assert_eq!(names.name("eth0"), "iface2");
+        assert_eq!(names.name("wwan0"), "iface3");
+    }
+
+    #[test]
+    fn real_names_never_appear() {
+        let mut names = Pseudonyms::new(true);
+        let ifaces = ["wlan0", "eth0", "ppp0", "iface2", "iface1"];
+        let shown: Vec<String> = ifaces.iter().map(|iface| names.name(iface)).collect();
+        assert_eq!(shown, ["iface1", "iface2", "iface3", "iface4", "iface5"]);
+        for (iface, shown) in ifaces.iter().zip(&shown) {
+            // a real name which looks like a pseudonym is renamed all the same
+            assert_ne!(iface, shown);
+        }
+    }
+
+    #[test]
+    fn disabled_passes_names_through() {
+        let mut names = Pseudonyms::new(false);
+        assert_eq!(names.name("wlan0"), "wlan0");
+        assert_eq!(names.name("eth0"), "eth0");
+    }
+}
@@ -0,0 +1,141 @@
+//! Archive of the totals of completed billing cycles.
+//!
+//! When the totals are reset at the end of a cycle, their final values are
+//! recorded in `net/cycles`, keyed by the local date the cycle ended (e.g.
+//! `"2020-09-14": { "rx": 1024, "tx": 512, "time": 1600117392 }`), so that
+//! usage can be compared from one cycle to the next. Only a limited number
+//! of cycles are kept, the oldest being pruned first.
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use indexmap::IndexMap;
+use nest::{Store, Value};
+
+use crate::error::NestError;
+use crate::keys::{CYCLES, NET};
+
+/// Format of the date keys of archived cycles
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Final totals (bytes) of a completed cycle
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Archived {
+    pub rx: u64,
+    pub tx: u64,
+    pub time: u64, // unix seconds of the reset which ended the cycle
+}
+
+impl Archived {
+    fn from_value(value: &Value) -> Archived {
+        let entry = value.as_object();
+        let field = |key| match entry.and_then(|cycle| cycle.get(key)) {
+            Some(Value::Uint(val)) => *val,
+            _ => 0,
+        };
+        Archived {
+            rx: field("rx"),
+            tx: field("tx"),
+            time: field("time"),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        let mut cycle = IndexMap::new();
+        cycle.insert("rx".to_string(), Value::Uint(self.rx));
+        cycle.insert("tx".to_string(), Value::Uint(self.tx));
+        cycle.insert("time".to_string(), Value::Uint(self.time));
+        Value::Object(cycle)
+    }
+}
+
+/// Archived cycles, ordered by the date they ended
+pub type Cycles = BTreeMap<NaiveDate, Archived>;
+
+/// Retrieve the archived cycles from the data store
+///
+/// Entries whose keys are not valid dates are skipped.
+pub fn load(store: &Store) -> Cycles {
+    match store.get(&[NET, CYCLES]) {
+        Ok(Value::Object(cycles)) => cycles
+            .iter()
+            .filter_map(|(date, value)| {
+                let date = NaiveDate::parse_from_str(date, DATE_FORMAT).ok()?;
+                Some((date, Archived::from_value(value)))
+            })
+            .collect(),
+        _ => Cycles::new(),
+    }
+}
+
+/// Archive the final totals of a cycle which ended on a date, keeping the latest `keep` cycles
+///
+/// A second reset on the same date adds its totals to those already archived
+/// for it, so that the usage between the two resets is not lost.
+pub fn record(
+    store: &Store,
+    end: NaiveDate,
+    totals: &Archived,
+    keep: usize,
+) -> Result<(), NestError> {
+    let mut cycles = load(store);
+    let cycle = cycles.entry(end).or_default();
+    cycle.rx += totals.rx;
+    cycle.tx += totals.tx;
+    cycle.time = totals.time;
+
+    let excess = cycles.len().saturating_sub(keep);
+    let kept: IndexMap<String, Value> = cycles
+        .iter()
+        .skip(excess)
+        .map(|(date, cycle)| (date.format(DATE_FORMAT).to_string(), cycle.to_value()))
+        .collect();
+    store.set(&[NET, CYCLES], &Value::Object(kept))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::scratch_dir;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    fn archived(rx: u64, tx: u64, time: u64) -> Archived {
+        Archived { rx, tx, time }
+    }
+
+    #[test]
+    fn oldest_cycles_are_pruned() {
+        let store = Store::new(scratch_dir("archive"), crate::cli::store_schema().unwrap());
+        for day in 1..=4 {
+            record(&store, date(day), &archived(u64::from(day), 0, 0), 3).unwrap();
+        }
+
+        let kept: Vec<_> = load(&store).into_iter().collect();
+        assert_eq!(
+            kept,
+            [
+                (date(2), archived(2, 0, 0)),
+                (date(3), archived(3, 0, 0)),
+                (date(4), archived(4, 0, 0))
+            ]
+        );
+    }
+
+    #[test]
+    fn second_reset_of_a_day_adds_to_its_cycle() {
+        let store = Store::new(
+            scratch_dir("archive-twice"),
+            crate::cli::store_schema().unwrap(),
+        );
+        record(&store, date(14), &archived(5000, 700, 100), 24).unwrap();
+        record(&store, date(14), &archived(300, 40, 200), 24).unwrap();
+
+        assert_eq!(load(&store)[&date(14)], archived(5300, 740, 200));
+    }
+}
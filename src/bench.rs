@@ -0,0 +1,107 @@
+//! Timing of the stages of a poll cycle.
+
+use std::time::{Duration, Instant};
+
+/// Durations recorded for a single stage of a poll cycle
+pub struct Timings {
+    name: &'static str,
+    samples: Vec<Duration>,
+}
+
+impl Timings {
+    pub fn new(name: &'static str) -> Timings {
+        Timings {
+            name,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Run an operation, recording how long it took
+    pub fn time<T, F: FnOnce() -> T>(&mut self, op: F) -> T {
+        let start = Instant::now();
+        let val = op();
+        self.samples.push(start.elapsed());
+        val
+    }
+
+    /// Number of durations recorded
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn min(&self) -> Duration {
+        self.samples.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+
+    pub fn avg(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::default();
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    /// Format the minimum, average and maximum durations as a table row
+    pub fn row(&self) -> String {
+        format!(
+            "{:<8} {:>12} {:>12} {:>12}",
+            self.name,
+            format!("{:.1?}", self.min()),
+            format!("{:.1?}", self.avg()),
+            format!("{:.1?}", self.max())
+        )
+    }
+}
+
+/// Header of the table formed by `Timings::row`
+pub fn header() -> String {
+    format!("{:<8} {:>12} {:>12} {:>12}", "Stage", "Min", "Avg", "Max")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timings(millis: &[u64]) -> Timings {
+        Timings {
+            name: "totals",
+            samples: millis.iter().copied().map(Duration::from_millis).collect(),
+        }
+    }
+
+    #[test]
+    fn statistics_of_the_durations() {
+        let timings = timings(&[30, 10, 20]);
+        assert_eq!(timings.count(), 3);
+        assert_eq!(timings.min(), Duration::from_millis(10));
+        assert_eq!(timings.avg(), Duration::from_millis(20));
+        assert_eq!(timings.max(), Duration::from_millis(30));
+    }
+
+    #[test]
+    fn no_durations() {
+        let timings = timings(&[]);
+        assert_eq!(timings.avg(), Duration::default());
+        assert_eq!(timings.max(), Duration::default());
+    }
+
+    #[test]
+    fn time_records_each_run() {
+        let mut timings = Timings::new("read");
+        assert_eq!(timings.time(|| 1 + 1), 2);
+        timings.time(|| std::thread::sleep(Duration::from_millis(5)));
+        assert_eq!(timings.count(), 2);
+        assert!(timings.max() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn rows_line_up_with_the_header() {
+        let row = timings(&[1, 2, 3]).row();
+        assert_eq!(row.len(), header().len());
+        assert!(row.starts_with("totals "));
+        assert!(row.ends_with("3.0ms"));
+    }
+}
@@ -0,0 +1,89 @@
+//! Named checkpoints of the usage totals.
+//!
+//! Checkpoints are stored in `net/checkpoint`, keyed by name, and are only
+//! read back to report usage since they were taken, so they never affect
+//! the totals themselves.
+
+use indexmap::IndexMap;
+use nest::{Store, Value};
+
+use crate::error::NestError;
+use crate::keys::{CHECKPOINT, NET};
+
+/// Usage totals (bytes) at the time a checkpoint was taken
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub rx: u64,
+    pub tx: u64,
+    pub time: u64, // unix seconds
+}
+
+impl Checkpoint {
+    /// Retrieve a checkpoint from the data store
+    pub fn load(store: &Store, name: &str) -> Option<Checkpoint> {
+        let checkpoint = match store.get(&[NET, CHECKPOINT, name]) {
+            Ok(Value::Object(checkpoint)) => checkpoint,
+            _ => return None,
+        };
+        let field = |key| match checkpoint.get(key) {
+            Some(Value::Uint(val)) => Some(*val),
+            _ => None,
+        };
+
+        Some(Checkpoint {
+            rx: field("rx")?,
+            tx: field("tx")?,
+            time: field("time")?,
+        })
+    }
+
+    /// Write a checkpoint to the data store, replacing any of the same name
+    pub fn save(&self, store: &Store, name: &str) -> Result<(), NestError> {
+        let mut checkpoint = IndexMap::new();
+        checkpoint.insert("rx".to_string(), Value::Uint(self.rx));
+        checkpoint.insert("tx".to_string(), Value::Uint(self.tx));
+        checkpoint.insert("time".to_string(), Value::Uint(self.time));
+        store.set(&[NET, CHECKPOINT, name], &Value::Object(checkpoint))?;
+
+        Ok(())
+    }
+
+    /// Usage (bytes) since the checkpoint, given the current received and transmitted totals
+    ///
+    /// Totals lower than at the checkpoint count as no usage.
+    pub fn since(&self, rx: u64, tx: u64) -> (u64, u64) {
+        (rx.saturating_sub(self.rx), tx.saturating_sub(self.tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::scratch_store;
+
+    #[test]
+    fn round_trip() {
+        let store = scratch_store("checkpoint");
+        let checkpoint = Checkpoint {
+            rx: 1000,
+            tx: 200,
+            time: 1_760_000_000,
+        };
+        checkpoint.save(&store, "before-update").unwrap();
+        assert_eq!(Checkpoint::load(&store, "before-update"), Some(checkpoint));
+        assert_eq!(Checkpoint::load(&store, "other"), None);
+    }
+
+    #[test]
+    fn usage_since() {
+        let checkpoint = Checkpoint {
+            rx: 1000,
+            tx: 200,
+            time: 0,
+        };
+        assert_eq!(checkpoint.since(1500, 260), (500, 60));
+        // totals reset since the checkpoint count as no usage
+        assert_eq!(checkpoint.since(10, 260), (0, 60));
+    }
+}
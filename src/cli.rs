@@ -0,0 +1,8291 @@
+//! Command line of the `peach-monitor` binary: its options, commands and daemon.
+//!
+//! The daemon's poll is shared with `Monitor`, which runs the same steps
+//! from the library.
+
+use std::convert::TryInto;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::iter;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use std::{thread, time};
+
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use indexmap::IndexMap;
+use log::{debug, error, info, warn};
+use nest::{Schema, Store, Value};
+use serde_json::json;
+use structopt::StructOpt;
+
+#[cfg(feature = "sqlite")]
+use crate::db;
+#[cfg(feature = "top-talkers")]
+use crate::talkers;
+use crate::{
+    archive, bench, config, cycle, doctor, history, hook, http, integrity, metered, metrics, nft,
+    nm, notification, profile, repl, schedule, shaping, sink, snapshot, snmp, trace,
+};
+use crate::{
+    bytes_to_thresholds, interface_values, is_metered, load_state, stored_alert, stored_bytes,
+    stored_string, stored_threshold, stored_total, stored_usage, stored_value, to_bytes,
+    total_bytes, MonitorState,
+};
+
+use crate::anonymize::Pseudonyms;
+use crate::archive::Archived;
+use crate::bench::Timings;
+use crate::checkpoint::Checkpoint;
+use crate::color::{Color, ColorChoice, Palette};
+use crate::config::{Origin, Setting, Spec};
+use crate::cycle::Cycle;
+use crate::dash::{Model, State, Usage};
+use crate::doctor::{Finding, Outcome};
+use crate::durability::{flush, Durability, Flusher};
+use crate::duration::parse_duration;
+use crate::error::{BoxError, NestError, NetworkError};
+use crate::flash::{on_root_flash, writes_per_day, Storage, FREQUENT_WRITES_PER_DAY};
+use crate::http::Response;
+use crate::identity::Identity;
+use crate::integrity::Kind;
+use crate::journal::Journal;
+use crate::keys::{
+    threshold_name, AlertFlag, AlertKey, Direction, Keys, Layout, Level, ThresholdKey, TotalUnit,
+    ALERT, ANOMALY_ALERT, ANOMALY_FACTOR, BASELINE, BOOT_ID, BUDGET_OVER, CYCLE_DAY, DAILY_BUDGET,
+    DAILY_EMA, DELTA_TIME, EMA_DATE, FILES, FIRST_SEEN, IFACE, IFACES, IFACE_LAST, INTERFACES,
+    METERED, NET, NOTIFY, PAUSED, PLAN, PLAN_MODE, PROJECTION_OVER, RATIO_BASELINE, RX_DELTA,
+    RX_LAST, RX_RATE, TOTALS_VERSION, TOTAL_UNIT, TRAFFIC, TX_DELTA, TX_LAST, TX_RATE, UPDATED,
+    UPLOAD_ANOMALY, UPLOAD_FACTOR, WINDOW_CUT, WINDOW_CUT_ALERT,
+};
+use crate::metrics::{InterfaceTotals, Labelled};
+use crate::notification::{Backend, Event};
+use crate::profile::Profile;
+use crate::rate::{Rate, RateMeter};
+use crate::repl::Input;
+use crate::retry::retry_with_backoff;
+use crate::schedule::Approach;
+use crate::shaping::{Edge, Shaper};
+use crate::sink::{Record, Sink};
+use crate::throttle::Throttled;
+use crate::trace::Reading;
+use crate::traffic::{Source, Traffic};
+use crate::units::{
+    format_bytes, format_rate, group_digits, parse_size, parse_unit, round_bytes, usage_gauge,
+};
+use crate::window::{Sample, Samples};
+
+#[derive(StructOpt, Debug)]
+#[structopt(
+    name = "peach-monitor",
+    about = "Monitor data usage and set alert flags"
+)]
+struct Opt {
+    /// Run daemon
+    #[structopt(short, long)]
+    daemon: bool,
+
+    /// Define network interface
+    #[structopt(short, long, default_value = "wlan0", env = "PEACH_MONITOR_IFACE")]
+    iface: String,
+
+    /// Save latest usage totals to file
+    #[structopt(short, long)]
+    save: bool,
+
+    /// Define time interval for updating alert flags (seconds)
+    #[structopt(
+        short = "t",
+        long,
+        default_value = "60",
+        env = "PEACH_MONITOR_INTERVAL"
+    )]
+    interval: u64,
+
+    /// Update alert flags
+    #[structopt(short, long)]
+    update: bool,
+
+    /// Poll more often while thresholds are being approached and less often while idle
+    #[structopt(long)]
+    adaptive_interval: bool,
+
+    /// Define the shortest interval between adaptive polls (seconds)
+    #[structopt(long, default_value = "10")]
+    min_interval: u64,
+
+    /// Define the longest interval between adaptive polls (seconds)
+    #[structopt(long, default_value = "600")]
+    max_interval: u64,
+
+    /// Align daemon polls to wall-clock multiples of the interval (e.g. every five minutes on the
+    /// minute with --interval 300), instead of counting intervals from the start
+    #[structopt(long, requires = "daemon", conflicts_with_all = &["jitter", "adaptive-interval"])]
+    align: bool,
+
+    /// Vary each interval between daemon polls randomly by up to a fraction of it (e.g. 0.1)
+    #[structopt(long, parse(try_from_str = parse_fraction))]
+    jitter: Option<f64>,
+
+    /// Derive warning (80%) and cutoff (100%) thresholds from a data plan (e.g. 50GB)
+    #[structopt(long, env = "PEACH_MONITOR_PLAN", parse(try_from_str = parse_size))]
+    plan: Option<u64>,
+
+    /// Define the day of the month the billing cycle starts on (1 to 31)
+    #[structopt(long, env = "PEACH_MONITOR_CYCLE_DAY", parse(try_from_str = cycle::parse_day))]
+    cycle_day: Option<u32>,
+
+    /// Define how many completed billing cycles to keep the final totals of, archived at each
+    /// reset (0 to archive none)
+    #[structopt(long, default_value = "24")]
+    keep_cycles: usize,
+
+    /// Define whether the data plan covers combined or per-direction traffic
+    #[structopt(
+        long,
+        default_value = "combined",
+        env = "PEACH_MONITOR_PLAN_MODE",
+        possible_values = &["combined", "directional"]
+    )]
+    plan_mode: PlanMode,
+
+    /// Evaluate the window cap against usage over a trailing window (e.g. 24h)
+    #[structopt(long, parse(try_from_str = parse_duration))]
+    window: Option<Duration>,
+
+    /// Zero the totals at the first save after each boot, so that they only cover the current boot
+    #[structopt(long)]
+    since_boot: bool,
+
+    /// Store the traffic counted by each save as rx_delta and tx_delta, instead of adding it to the totals
+    #[structopt(long)]
+    delta_mode: bool,
+
+    /// Ignore polls whose combined received and transmitted traffic is below a size (e.g. 4KB)
+    #[structopt(long, parse(try_from_str = parse_size))]
+    min_delta: Option<u64>,
+
+    /// Inflate the counted traffic by a percentage, estimating protocol overhead counted by the ISP (e.g. 3.5)
+    #[structopt(long, parse(try_from_str = parse_percent))]
+    overhead_percent: Option<f64>,
+
+    /// Ask NetworkManager whether the connection is metered at each save, only counting traffic while it is
+    #[structopt(long)]
+    detect_metered: bool,
+
+    /// Evaluate alerts against the data limits of the interface's NetworkManager connection,
+    /// where it has any, in place of the stored thresholds
+    #[structopt(long)]
+    nm_thresholds: bool,
+
+    /// Raise alerts once a total reaches its threshold, rather than only once it exceeds it
+    #[structopt(long)]
+    inclusive_thresholds: bool,
+
+    /// Only raise warning alerts, leaving every cutoff alert (including the window cap) unset
+    #[structopt(long)]
+    warn_only: bool,
+
+    /// Define the number of attempts made to write the alert flags of an update
+    #[structopt(long, default_value = "3")]
+    write_attempts: u32,
+
+    /// Evaluate only the listed alert flags, leaving the others untouched (e.g. rx_cut_alert,total_cut_alert)
+    #[structopt(long, use_delimiter = true)]
+    alerts: Vec<AlertFlag>,
+
+    /// Override thresholds or stop counting traffic at certain times
+    /// (e.g. window=02:00-06:00,unmetered or days=sat-sun,window=00:00-24:00,total_cut=80GB)
+    #[structopt(long = "profile", number_of_values = 1)]
+    profiles: Vec<Profile>,
+
+    /// Define the cap on combined usage within the trailing window (e.g. 5GB)
+    #[structopt(long, env = "PEACH_MONITOR_WINDOW_CAP", parse(try_from_str = parse_size))]
+    window_cap: Option<u64>,
+
+    /// Refuse thresholds, data plans and caps given on the command line which are larger than this
+    #[structopt(long, default_value = "2TB", parse(try_from_str = parse_size))]
+    max_threshold: u64,
+
+    /// Raise the anomaly alert when a day's usage exceeds the baseline by a factor (e.g. 3)
+    #[structopt(long, parse(try_from_str = parse_factor))]
+    anomaly_factor: Option<f64>,
+
+    /// Define the number of days of the billing cycle which must have elapsed before its usage is
+    /// projected
+    #[structopt(long, default_value = "3")]
+    projection_min_days: u32,
+
+    /// Once the projection alert is raised, only clear it when the projection falls below the
+    /// cutoff by a percentage of it (e.g. 5), so that it does not flicker
+    #[structopt(long, default_value = "5", parse(try_from_str = parse_percent))]
+    projection_clear_margin: f64,
+
+    /// Define the daily usage projections are based on: the average of the cycle's elapsed days,
+    /// or an exponential moving average (ema) of daily usage carried across cycles
+    #[structopt(long, default_value = "average", possible_values = &["average", "ema"])]
+    forecast_basis: ForecastBasis,
+
+    /// Define the weight of each day's usage in the moving average of daily usage (above 0 and
+    /// below 1)
+    #[structopt(long, default_value = "0.3", parse(try_from_str = parse_fraction))]
+    ema_alpha: f64,
+
+    /// Define the number of days before today averaged into the usage baseline
+    #[structopt(long, default_value = "7")]
+    baseline_days: u32,
+
+    /// Raise the upload anomaly alert when a day's ratio of transmitted to received traffic
+    /// exceeds the ratio baseline by a factor (e.g. 4)
+    #[structopt(long, parse(try_from_str = parse_factor))]
+    upload_factor: Option<f64>,
+
+    /// Define the number of days before today whose traffic makes up the ratio baseline
+    #[structopt(long, default_value = "14")]
+    ratio_baseline_days: u32,
+
+    /// Only raise the upload anomaly alert once a day's transmitted traffic exceeds a size, so
+    /// that the first traffic of a day is not taken for an anomaly
+    #[structopt(long, default_value = "100MB", parse(try_from_str = parse_size))]
+    upload_min: u64,
+
+    /// Print the raw interface counters alongside the stored counters and computed delta
+    #[structopt(long)]
+    debug_counters: bool,
+
+    /// Append the raw counters read by each save to a trace file, which `replay` can read
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// Read traffic counters from the output of a shell command instead of the kernel
+    #[structopt(long)]
+    stats_command: Option<String>,
+
+    /// Read traffic counters from an SNMP agent instead of the kernel
+    /// (e.g. host=192.168.1.1,index=2,community=public or host=192.168.1.1,index=2,user=monitor,auth=...,priv=...)
+    #[structopt(long, conflicts_with = "stats-command")]
+    snmp: Option<snmp::Agent>,
+
+    /// Read the counters of traffic classes from named nftables counters instead of the kernel
+    /// (e.g. table=peach,class=metered or table=peach,exclude=lan)
+    #[structopt(long, conflicts_with_all = &["stats-command", "snmp"])]
+    nft: Option<nft::Counters>,
+
+    /// Define how a decrease in the interface counters is interpreted
+    #[structopt(long, default_value = "per-link", possible_values = &["per-link", "cumulative"])]
+    counter_semantics: CounterSemantics,
+
+    /// Define whether the first daemon poll counts the traffic since the last save, made while
+    /// the daemon was stopped
+    #[structopt(long, default_value = "on-restart", possible_values = &["on-restart", "off"])]
+    catch_up: CatchUp,
+
+    /// Stop the daemon after it has run for a duration (e.g. 1h), saving once more as on a stop signal
+    #[structopt(long, parse(try_from_str = parse_duration))]
+    max_runtime: Option<Duration>,
+
+    /// Hold back alert notifications for a duration after the daemon starts (e.g. 10m), while
+    /// still setting the alert flags
+    #[structopt(long, parse(try_from_str = parse_duration))]
+    startup_grace: Option<Duration>,
+
+    /// Shape the interface while its smoothed combined rate exceeds a rate (bytes per second,
+    /// e.g. 2MB)
+    #[structopt(long, requires = "daemon", parse(try_from_str = parse_size))]
+    shape_rate: Option<u64>,
+
+    /// Restore a shaped interface once its smoothed rate falls to a rate (bytes per second;
+    /// defaults to the shaping rate)
+    #[structopt(long, requires = "shape-rate", parse(try_from_str = parse_size))]
+    restore_rate: Option<u64>,
+
+    /// Define the weight of each poll's rate in the smoothed rate (above 0 and below 1)
+    #[structopt(long, default_value = "0.3", parse(try_from_str = parse_fraction))]
+    shape_smoothing: f64,
+
+    /// Define the command run with sh -c to shape the interface (given PM_IFACE and PM_RATE)
+    #[structopt(
+        long,
+        default_value = "tc qdisc replace dev \"$PM_IFACE\" root tbf rate \"${PM_RATE}bps\" burst 64kb latency 400ms"
+    )]
+    shape_command: String,
+
+    /// Define the command run with sh -c to restore the interface (given PM_IFACE and PM_RATE)
+    #[structopt(long, default_value = "tc qdisc del dev \"$PM_IFACE\" root")]
+    restore_command: String,
+
+    /// Define how long to wait for the final save when stopping the daemon (seconds)
+    #[structopt(long, default_value = "5")]
+    shutdown_timeout: u64,
+
+    /// Also record each save as a sample in an SQLite database, which history is then read from
+    /// (can be repeated, history being read from the first)
+    #[cfg(feature = "sqlite")]
+    #[structopt(long = "db", number_of_values = 1, parse(from_os_str))]
+    dbs: Vec<PathBuf>,
+
+    /// Define the number of days of usage history to keep (0 keeps it indefinitely)
+    #[structopt(long, default_value = "90")]
+    history_days: u32,
+
+    /// Also record the thresholds in effect into each day's usage history entry
+    #[structopt(long)]
+    history_thresholds: bool,
+
+    /// Define the directory holding the data store (defaults to $XDG_DATA_HOME/peachcloud, or
+    /// /var/lib/peachcloud without a home directory)
+    #[structopt(long, env = "PEACH_MONITOR_DATA_DIR", parse(from_os_str))]
+    data_dir: Option<PathBuf>,
+
+    /// Define the layout of keys written to the data store
+    #[structopt(
+        long,
+        default_value = "v2",
+        possible_values = &["v2", "legacy", "per-interface"]
+    )]
+    schema: Layout,
+
+    /// Also mirror the monitored interface's totals and alert flags to the flat keys (per-interface schema)
+    #[structopt(long)]
+    compat_flat_keys: bool,
+
+    /// Define the unit the totals are stored in: bytes, or kb for whole kilobytes with the bytes
+    /// below a kilobyte kept apart
+    #[structopt(long, default_value = "bytes", possible_values = &["bytes", "kb"])]
+    total_unit: TotalUnit,
+
+    /// Read and write a threshold under another key of net/notify (e.g. total_warn=data_warn)
+    #[structopt(long = "threshold-key", number_of_values = 1)]
+    threshold_keys: Vec<ThresholdKey>,
+
+    /// Write the alert flag of a threshold under another key of net/alert (e.g.
+    /// total_cut_alert=data_cut)
+    #[structopt(long = "alert-key", number_of_values = 1)]
+    alert_keys: Vec<AlertKey>,
+
+    /// Define whether the data store is on flash storage, for the frequent write warning
+    #[structopt(long, default_value = "auto", possible_values = &["auto", "flash", "other"])]
+    storage: Storage,
+
+    /// Define when writes to the data store are flushed to the storage medium: after every
+    /// poll (sync), or every --flush-interval and when the daemon stops (lazy)
+    #[structopt(long, default_value = "lazy", possible_values = &["sync", "lazy"])]
+    durability: Durability,
+
+    /// Define how often the daemon flushes the data store under lazy durability (e.g. 5m)
+    #[structopt(long, default_value = "5m", parse(try_from_str = parse_duration))]
+    flush_interval: Duration,
+
+    /// Serve OpenMetrics at /metrics on an address while running the daemon (e.g. 127.0.0.1:9101)
+    #[structopt(long)]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Serve a read-only JSON API on a port while running the daemon (/totals, /thresholds,
+    /// /alerts and /status)
+    #[structopt(long)]
+    api_port: Option<u16>,
+
+    /// Define the address the JSON API listens on
+    #[structopt(long, default_value = "127.0.0.1")]
+    api_bind: IpAddr,
+
+    /// Send the totals and alert flags of each daemon poll to the systemd journal as fields
+    #[structopt(long)]
+    journal: bool,
+
+    /// Print a one-line summary of each daemon poll to stdout (time, totals, rates and
+    /// raised alerts)
+    #[structopt(long)]
+    log_each_cycle: bool,
+
+    /// Cap the number of log lines per minute, coalescing those beyond it (lines/min)
+    #[structopt(long)]
+    max_log_rate: Option<u32>,
+
+    /// Run a script whenever an alert flag is raised or cleared
+    #[structopt(long, parse(from_os_str))]
+    on_alert: Option<PathBuf>,
+
+    /// Notify a backend whenever an alert flag is raised or cleared (repeatable): notify-send,
+    /// journal, webhook=<url>, email=<address> or script=<path>
+    #[structopt(long = "notify", number_of_values = 1)]
+    notify: Vec<Backend>,
+
+    /// Back up and reinitialise data store files which cannot be read, after confirmation
+    #[structopt(long)]
+    repair: bool,
+
+    /// Only read the data store, e.g. to display or serve it alongside the daemon which writes it
+    #[structopt(long, conflicts_with = "repair")]
+    observe: bool,
+
+    /// Exit with an error if the store cannot be written, a stored value has an unexpected type or
+    /// the first daemon poll fails, rather than logging the failure and carrying on
+    #[structopt(long, requires = "daemon", conflicts_with = "observe")]
+    fail_fast: bool,
+
+    /// Fail if a stored threshold, total or flag has an unexpected type instead of ignoring it
+    #[structopt(long)]
+    strict: bool,
+
+    /// Create a file while a cutoff alert is set and remove it once cleared
+    #[structopt(long, parse(from_os_str))]
+    breach_file: Option<PathBuf>,
+
+    /// Write the overall alert state (ok, warn or cut) and the totals to a file at each alert
+    /// update, for prompts and status bars
+    #[structopt(long, parse(from_os_str))]
+    status_file: Option<PathBuf>,
+
+    /// Colour alert states in the status and watch-alerts output (auto colours a terminal
+    /// unless NO_COLOR is set)
+    #[structopt(long, default_value = "auto", possible_values = &["auto", "always", "never"])]
+    color: ColorChoice,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Print the stored usage totals, data plan and alert flags
+    Status {
+        /// Round displayed totals to the nearest unit (e.g. MB or GB)
+        #[structopt(long, parse(try_from_str = parse_unit))]
+        round: Option<u64>,
+
+        /// Replace interface names with pseudonyms (iface1, iface2, ...)
+        #[structopt(long)]
+        anonymize: bool,
+    },
+    /// Print the totals and raised alerts of every interface in the data store, and their sum
+    Summary,
+    /// Show a live dashboard of the totals, thresholds, recent rates and raised alerts,
+    /// redrawn every interval
+    Dash,
+    /// Wait until an alert flag is raised, reading it at each interval, then print it and exit
+    WatchAlerts {
+        /// Alert flag to wait for (e.g. rx_cut_alert)
+        #[structopt(long)]
+        flag: String,
+
+        /// Fail if the flag has not been raised within a duration (e.g. 2h)
+        #[structopt(long, parse(try_from_str = parse_duration))]
+        timeout: Option<Duration>,
+    },
+    /// Notify the configured backends of an alert flag being raised, then cleared, without
+    /// changing the stored totals or flags
+    TestAlert {
+        /// Alert flag to notify of (e.g. rx_cut_alert)
+        #[structopt(long)]
+        flag: String,
+    },
+    /// Pause accounting so that traffic is not counted towards the totals
+    Pause,
+    /// Resume accounting from the current interface counters
+    Resume,
+    /// Mark the connection as metered, so that its traffic is counted and alerted on
+    Metered,
+    /// Mark the connection as unmetered, so that its traffic is neither counted nor alerted on
+    Unmetered,
+    /// Set thresholds which differ from the given values, printing "changed" or "unchanged"
+    EnsureThresholds {
+        /// Combined warning threshold (e.g. 40GB)
+        #[structopt(long, env = "PEACH_MONITOR_TOTAL_WARN", parse(try_from_str = parse_size))]
+        total_warn: Option<u64>,
+
+        /// Combined cutoff threshold
+        #[structopt(long, env = "PEACH_MONITOR_TOTAL_CUT", parse(try_from_str = parse_size))]
+        total_cut: Option<u64>,
+
+        /// Received warning threshold
+        #[structopt(long, env = "PEACH_MONITOR_RX_WARN", parse(try_from_str = parse_size))]
+        rx_warn: Option<u64>,
+
+        /// Received cutoff threshold
+        #[structopt(long, env = "PEACH_MONITOR_RX_CUT", parse(try_from_str = parse_size))]
+        rx_cut: Option<u64>,
+
+        /// Transmitted warning threshold
+        #[structopt(long, env = "PEACH_MONITOR_TX_WARN", parse(try_from_str = parse_size))]
+        tx_warn: Option<u64>,
+
+        /// Transmitted cutoff threshold
+        #[structopt(long, env = "PEACH_MONITOR_TX_CUT", parse(try_from_str = parse_size))]
+        tx_cut: Option<u64>,
+    },
+    /// Report the keys each threshold is read from, and stored keys which are never read
+    CheckKeys,
+    /// Check the configuration and data store for common problems, printing a checklist
+    Doctor,
+    /// Print the options and thresholds in effect, with the source each came from
+    Config {
+        /// Format to print the configuration in
+        #[structopt(long, default_value = "text", possible_values = &["text", "json"])]
+        format: ConfigFormat,
+    },
+    /// Explore and change the data store interactively (get, set, status and reset)
+    Repl,
+    /// Store the current usage totals as a named checkpoint
+    Checkpoint {
+        /// Name of the checkpoint
+        #[structopt(default_value = "default")]
+        name: String,
+    },
+    /// Print the usage since a named checkpoint
+    Diff {
+        /// Name of the checkpoint
+        #[structopt(default_value = "default")]
+        name: String,
+    },
+    /// Zero the usage totals of the interface, once confirmed
+    Reset {
+        /// Reset without asking, as required when not run from a terminal
+        #[structopt(long)]
+        confirm: bool,
+
+        /// Only zero the received total
+        #[structopt(long, conflicts_with = "tx-only")]
+        rx_only: bool,
+
+        /// Only zero the transmitted total
+        #[structopt(long)]
+        tx_only: bool,
+    },
+    /// Print the daily usage history
+    History,
+    /// Print the final totals of the billing cycles archived at each reset, oldest first
+    Cycles,
+    /// Export the daily usage history for other tools, oldest first
+    Export {
+        /// Format to export in
+        #[structopt(long, default_value = "csv", possible_values = &["csv", "grafana-json"])]
+        format: ExportFormat,
+
+        /// Only export days from a date onwards (e.g. 2020-09-01)
+        #[structopt(long)]
+        from: Option<NaiveDate>,
+
+        /// Only export days up to and including a date
+        #[structopt(long)]
+        to: Option<NaiveDate>,
+
+        /// Identify the device in each exported day, by the MAC address of the interface or the
+        /// machine ID
+        #[structopt(long)]
+        include_identity: bool,
+
+        /// Replace the identifier with a stable pseudonym derived from it
+        #[structopt(long, requires = "include-identity")]
+        anonymize: bool,
+    },
+    /// Save the full state of the data store (totals, thresholds, alerts, history, ...) as JSON
+    Snapshot {
+        /// File to write the snapshot to, instead of stdout
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+
+        /// Identify the device in the snapshot, by the MAC address of the interface or the
+        /// machine ID
+        #[structopt(long)]
+        include_identity: bool,
+
+        /// Replace the identifier with a stable pseudonym derived from it
+        #[structopt(long, requires = "include-identity")]
+        anonymize: bool,
+    },
+    /// Replace the state of the data store with a snapshot, once confirmed
+    Restore {
+        /// Snapshot file written by the snapshot command
+        snapshot: PathBuf,
+
+        /// Restore without asking, as required when not run from a terminal
+        #[structopt(long)]
+        confirm: bool,
+    },
+    /// Print the differences in totals, thresholds and alert flags between two snapshots
+    Compare {
+        /// Earlier snapshot file written by the snapshot command
+        #[structopt(parse(from_os_str))]
+        before: PathBuf,
+
+        /// Later snapshot file
+        #[structopt(parse(from_os_str))]
+        after: PathBuf,
+
+        /// Format to print the differences in
+        #[structopt(long, default_value = "human", possible_values = &["human", "json"])]
+        format: CompareFormat,
+    },
+    /// Remove usage history entries older than a given age
+    PruneHistory {
+        /// Age of the entries to remove (e.g. 60d)
+        #[structopt(long, parse(try_from_str = parse_duration))]
+        older_than: Duration,
+
+        /// List the dates which would be removed without removing them
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Measure the duration of each stage of a poll cycle against a scratch data store
+    Bench {
+        /// Number of poll cycles to run
+        #[structopt(long, default_value = "100")]
+        cycles: u32,
+    },
+    /// Feed a trace written with --record through accounting and alerting against a scratch data store
+    Replay {
+        /// Trace file to replay
+        #[structopt(parse(from_os_str))]
+        trace: PathBuf,
+    },
+    /// List the hosts which transferred the most data, from the connection tracking table
+    #[cfg(feature = "top-talkers")]
+    TopTalkers {
+        /// Number of hosts to list
+        #[structopt(long, default_value = "10")]
+        count: usize,
+
+        /// End of the connections to group usage by
+        #[structopt(long, default_value = "destination", possible_values = &["destination", "source"])]
+        by: talkers::Grouping,
+
+        /// Connection tracking listing to read, such as saved from `conntrack -L -o extended` (- for stdin)
+        #[structopt(long, parse(from_os_str), default_value = "/proc/net/nf_conntrack")]
+        file: PathBuf,
+    },
+}
+
+/// Options listed by the `config` command, in the order they are defined in `Opt`
+///
+/// The list is kept alongside `Opt` by hand, as clap does not expose the
+/// options of an application, and a test checks that it names every one.
+fn option_specs() -> Vec<Spec> {
+    let mut specs = vec![
+        Spec::flag("daemon"),
+        Spec::value("iface").env("PEACH_MONITOR_IFACE").default("wlan0"),
+        Spec::flag("save"),
+        Spec::value("interval").env("PEACH_MONITOR_INTERVAL").default("60"),
+        Spec::flag("update"),
+        Spec::flag("adaptive-interval"),
+        Spec::value("min-interval").default("10"),
+        Spec::value("max-interval").default("600"),
+        Spec::flag("align"),
+        Spec::value("jitter"),
+        Spec::value("plan").env("PEACH_MONITOR_PLAN"),
+        Spec::value("cycle-day").env("PEACH_MONITOR_CYCLE_DAY"),
+        Spec::value("keep-cycles").default("24"),
+        Spec::value("plan-mode").env("PEACH_MONITOR_PLAN_MODE").default("combined"),
+        Spec::value("window"),
+        Spec::flag("since-boot"),
+        Spec::flag("delta-mode"),
+        Spec::value("min-delta"),
+        Spec::value("overhead-percent"),
+        Spec::flag("detect-metered"),
+        Spec::flag("nm-thresholds"),
+        Spec::flag("inclusive-thresholds"),
+        Spec::flag("warn-only"),
+        Spec::value("write-attempts").default("3"),
+        Spec::values("alerts"),
+        Spec::values("profiles").long("profile"),
+        Spec::value("window-cap").env("PEACH_MONITOR_WINDOW_CAP"),
+        Spec::value("max-threshold").default("2TB"),
+        Spec::value("anomaly-factor"),
+        Spec::value("projection-min-days").default("3"),
+        Spec::value("projection-clear-margin").default("5"),
+        Spec::value("forecast-basis").default("average"),
+        Spec::value("ema-alpha").default("0.3"),
+        Spec::value("baseline-days").default("7"),
+        Spec::value("upload-factor"),
+        Spec::value("ratio-baseline-days").default("14"),
+        Spec::value("upload-min").default("100MB"),
+        Spec::flag("debug-counters"),
+        Spec::value("record"),
+        Spec::value("stats-command"),
+        Spec::value("snmp"),
+        Spec::value("nft"),
+        Spec::value("counter-semantics").default("per-link"),
+        Spec::value("catch-up").default("on-restart"),
+        Spec::value("max-runtime"),
+        Spec::value("startup-grace"),
+        Spec::value("shape-rate"),
+        Spec::value("restore-rate"),
+        Spec::value("shape-smoothing").default("0.3"),
+        Spec::value("shape-command").default("tc qdisc replace dev \"$PM_IFACE\" root tbf rate \"${PM_RATE}bps\" burst 64kb latency 400ms"),
+        Spec::value("restore-command").default("tc qdisc del dev \"$PM_IFACE\" root"),
+        Spec::value("shutdown-timeout").default("5"),
+    ];
+    #[cfg(feature = "sqlite")]
+    specs.push(Spec::values("dbs").long("db"));
+    specs.extend_from_slice(&[
+        Spec::value("history-days").default("90"),
+        Spec::flag("history-thresholds"),
+        Spec::value("data-dir").env("PEACH_MONITOR_DATA_DIR"),
+        Spec::value("schema").default("v2"),
+        Spec::flag("compat-flat-keys"),
+        Spec::value("total-unit").default("bytes"),
+        Spec::values("threshold-keys").long("threshold-key"),
+        Spec::values("alert-keys").long("alert-key"),
+        Spec::value("storage").default("auto"),
+        Spec::value("durability").default("lazy"),
+        Spec::value("flush-interval").default("5m"),
+        Spec::value("metrics-addr"),
+        Spec::value("api-port"),
+        Spec::value("api-bind").default("127.0.0.1"),
+        Spec::flag("journal"),
+        Spec::flag("log-each-cycle"),
+        Spec::value("max-log-rate"),
+        Spec::value("on-alert"),
+        Spec::values("notify"),
+        Spec::flag("repair"),
+        Spec::flag("observe"),
+        Spec::flag("fail-fast"),
+        Spec::flag("strict"),
+        Spec::value("breach-file"),
+        Spec::value("status-file"),
+        Spec::value("color").default("auto"),
+    ]);
+    specs
+}
+
+impl Opt {
+    /// Keys the data store is written with
+    fn keys(&self) -> Keys {
+        Keys::new(
+            self.schema,
+            self.compat_flat_keys,
+            self.threshold_keys.clone(),
+            self.alert_keys.clone(),
+            self.total_unit,
+        )
+    }
+
+    /// Source of the network traffic counters
+    fn source(&self) -> Source {
+        match (&self.stats_command, &self.snmp, &self.nft) {
+            (Some(command), _, _) => Source::Command(command.clone()),
+            (None, Some(agent), _) => Source::Snmp(agent.clone()),
+            (None, None, Some(counters)) => Source::Nft(counters.clone()),
+            (None, None, None) => Source::Proc,
+        }
+    }
+
+    /// Colours for the status and watch-alerts output
+    fn palette(&self) -> Palette {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Palette::new(self.color.enabled(io::stdout().is_terminal(), no_color))
+    }
+
+    /// Shaper of the interface's rate, if a shaping rate is given
+    fn shaper(&self) -> Option<Shaper> {
+        self.shape_rate
+            .map(|limit| Shaper::new(limit, self.restore_rate, self.shape_smoothing))
+    }
+
+    /// Notification backends, including the alert script
+    fn backends(&self) -> Vec<Backend> {
+        let mut backends = self.notify.clone();
+        if let Some(ref script) = self.on_alert {
+            backends.push(Backend::Script(script.clone()));
+        }
+        backends
+    }
+
+    /// Backends which the usage counted by each save is written to, besides the data store
+    ///
+    /// The backends are built once, before the first save, rather than for
+    /// each save.
+    fn sinks(&self) -> Vec<Box<dyn Sink>> {
+        #[cfg_attr(not(feature = "sqlite"), allow(unused_mut))]
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+        #[cfg(feature = "sqlite")]
+        for path in &self.dbs {
+            sinks.push(Box::new(db::Samples::new(path)));
+        }
+        sinks
+    }
+
+    /// Whether an alert flag is evaluated (all are unless some have been selected)
+    fn evaluates(&self, flag: AlertFlag) -> bool {
+        self.alerts.is_empty() || self.alerts.contains(&flag)
+    }
+
+    /// Whether the requested operations write to the data store
+    fn writes(&self) -> bool {
+        self.save
+            || self.update
+            || self.plan.is_some()
+            || self.window_cap.is_some()
+            || self.anomaly_factor.is_some()
+            || self.upload_factor.is_some()
+            || self.cycle_day.is_some()
+            || matches!(
+                self.cmd,
+                Some(Command::Pause)
+                    | Some(Command::Resume)
+                    | Some(Command::Metered)
+                    | Some(Command::Unmetered)
+                    | Some(Command::Checkpoint { .. })
+                    | Some(Command::Reset { .. })
+                    | Some(Command::Restore { .. })
+                    | Some(Command::EnsureThresholds { .. })
+                    | Some(Command::PruneHistory { dry_run: false, .. })
+            )
+    }
+}
+
+/// Parse a multiplier, which must be a positive number
+fn parse_factor(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(factor),
+        _ => Err(format!(
+            "Invalid factor: {} (expected a positive number)",
+            input
+        )),
+    }
+}
+
+/// Parse a percentage, which must not be negative
+fn parse_percent(input: &str) -> Result<f64, String> {
+    match input.trim().trim_end_matches('%').parse::<f64>() {
+        Ok(percent) if percent.is_finite() && percent >= 0.0 => Ok(percent),
+        _ => Err(format!(
+            "Invalid percentage: {} (expected a number of at least 0)",
+            input
+        )),
+    }
+}
+
+/// Parse a fraction, which must be above 0 and below 1
+fn parse_fraction(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(fraction) if fraction > 0.0 && fraction < 1.0 => Ok(fraction),
+        _ => Err(format!(
+            "Invalid fraction: {} (expected a number between 0 and 1)",
+            input
+        )),
+    }
+}
+
+/// Traffic directions covered by a data plan
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlanMode {
+    /// The plan covers received and transmitted traffic together
+    Combined,
+    /// The plan applies to received and transmitted traffic separately
+    Directional,
+}
+
+impl PlanMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            PlanMode::Combined => "combined",
+            PlanMode::Directional => "directional",
+        }
+    }
+}
+
+impl FromStr for PlanMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PlanMode, String> {
+        match s {
+            "combined" => Ok(PlanMode::Combined),
+            "directional" => Ok(PlanMode::Directional),
+            _ => Err(format!("Unknown plan mode: {}", s)),
+        }
+    }
+}
+
+/// Format of exported usage history
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+    /// Comma-separated `date,rx,tx` rows under a header
+    Csv,
+    /// Array of `{time, rx, tx}` objects, timed in unix milliseconds at local midnight
+    GrafanaJson,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExportFormat, String> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "grafana-json" => Ok(ExportFormat::GrafanaJson),
+            _ => Err(format!("Unknown export format: {}", s)),
+        }
+    }
+}
+
+/// Daily usage which usage is projected at
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ForecastBasis {
+    /// Average usage of the days of the cycle elapsed so far
+    Average,
+    /// Exponential moving average of daily usage, weighting recent days
+    Ema,
+}
+
+impl FromStr for ForecastBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ForecastBasis, String> {
+        match s {
+            "average" => Ok(ForecastBasis::Average),
+            "ema" => Ok(ForecastBasis::Ema),
+            _ => Err(format!("Unknown forecast basis: {}", s)),
+        }
+    }
+}
+
+/// Format of the differences between snapshots
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CompareFormat {
+    /// One line per key, aligned for reading
+    Human,
+    /// Object holding both snapshots' times and an array of the changed keys
+    Json,
+}
+
+impl FromStr for CompareFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CompareFormat, String> {
+        match s {
+            "human" => Ok(CompareFormat::Human),
+            "json" => Ok(CompareFormat::Json),
+            _ => Err(format!("Unknown compare format: {}", s)),
+        }
+    }
+}
+
+/// Format of the effective configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ConfigFormat {
+    /// One line per setting, aligned for reading
+    Text,
+    /// Object holding arrays of the options and thresholds
+    Json,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ConfigFormat, String> {
+        match s {
+            "text" => Ok(ConfigFormat::Text),
+            "json" => Ok(ConfigFormat::Json),
+            _ => Err(format!("Unknown config format: {}", s)),
+        }
+    }
+}
+
+/// How interface counters behave when the link goes down and up again
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CounterSemantics {
+    /// Counters restart from zero when the link is bounced, so a decrease is a reset
+    PerLink,
+    /// Counters only restart on reboot, so a decrease is anomalous
+    Cumulative,
+}
+
+impl FromStr for CounterSemantics {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CounterSemantics, String> {
+        match s {
+            "per-link" => Ok(CounterSemantics::PerLink),
+            "cumulative" => Ok(CounterSemantics::Cumulative),
+            _ => Err(format!("Unknown counter semantics: {}", s)),
+        }
+    }
+}
+
+/// Whether the traffic counted by the interface while the daemon was stopped is counted
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CatchUp {
+    /// The first poll after the daemon starts counts the counters' advance since the last save
+    OnRestart,
+    /// The first poll after the daemon starts only takes the counters as its baseline
+    Off,
+}
+
+impl FromStr for CatchUp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CatchUp, String> {
+        match s {
+            "on-restart" => Ok(CatchUp::OnRestart),
+            "off" => Ok(CatchUp::Off),
+            _ => Err(format!("Unknown catch-up: {}", s)),
+        }
+    }
+}
+
+/// Warning and cutoff network traffic thresholds (megabytes)
+///
+/// Thresholds which have not been defined are `None` and never raise alerts.
+#[derive(Default)]
+struct Threshold {
+    total_warn: Option<u64>, // combined warning threshold
+    total_cut: Option<u64>,  // combined cutoff threshold
+    rx_warn: Option<u64>,    // received warning threshold
+    rx_cut: Option<u64>,     // received cutoff threshold
+    tx_warn: Option<u64>,    // transmitted warning threshold
+    tx_cut: Option<u64>,     // transmitted cutoff threshold
+    inclusive: bool,         // whether a total equal to a threshold reaches it
+}
+
+impl Threshold {
+    /// Retrieve latest alert thresholds from the data store
+    fn get(store: &Store, keys: &Keys) -> Threshold {
+        let get = |direction, level| stored_threshold(store, keys, direction, level);
+
+        Threshold {
+            total_warn: get(Direction::Combined, Level::Warn),
+            total_cut: get(Direction::Combined, Level::Cut),
+            rx_warn: get(Direction::Rx, Level::Warn),
+            rx_cut: get(Direction::Rx, Level::Cut),
+            tx_warn: get(Direction::Tx, Level::Warn),
+            tx_cut: get(Direction::Tx, Level::Cut),
+            inclusive: false,
+        }
+    }
+
+    /// Thresholds in effect at a time
+    ///
+    /// Limits of the connection replace the stored thresholds, and those of
+    /// the active profile take precedence over both.
+    fn active(
+        store: &Store,
+        keys: &Keys,
+        connection: &[(Direction, Level, u64)],
+        profiles: &[Profile],
+        time: u64,
+    ) -> Threshold {
+        let mut threshold = Threshold::get(store, keys);
+        for &(direction, level, bytes) in connection {
+            threshold.set(direction, level, to_megabytes(bytes));
+        }
+        if let Some(profile) = active_profile(profiles, time) {
+            debug!("Profile {} is active", profile.spec);
+            for &(direction, level, bytes) in &profile.thresholds {
+                threshold.set(direction, level, to_megabytes(bytes));
+            }
+        }
+
+        threshold
+    }
+
+    /// Replace a threshold (megabytes)
+    fn set(&mut self, direction: Direction, level: Level, val: u64) {
+        let threshold = match (direction, level) {
+            (Direction::Rx, Level::Warn) => &mut self.rx_warn,
+            (Direction::Rx, Level::Cut) => &mut self.rx_cut,
+            (Direction::Tx, Level::Warn) => &mut self.tx_warn,
+            (Direction::Tx, Level::Cut) => &mut self.tx_cut,
+            (Direction::Combined, Level::Warn) => &mut self.total_warn,
+            (Direction::Combined, Level::Cut) => &mut self.total_cut,
+        };
+        *threshold = Some(val);
+    }
+
+    /// Remove the cutoff thresholds, so that no cutoff alert can be raised
+    fn clear_cuts(&mut self) {
+        self.rx_cut = None;
+        self.tx_cut = None;
+        self.total_cut = None;
+    }
+
+    /// Thresholds which have been defined, by name (e.g. `total_cut`)
+    fn named(&self) -> Vec<(String, u64)> {
+        Direction::ALL
+            .iter()
+            .flat_map(|&direction| {
+                let (warn, cut) = self.of(direction);
+                vec![(Level::Warn, warn), (Level::Cut, cut)]
+                    .into_iter()
+                    .filter_map(move |(level, val)| {
+                        val.map(|val| (threshold_name(direction, level), val))
+                    })
+            })
+            .collect()
+    }
+
+    /// Warning and cutoff thresholds for a direction
+    fn of(&self, direction: Direction) -> (Option<u64>, Option<u64>) {
+        match direction {
+            Direction::Rx => (self.rx_warn, self.rx_cut),
+            Direction::Tx => (self.tx_warn, self.tx_cut),
+            Direction::Combined => (self.total_warn, self.total_cut),
+        }
+    }
+
+    /// Fail if a warning threshold is not below the cutoff of its direction,
+    /// which would raise both alerts at once or leave the warning unreachable
+    fn check_levels(&self) -> Result<(), String> {
+        for &direction in &Direction::ALL {
+            if let (Some(warn), Some(cut)) = self.of(direction) {
+                if warn >= cut {
+                    return Err(format!(
+                        "{} ({}) must be below {} ({})",
+                        threshold_name(direction, Level::Warn),
+                        format_bytes(to_bytes(warn)),
+                        threshold_name(direction, Level::Cut),
+                        format_bytes(to_bytes(cut))
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fail if a threshold (bytes) given on the command line cannot be meant
+///
+/// Thresholds are stored in whole megabytes, so anything smaller would be
+/// stored as 0 and raise its alert immediately; anything above the maximum
+/// is most likely a typo (e.g. `5000GB` for `500GB`).
+fn check_threshold(name: &str, bytes: u64, max: u64) -> Result<(), String> {
+    if to_megabytes(bytes) == 0 {
+        return Err(format!(
+            "Invalid {}: {} is below 1MB, the smallest threshold which can be stored",
+            name,
+            format_bytes(bytes)
+        ));
+    }
+    if bytes > max {
+        return Err(format!(
+            "Invalid {}: {} is larger than {} (raise --max-threshold if this is intended)",
+            name,
+            format_bytes(bytes),
+            format_bytes(max)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validate the thresholds, data plan and caps given on the command line,
+/// before anything is written to the data store
+fn check_threshold_options(opt: &Opt) -> Result<(), String> {
+    let max = opt.max_threshold;
+    if let Some(plan) = opt.plan {
+        check_threshold("plan", plan, max)?;
+    }
+    if let Some(cap) = opt.window_cap {
+        check_threshold("window cap", cap, max)?;
+    }
+    for profile in &opt.profiles {
+        let mut threshold = Threshold::default();
+        for &(direction, level, bytes) in &profile.thresholds {
+            check_threshold(&threshold_name(direction, level), bytes, max)?;
+            threshold.set(direction, level, to_megabytes(bytes));
+        }
+        threshold
+            .check_levels()
+            .map_err(|e| format!("Invalid profile {}: {}", profile.spec, e))?;
+    }
+    if let Some(Command::EnsureThresholds {
+        total_warn,
+        total_cut,
+        rx_warn,
+        rx_cut,
+        tx_warn,
+        tx_cut,
+    }) = opt.cmd
+    {
+        let given = [
+            ("total_warn", total_warn),
+            ("total_cut", total_cut),
+            ("rx_warn", rx_warn),
+            ("rx_cut", rx_cut),
+            ("tx_warn", tx_warn),
+            ("tx_cut", tx_cut),
+        ];
+        for (name, bytes) in given.iter() {
+            if let Some(bytes) = *bytes {
+                check_threshold(name, bytes, max)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the thresholds (bytes) given in the environment, e.g. `PEACH_MONITOR_RX_CUT=5GB`
+///
+/// The variables are those read by `ensure-thresholds`, named after each
+/// threshold. Unset and empty variables are skipped, and the sizes given are
+/// validated as on the command line.
+fn env_thresholds(max: u64) -> Result<Vec<(Direction, Level, Option<u64>)>, String> {
+    let mut thresholds = Vec::new();
+    for &direction in &Direction::ALL {
+        for &level in &Level::ALL {
+            let name = threshold_name(direction, level);
+            let var = format!("PEACH_MONITOR_{}", name.to_uppercase());
+            let value = match env::var(&var) {
+                Ok(value) if !value.trim().is_empty() => value,
+                Ok(_) | Err(env::VarError::NotPresent) => continue,
+                Err(env::VarError::NotUnicode(_)) => return Err(format!("Invalid {}", var)),
+            };
+            let bytes = parse_size(&value).map_err(|e| format!("Invalid {}: {}", var, e))?;
+            check_threshold(&name, bytes, max)?;
+            thresholds.push((direction, level, Some(bytes)));
+        }
+    }
+
+    Ok(thresholds)
+}
+
+/// Warning and cutoff alert flags for a traffic total
+#[derive(Debug)]
+struct Alert {
+    warn: bool,
+    cut: bool,
+}
+
+impl Alert {
+    /// Evaluate a traffic total (bytes) against warning and cutoff thresholds (megabytes)
+    ///
+    /// A total equal to a threshold only reaches it when the thresholds are inclusive.
+    fn evaluate(total: u64, warn: Option<u64>, cut: Option<u64>, inclusive: bool) -> Alert {
+        let reaches = |threshold: Option<u64>| match threshold.map(to_bytes) {
+            Some(threshold) if inclusive => total >= threshold,
+            Some(threshold) => total > threshold,
+            None => false,
+        };
+        Alert {
+            warn: reaches(warn),
+            cut: reaches(cut),
+        }
+    }
+
+    /// Value of the flag for an alert level
+    fn flag(&self, level: Level) -> bool {
+        match level {
+            Level::Warn => self.warn,
+            Level::Cut => self.cut,
+        }
+    }
+}
+
+/// Convert a byte value to whole megabytes
+fn to_megabytes(val: u64) -> u64 {
+    val / (1024 * 1024)
+}
+
+/// Retrieve a value of a file of `net`, storing a default in its place if it is absent
+///
+/// A value which is present is returned as stored, whatever its type, and a
+/// file which cannot be read is reported rather than overwritten.
+fn get_or_init(store: &Store, file: &str, key: &str, default: Value) -> Result<Value, NestError> {
+    let path = [NET, file, key];
+    match store.get(&path) {
+        Ok(value) => Ok(value),
+        Err(nest::Error::GetSchema { .. }) => {
+            store.set(&path, &default)?;
+            Ok(default)
+        }
+        Err(nest::Error::ReadSource { ref source, .. })
+            if source.kind() == io::ErrorKind::NotFound =>
+        {
+            store.set(&path, &default)?;
+            Ok(default)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Store the defaults of the totals and alert flags which are absent
+///
+/// Totals count from zero and flags start lowered, so that every key of the
+/// layout can be read once a save has been made. Thresholds have no default,
+/// since an undefined threshold raises no alert where a zero one would.
+/// Under the per-interface layout the entry of an interface is written
+/// whole by each save, so only the mirrored flat keys are initialised.
+fn init_keys(store: &Store, keys: &Keys) -> Result<(), NestError> {
+    if !keys.flat() {
+        return Ok(());
+    }
+    for &direction in &Direction::ALL {
+        get_or_init(store, TRAFFIC, keys.total(direction), Value::Uint(0))?;
+        for &level in &Level::ALL {
+            for key in keys.alert(direction, level) {
+                get_or_init(store, ALERT, key, Value::Bool(false))?;
+            }
+        }
+    }
+    get_or_init(store, TRAFFIC, TOTALS_VERSION, Value::Uint(0))?;
+
+    Ok(())
+}
+
+/// Write totals or alert flags of an interface to the data store
+///
+/// Values go to the entry of the interface in `net/interfaces` under the
+/// per-interface layout, and to a file of `net` whenever flat keys are in use.
+fn set_values(
+    store: &Store,
+    keys: &Keys,
+    iface: &str,
+    file: &str,
+    values: &[(&str, Value)],
+) -> Result<(), NestError> {
+    if keys.per_interface() {
+        let mut stored = interface_values(store, iface);
+        for (key, value) in values {
+            stored.insert(key.to_string(), value.clone());
+        }
+        store.set(&[NET, INTERFACES, iface], &Value::Object(stored))?;
+    }
+    if keys.flat() {
+        for (key, value) in values {
+            store.set(&[NET, file, key], value)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Retrieve the stored usage total (bytes) for a direction to evaluate its alerts against
+///
+/// A total stored as another kind of number (e.g. `5000.0` written by
+/// another tool) is read as its value if it is whole and not negative. Any
+/// other value is logged and read as zero, as an unset total would be after
+/// a save, so that the alerts of the direction are cleared rather than left
+/// as evaluated against an earlier total.
+fn alert_usage(store: &Store, keys: &Keys, direction: Direction) -> Option<u64> {
+    let key = keys.total(direction);
+    let total = match stored_value(store, keys, TRAFFIC, key)? {
+        Value::Uint(total) => total,
+        Value::Int(total) if total >= 0 => total as u64,
+        Value::Float(total) if total >= 0.0 && total.fract() == 0.0 && total < u64::MAX as f64 => {
+            total as u64
+        }
+        value => {
+            error!(
+                "Stored {} total is not a number of bytes: {:?}; its alerts are evaluated as if it were 0",
+                key, value
+            );
+            return Some(0);
+        }
+    };
+
+    Some(stored_bytes(store, keys, direction, total))
+}
+
+/// Evaluate each stored traffic total against its thresholds
+///
+/// A direction without a counter on the interface never raises its alerts.
+fn alert_totals(store: &Store, keys: &Keys, threshold: &Threshold) -> Vec<(Direction, Alert)> {
+    Direction::ALL
+        .iter()
+        // skip totals which have not been saved yet
+        .filter_map(|&direction| {
+            let (warn, cut) = if counted(store, keys, direction) {
+                threshold.of(direction)
+            } else {
+                (None, None)
+            };
+            alert_usage(store, keys, direction).map(|total| {
+                (
+                    direction,
+                    Alert::evaluate(total, warn, cut, threshold.inclusive),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Whether the monitored interface counts traffic in a direction
+///
+/// Some tunnel and virtual interfaces only count one direction, leaving the
+/// other counter absent or at zero. A direction is taken not to be counted
+/// while its counter and total have stayed at zero as the other direction's
+/// counter advanced.
+fn counted(store: &Store, keys: &Keys, direction: Direction) -> bool {
+    let last = match stored_string(store, IFACE_LAST) {
+        Some(iface) => LastCounters::get(store, keys, &iface),
+        None => return true,
+    };
+    let (counter, other) = match direction {
+        Direction::Rx => (last.rx, last.tx),
+        Direction::Tx => (last.tx, last.rx),
+        Direction::Combined => return true,
+    };
+
+    counter != Some(0)
+        || other.unwrap_or(0) == 0
+        || stored_usage(store, keys, direction).unwrap_or(0) > 0
+}
+
+/// Evaluate traffic values against alert thresholds and set flags
+///
+/// Only the flags for which `evaluates` holds are evaluated and written; the
+/// others keep their stored values, which are returned in their place
+/// alongside the evaluated alerts, paired with their directions.
+fn set_alert_flags(
+    store: &Store,
+    keys: &Keys,
+    threshold: &Threshold,
+    evaluates: impl Fn(AlertFlag) -> bool,
+    attempts: u32,
+) -> Result<Vec<(Direction, Alert)>, NestError> {
+    let mut alerts = alert_totals(store, keys, threshold);
+    // every flag is evaluated before any is written, so that they are written as one batch
+    let mut flags = Vec::new();
+    let mut previous = Vec::new();
+    for (direction, alert) in &mut alerts {
+        let direction = *direction;
+        let (evaluated, kept): (Vec<Level>, Vec<Level>) = Level::ALL
+            .iter()
+            .partition(|&&level| evaluates(AlertFlag::Threshold(direction, level)));
+        for level in kept {
+            let stored =
+                stored_alert(store, keys, keys.alert(direction, level)[0]).unwrap_or(false);
+            match level {
+                Level::Warn => alert.warn = stored,
+                Level::Cut => alert.cut = stored,
+            }
+        }
+        for level in evaluated {
+            for key in keys.alert(direction, level) {
+                flags.push((key, Value::Bool(alert.flag(level))));
+                if let Some(stored) = stored_alert(store, keys, key) {
+                    previous.push((key, Value::Bool(stored)));
+                }
+            }
+        }
+    }
+    write_alert_flags(store, keys, &flags, &previous, attempts)?;
+
+    Ok(alerts)
+}
+
+/// Evaluate the alert flags at a time (unix seconds) and set them
+///
+/// The thresholds are those of the profile active at the time, if any. No
+/// flag is evaluated while the connection is unmetered, so the stored
+/// threshold alerts are returned as they are. In warn-only mode the cutoff
+/// flags are evaluated without any cutoff, leaving them unset.
+fn update_alerts(
+    opt: &Opt,
+    store: &Store,
+    keys: &Keys,
+    time: u64,
+) -> Result<Vec<(Direction, Alert)>, NestError> {
+    let metered = is_metered(store);
+    let evaluates = |flag| metered && opt.evaluates(flag);
+
+    let connection = if opt.nm_thresholds {
+        nm::limits(&opt.iface)
+    } else {
+        Vec::new()
+    };
+    let mut threshold = Threshold::active(store, keys, &connection, &opt.profiles, time);
+    threshold.inclusive = opt.inclusive_thresholds;
+    // the projection warns of the cutoff, so it is still evaluated in warn-only mode
+    let (_, cut) = threshold.of(Direction::Combined);
+    if opt.warn_only {
+        threshold.clear_cuts();
+    }
+    let alerts = set_alert_flags(store, keys, &threshold, evaluates, opt.write_attempts)?;
+    if let Some(window) = opt.window.filter(|_| evaluates(AlertFlag::WindowCut)) {
+        if opt.warn_only {
+            store.set(&[NET, ALERT, WINDOW_CUT_ALERT], &Value::Bool(false))?;
+        } else {
+            set_window_alert(store, window, time, opt.inclusive_thresholds)?;
+        }
+    }
+    if evaluates(AlertFlag::Anomaly) {
+        set_anomaly_alert(store, local_date(time))?;
+    }
+    if evaluates(AlertFlag::UploadAnomaly) {
+        set_upload_anomaly(store, local_date(time), opt.upload_min)?;
+    }
+    if evaluates(AlertFlag::Projection) {
+        set_projection_alert(opt, store, cut, local_date(time))?;
+    }
+    if evaluates(AlertFlag::Budget) {
+        set_budget_alert(opt, store, cut, local_date(time))?;
+    }
+
+    Ok(alerts)
+}
+
+/// Delay before the first retry of a failed alert flag write
+const ALERT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Write a batch of alert flags, retrying the whole batch if a write fails
+///
+/// A failure part-way through a batch would leave some flags updated and
+/// others not, so should every attempt fail the previously stored flags
+/// are written back before the error is returned. Flags belong to the
+/// interface the stored totals were counted on.
+fn write_alert_flags(
+    store: &Store,
+    keys: &Keys,
+    flags: &[(&str, Value)],
+    previous: &[(&str, Value)],
+    attempts: u32,
+) -> Result<(), NestError> {
+    if flags.is_empty() {
+        return Ok(());
+    }
+    let iface = stored_string(store, IFACE_LAST).unwrap_or_default();
+    write_alert_flags_with(flags, previous, attempts, |values| {
+        set_values(store, keys, &iface, ALERT, values)
+    })
+}
+
+/// Write a batch of alert flags with `write`, restoring the previous flags if every attempt fails
+fn write_alert_flags_with(
+    flags: &[(&str, Value)],
+    previous: &[(&str, Value)],
+    attempts: u32,
+    mut write: impl FnMut(&[(&str, Value)]) -> Result<(), NestError>,
+) -> Result<(), NestError> {
+    retry_with_backoff(attempts, ALERT_RETRY_DELAY, || write(flags)).inspect_err(|_| {
+        if let Err(e) = write(previous) {
+            error!("Failed to restore the previous alert flags: {}", e);
+        }
+    })
+}
+
+/// Create the breach file while any cutoff alert is set and remove it once all have cleared
+///
+/// The file lists the breached cutoff alert flags, one per line. It is
+/// written atomically, so that watchdogs never observe it partially written.
+fn update_breach_file(path: &Path, keys: &Keys, alerts: &[(Direction, Alert)]) -> io::Result<()> {
+    let breached: Vec<String> = alerts
+        .iter()
+        .filter(|(_, alert)| alert.cut)
+        .map(|(direction, _)| format!("{}\n", keys.alert(*direction, Level::Cut)[0]))
+        .collect();
+
+    if breached.is_empty() {
+        match fs::remove_file(path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    } else {
+        write_atomically(path, &breached.concat())
+    }
+}
+
+/// Overall alert state written to the status file
+fn alert_state(alerts: &[(Direction, Alert)]) -> &'static str {
+    if alerts.iter().any(|(_, alert)| alert.cut) {
+        "cut"
+    } else if alerts.iter().any(|(_, alert)| alert.warn) {
+        "warn"
+    } else {
+        "ok"
+    }
+}
+
+/// Write the status file: the overall alert state (`ok`, `warn` or `cut`) on
+/// its first line, followed by the totals (bytes) and the time of the update
+///
+/// e.g. `ok`, `rx=1234567`, `tx=89012`, `total=1323579`, `time=1700000000`,
+/// one per line.
+fn update_status_file(
+    path: &Path,
+    store: &Store,
+    keys: &Keys,
+    alerts: &[(Direction, Alert)],
+    time: u64,
+) -> io::Result<()> {
+    let mut contents = format!("{}\n", alert_state(alerts));
+    for &direction in &Direction::ALL {
+        let total = stored_usage(store, keys, direction).unwrap_or(0);
+        contents.push_str(&format!("{}={}\n", direction.as_str(), total));
+    }
+    contents.push_str(&format!("time={}\n", time));
+
+    write_atomically(path, &contents)
+}
+
+/// Write a file through a temporary file renamed into place, so that readers
+/// never observe it partially written
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+/// Path of the kernel's identifier for the current boot
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Retrieve the identifier of the current boot
+fn boot_id() -> Option<String> {
+    fs::read_to_string(BOOT_ID_PATH)
+        .ok()
+        .map(|id| id.trim().to_string())
+}
+
+/// Identifier of the boot the counters of a source belong to
+///
+/// A remote agent's counters do not belong to the boot of this host, and
+/// neither may those printed by a stats command (e.g. of a separate
+/// router), which are left without one: a reset of such counters is
+/// handled as any other decrease under the counter semantics.
+fn counters_boot(source: &Source) -> Option<String> {
+    match source {
+        Source::Proc | Source::Nft(_) => boot_id(),
+        Source::Snmp(agent) => Some(agent.counters_id()),
+        Source::Command(_) => None,
+    }
+}
+
+/// Calculate the traffic (bytes) counted since the last reading of an interface counter
+fn counter_delta(last: u64, current: u64) -> u64 {
+    // a counter lower than its last reading has been reset, so count it from zero
+    if current < last {
+        current
+    } else {
+        current - last
+    }
+}
+
+/// Current time as seconds since the Unix epoch
+fn unix_time() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Counters recorded by the previous save, and the interface and boot they belong to
+struct LastCounters {
+    iface: Option<String>,
+    boot: Option<String>,
+    rx: Option<u64>,
+    tx: Option<u64>,
+}
+
+impl LastCounters {
+    /// Retrieve the counters the next delta of an interface is calculated from
+    ///
+    /// Under the per-interface layout each interface keeps its own counters,
+    /// so that monitors of different interfaces can share a data store.
+    fn get(store: &Store, keys: &Keys, iface: &str) -> LastCounters {
+        if !keys.per_interface() {
+            return LastCounters {
+                iface: stored_string(store, IFACE_LAST),
+                // earlier releases stored an unknown boot as an empty string
+                boot: stored_string(store, BOOT_ID).filter(|boot| !boot.is_empty()),
+                rx: stored_total(store, RX_LAST),
+                tx: stored_total(store, TX_LAST),
+            };
+        }
+
+        let entry = interface_values(store, iface);
+        let uint = |key| match entry.get(key) {
+            Some(Value::Uint(val)) => Some(*val),
+            _ => None,
+        };
+        LastCounters {
+            // an entry without counters predates them being kept per interface
+            iface: uint(RX_LAST).map(|_| iface.to_string()),
+            boot: match entry.get(BOOT_ID) {
+                Some(Value::String(boot)) if !boot.is_empty() => Some(boot.clone()),
+                _ => None,
+            },
+            rx: uint(RX_LAST),
+            tx: uint(TX_LAST),
+        }
+    }
+
+    /// Change in the counters of an interface up to a reading, whether or not it is counted
+    ///
+    /// This is the traffic the link carried since the previous save, as
+    /// shown by the transfer rates, before accounting skips any of it (e.g.
+    /// while paused or unmetered). Counters lower than these are counted
+    /// from zero, and the change is zero when these belong to another
+    /// interface or there are none.
+    fn change(&self, reading: &Reading) -> Traffic {
+        let traffic = &reading.traffic;
+        match (self.iface.as_deref(), self.rx, self.tx) {
+            (Some(iface), Some(rx), Some(tx)) if iface == reading.iface => {
+                if rebooted(&self.boot, &reading.boot) {
+                    Traffic {
+                        rx: traffic.rx,
+                        tx: traffic.tx,
+                    }
+                } else {
+                    Traffic {
+                        rx: counter_delta(rx, traffic.rx),
+                        tx: counter_delta(tx, traffic.tx),
+                    }
+                }
+            }
+            _ => Traffic { rx: 0, tx: 0 },
+        }
+    }
+}
+
+/// Change in the interface counters since the previous save (bytes)
+#[derive(Debug)]
+struct Delta {
+    rx: u64,
+    tx: u64,
+    basis: &'static str, // how the delta was calculated
+}
+
+impl Delta {
+    /// Calculate the delta between the stored and latest interface counters
+    ///
+    /// Counters lower than their last reading are counted from zero under
+    /// per-link semantics. Under cumulative semantics the decrease is logged
+    /// and the affected counter is re-baselined without counting any traffic.
+    fn calculate(
+        iface: &str,
+        traffic: &Traffic,
+        store: &Store,
+        last: &LastCounters,
+        boot: &Option<String>,
+        semantics: CounterSemantics,
+    ) -> Delta {
+        let seen = match store.get(&[NET, TRAFFIC, FIRST_SEEN]) {
+            Ok(Value::Object(seen)) => seen.contains_key(iface),
+            _ => false,
+        };
+
+        let paused = matches!(store.get(&[NET, TRAFFIC, PAUSED]), Ok(Value::Bool(true)));
+
+        let (rx, tx, basis) = if !seen {
+            // first poll for this interface: the counters become the baseline
+            (0, 0, "first poll of interface")
+        } else if paused {
+            // the counters still advance, so resuming counts from the latest reading
+            (0, 0, "accounting paused")
+        } else if last.iface.as_deref() != Some(iface) {
+            (0, 0, "stored counters belong to another interface")
+        } else if rebooted(&last.boot, boot) {
+            // interface counters start from zero on each boot
+            (traffic.rx, traffic.tx, "counters reset by reboot")
+        } else {
+            let rx_last = last.rx.unwrap_or(0);
+            let tx_last = last.tx.unwrap_or(0);
+            if traffic.rx >= rx_last && traffic.tx >= tx_last {
+                (
+                    traffic.rx - rx_last,
+                    traffic.tx - tx_last,
+                    "change since last reading",
+                )
+            } else if semantics == CounterSemantics::PerLink {
+                (
+                    counter_delta(rx_last, traffic.rx),
+                    counter_delta(tx_last, traffic.tx),
+                    "counters reset by link bounce",
+                )
+            } else {
+                warn!(
+                    "{}: counters decreased unexpectedly (rx {} to {}, tx {} to {}); \
+                     counting from the latest reading",
+                    iface, rx_last, traffic.rx, tx_last, traffic.tx
+                );
+                (
+                    traffic.rx.saturating_sub(rx_last),
+                    traffic.tx.saturating_sub(tx_last),
+                    "counters decreased unexpectedly",
+                )
+            }
+        };
+
+        Delta { rx, tx, basis }
+    }
+}
+
+/// Whether the boot of a reading differs from that of the stored counters
+///
+/// A boot which is not known on either side is taken to be unchanged, so
+/// that the change in the counters is counted rather than the whole of them.
+fn rebooted(last: &Option<String>, current: &Option<String>) -> bool {
+    matches!((last, current), (Some(last), Some(current)) if last != current)
+}
+
+/// Describe the raw counters, the stored counters and the delta calculated from them
+fn counter_report(iface: &str, traffic: &Traffic, last: &LastCounters, delta: &Delta) -> String {
+    let show = |counter: Option<u64>| {
+        counter
+            .map(group_digits)
+            .unwrap_or_else(|| "none".to_string())
+    };
+    format!(
+        "{}: rx {} (rx_last {}, delta {}), tx {} (tx_last {}, delta {}); {}",
+        iface,
+        group_digits(traffic.rx),
+        show(last.rx),
+        group_digits(delta.rx),
+        group_digits(traffic.tx),
+        show(last.tx),
+        group_digits(delta.tx),
+        delta.basis
+    )
+}
+
+/// Date of a time (unix seconds) in the local timezone
+fn local_date(time: u64) -> NaiveDate {
+    match Local.timestamp_opt(time as i64, 0).single() {
+        Some(time) => time.date_naive(),
+        None => Local::now().date_naive(),
+    }
+}
+
+/// The first of the profiles which is active at a time (unix seconds), in the local timezone
+fn active_profile(profiles: &[Profile], time: u64) -> Option<&Profile> {
+    let time: NaiveDateTime = Local.timestamp_opt(time as i64, 0).single()?.naive_local();
+    profile::active(profiles, &time)
+}
+
+/// Read the interface counters for a save, appending them to a trace when recording
+fn take_reading(
+    iface: &str,
+    source: &Source,
+    record: Option<&Path>,
+) -> Result<Reading, NetworkError> {
+    let reading = Reading {
+        traffic: Traffic::get(iface, source)?,
+        time: unix_time(),
+        iface: iface.to_string(),
+        boot: counters_boot(source),
+    };
+    if let Some(path) = record {
+        // the trace is a debugging aid, so failing to write it does not stop accounting
+        if let Err(e) = trace::append(path, &reading) {
+            error!("Failed to record reading to {}: {}", path.display(), e);
+        }
+    }
+
+    Ok(reading)
+}
+
+/// Print the raw interface counters and the delta the next save would count
+fn print_counters(
+    iface: &str,
+    source: &Source,
+    store: &Store,
+    keys: &Keys,
+    semantics: CounterSemantics,
+) -> Result<(), NetworkError> {
+    let traffic = Traffic::get(iface, source)?;
+    let last = LastCounters::get(store, keys, iface);
+    let boot = counters_boot(source);
+    let delta = Delta::calculate(iface, &traffic, store, &last, &boot, semantics);
+    println!("{}", counter_report(iface, &traffic, &last, &delta));
+
+    Ok(())
+}
+
+/// Calculate and store the latest network transmission totals
+///
+/// Totals are accumulated from the change in the interface counters since
+/// the previous save. The first time an interface is seen, its counters
+/// are recorded as a baseline and no usage is counted, so that totals
+/// represent usage since monitoring started rather than since the
+/// interface came up. During an unmetered profile the counters are
+/// recorded without counting any usage.
+fn update_transmission_totals(
+    opt: &Opt,
+    reading: &Reading,
+    store: &Store,
+    keys: &Keys,
+    restarted: bool,
+) -> Result<Delta, BoxError> {
+    init_keys(store, keys)?;
+    let (iface, traffic) = (&reading.iface, &reading.traffic);
+    let last = LastCounters::get(store, keys, iface);
+    let semantics = opt.counter_semantics;
+    let mut delta = Delta::calculate(iface, traffic, store, &last, &reading.boot, semantics);
+    // the counters are stored all the same, so that the next poll counts from them
+    if restarted && opt.catch_up == CatchUp::Off && delta.rx + delta.tx > 0 {
+        info!(
+            "{}: not counting {} of traffic since the last save, made while the daemon was stopped",
+            iface,
+            format_bytes(delta.rx + delta.tx)
+        );
+        delta = Delta {
+            rx: 0,
+            tx: 0,
+            basis: "traffic while stopped ignored",
+        };
+    }
+    if opt.detect_metered {
+        match metered::detect(iface) {
+            Some(metered) if metered != is_metered(store) => set_metered(store, metered)?,
+            // the stored state applies while it is unchanged or cannot be detected
+            _ => (),
+        }
+    }
+    // the counters are still stored, so that unmetered traffic is never counted later
+    let unmetered = if active_profile(&opt.profiles, reading.time).is_some_and(|p| p.unmetered) {
+        Some("unmetered profile")
+    } else if !is_metered(store) {
+        Some("unmetered connection")
+    } else {
+        None
+    };
+    if let Some(basis) = unmetered {
+        delta = Delta {
+            rx: 0,
+            tx: 0,
+            basis,
+        };
+    }
+    // dropped rather than carried over, as the counters are stored regardless
+    let total = delta.rx + delta.tx;
+    if opt.min_delta.is_some_and(|min| total > 0 && total < min) {
+        delta = Delta {
+            rx: 0,
+            tx: 0,
+            basis: "below minimum delta",
+        };
+    }
+    // inflate the delta itself, so that the totals, history and samples all include the overhead
+    if let Some(percent) = opt.overhead_percent {
+        let inflate = |bytes: u64| (bytes as f64 * (1.0 + percent / 100.0)).round() as u64;
+        delta.rx = inflate(delta.rx);
+        delta.tx = inflate(delta.tx);
+    }
+    debug!("{}", counter_report(iface, traffic, &last, &delta));
+
+    // the delta of the first save after a boot is the traffic of the boot so far
+    if opt.since_boot && rebooted(&last.boot, &reading.boot) {
+        info!("{}: new boot; resetting the totals", iface);
+        let current = Totals::get(store, keys);
+        let totals = Totals {
+            rx: 0,
+            tx: 0,
+            total: 0,
+            version: current.version + 1,
+        };
+        totals.set(store, keys, iface)?;
+    }
+    store_delta(reading, &delta, store, keys, opt.delta_mode)?;
+    let today = local_date(reading.time);
+    let thresholds = if opt.history_thresholds {
+        Some(Threshold::get(store, keys).named())
+    } else {
+        None
+    };
+    let new_day = history::record(
+        store,
+        today,
+        delta.rx,
+        delta.tx,
+        opt.history_days,
+        thresholds.as_deref(),
+    )?;
+    // the baseline only changes at a day boundary, unless none could be set before
+    if new_day || stored_total(store, BASELINE).is_none() {
+        update_baseline(store, today, opt.baseline_days)?;
+    }
+    if new_day || store.get(&[NET, TRAFFIC, RATIO_BASELINE]).is_err() {
+        update_ratio_baseline(store, today, opt.ratio_baseline_days)?;
+    }
+    if opt.forecast_basis == ForecastBasis::Ema
+        && (new_day || stored_total(store, DAILY_EMA).is_none())
+    {
+        update_daily_ema(store, today, opt.ema_alpha)?;
+    }
+
+    Ok(delta)
+}
+
+/// Store the average daily usage of the days before a date as the baseline
+fn update_baseline(store: &Store, date: NaiveDate, days: u32) -> Result<(), NestError> {
+    if let Some(baseline) = history::baseline(&history::load(store), date, days) {
+        debug!("Usage baseline: {} bytes per day", group_digits(baseline));
+        store.set(&[NET, TRAFFIC, BASELINE], &Value::Uint(baseline))?;
+    }
+
+    Ok(())
+}
+
+/// Store the ratio of transmitted to received traffic over the days before a date as the ratio
+/// baseline
+fn update_ratio_baseline(store: &Store, date: NaiveDate, days: u32) -> Result<(), NestError> {
+    if let Some(ratio) = history::upload_ratio(&history::load(store), date, days) {
+        debug!("Upload ratio baseline: {:.3}", ratio);
+        store.set(&[NET, TRAFFIC, RATIO_BASELINE], &Value::Float(ratio))?;
+    }
+
+    Ok(())
+}
+
+/// Fold the days before a date which are not yet in the moving average of daily usage into it
+fn update_daily_ema(store: &Store, date: NaiveDate, alpha: f64) -> Result<(), NestError> {
+    let state = stored_total(store, DAILY_EMA).zip(stored_ema_date(store));
+    let updated = history::update_ema(&history::load(store), state, date, alpha);
+    if let Some((average, last)) = updated.filter(|&updated| Some(updated) != state) {
+        debug!(
+            "Moving average of daily usage: {} bytes (up to {})",
+            group_digits(average),
+            last
+        );
+        store.set(&[NET, TRAFFIC, DAILY_EMA], &Value::Uint(average))?;
+        let last = Value::String(last.format("%Y-%m-%d").to_string());
+        store.set(&[NET, TRAFFIC, EMA_DATE], &last)?;
+    }
+
+    Ok(())
+}
+
+/// Last day folded into the moving average of daily usage
+fn stored_ema_date(store: &Store) -> Option<NaiveDate> {
+    stored_string(store, EMA_DATE).and_then(|date| date.parse().ok())
+}
+
+/// Add a delta to the stored totals and record the counters it was calculated from
+///
+/// In delta mode the delta replaces the previous one instead, leaving the
+/// totals untouched for consumers which accumulate the deltas themselves.
+fn store_delta(
+    reading: &Reading,
+    delta: &Delta,
+    store: &Store,
+    keys: &Keys,
+    delta_mode: bool,
+) -> Result<(), NestError> {
+    let (iface, traffic) = (reading.iface.as_str(), &reading.traffic);
+
+    // mark the interface as seen from now on
+    let mut first_seen = match store.get(&[NET, TRAFFIC, FIRST_SEEN]) {
+        Ok(Value::Object(seen)) => seen,
+        _ => IndexMap::new(),
+    };
+    if !first_seen.contains_key(iface) {
+        first_seen.insert(iface.to_string(), Value::Uint(reading.time));
+        store.set(&[NET, TRAFFIC, FIRST_SEEN], &Value::Object(first_seen))?;
+    }
+
+    // record the counters the next delta is calculated from, first so that
+    // the totals below are those of this interface under the per-interface layout
+    let mut last = vec![
+        (RX_LAST, Value::Uint(traffic.rx)),
+        (TX_LAST, Value::Uint(traffic.tx)),
+        (IFACE_LAST, Value::String(iface.to_string())),
+    ];
+    // an unknown boot leaves the stored one, which is still that of the counters if known
+    if let Some(ref boot) = reading.boot {
+        last.push((BOOT_ID, Value::String(boot.clone())));
+    }
+    for (key, val) in &last {
+        store.set(&[NET, TRAFFIC, key], val)?;
+    }
+    if keys.per_interface() {
+        let mut entry = interface_values(store, iface);
+        for (key, val) in last.iter().filter(|(key, _)| *key != IFACE_LAST) {
+            entry.insert(key.to_string(), val.clone());
+        }
+        store.set(&[NET, INTERFACES, iface], &Value::Object(entry))?;
+    }
+    label_interfaces(store, keys, iface)?;
+
+    if delta_mode {
+        let values = [
+            (RX_DELTA, Value::Uint(delta.rx)),
+            (TX_DELTA, Value::Uint(delta.tx)),
+            // lets consumers tell a new delta from one they have already counted
+            (DELTA_TIME, Value::Uint(reading.time)),
+        ];
+        return set_values(store, keys, iface, TRAFFIC, &values);
+    }
+
+    // store updated network traffic statistics (totals)
+    add_totals(store, keys, iface, delta)
+}
+
+/// Store the name of the monitored interface, and under the per-interface
+/// layout those of all interfaces holding totals, for consumers to label them
+///
+/// Each is only written when it changes, e.g. when another interface is monitored.
+fn label_interfaces(store: &Store, keys: &Keys, iface: &str) -> Result<(), NestError> {
+    if stored_string(store, IFACE).as_deref() != Some(iface) {
+        store.set(&[NET, TRAFFIC, IFACE], &Value::String(iface.to_string()))?;
+    }
+    if keys.per_interface() {
+        let ifaces: Vec<Value> = monitored_interfaces(store, keys)
+            .into_iter()
+            .map(Value::String)
+            .collect();
+        if store.get(&[NET, TRAFFIC, IFACES]).ok() != Some(Value::Array(ifaces.clone())) {
+            store.set(&[NET, TRAFFIC, IFACES], &Value::Array(ifaces))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of the monitored interface, falling back to that of the stored counters
+/// for data stores written before it was stored
+fn monitored_interface(store: &Store) -> Option<String> {
+    stored_string(store, IFACE).or_else(|| stored_string(store, IFACE_LAST))
+}
+
+/// Names of the interfaces holding totals
+///
+/// Under the flat layouts only the monitored interface holds totals.
+fn monitored_interfaces(store: &Store, keys: &Keys) -> Vec<String> {
+    if keys.per_interface() {
+        interface_entries(store, keys)
+            .into_iter()
+            .map(|(iface, _)| iface)
+            .collect()
+    } else {
+        monitored_interface(store).into_iter().collect()
+    }
+}
+
+/// Usage totals (bytes) of the monitored interface and the version of their last write
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Totals {
+    rx: u64,
+    tx: u64,
+    total: u64,
+    version: u64,
+}
+
+impl Totals {
+    /// Retrieve the stored totals, any of which are 0 if not yet stored
+    fn get(store: &Store, keys: &Keys) -> Totals {
+        let version = match stored_value(store, keys, TRAFFIC, TOTALS_VERSION) {
+            Some(Value::Uint(version)) => version,
+            _ => 0,
+        };
+        Totals {
+            rx: stored_usage(store, keys, Direction::Rx).unwrap_or(0),
+            tx: stored_usage(store, keys, Direction::Tx).unwrap_or(0),
+            total: stored_usage(store, keys, Direction::Combined).unwrap_or(0),
+            version,
+        }
+    }
+
+    /// Totals with usage added, as the next version
+    fn add(self, rx: u64, tx: u64) -> Totals {
+        Totals {
+            rx: self.rx + rx,
+            tx: self.tx + tx,
+            total: self.total + rx + tx,
+            version: self.version + 1,
+        }
+    }
+
+    fn set(self, store: &Store, keys: &Keys, iface: &str) -> Result<(), NestError> {
+        let unit = keys.unit();
+        let totals = [
+            (Direction::Rx, self.rx),
+            (Direction::Tx, self.tx),
+            (Direction::Combined, self.total),
+        ];
+        let mut values: Vec<(&str, Value)> = totals
+            .iter()
+            .map(|&(direction, bytes)| (keys.total(direction), Value::Uint(bytes / unit.bytes())))
+            .collect();
+        // the unit is recorded once it is not bytes, and from then on, so that
+        // totals stored in bytes keep the keys they always had
+        if unit != TotalUnit::Bytes || stored_value(store, keys, TRAFFIC, TOTAL_UNIT).is_some() {
+            values.push((TOTAL_UNIT, Value::String(unit.as_str().to_string())));
+            for &(direction, bytes) in &totals {
+                values.push((keys.carry(direction), Value::Uint(bytes % unit.bytes())));
+            }
+        }
+        // written last, so that it only moves on once the totals have
+        values.push((TOTALS_VERSION, Value::Uint(self.version)));
+        set_values(store, keys, iface, TRAFFIC, &values)
+    }
+}
+
+/// Number of times a delta is applied to the totals before conflicting writes are ignored
+const TOTALS_ATTEMPTS: u32 = 5;
+
+/// Add the usage counted by a save to the stored totals
+///
+/// The data store cannot compare and set, and each write rewrites a whole
+/// file, so another process saving at the same time (e.g. a cron `--save`
+/// alongside the daemon) could overwrite the totals with stale values. The
+/// totals are therefore written optimistically: they are read back after
+/// the write, and the delta is re-applied to the latest totals whenever the
+/// write was lost.
+fn add_totals(store: &Store, keys: &Keys, iface: &str, delta: &Delta) -> Result<(), NestError> {
+    add_totals_with(store, keys, delta, |totals| totals.set(store, keys, iface))
+}
+
+/// Add the usage counted by a save to the stored totals, writing them with `write`
+fn add_totals_with(
+    store: &Store,
+    keys: &Keys,
+    delta: &Delta,
+    mut write: impl FnMut(Totals) -> Result<(), NestError>,
+) -> Result<(), NestError> {
+    for _ in 0..TOTALS_ATTEMPTS {
+        let updated = Totals::get(store, keys).add(delta.rx, delta.tx);
+        write(updated)?;
+
+        // a later version was made from this write, since it had to be read first
+        let stored = Totals::get(store, keys);
+        if stored == updated || stored.version > updated.version {
+            return Ok(());
+        }
+        debug!("Totals were overwritten while being updated, retrying");
+    }
+
+    warn!("Totals kept changing while being updated; another process may be writing to the data store");
+    write(Totals::get(store, keys).add(delta.rx, delta.tx))
+}
+
+/// Write the usage counted by a save at a time (unix seconds) to the backends besides the data store
+///
+/// The data store is not one of the backends: it holds the totals the
+/// delta is counted against and the alerts are evaluated on, so it is
+/// written first and its failure fails the save. Backends which fail are
+/// logged rather than failing the save, whose totals are already stored.
+fn write_sinks(sinks: &[Box<dyn Sink>], iface: &str, delta: &Delta, time: u64) {
+    if sinks.is_empty() {
+        return;
+    }
+    let record = Record {
+        time,
+        iface,
+        rx: delta.rx,
+        tx: delta.tx,
+    };
+    let written = sink::write_all(sinks, &record);
+    debug!("Wrote usage to {} of {} backends", written, sinks.len());
+}
+
+/// Record the usage counted by a save as a sample for the trailing window
+fn record_sample(
+    store: &Store,
+    delta: &Delta,
+    window: Duration,
+    time: u64,
+) -> Result<(), NestError> {
+    let mut samples = Samples::load(store);
+    let sample = Sample {
+        time,
+        rx: delta.rx,
+        tx: delta.tx,
+    };
+    samples.record(sample, window);
+    samples.save(store)
+}
+
+/// Evaluate combined usage over the trailing window against the window cap and set its flag
+///
+/// Until the samples cover the whole window the total is a lower bound, so
+/// a breach is still reported but the flag cannot otherwise be relied upon.
+fn set_window_alert(
+    store: &Store,
+    window: Duration,
+    time: u64,
+    inclusive: bool,
+) -> Result<bool, NestError> {
+    let total = Samples::load(store).total(time, window);
+    if !total.complete {
+        debug!(
+            "Samples do not yet cover the {}s window; the window total is incomplete",
+            window.as_secs()
+        );
+    }
+
+    let cap = match store.get(&[NET, NOTIFY, WINDOW_CUT]) {
+        Ok(Value::Uint(cap)) => Some(cap),
+        _ => None,
+    };
+    let alert = Alert::evaluate(total.rx + total.tx, None, cap, inclusive).cut;
+    store.set(&[NET, ALERT, WINDOW_CUT_ALERT], &Value::Bool(alert))?;
+
+    Ok(alert)
+}
+
+/// Evaluate the usage of a day (today) against the baseline and set the anomaly flag
+///
+/// The flag is left alone until both an anomaly factor and a baseline have
+/// been stored.
+fn set_anomaly_alert(store: &Store, today: NaiveDate) -> Result<Option<bool>, NestError> {
+    let factor = match store.get(&[NET, NOTIFY, ANOMALY_FACTOR]) {
+        Ok(Value::Float(factor)) => factor,
+        _ => return Ok(None),
+    };
+    let baseline = match stored_total(store, BASELINE) {
+        Some(baseline) => baseline,
+        None => return Ok(None),
+    };
+
+    let today = history::load(store)
+        .get(&today)
+        .map_or(0, |day| day.rx + day.tx);
+    let alert = today as f64 > baseline as f64 * factor;
+    store.set(&[NET, ALERT, ANOMALY_ALERT], &Value::Bool(alert))?;
+
+    Ok(Some(alert))
+}
+
+/// Evaluate the ratio of transmitted to received traffic of a day (today) against the ratio
+/// baseline and set the upload anomaly flag
+///
+/// The flag is left alone until both an upload factor and a ratio baseline
+/// have been stored. It is lowered while the day has transmitted no more
+/// than `min_tx` bytes, and raised if it has received nothing but
+/// transmitted more than that.
+fn set_upload_anomaly(
+    store: &Store,
+    today: NaiveDate,
+    min_tx: u64,
+) -> Result<Option<bool>, NestError> {
+    let factor = match store.get(&[NET, NOTIFY, UPLOAD_FACTOR]) {
+        Ok(Value::Float(factor)) => factor,
+        _ => return Ok(None),
+    };
+    let baseline = match store.get(&[NET, TRAFFIC, RATIO_BASELINE]) {
+        Ok(Value::Float(baseline)) => baseline,
+        _ => return Ok(None),
+    };
+
+    let (rx, tx) = history::load(store)
+        .get(&today)
+        .map_or((0, 0), |day| (day.rx, day.tx));
+    let alert = tx > min_tx && history::ratio(rx, tx).is_none_or(|ratio| ratio > baseline * factor);
+    store.set(&[NET, ALERT, UPLOAD_ANOMALY], &Value::Bool(alert))?;
+
+    Ok(Some(alert))
+}
+
+/// Project the combined total for the end of the billing cycle and set the projection flag
+/// while it exceeds the cutoff (megabytes)
+///
+/// The usage of the cycle so far is that of its days in the history, rather
+/// than the stored total, which spans every cycle since the last reset. The
+/// flag is left alone until a cycle day and a combined cutoff have been
+/// set, and under the moving average basis until an average is stored. On
+/// either basis it is lowered while fewer than `min_days` days of the cycle
+/// have elapsed, since the usage of its first days says little about the
+/// rest of it. Once raised, it is only cleared when the projection
+/// falls below the cutoff by the clear margin, as the daily usage it is
+/// projected at moves up and down from one day to the next.
+fn set_projection_alert(
+    opt: &Opt,
+    store: &Store,
+    cut: Option<u64>,
+    today: NaiveDate,
+) -> Result<Option<bool>, NestError> {
+    let (cycle, cut) = match (stored_cycle(store, today), cut) {
+        (Some(cycle), Some(cut)) => (cycle, cut),
+        _ => return Ok(None),
+    };
+    let elapsed = cycle.elapsed(today);
+    let history = history::load(store);
+    let elapsed_usage: u64 = history
+        .range(cycle.start..today)
+        .map(|(_, day)| day.rx + day.tx)
+        .sum();
+    let daily = match opt.forecast_basis {
+        ForecastBasis::Ema => match stored_total(store, DAILY_EMA) {
+            Some(_) if elapsed < i64::from(opt.projection_min_days) => None,
+            Some(average) => Some(average),
+            None => return Ok(None),
+        },
+        ForecastBasis::Average if elapsed < i64::from(opt.projection_min_days) => None,
+        // on the first day of a cycle no day has elapsed to average
+        ForecastBasis::Average => Some(elapsed_usage / elapsed.max(1) as u64),
+    };
+
+    let alert = match daily {
+        Some(daily) => {
+            let total = elapsed_usage + history.get(&today).map_or(0, |day| day.rx + day.tx);
+            let projected = cycle.projection(today, total, daily);
+            debug!(
+                "Projected usage by {}: {} bytes",
+                cycle.end,
+                group_digits(projected)
+            );
+            let raised = Alert::evaluate(projected, None, Some(cut), opt.inclusive_thresholds).cut;
+            let clear_below = to_bytes(cut) as f64 * (1.0 - opt.projection_clear_margin / 100.0);
+            let previous = matches!(
+                store.get(&[NET, ALERT, PROJECTION_OVER]),
+                Ok(Value::Bool(true))
+            );
+            raised || (previous && projected as f64 >= clear_below)
+        }
+        None => false,
+    };
+    store.set(&[NET, ALERT, PROJECTION_OVER], &Value::Bool(alert))?;
+
+    Ok(Some(alert))
+}
+
+/// Store the daily budget of the billing cycle, then set the flag of
+/// today's usage exceeding it
+///
+/// The budget is what remains of the combined cutoff (megabytes) after the
+/// usage of the days of the cycle before today, spread over the days left,
+/// so it stays the same throughout the day. That usage is read from the
+/// history rather than the stored total, which spans every cycle since the
+/// last reset. The flag is left alone until a
+/// cycle day and a combined cutoff are set. It advises on pacing rather
+/// than enforcing the cutoff, so is still evaluated in warn-only mode.
+fn set_budget_alert(
+    opt: &Opt,
+    store: &Store,
+    cut: Option<u64>,
+    today: NaiveDate,
+) -> Result<Option<bool>, NestError> {
+    let (cycle, cut) = match (stored_cycle(store, today), cut) {
+        (Some(cycle), Some(cut)) => (cycle, cut),
+        _ => return Ok(None),
+    };
+    let history = history::load(store);
+    let used_before: u64 = history
+        .range(cycle.start..today)
+        .map(|(_, day)| day.rx + day.tx)
+        .sum();
+    let used_today = history.get(&today).map_or(0, |day| day.rx + day.tx);
+    let budget = cycle.daily_budget(today, to_bytes(cut), used_before);
+    debug!(
+        "Daily budget until {}: {} bytes",
+        cycle.end,
+        group_digits(budget)
+    );
+    store.set(&[NET, TRAFFIC, DAILY_BUDGET], &Value::Uint(budget))?;
+
+    let alert = if opt.inclusive_thresholds {
+        used_today >= budget
+    } else {
+        used_today > budget
+    };
+    store.set(&[NET, ALERT, BUDGET_OVER], &Value::Bool(alert))?;
+
+    Ok(Some(alert))
+}
+
+/// Alert flags in the data store, by the keys they are read from (renamed or of the layout)
+fn alert_flags<'a>(store: &Store, keys: &'a Keys) -> Vec<(&'a str, bool)> {
+    let mut flags: Vec<(&str, bool)> = Direction::ALL
+        .iter()
+        .flat_map(|&direction| {
+            Level::ALL
+                .iter()
+                .map(move |&level| keys.alert(direction, level)[0])
+        })
+        .filter_map(|name| stored_alert(store, keys, name).map(|value| (name, value)))
+        .collect();
+    // the trailing window and the baseline are not tracked per interface
+    for &name in &[
+        WINDOW_CUT_ALERT,
+        ANOMALY_ALERT,
+        PROJECTION_OVER,
+        UPLOAD_ANOMALY,
+        BUDGET_OVER,
+    ] {
+        if let Ok(Value::Bool(value)) = store.get(&[NET, ALERT, name]) {
+            flags.push((name, value));
+        }
+    }
+
+    flags
+}
+
+/// Check that an alert flag is known, by the name of its key
+fn check_alert_flag(keys: &Keys, flag: &str) -> Result<(), String> {
+    let mut known: Vec<&str> = Direction::ALL
+        .iter()
+        .flat_map(|&direction| {
+            Level::ALL
+                .iter()
+                .map(move |&level| keys.alert(direction, level)[0])
+        })
+        .collect();
+    known.extend_from_slice(&[
+        WINDOW_CUT_ALERT,
+        ANOMALY_ALERT,
+        PROJECTION_OVER,
+        UPLOAD_ANOMALY,
+        BUDGET_OVER,
+    ]);
+    if !known.contains(&flag) {
+        return Err(format!(
+            "Unknown alert flag: {} (expected one of {})",
+            flag,
+            known.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Wait until an alert flag is raised, reading the stored flags at each interval
+///
+/// The flags are read as last set by a save with `-u` or the daemon. An
+/// error is returned once the timeout, if any, passes with the flag unset.
+fn watch_alerts(
+    store: &Store,
+    keys: &Keys,
+    flag: &str,
+    interval: Duration,
+    timeout: Option<Duration>,
+    palette: Palette,
+) -> Result<(), BoxError> {
+    check_alert_flag(keys, flag)?;
+
+    let cut = flag == WINDOW_CUT_ALERT
+        || Direction::ALL
+            .iter()
+            .any(|&direction| keys.alert(direction, Level::Cut)[0] == flag);
+    let color = if cut { Color::Red } else { Color::Yellow };
+
+    let start = Instant::now();
+    loop {
+        if alert_flags(store, keys).contains(&(flag, true)) {
+            let iface = stored_string(store, IFACE_LAST).unwrap_or_default();
+            println!(
+                "{} {} raised on {}",
+                format_local_time(unix_time()),
+                palette.paint(flag, color),
+                iface
+            );
+            return Ok(());
+        }
+
+        let wait = match timeout {
+            Some(timeout) => match timeout.checked_sub(start.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => remaining.min(interval),
+                _ => {
+                    return Err(
+                        format!("{} was not raised within {}s", flag, timeout.as_secs()).into(),
+                    )
+                }
+            },
+            None => interval,
+        };
+        thread::sleep(wait);
+    }
+}
+
+/// Notify each backend of the flags which changed since an earlier reading
+///
+/// Notifications are delivered in the background; the returned handles
+/// finish with them.
+fn notify_alert_changes(
+    backends: &[Backend],
+    iface: &str,
+    store: &Store,
+    keys: &Keys,
+    before: &[(&str, bool)],
+) -> Vec<JoinHandle<()>> {
+    hook::edges(before, &alert_flags(store, keys))
+        .into_iter()
+        .flat_map(|(flag, value)| {
+            notification::dispatch(backends, &alert_event(iface, store, keys, flag, value))
+        })
+        .collect()
+}
+
+/// Event of an alert flag being raised or cleared, with the stored totals
+fn alert_event(iface: &str, store: &Store, keys: &Keys, flag: &str, value: bool) -> Event {
+    Event {
+        flag: flag.to_string(),
+        value,
+        iface: iface.to_string(),
+        rx: stored_usage(store, keys, Direction::Rx).unwrap_or(0),
+        tx: stored_usage(store, keys, Direction::Tx).unwrap_or(0),
+    }
+}
+
+/// Notify every backend of an alert flag being raised, then cleared, without setting it
+///
+/// The flag is only cleared with the backends afterwards if it is not
+/// raised in the data store, so that they are left in the real state.
+fn test_alert(
+    backends: &[Backend],
+    iface: &str,
+    store: &Store,
+    keys: &Keys,
+    flag: &str,
+) -> Result<(), BoxError> {
+    check_alert_flag(keys, flag)?;
+    if backends.is_empty() {
+        return Err("No notification backends to test (see --notify and --on-alert)".into());
+    }
+
+    let raised = alert_flags(store, keys).contains(&(flag, true));
+    let edges: &[bool] = if raised { &[true] } else { &[true, false] };
+    let mut failed = 0;
+    for &value in edges {
+        let event = alert_event(iface, store, keys, flag, value);
+        let change = if value { "raised" } else { "cleared" };
+        for (backend, outcome) in notification::deliver(backends, &event) {
+            match outcome {
+                Ok(()) => println!("Notified {} of {} being {}", backend, flag, change),
+                Err(e) => {
+                    println!(
+                        "Failed to notify {} of {} being {}: {}",
+                        backend, flag, change, e
+                    );
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if raised {
+        println!(
+            "{} is raised in the data store, so it was not cleared",
+            flag
+        );
+    }
+    if failed > 0 {
+        return Err(format!("{} test notification(s) failed", failed).into());
+    }
+
+    Ok(())
+}
+
+/// Pause or resume accounting
+fn set_paused(store: &Store, paused: bool) -> Result<(), NestError> {
+    store.set(&[NET, TRAFFIC, PAUSED], &Value::Bool(paused))?;
+
+    Ok(())
+}
+
+/// Mark the connection as metered or unmetered
+fn set_metered(store: &Store, metered: bool) -> Result<(), NestError> {
+    store.set(&[NET, TRAFFIC, METERED], &Value::Bool(metered))?;
+
+    Ok(())
+}
+
+/// Record the transfer rates of a poll along with the (wall-clock) time of the update
+///
+/// The update time is recorded even when no rates could be measured, while
+/// the rates of an earlier poll are left in place.
+fn store_rate(store: &Store, rate: Option<&Rate>) -> Result<(), NestError> {
+    if let Some(rate) = rate {
+        store.set(&[NET, TRAFFIC, RX_RATE], &Value::Uint(rate.rx))?;
+        store.set(&[NET, TRAFFIC, TX_RATE], &Value::Uint(rate.tx))?;
+    }
+    store.set(&[NET, TRAFFIC, UPDATED], &Value::Uint(unix_time()))?;
+
+    Ok(())
+}
+
+/// Structured fields describing the outcome of a poll
+///
+/// Field names follow the journal convention (e.g. `RX_BYTES`, `RX_CUT_ALERT`).
+fn poll_fields(
+    iface: &str,
+    store: &Store,
+    keys: &Keys,
+    alerts: &[(Direction, Alert)],
+    rate: Option<&Rate>,
+) -> Vec<(String, String)> {
+    let mut fields = vec![("IFACE".to_string(), iface.to_string())];
+    for &direction in &Direction::ALL {
+        if let Some(total) = stored_usage(store, keys, direction) {
+            let name = format!("{}_BYTES", direction.as_str().to_uppercase());
+            fields.push((name, total.to_string()));
+        }
+    }
+    if let Some(rate) = rate {
+        fields.push(("RX_RATE".to_string(), rate.rx.to_string()));
+        fields.push(("TX_RATE".to_string(), rate.tx.to_string()));
+    }
+    for (direction, alert) in alerts {
+        for &level in &Level::ALL {
+            let name = keys.alert(*direction, level)[0].to_uppercase();
+            fields.push((name, alert.flag(level).to_string()));
+        }
+    }
+
+    fields
+}
+
+/// Summarise a poll on one line of space-separated `name=value` fields
+///
+/// The fields are always given in the same order: `time` (unix seconds),
+/// `iface`, the `rx` and `tx` totals (bytes), the `rx_rate` and `tx_rate`
+/// (bytes per second, `-` until a second poll measures them) and the
+/// comma-separated `alerts` raised (e.g. `total_warn,rx_cut`, or `none`).
+fn cycle_summary(
+    time: u64,
+    iface: &str,
+    store: &Store,
+    keys: &Keys,
+    alerts: &[(Direction, Alert)],
+    rate: Option<&Rate>,
+) -> String {
+    let total = |direction| stored_usage(store, keys, direction).unwrap_or(0);
+    let (rx_rate, tx_rate) = match rate {
+        Some(rate) => (rate.rx.to_string(), rate.tx.to_string()),
+        None => ("-".to_string(), "-".to_string()),
+    };
+    let raised: Vec<String> = alerts
+        .iter()
+        .flat_map(|&(direction, ref alert)| {
+            Level::ALL
+                .iter()
+                .filter(move |&&level| alert.flag(level))
+                .map(move |&level| threshold_name(direction, level))
+        })
+        .collect();
+    let raised = if raised.is_empty() {
+        "none".to_string()
+    } else {
+        raised.join(",")
+    };
+
+    format!(
+        "time={} iface={} rx={} tx={} rx_rate={} tx_rate={} alerts={}",
+        time,
+        iface,
+        total(Direction::Rx),
+        total(Direction::Tx),
+        rx_rate,
+        tx_rate,
+        raised
+    )
+}
+
+/// Report the outcome of a poll to the journal, or to the log if it is unavailable
+fn report_poll(journal: Option<&Journal>, fields: &[(String, String)]) {
+    let message = "Updated usage totals and alert flags";
+    if let Some(journal) = journal {
+        match journal.send(message, fields) {
+            Ok(()) => return,
+            Err(e) => warn!("Failed to write to the systemd journal: {}", e),
+        }
+    }
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    info!("{}: {}", message, fields.join(" "));
+}
+
+/// Holds back alert notifications while the daemon's startup grace period lasts
+///
+/// The flags stored when the period starts are kept, so that the first
+/// poll after it notifies every flag which has changed since, including
+/// flags raised during the period which are still raised.
+struct Grace<'a> {
+    until: Option<Instant>,
+    before: Option<Vec<(&'a str, bool)>>,
+}
+
+impl<'a> Grace<'a> {
+    fn new(period: Option<Duration>, now: Instant) -> Grace<'a> {
+        Grace {
+            until: period.map(|period| now + period),
+            before: None,
+        }
+    }
+
+    /// The flags to notify changes from at a poll given the flags stored
+    /// before it, or `None` while notifications are held back
+    fn before(
+        &mut self,
+        now: Instant,
+        flags: Vec<(&'a str, bool)>,
+    ) -> Option<Vec<(&'a str, bool)>> {
+        match self.until {
+            Some(until) if now < until => {
+                self.before.get_or_insert(flags);
+                None
+            }
+            _ => {
+                self.until = None;
+                Some(self.before.take().unwrap_or(flags))
+            }
+        }
+    }
+}
+
+/// Where the daemon reports each poll and records its usage, besides the data store
+#[derive(Default)]
+struct Outputs<'a> {
+    journal: Option<&'a Journal>,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+/// Data store updates of a poll
+struct Polled {
+    time: u64,
+    delta: Delta,
+    rate: Option<Rate>,
+    alerts: Vec<(Direction, Alert)>,
+}
+
+/// Update the data store with a poll: take a reading, accumulate the totals
+/// it adds and store the rates, then set the alert flags
+///
+/// These are the steps of the daemon's poll which `Monitor` runs, leaving
+/// out the notifications, backends and files the daemon updates after them.
+fn poll_store(
+    opt: &Opt,
+    store: &Store,
+    keys: &Keys,
+    meter: &mut RateMeter,
+    restarted: bool,
+) -> Result<Polled, BoxError> {
+    let reading = take_reading(&opt.iface, &opt.source(), opt.record.as_deref())?;
+    let change = LastCounters::get(store, keys, &reading.iface).change(&reading);
+    let delta = update_transmission_totals(opt, &reading, store, keys, restarted)?;
+    let rate = meter.record(Instant::now(), change.rx, change.tx);
+    store_rate(store, rate.as_ref())?;
+    if let Some(window) = opt.window {
+        record_sample(store, &delta, window, reading.time)?;
+    }
+
+    // test transmission totals against alert thresholds and set flags
+    let alerts = update_alerts(opt, store, keys, reading.time)?;
+
+    Ok(Polled {
+        time: reading.time,
+        delta,
+        rate,
+        alerts,
+    })
+}
+
+/// Run a single daemon poll: accumulate the latest totals, then set alert flags
+fn poll<'a>(
+    opt: &Opt,
+    store: &Store,
+    keys: &'a Keys,
+    outputs: &Outputs,
+    meter: &mut RateMeter,
+    grace: &mut Grace<'a>,
+    restarted: bool,
+) -> Result<Option<Rate>, BoxError> {
+    let flags = alert_flags(store, keys);
+    let Polled {
+        time,
+        delta,
+        rate,
+        alerts,
+    } = poll_store(opt, store, keys, meter, restarted)?;
+    write_sinks(&outputs.sinks, &opt.iface, &delta, time);
+
+    let backends = opt.backends();
+    if !backends.is_empty() {
+        match grace.before(Instant::now(), flags) {
+            Some(before) => {
+                notify_alert_changes(&backends, &opt.iface, store, keys, &before);
+            }
+            None => debug!("Holding back alert notifications during the startup grace period"),
+        }
+    }
+
+    if let Some(ref breach_file) = opt.breach_file {
+        if let Err(e) = update_breach_file(breach_file, keys, &alerts) {
+            error!("Failed to update breach file: {}", e);
+        }
+    }
+    if let Some(ref status_file) = opt.status_file {
+        if let Err(e) = update_status_file(status_file, store, keys, &alerts, time) {
+            error!("Failed to update status file: {}", e);
+        }
+    }
+
+    if opt.journal {
+        report_poll(
+            outputs.journal,
+            &poll_fields(&opt.iface, store, keys, &alerts, rate.as_ref()),
+        );
+    }
+    if opt.log_each_cycle {
+        println!(
+            "{}",
+            cycle_summary(time, &opt.iface, store, keys, &alerts, rate.as_ref())
+        );
+    }
+
+    Ok(rate)
+}
+
+/// Data store polled by a `Monitor` as the daemon polls it, under the options of the binary
+pub(crate) struct Polls {
+    opt: Opt,
+    store: Store,
+    keys: Keys,
+    meter: RateMeter,
+    restarted: bool,
+}
+
+impl Polls {
+    /// Open the data store given by the arguments of the binary (e.g. `--data-dir` and `--iface`)
+    ///
+    /// The data store is checked and configured as a run of the binary
+    /// would do before polling it: the thresholds and billing cycle given are
+    /// stored, and a data store which is corrupt or cannot be written fails.
+    pub(crate) fn open<I>(args: I) -> Result<Polls, BoxError>
+    where
+        I: IntoIterator<Item = OsString>,
+    {
+        let opt = Opt::from_iter_safe(iter::once(OsString::from("peach-monitor")).chain(args))?;
+        let keys = opt.keys();
+        keys.check_alert_keys()?;
+        check_threshold_options(&opt)?;
+        let env_thresholds = env_thresholds(opt.max_threshold)?;
+
+        let path = create_data_dir(opt.data_dir.as_deref())?;
+        let store = Store::new(&path, store_schema()?);
+        let corrupt = integrity::check(&store, &path);
+        if !corrupt.is_empty() {
+            return Err(NestError::Corrupt { paths: corrupt }.into());
+        }
+        check_writable(&path)?;
+        store_config(&opt, &store, &keys)?;
+        ensure_thresholds(&store, &keys, &env_thresholds)?;
+
+        Ok(Polls {
+            opt,
+            store,
+            keys,
+            meter: RateMeter::new(),
+            restarted: true,
+        })
+    }
+
+    /// Run the data store updates of a daemon poll, returning the state they left
+    pub(crate) fn poll(&mut self) -> Result<MonitorState, BoxError> {
+        poll_store(
+            &self.opt,
+            &self.store,
+            &self.keys,
+            &mut self.meter,
+            self.restarted,
+        )?;
+        // until a poll has stored the counters, they are still those of before the start
+        self.restarted = false;
+        Ok(load_state(&self.store, &self.keys))
+    }
+
+    /// Time between polls (`--interval`)
+    pub(crate) fn interval(&self) -> Duration {
+        Duration::from_secs(self.opt.interval)
+    }
+}
+
+/// Derive thresholds (megabytes) from a data plan (bytes)
+///
+/// The warning threshold is set at 80% of the plan and the cutoff at 100%.
+fn plan_thresholds(plan: u64, mode: PlanMode) -> Vec<(Direction, Level, u64)> {
+    let plan = to_megabytes(plan);
+    let warn = plan * 4 / 5;
+    let directions: &[Direction] = match mode {
+        PlanMode::Combined => &[Direction::Combined],
+        PlanMode::Directional => &[Direction::Rx, Direction::Tx],
+    };
+    directions
+        .iter()
+        .flat_map(|&direction| {
+            vec![
+                (direction, Level::Warn, warn),
+                (direction, Level::Cut, plan),
+            ]
+        })
+        .collect()
+}
+
+/// Store a data plan and the thresholds derived from it
+fn set_plan(store: &Store, keys: &Keys, plan: u64, mode: PlanMode) -> Result<(), NestError> {
+    for (direction, level, val) in plan_thresholds(plan, mode) {
+        let key = keys.threshold(direction, level)[0];
+        store.set(&[NET, NOTIFY, key], &Value::Uint(val))?;
+    }
+    store.set(&[NET, NOTIFY, PLAN], &Value::Uint(to_megabytes(plan)))?;
+    store.set(
+        &[NET, NOTIFY, PLAN_MODE],
+        &Value::String(mode.as_str().to_string()),
+    )?;
+
+    Ok(())
+}
+
+/// Render the stored totals, thresholds and alert flags as OpenMetrics
+fn metrics_exposition(store: &Store, keys: &Keys) -> String {
+    let ifaces: Vec<InterfaceTotals> = interface_entries(store, keys)
+        .into_iter()
+        .map(|(iface, entry)| InterfaceTotals {
+            rx: entry_usage(&entry, keys, Direction::Rx),
+            tx: entry_usage(&entry, keys, Direction::Tx),
+            iface,
+        })
+        .collect();
+
+    let threshold = Threshold::get(store, keys);
+    let mut thresholds = Vec::new();
+    let mut alerts = Vec::new();
+    for &direction in &Direction::ALL {
+        let (warn, cut) = threshold.of(direction);
+        for &(level, value) in &[(Level::Warn, warn), (Level::Cut, cut)] {
+            if let Some(value) = value {
+                thresholds.push(Labelled {
+                    direction: direction.as_str(),
+                    level: level.as_str(),
+                    value: to_bytes(value),
+                });
+            }
+            if let Some(value) = stored_alert(store, keys, keys.alert(direction, level)[0]) {
+                alerts.push(Labelled {
+                    direction: direction.as_str(),
+                    level: level.as_str(),
+                    value,
+                });
+            }
+        }
+    }
+
+    metrics::exposition(&ifaces, &thresholds, &alerts)
+}
+
+/// Content type of JSON API responses
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Stored totals (bytes) of the monitored interface as JSON
+fn api_totals(store: &Store, keys: &Keys) -> serde_json::Value {
+    let mut totals = serde_json::Map::new();
+    totals.insert("iface".to_string(), json!(monitored_interface(store)));
+    for &direction in &Direction::ALL {
+        totals.insert(
+            direction.as_str().to_string(),
+            json!(stored_usage(store, keys, direction).unwrap_or(0)),
+        );
+    }
+    serde_json::Value::Object(totals)
+}
+
+/// Thresholds (bytes) by direction and level as JSON, with `null` for those not set
+fn api_thresholds(store: &Store, keys: &Keys) -> serde_json::Value {
+    let threshold = Threshold::get(store, keys);
+    let mut thresholds = serde_json::Map::new();
+    for &direction in &Direction::ALL {
+        let (warn, cut) = threshold.of(direction);
+        thresholds.insert(
+            direction.as_str().to_string(),
+            json!({ "warn": warn.map(to_bytes), "cut": cut.map(to_bytes) }),
+        );
+    }
+    let window_cut = match store.get(&[NET, NOTIFY, WINDOW_CUT]) {
+        Ok(Value::Uint(cut)) => Some(to_bytes(cut)),
+        _ => None,
+    };
+    thresholds.insert(WINDOW_CUT.to_string(), json!(window_cut));
+    serde_json::Value::Object(thresholds)
+}
+
+/// Alert flags which have been set, by the keys they are read from, as JSON
+fn api_alerts(store: &Store, keys: &Keys) -> serde_json::Value {
+    let flags = alert_flags(store, keys)
+        .into_iter()
+        .map(|(flag, value)| (flag.to_string(), json!(value)))
+        .collect();
+    serde_json::Value::Object(flags)
+}
+
+/// The billing cycle which a date falls in, if the day cycles start on is stored
+fn stored_cycle(store: &Store, date: NaiveDate) -> Option<Cycle> {
+    match store.get(&[NET, NOTIFY, CYCLE_DAY]) {
+        Ok(Value::Uint(day)) if (1..=31).contains(&day) => {
+            Some(Cycle::containing(date, day as u32))
+        }
+        _ => None,
+    }
+}
+
+/// Current billing cycle and the days elapsed and remaining in it as JSON, or `null` if not defined
+fn api_cycle(store: &Store) -> serde_json::Value {
+    let today = Local::now().date_naive();
+    match stored_cycle(store, today) {
+        Some(cycle) => json!({
+            "start": cycle.start.to_string(),
+            "end": cycle.end.to_string(),
+            "elapsed_days": cycle.elapsed(today),
+            "remaining_days": cycle.remaining(today),
+            "daily_budget": stored_total(store, DAILY_BUDGET),
+        }),
+        None => serde_json::Value::Null,
+    }
+}
+
+/// Totals, thresholds, alert flags and accounting state as JSON
+fn api_status(store: &Store, keys: &Keys) -> serde_json::Value {
+    let rate = match (stored_total(store, RX_RATE), stored_total(store, TX_RATE)) {
+        (Some(rx), Some(tx)) => json!({ "rx": rx, "tx": tx }),
+        _ => serde_json::Value::Null,
+    };
+    json!({
+        "iface": monitored_interface(store),
+        "ifaces": monitored_interfaces(store, keys),
+        "totals": api_totals(store, keys),
+        "thresholds": api_thresholds(store, keys),
+        "alerts": api_alerts(store, keys),
+        "paused": matches!(store.get(&[NET, TRAFFIC, PAUSED]), Ok(Value::Bool(true))),
+        "metered": is_metered(store),
+        "cycle": api_cycle(store),
+        "rate": rate,
+        "updated": stored_total(store, UPDATED),
+    })
+}
+
+/// Answer a request to the JSON API
+fn api_response(path: &str, store: &Store, keys: &Keys) -> Option<Response> {
+    let body = match path {
+        "/totals" => api_totals(store, keys),
+        "/thresholds" => api_thresholds(store, keys),
+        "/alerts" => api_alerts(store, keys),
+        "/status" => api_status(store, keys),
+        _ => return None,
+    };
+
+    Some(Response {
+        content_type: JSON_CONTENT_TYPE,
+        body: format!("{}\n", body),
+    })
+}
+
+/// Poll at each interval until a stop signal is received, then save once more
+///
+/// The final save counts the usage since the last poll, so that it is not
+/// lost when the daemon is stopped before a reboot. With `--fail-fast`, a
+/// failure of the first poll stops the daemon at once and is returned.
+fn run_daemon(
+    opt: &Opt,
+    store: &Store,
+    keys: &Keys,
+    path: &Path,
+    mut persist: bool,
+    journal: Option<&Journal>,
+    stop: &Receiver<()>,
+) -> Result<(), String> {
+    let source = opt.source();
+    let mut interval = Duration::from_secs(opt.interval);
+    let mut meter = RateMeter::new();
+    let mut flusher = Flusher::new(opt.durability, opt.flush_interval, Instant::now());
+    let mut grace = Grace::new(opt.startup_grace, Instant::now());
+    let mut shaper = opt.shaper();
+    let outputs = Outputs {
+        journal,
+        sinks: opt.sinks(),
+    };
+    let mut restarted = true;
+    let mut first_wait = true;
+    loop {
+        if persist {
+            let polled = poll(
+                opt, store, keys, &outputs, &mut meter, &mut grace, restarted,
+            );
+            if let (Some(shaper), Ok(Some(rate))) = (shaper.as_mut(), &polled) {
+                shape(opt, shaper, rate);
+            }
+            if let Err(ref e) = polled {
+                // restarted holds until a poll succeeds, which under fail-fast is the first one
+                if opt.fail_fast && restarted {
+                    return Err(format!("First poll failed: {}", e));
+                }
+            }
+            // until a poll has stored the counters, they are still those of before the start
+            restarted &= polled.is_err();
+            if polled.is_ok() && flusher.due(Instant::now()) {
+                flush_store(path);
+            }
+            match polled {
+                Ok(Some(rate)) if opt.adaptive_interval => {
+                    interval = adaptive_interval(opt, store, keys, &rate);
+                    debug!("Next poll in {}s", interval.as_secs());
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    error!("{}", e);
+                    // a failed store write means later writes will fail too
+                    if e.downcast_ref::<NestError>().is_some() {
+                        error!("Persistence is disabled");
+                        persist = false;
+                    }
+                }
+            }
+        } else {
+            log_unpersisted(&opt.iface, &source, store, keys, opt.inclusive_thresholds);
+        }
+
+        let wait = match opt.jitter {
+            None if opt.align => aligned_wait(interval, first_wait),
+            Some(fraction) => {
+                let wait = schedule::jitter(interval, fraction, schedule::random_offset());
+                debug!("Sleeping for {}ms", wait.as_millis());
+                wait
+            }
+            None => interval,
+        };
+        first_wait = false;
+        if let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(wait) {
+            continue;
+        }
+        break;
+    }
+
+    if persist {
+        match poll(
+            opt, store, keys, &outputs, &mut meter, &mut grace, restarted,
+        ) {
+            Ok(_) => flush_store(path),
+            Err(e) => error!("Final save failed: {}", e),
+        }
+    }
+    // the shaping is tied to the rates the daemon measures, so it is not left behind
+    if shaper.as_ref().is_some_and(Shaper::is_shaped) {
+        restore_shaping(opt);
+    }
+
+    Ok(())
+}
+
+/// Stop the daemon once it has run for a duration, as a stop signal would
+///
+/// Both the daemon loop and the shutdown timeout are signalled, so that the
+/// final save is made and bounded as on a stop signal.
+fn stop_after(runtime: Duration, stop: Sender<()>, signal: Sender<()>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        thread::sleep(runtime);
+        info!("Maximum runtime of {}s reached", runtime.as_secs());
+        let _ = stop.send(());
+        let _ = signal.send(());
+    })
+}
+
+/// Shape or restore the interface as the smoothed rate crosses the shaping rate
+///
+/// A command which fails is logged, and is not run again until the next crossing.
+fn shape(opt: &Opt, shaper: &mut Shaper, rate: &Rate) {
+    let edge = shaper.record(rate.rx + rate.tx);
+    let smoothed = format_rate(shaper.smoothed().unwrap_or(0));
+    let limit = opt.shape_rate.unwrap_or(0);
+    match edge {
+        Some(Edge::Rising) => {
+            info!(
+                "Shaping {}: smoothed rate {} exceeds {}",
+                opt.iface,
+                smoothed,
+                format_rate(limit)
+            );
+            if let Err(e) = shaping::run(&opt.shape_command, &opt.iface, limit) {
+                error!("Failed to shape {}: {}", opt.iface, e);
+            }
+        }
+        Some(Edge::Falling) => {
+            info!("Restoring {}: smoothed rate {}", opt.iface, smoothed);
+            restore_shaping(opt);
+        }
+        None => debug!("Smoothed rate of {}: {}", opt.iface, smoothed),
+    }
+}
+
+/// Run the command restoring the interface from shaping
+fn restore_shaping(opt: &Opt) {
+    if let Err(e) = shaping::run(
+        &opt.restore_command,
+        &opt.iface,
+        opt.shape_rate.unwrap_or(0),
+    ) {
+        error!("Failed to restore {}: {}", opt.iface, e);
+    }
+}
+
+/// Wait until the next wall-clock boundary of the interval
+///
+/// The first poll is made at the start, wherever it falls.
+fn aligned_wait(interval: Duration, first: bool) -> Duration {
+    let now = time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let offset = Local::now().offset().local_minus_utc();
+    let wait = schedule::aligned_wait(now, offset.into(), interval, first);
+    debug!(
+        "Sleeping for {}ms until the next boundary",
+        wait.as_millis()
+    );
+    wait
+}
+
+/// Flush the data store to the storage medium, logging a failure
+fn flush_store(path: &Path) {
+    match flush(path) {
+        Ok(()) => debug!("Flushed the data store"),
+        Err(e) => error!("Failed to flush the data store: {}", e),
+    }
+}
+
+/// Usage heading towards each threshold which has not been crossed, at the rates of a poll
+fn approaches(store: &Store, keys: &Keys, rate: &Rate) -> Vec<Approach> {
+    let threshold = Threshold::get(store, keys);
+    Direction::ALL
+        .iter()
+        .flat_map(|&direction| {
+            let total = stored_usage(store, keys, direction).unwrap_or(0);
+            let rate = match direction {
+                Direction::Rx => rate.rx,
+                Direction::Tx => rate.tx,
+                Direction::Combined => rate.rx + rate.tx,
+            };
+            let (warn, cut) = threshold.of(direction);
+            let remaining = bytes_to_thresholds(total, warn.map(to_bytes), cut.map(to_bytes));
+            vec![remaining.warn, remaining.cut]
+                .into_iter()
+                .flatten()
+                .filter(|&remaining| remaining > 0)
+                .map(move |remaining| Approach { remaining, rate })
+        })
+        .collect()
+}
+
+/// Interval before the next adaptive poll, given the rates of the last poll
+fn adaptive_interval(opt: &Opt, store: &Store, keys: &Keys, rate: &Rate) -> Duration {
+    schedule::next_interval(
+        Duration::from_secs(opt.min_interval),
+        Duration::from_secs(opt.max_interval),
+        &approaches(store, keys, rate),
+    )
+}
+
+/// Store the desired thresholds (bytes) which differ from those in effect
+///
+/// Returns whether any threshold was written, so that provisioning runs can
+/// report changes without writing to the store when nothing differs.
+fn ensure_thresholds(
+    store: &Store,
+    keys: &Keys,
+    desired: &[(Direction, Level, Option<u64>)],
+) -> Result<bool, BoxError> {
+    // the thresholds which are not given stay in effect alongside those which are
+    let mut resulting = Threshold::get(store, keys);
+    for &(direction, level, bytes) in desired {
+        if let Some(bytes) = bytes {
+            resulting.set(direction, level, to_megabytes(bytes));
+        }
+    }
+    resulting.check_levels()?;
+
+    let mut changed = false;
+    for &(direction, level, bytes) in desired {
+        if let Some(bytes) = bytes {
+            let val = to_megabytes(bytes);
+            if stored_threshold(store, keys, direction, level) != Some(val) {
+                let key = keys.threshold(direction, level)[0];
+                store.set(&[NET, NOTIFY, key], &Value::Uint(val))?;
+                changed = true;
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Store the current usage totals as a named checkpoint
+fn set_checkpoint(store: &Store, keys: &Keys, name: &str) -> Result<(), NestError> {
+    let checkpoint = Checkpoint {
+        rx: stored_usage(store, keys, Direction::Rx).unwrap_or(0),
+        tx: stored_usage(store, keys, Direction::Tx).unwrap_or(0),
+        time: unix_time(),
+    };
+    checkpoint.save(store, name)?;
+    println!("Stored checkpoint {}", name);
+
+    Ok(())
+}
+
+/// Format a time (unix seconds) in the local timezone
+fn format_local_time(time: u64) -> String {
+    match Local.timestamp_opt(time as i64, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => time.to_string(),
+    }
+}
+
+/// Print the usage since a named checkpoint
+fn print_diff(store: &Store, keys: &Keys, name: &str) -> Result<(), BoxError> {
+    let checkpoint =
+        Checkpoint::load(store, name).ok_or_else(|| format!("No checkpoint named {}", name))?;
+    let (rx, tx) = checkpoint.since(
+        stored_usage(store, keys, Direction::Rx).unwrap_or(0),
+        stored_usage(store, keys, Direction::Tx).unwrap_or(0),
+    );
+
+    let taken = format_local_time(checkpoint.time);
+    let show = |bytes| format!("{} ({} bytes)", format_bytes(bytes), group_digits(bytes));
+    println!("Checkpoint:  {} ({})", name, taken);
+    println!("Received:    {}", show(rx));
+    println!("Transmitted: {}", show(tx));
+    println!("Total:       {}", show(rx + tx));
+
+    Ok(())
+}
+
+/// Daily usage (bytes) by date, from the SQLite database if one is in use
+#[cfg_attr(not(feature = "sqlite"), allow(unused_variables))]
+fn daily_usage(opt: &Opt, store: &Store) -> Result<Vec<(String, u64, u64)>, BoxError> {
+    #[cfg(feature = "sqlite")]
+    {
+        if let Some(path) = opt.dbs.first() {
+            return Ok(db::Database::open(path)?.daily()?);
+        }
+    }
+
+    Ok(history::load(store)
+        .iter()
+        .map(|(date, day)| (date.to_string(), day.rx, day.tx))
+        .collect())
+}
+
+/// Print the daily usage history, oldest first
+fn print_history(opt: &Opt, store: &Store) -> Result<(), BoxError> {
+    println!(
+        "{:<10}  {:>11}  {:>11}  {:>11}",
+        "Date", "Received", "Transmitted", "Total"
+    );
+    for (date, rx, tx) in daily_usage(opt, store)? {
+        println!(
+            "{:<10}  {:>11}  {:>11}  {:>11}",
+            date,
+            format_bytes(rx),
+            format_bytes(tx),
+            format_bytes(rx + tx)
+        );
+    }
+
+    Ok(())
+}
+
+/// Read what the dashboard shows from the data store
+fn dash_state(store: &Store, keys: &Keys) -> State {
+    let threshold = Threshold::get(store, keys);
+    let usage = [
+        (Direction::Rx, "Received"),
+        (Direction::Tx, "Transmitted"),
+        (Direction::Combined, "Total"),
+    ]
+    .iter()
+    .filter(|&&(direction, _)| direction == Direction::Combined || counted(store, keys, direction))
+    .map(|&(direction, label)| {
+        let (warn, cut) = threshold.of(direction);
+        Usage {
+            label,
+            total: stored_usage(store, keys, direction).unwrap_or(0),
+            warn: warn.map(to_bytes),
+            cut: cut.map(to_bytes),
+        }
+    })
+    .collect();
+    let rate = match (stored_total(store, RX_RATE), stored_total(store, TX_RATE)) {
+        (Some(rx), Some(tx)) => Some((rx, tx)),
+        _ => None,
+    };
+
+    State {
+        iface: monitored_interface(store).unwrap_or_default(),
+        time: format_local_time(unix_time()),
+        usage,
+        rate,
+        samples: Samples::load(store).list,
+        alerts: alert_flags(store, keys)
+            .into_iter()
+            .filter(|&(_, raised)| raised)
+            .map(|(flag, _)| flag.to_string())
+            .collect(),
+    }
+}
+
+/// Redraw the dashboard at each interval (seconds) until interrupted
+///
+/// The data store is only read, so the daemon must be running alongside to
+/// keep the totals and rates up to date.
+fn run_dash(store: &Store, keys: &Keys, interval: u64, palette: Palette) -> Result<(), BoxError> {
+    let mut stdout = io::stdout();
+    loop {
+        let model = Model::build(&dash_state(store, keys));
+        // clear the screen and move to its top left corner before drawing
+        writeln!(stdout, "\x1b[2J\x1b[H{}", model.render(palette))?;
+        stdout.flush()?;
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/// Print the final totals of each archived billing cycle, by the date it ended
+fn print_cycles(store: &Store) {
+    println!(
+        "{:<10}  {:>11}  {:>11}  {:>11}",
+        "Ended", "Received", "Transmitted", "Total"
+    );
+    for (date, cycle) in archive::load(store) {
+        println!(
+            "{:<10}  {:>11}  {:>11}  {:>11}",
+            date,
+            format_bytes(cycle.rx),
+            format_bytes(cycle.tx),
+            format_bytes(cycle.rx + cycle.tx)
+        );
+    }
+}
+
+/// Print the hosts which transferred the most data according to a connection tracking listing
+#[cfg(feature = "top-talkers")]
+fn print_top_talkers(file: &Path, by: talkers::Grouping, count: usize) -> Result<(), BoxError> {
+    let listing = if file == Path::new("-") {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?
+    };
+    let flows = talkers::parse(&listing)?;
+
+    println!("{:<39}  {:>11}  {:>11}", "Host", "Total", "Connections");
+    for (host, bytes) in talkers::rank(&flows, by, count) {
+        let connections = flows
+            .iter()
+            .filter(|flow| match by {
+                talkers::Grouping::Destination => flow.dst == host,
+                talkers::Grouping::Source => flow.src == host,
+            })
+            .count();
+        println!(
+            "{:<39}  {:>11}  {:>11}",
+            host,
+            format_bytes(bytes),
+            connections
+        );
+    }
+
+    Ok(())
+}
+
+/// Time of the local midnight starting a date (unix milliseconds)
+fn local_midnight_millis(date: NaiveDate) -> i64 {
+    let midnight = date.and_time(NaiveTime::MIN);
+    match Local.from_local_datetime(&midnight).earliest() {
+        Some(time) => time.timestamp_millis(),
+        // midnight was skipped by a change to daylight saving time
+        None => midnight.and_utc().timestamp_millis(),
+    }
+}
+
+/// Daily usage (bytes) between two dates (inclusive), oldest first
+fn exported_days(
+    opt: &Opt,
+    store: &Store,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<(NaiveDate, u64, u64)>, BoxError> {
+    Ok(daily_usage(opt, store)?
+        .into_iter()
+        .filter_map(|(date, rx, tx)| Some((date.parse().ok()?, rx, tx)))
+        .filter(|&(date, _, _)| {
+            from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to)
+        })
+        .collect())
+}
+
+/// Daily usage as the points of a Grafana JSON datasource, each timed at its local midnight
+fn grafana_points(
+    days: &[(NaiveDate, u64, u64)],
+    identity: Option<&Identity>,
+) -> serde_json::Value {
+    days.iter()
+        .map(|&(date, rx, tx)| {
+            let mut point = json!({ "time": local_midnight_millis(date), "rx": rx, "tx": tx });
+            if let Some(identity) = identity {
+                point["identity"] = json!(identity.value);
+            }
+            point
+        })
+        .collect()
+}
+
+/// Print the daily usage history between two dates (inclusive) in an export format
+fn export_history(
+    opt: &Opt,
+    store: &Store,
+    format: ExportFormat,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    identity: Option<&Identity>,
+) -> Result<(), BoxError> {
+    let days = exported_days(opt, store, from, to)?;
+    match format {
+        ExportFormat::Csv => match identity {
+            Some(identity) => {
+                println!("date,rx,tx,identity");
+                for (date, rx, tx) in days {
+                    println!("{},{},{},{}", date, rx, tx, identity.value);
+                }
+            }
+            None => {
+                println!("date,rx,tx");
+                for (date, rx, tx) in days {
+                    println!("{},{},{}", date, rx, tx);
+                }
+            }
+        },
+        ExportFormat::GrafanaJson => println!("{}", grafana_points(&days, identity)),
+    }
+
+    Ok(())
+}
+
+/// Identifier of the device to include in exported data, if requested
+fn read_identity(iface: &str, include: bool, anonymize: bool) -> Result<Option<Identity>, String> {
+    if !include {
+        return Ok(None);
+    }
+    let identity = Identity::read(iface)?;
+    debug!("Identifying the device by its {}", identity.kind);
+
+    Ok(Some(match anonymize {
+        true => identity.pseudonym(),
+        false => identity,
+    }))
+}
+
+/// Remove usage history entries older than an age, or list them for a dry run
+fn prune_history(store: &Store, older_than: Duration, dry_run: bool) -> Result<(), NestError> {
+    let days = (older_than.as_secs() / (24 * 60 * 60)) as i64;
+    let cutoff = Local::now().date_naive() - chrono::Duration::days(days);
+    let (pruned, remaining) = history::prune(store, cutoff, dry_run)?;
+
+    if dry_run {
+        for date in &pruned {
+            println!("{}", date);
+        }
+        println!(
+            "Would prune {} history entries older than {}, leaving {}",
+            pruned.len(),
+            cutoff,
+            remaining
+        );
+    } else {
+        println!(
+            "Pruned {} history entries older than {}, leaving {}",
+            pruned.len(),
+            cutoff,
+            remaining
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the stored usage totals, data plan and alert flags
+///
+/// When a rounding unit is given, totals are rounded for display only.
+/// Interface names are displayed through `names`, which may anonymize them.
+fn print_status(
+    store: &Store,
+    keys: &Keys,
+    round: Option<u64>,
+    names: &mut Pseudonyms,
+    palette: Palette,
+) {
+    // list the monitored interface first so that it receives the first pseudonym
+    if let Some(ref iface) = monitored_interface(store) {
+        println!("Interface:   {}", names.name(iface));
+    }
+    if keys.per_interface() {
+        let ifaces: Vec<String> = monitored_interfaces(store, keys)
+            .iter()
+            .map(|iface| names.name(iface))
+            .collect();
+        println!("Monitored:   {}", ifaces.join(", "));
+    }
+    if let Ok(Value::Object(seen)) = store.get(&[NET, TRAFFIC, FIRST_SEEN]) {
+        let seen: Vec<String> = seen.keys().map(|iface| names.name(iface)).collect();
+        println!("Seen:        {}", seen.join(", "));
+    }
+
+    let total = |direction| {
+        let total = stored_usage(store, keys, direction).unwrap_or(0);
+        round.map_or(total, |unit| round_bytes(total, unit))
+    };
+    let (rx, tx, combined) = (
+        total(Direction::Rx),
+        total(Direction::Tx),
+        total(Direction::Combined),
+    );
+    if let Ok(Value::Bool(true)) = store.get(&[NET, TRAFFIC, PAUSED]) {
+        println!("Accounting:  paused");
+    }
+    if !is_metered(store) {
+        println!("Connection:  unmetered (neither counted nor alerted on)");
+    }
+    // the gauges compare the exact totals against the cutoffs, whatever the rounding
+    let threshold = Threshold::get(store, keys);
+    let show = |bytes, direction| {
+        let shown = format!("{} ({} bytes)", format_bytes(bytes), group_digits(bytes));
+        match threshold.of(direction).1 {
+            Some(cut) => {
+                let used = stored_usage(store, keys, direction).unwrap_or(0);
+                format!("{} {}", shown, usage_gauge(used, to_bytes(cut)))
+            }
+            None => shown,
+        }
+    };
+    let show_counted = |bytes, direction| {
+        if counted(store, keys, direction) {
+            show(bytes, direction)
+        } else {
+            "N/A (not counted by this interface)".to_string()
+        }
+    };
+    println!("Received:    {}", show_counted(rx, Direction::Rx));
+    println!("Transmitted: {}", show_counted(tx, Direction::Tx));
+    println!("Total:       {}", show(combined, Direction::Combined));
+    if let (Some(rx_rate), Some(tx_rate)) =
+        (stored_total(store, RX_RATE), stored_total(store, TX_RATE))
+    {
+        println!(
+            "Rate:        received {}, transmitted {}",
+            format_rate(rx_rate),
+            format_rate(tx_rate)
+        );
+    }
+    if let Some(updated) = stored_total(store, UPDATED) {
+        println!("Updated:     {}", format_local_time(updated));
+    }
+    let delta = |key| match stored_value(store, keys, TRAFFIC, key) {
+        Some(Value::Uint(bytes)) => Some(bytes),
+        _ => None,
+    };
+    if let (Some(rx), Some(tx), Some(time)) = (delta(RX_DELTA), delta(TX_DELTA), delta(DELTA_TIME))
+    {
+        println!(
+            "Last delta:  received {}, transmitted {} (up to {})",
+            format_bytes(rx),
+            format_bytes(tx),
+            format_local_time(time)
+        );
+    }
+    if let Some(baseline) = stored_total(store, BASELINE) {
+        match store.get(&[NET, NOTIFY, ANOMALY_FACTOR]) {
+            Ok(Value::Float(factor)) => println!(
+                "Baseline:    {} per day (anomalous above {}x)",
+                format_bytes(baseline),
+                factor
+            ),
+            _ => println!("Baseline:    {} per day", format_bytes(baseline)),
+        }
+    }
+
+    if let Ok(Value::Float(ratio)) = store.get(&[NET, TRAFFIC, RATIO_BASELINE]) {
+        match store.get(&[NET, NOTIFY, UPLOAD_FACTOR]) {
+            Ok(Value::Float(factor)) => println!(
+                "Upload ratio: {:.3} transmitted per byte received (anomalous above {}x)",
+                ratio, factor
+            ),
+            _ => println!("Upload ratio: {:.3} transmitted per byte received", ratio),
+        }
+    }
+    if let Some(average) = stored_total(store, DAILY_EMA) {
+        let up_to = stored_string(store, EMA_DATE).unwrap_or_else(|| "unknown".to_string());
+        println!(
+            "Daily EMA:   {} per day (up to {})",
+            format_bytes(average),
+            up_to
+        );
+    }
+
+    if let Ok(Value::Uint(plan)) = store.get(&[NET, NOTIFY, PLAN]) {
+        let plan = format_bytes(to_bytes(plan));
+        match store.get(&[NET, NOTIFY, PLAN_MODE]) {
+            Ok(Value::String(ref mode)) if mode == PlanMode::Directional.as_str() => println!(
+                "Plan:        received {} of {} used, transmitted {} of {} used",
+                format_bytes(rx),
+                plan,
+                format_bytes(tx),
+                plan
+            ),
+            _ => println!("Plan:        {} of {} used", format_bytes(combined), plan),
+        }
+    }
+    let today = Local::now().date_naive();
+    if let Some(cycle) = stored_cycle(store, today) {
+        let days = |days: i64| match days {
+            1 => "1 day".to_string(),
+            _ => format!("{} days", days),
+        };
+        println!(
+            "Cycle:       {} of {} elapsed, {} left (started {})",
+            days(cycle.elapsed(today)),
+            cycle.length(),
+            days(cycle.remaining(today)),
+            cycle.start
+        );
+        if let Some(budget) = stored_total(store, DAILY_BUDGET) {
+            let used_today = history::load(store)
+                .get(&today)
+                .map_or(0, |day| day.rx + day.tx);
+            let budget = match (budget, cycle.remaining(today)) {
+                (0, _) => "none left for the rest of the cycle".to_string(),
+                (budget, 1) => format!(
+                    "{} left for the last day of the cycle",
+                    format_bytes(budget)
+                ),
+                (budget, _) => {
+                    format!("{} per day for the rest of the cycle", format_bytes(budget))
+                }
+            };
+            println!(
+                "Budget:      {}, {} used today",
+                budget,
+                format_bytes(used_today)
+            );
+        }
+    }
+
+    let mut flags = Vec::new();
+    for &direction in &[Direction::Combined, Direction::Rx, Direction::Tx] {
+        for &level in &Level::ALL {
+            let key = keys.alert(direction, level)[0];
+            let raised = match level {
+                Level::Warn => Color::Yellow,
+                Level::Cut => Color::Red,
+            };
+            let state = match stored_alert(store, keys, key) {
+                _ if !counted(store, keys, direction) => "n/a".to_string(),
+                Some(true) => palette.paint("on", raised),
+                _ => palette.paint("off", Color::Green),
+            };
+            flags.push(format!(
+                "{} {} {}",
+                direction.as_str(),
+                level.as_str(),
+                state
+            ));
+        }
+    }
+    let state = |alert, raised| {
+        if alert {
+            palette.paint("on", raised)
+        } else {
+            palette.paint("off", Color::Green)
+        }
+    };
+    if let Ok(Value::Bool(alert)) = store.get(&[NET, ALERT, WINDOW_CUT_ALERT]) {
+        flags.push(format!("window cut {}", state(alert, Color::Red)));
+    }
+    if let Ok(Value::Bool(alert)) = store.get(&[NET, ALERT, ANOMALY_ALERT]) {
+        flags.push(format!("anomaly {}", state(alert, Color::Yellow)));
+    }
+    if let Ok(Value::Bool(alert)) = store.get(&[NET, ALERT, PROJECTION_OVER]) {
+        flags.push(format!("projection {}", state(alert, Color::Yellow)));
+    }
+    if let Ok(Value::Bool(alert)) = store.get(&[NET, ALERT, UPLOAD_ANOMALY]) {
+        flags.push(format!("upload anomaly {}", state(alert, Color::Yellow)));
+    }
+    if let Ok(Value::Bool(alert)) = store.get(&[NET, ALERT, BUDGET_OVER]) {
+        flags.push(format!("budget {}", state(alert, Color::Yellow)));
+    }
+    println!("Alerts:      {}", flags.join(", "));
+}
+
+/// Totals and alert flags held for each interface
+///
+/// Under the flat layouts only those of the monitored interface are held.
+fn interface_entries(store: &Store, keys: &Keys) -> Vec<(String, IndexMap<String, Value>)> {
+    if keys.per_interface() {
+        return match store.get(&[NET, INTERFACES]) {
+            Ok(Value::Object(entries)) => entries
+                .into_iter()
+                .filter_map(|(iface, entry)| match entry {
+                    Value::Object(entry) => Some((iface, entry)),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+
+    let mut entry = IndexMap::new();
+    for file in &[TRAFFIC, ALERT] {
+        if let Ok(Value::Object(values)) = store.get(&[NET, file]) {
+            entry.extend(values);
+        }
+    }
+    stored_string(store, IFACE_LAST)
+        .map(|iface| (iface, entry))
+        .into_iter()
+        .collect()
+}
+
+/// Usage total (bytes) of a direction in an entry of `interface_entries`, 0 if not yet stored
+fn entry_usage(entry: &IndexMap<String, Value>, keys: &Keys, direction: Direction) -> u64 {
+    match entry.get(keys.total(direction)) {
+        Some(Value::Uint(total)) => total_bytes(
+            *total,
+            entry.get(TOTAL_UNIT),
+            entry.get(keys.carry(direction)),
+        ),
+        _ => 0,
+    }
+}
+
+/// Usage (bytes) and raised alert flags of an interface, in the summary of every interface
+#[derive(Debug, PartialEq)]
+struct InterfaceSummary {
+    iface: String,
+    rx: u64,
+    tx: u64,
+    raised: Vec<String>, // keys of the raised flags, as renamed by --alert-key
+}
+
+impl InterfaceSummary {
+    /// Whether a cutoff alert of the interface is raised
+    fn over_cap(&self, keys: &Keys) -> bool {
+        Direction::ALL.iter().any(|&direction| {
+            let flag = keys.alert(direction, Level::Cut)[0];
+            self.raised.iter().any(|raised| raised == flag)
+        })
+    }
+}
+
+/// Usage and raised alert flags of each interface held in the data store
+fn summarize(store: &Store, keys: &Keys) -> Vec<InterfaceSummary> {
+    interface_entries(store, keys)
+        .into_iter()
+        .map(|(iface, entry)| {
+            let mut raised = Vec::new();
+            for &direction in &Direction::ALL {
+                for &level in &Level::ALL {
+                    let flag = keys.alert(direction, level)[0];
+                    if let Some(Value::Bool(true)) = entry.get(flag) {
+                        raised.push(flag.to_string());
+                    }
+                }
+            }
+            InterfaceSummary {
+                rx: entry_usage(&entry, keys, Direction::Rx),
+                tx: entry_usage(&entry, keys, Direction::Tx),
+                iface,
+                raised,
+            }
+        })
+        .collect()
+}
+
+/// Print the totals and raised alert flags of each interface, then their sums
+fn print_summary(store: &Store, keys: &Keys) {
+    let row = |name: &str, rx: u64, tx: u64, alerts: &str| {
+        let line = format!(
+            "{:<10}  {:>11}  {:>11}  {:>11}  {}",
+            name,
+            format_bytes(rx),
+            format_bytes(tx),
+            format_bytes(rx + tx),
+            alerts
+        );
+        println!("{}", line.trim_end())
+    };
+    println!(
+        "{:<10}  {:>11}  {:>11}  {:>11}  Alerts",
+        "Interface", "Received", "Transmitted", "Total"
+    );
+
+    let summaries = summarize(store, keys);
+    for summary in &summaries {
+        let alerts = if summary.raised.is_empty() {
+            "none".to_string()
+        } else {
+            summary.raised.join(", ")
+        };
+        row(&summary.iface, summary.rx, summary.tx, &alerts);
+    }
+    row(
+        "All",
+        summaries.iter().map(|summary| summary.rx).sum(),
+        summaries.iter().map(|summary| summary.tx).sum(),
+        "",
+    );
+
+    let over_cap: Vec<&str> = summaries
+        .iter()
+        .filter(|summary| summary.over_cap(keys))
+        .map(|summary| summary.iface.as_str())
+        .collect();
+    if over_cap.is_empty() {
+        println!("Over cap:   none");
+    } else {
+        println!("Over cap:   {}", over_cap.join(", "));
+    }
+}
+
+/// Types expected of the known keys of each file of the data store
+fn expected_types(keys: &Keys) -> Vec<(&'static str, Vec<(&str, Kind)>)> {
+    let mut totals: Vec<(&str, Kind)> = Direction::ALL
+        .iter()
+        .flat_map(|&direction| {
+            vec![
+                (keys.total(direction), Kind::Uint),
+                (keys.carry(direction), Kind::Uint),
+            ]
+        })
+        .collect();
+    let mut thresholds = vec![
+        (PLAN, Kind::Uint),
+        (PLAN_MODE, Kind::String),
+        (WINDOW_CUT, Kind::Uint),
+        (ANOMALY_FACTOR, Kind::Float),
+        (UPLOAD_FACTOR, Kind::Float),
+        (CYCLE_DAY, Kind::Uint),
+    ];
+    let mut alerts = Vec::new();
+    for &direction in &Direction::ALL {
+        for &level in &Level::ALL {
+            thresholds.extend(
+                keys.threshold(direction, level)
+                    .into_iter()
+                    .map(|key| (key, Kind::Uint)),
+            );
+            alerts.extend(
+                keys.alert(direction, level)
+                    .iter()
+                    .map(|&key| (key, Kind::Bool)),
+            );
+        }
+    }
+    let mut interface: Vec<(&str, Kind)> = totals.iter().chain(alerts.iter()).copied().collect();
+    interface.extend_from_slice(&[
+        (RX_LAST, Kind::Uint),
+        (TX_LAST, Kind::Uint),
+        (BOOT_ID, Kind::String),
+        (TOTALS_VERSION, Kind::Uint),
+        (TOTAL_UNIT, Kind::String),
+        (RX_DELTA, Kind::Uint),
+        (TX_DELTA, Kind::Uint),
+        (DELTA_TIME, Kind::Uint),
+    ]);
+    totals.extend_from_slice(&[
+        (RX_LAST, Kind::Uint),
+        (TX_LAST, Kind::Uint),
+        (IFACE_LAST, Kind::String),
+        (IFACE, Kind::String),
+        (IFACES, Kind::Array),
+        (BOOT_ID, Kind::String),
+        (FIRST_SEEN, Kind::Object),
+        (PAUSED, Kind::Bool),
+        (METERED, Kind::Bool),
+        (RX_RATE, Kind::Uint),
+        (TX_RATE, Kind::Uint),
+        (UPDATED, Kind::Uint),
+        (BASELINE, Kind::Uint),
+        (RATIO_BASELINE, Kind::Float),
+        (DAILY_EMA, Kind::Uint),
+        (EMA_DATE, Kind::String),
+        (DAILY_BUDGET, Kind::Uint),
+        (TOTALS_VERSION, Kind::Uint),
+        (TOTAL_UNIT, Kind::String),
+        (RX_DELTA, Kind::Uint),
+        (TX_DELTA, Kind::Uint),
+        (DELTA_TIME, Kind::Uint),
+    ]);
+    alerts.extend_from_slice(&[
+        (WINDOW_CUT_ALERT, Kind::Bool),
+        (ANOMALY_ALERT, Kind::Bool),
+        (PROJECTION_OVER, Kind::Bool),
+        (UPLOAD_ANOMALY, Kind::Bool),
+        (BUDGET_OVER, Kind::Bool),
+    ]);
+
+    vec![
+        (TRAFFIC, totals),
+        (NOTIFY, thresholds),
+        (ALERT, alerts),
+        (INTERFACES, interface),
+    ]
+}
+
+/// Fail if any known key of the data store holds a value of an unexpected type
+///
+/// Files which have not been written yet are skipped. Entries of
+/// `net/interfaces` are each checked against the per-interface keys.
+fn check_types(store: &Store, keys: &Keys) -> Result<(), NestError> {
+    let mut mismatches = Vec::new();
+    for (file, expected) in expected_types(keys) {
+        let values = match store.get(&[NET, file]) {
+            Ok(Value::Object(values)) => values,
+            _ => continue,
+        };
+        if file == INTERFACES {
+            for (iface, entry) in &values {
+                let path = format!("{}/{}/{}", NET, file, iface);
+                match entry {
+                    Value::Object(entry) => {
+                        mismatches.extend(integrity::mismatched_types(&path, entry, &expected))
+                    }
+                    _ => mismatches.push(format!("{}: expected an object", path)),
+                }
+            }
+        } else {
+            let path = format!("{}/{}", NET, file);
+            mismatches.extend(integrity::mismatched_types(&path, &values, &expected));
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(NestError::UnexpectedTypes { mismatches })
+    }
+}
+
+/// Report the keys each threshold is read from, failing if the stored
+/// thresholds would not all be read as expected
+///
+/// A key of `net/notify` which is not read at all (e.g. written by another
+/// tool under a different name) would silently leave its threshold unset, and
+/// a fallback key holding a different value than the key read first would
+/// be ignored.
+fn check_keys(store: &Store, keys: &Keys) -> Result<(), NestError> {
+    let values = match store.get(&[NET, NOTIFY]) {
+        Ok(Value::Object(values)) => values,
+        _ => IndexMap::new(),
+    };
+    let mut known = vec![
+        PLAN,
+        PLAN_MODE,
+        WINDOW_CUT,
+        ANOMALY_FACTOR,
+        UPLOAD_FACTOR,
+        CYCLE_DAY,
+    ];
+    let mut problems = Vec::new();
+    for &direction in &Direction::ALL {
+        for &level in &Level::ALL {
+            let candidates = keys.threshold(direction, level);
+            let set: Vec<&str> = candidates
+                .iter()
+                .copied()
+                .filter(|&key| values.contains_key(key))
+                .collect();
+            let source = match set.first() {
+                Some(key) => format!("set in {}", key),
+                None => "not set".to_string(),
+            };
+            println!(
+                "{:<11} read from {} ({})",
+                threshold_name(direction, level),
+                candidates.join(", "),
+                source
+            );
+            if let Some((&read, fallbacks)) = set.split_first() {
+                for &key in fallbacks.iter().filter(|&&key| values[key] != values[read]) {
+                    problems.push(format!(
+                        "{}/{}/{} differs from {}/{}/{}, which is read instead",
+                        NET, NOTIFY, key, NET, NOTIFY, read
+                    ));
+                }
+            }
+            known.extend(candidates);
+        }
+    }
+    for key in values.keys().filter(|key| !known.contains(&key.as_str())) {
+        problems.push(format!(
+            "{}/{}/{} is never read (map it with --threshold-key <threshold>={})",
+            NET, NOTIFY, key, key
+        ));
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(NestError::KeyMismatch { problems })
+    }
+}
+
+/// Check the configuration and data store for common problems, printing a checklist
+///
+/// An error is returned if any check fails, after the checklist and the
+/// overall verdict have been printed.
+fn doctor(opt: &Opt, store: &Store, keys: &Keys, path: &Path) -> Result<(), BoxError> {
+    let findings = diagnose(opt, store, keys, path);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    println!("Verdict: {}", doctor::verdict(&findings));
+
+    if findings
+        .iter()
+        .any(|finding| finding.outcome == Outcome::Fail)
+    {
+        return Err("Some checks failed".into());
+    }
+
+    Ok(())
+}
+
+/// Findings of each check of the configuration and data store, in checklist order
+fn diagnose(opt: &Opt, store: &Store, keys: &Keys, path: &Path) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    findings.push(match Traffic::get(&opt.iface, &opt.source()) {
+        Ok(_) => Finding::pass("Interface", format!("{} is counted", opt.iface)),
+        Err(e) => Finding::fail("Interface", e.to_string()),
+    });
+
+    // probing for write access would write to the data directory
+    findings.push(match check_writable(path) {
+        _ if opt.observe => Finding::pass("Data directory", "not probed in observe mode"),
+        Ok(()) => Finding::pass("Data directory", format!("{} is writable", path.display())),
+        Err(e) => Finding::fail("Data directory", e.to_string()),
+    });
+
+    let corrupt = integrity::check(store, path);
+    findings.push(if corrupt.is_empty() {
+        Finding::pass("Store files", "all can be read")
+    } else {
+        let paths: Vec<String> = corrupt.iter().map(|p| p.display().to_string()).collect();
+        Finding::fail(
+            "Store files",
+            format!(
+                "cannot read {} (back up and reinitialise with --repair)",
+                paths.join(", ")
+            ),
+        )
+    });
+
+    findings.push(match check_types(store, keys) {
+        Ok(()) => Finding::pass("Stored types", "all values have their expected types"),
+        Err(e) => Finding::warn("Stored types", e.to_string()),
+    });
+
+    let threshold = Threshold::get(store, keys);
+    let named = threshold.named();
+    let mut problems: Vec<String> = named
+        .iter()
+        .filter(|(_, val)| *val == 0)
+        .map(|(name, _)| format!("{} is 0, so its alert is always raised", name))
+        .collect();
+    for &direction in &Direction::ALL {
+        if let (Some(warn), Some(cut)) = threshold.of(direction) {
+            if warn >= cut {
+                problems.push(format!(
+                    "{} is not below {}",
+                    threshold_name(direction, Level::Warn),
+                    threshold_name(direction, Level::Cut)
+                ));
+            }
+        }
+    }
+    findings.push(if named.is_empty() {
+        Finding::warn("Thresholds", "none are set, so no alert can be raised")
+    } else if problems.is_empty() {
+        let names: Vec<&str> = named.iter().map(|(name, _)| name.as_str()).collect();
+        Finding::pass("Thresholds", format!("{} set", names.join(", ")))
+    } else {
+        Finding::warn("Thresholds", problems.join("; "))
+    });
+
+    let legacy_only: Vec<String> = [("warn", "total_warn"), ("cut", "total_cut")]
+        .iter()
+        .filter(|&&(legacy, current)| {
+            store.get(&[NET, NOTIFY, legacy]).is_ok() && store.get(&[NET, NOTIFY, current]).is_err()
+        })
+        .map(|&(legacy, current)| {
+            format!("{} is only stored under its legacy key {}", current, legacy)
+        })
+        .collect();
+    findings.push(match opt.schema {
+        Layout::Legacy => Finding::warn(
+            "Schema",
+            "the legacy layout is in use, while v2 is current (--schema v2)",
+        ),
+        _ if !legacy_only.is_empty() => Finding::warn("Schema", legacy_only.join("; ")),
+        _ => Finding::pass("Schema", "the current layout is in use"),
+    });
+
+    // a daemon which has polled before is expected to keep polling
+    let interval = if opt.adaptive_interval {
+        opt.max_interval
+    } else {
+        opt.interval
+    };
+    findings.push(match stored_total(store, UPDATED) {
+        None => Finding::pass("Daemon", "no daemon poll has been recorded"),
+        Some(updated) if unix_time().saturating_sub(updated) > 3 * interval => Finding::warn(
+            "Daemon",
+            format!(
+                "last polled at {}, over three intervals ago; is the daemon running?",
+                format_local_time(updated)
+            ),
+        ),
+        Some(updated) => Finding::pass(
+            "Daemon",
+            format!("last polled at {}", format_local_time(updated)),
+        ),
+    });
+
+    findings
+}
+
+/// Run a command of the interactive mode against the data store
+fn run_input(opt: &Opt, store: &Store, keys: &Keys, input: Input) -> Result<(), BoxError> {
+    if opt.observe && matches!(input, Input::Set(..) | Input::Reset { .. }) {
+        return Err("The data store is read-only in observe mode".into());
+    }
+    match input {
+        Input::Get(path) => {
+            let value: serde_json::Value = store.get(&path).map_err(NestError::from)?.try_into()?;
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        }
+        Input::Set(path, value) => store
+            .set(&path, &Value::from(value))
+            .map_err(NestError::from)?,
+        Input::Status => print_status(
+            store,
+            keys,
+            None,
+            &mut Pseudonyms::new(false),
+            opt.palette(),
+        ),
+        Input::Reset {
+            confirm,
+            rx_only,
+            tx_only,
+        } => reset_totals(opt, store, keys, confirm, rx_only, tx_only)?,
+        Input::Help => println!("{}", repl::HELP),
+        Input::Quit | Input::Empty => (),
+    }
+
+    Ok(())
+}
+
+/// Read commands of the interactive mode from stdin until `quit` or the end of input
+///
+/// A command which fails has its error printed before the next is read, so
+/// that a mistake does not end the session. A prompt is only shown when
+/// stdin is a terminal.
+fn repl(opt: &Opt, store: &Store, keys: &Keys) -> Result<(), BoxError> {
+    let prompt = io::stdin().is_terminal();
+    let mut line = String::new();
+    loop {
+        if prompt {
+            print!("> ");
+            io::stdout().flush()?;
+        }
+        line.clear();
+        if io::stdin().read_line(&mut line)? == 0 {
+            break;
+        }
+        match repl::parse(&line) {
+            Ok(Input::Quit) => break,
+            Ok(input) => {
+                if let Err(e) = run_input(opt, store, keys, input) {
+                    println!("Error: {}", e);
+                }
+            }
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask whether to go ahead, defaulting to no
+fn confirm(question: &str) -> io::Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Zero the usage totals of the interface, or those of one direction
+///
+/// Without `--confirm` the reset is only made once confirmed at a prompt,
+/// and refused outright when stdin is not a terminal. The combined total is
+/// left holding the usage of the direction which is kept, if any.
+fn reset_totals(
+    opt: &Opt,
+    store: &Store,
+    keys: &Keys,
+    confirmed: bool,
+    rx_only: bool,
+    tx_only: bool,
+) -> Result<(), BoxError> {
+    let (reset_rx, reset_tx) = (!tx_only, !rx_only);
+    let described = match (reset_rx, reset_tx) {
+        (true, false) => "received total",
+        (false, true) => "transmitted total",
+        _ => "usage totals",
+    };
+    let question = format!("Reset the {} of {} to zero?", described, opt.iface);
+    if !confirmed {
+        if !io::stdin().is_terminal() {
+            return Err(format!("Not resetting the {} without --confirm", described).into());
+        }
+        if !confirm(&question)? {
+            println!("Reset cancelled");
+            return Ok(());
+        }
+    }
+
+    let current = Totals::get(store, keys);
+    // a reset of both directions ends the billing cycle
+    if reset_rx && reset_tx && opt.keep_cycles > 0 {
+        let time = unix_time();
+        let totals = Archived {
+            rx: current.rx,
+            tx: current.tx,
+            time,
+        };
+        archive::record(store, local_date(time), &totals, opt.keep_cycles)?;
+    }
+    let (rx, tx) = (
+        if reset_rx { 0 } else { current.rx },
+        if reset_tx { 0 } else { current.tx },
+    );
+    let totals = Totals {
+        rx,
+        tx,
+        total: rx + tx,
+        version: current.version + 1,
+    };
+    totals.set(store, keys, &opt.iface)?;
+    println!("Reset the {} of {}", described, opt.iface);
+
+    Ok(())
+}
+
+/// Write the contents of each file of the data store as a snapshot, to a file or stdout
+///
+/// Files which have not been written yet are left out. A snapshot file is
+/// replaced atomically, so an interrupted snapshot never leaves a partial one.
+fn write_snapshot(
+    store: &Store,
+    output: Option<&Path>,
+    identity: Option<&Identity>,
+) -> Result<(), BoxError> {
+    let mut files = serde_json::Map::new();
+    for &file in &FILES {
+        if let Ok(contents @ Value::Object(_)) = store.get(&[NET, file]) {
+            files.insert(file.to_string(), contents.try_into()?);
+        }
+    }
+    let snapshot = serde_json::to_string_pretty(&snapshot::create(files, unix_time(), identity))?;
+
+    match output {
+        Some(path) => write_atomically(path, &(snapshot + "\n"))?,
+        None => println!("{}", snapshot),
+    }
+
+    Ok(())
+}
+
+/// Read a snapshot file
+fn read_snapshot(path: &Path) -> Result<serde_json::Value, BoxError> {
+    let snapshot = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| format!("Failed to parse snapshot {}: {}", path.display(), e))?;
+
+    Ok(snapshot)
+}
+
+/// Replace the files of the data store with those of a snapshot
+///
+/// The whole snapshot is checked before anything is written, and files which
+/// it does not hold are emptied, so that nothing of the previous state is
+/// left mixed in. As for a reset, this is only done once confirmed.
+fn restore_snapshot(path: &Path, store: &Store, confirmed: bool) -> Result<(), BoxError> {
+    let snapshot = read_snapshot(path)?;
+    let (created, files) = snapshot::validate(&snapshot)?;
+    let taken = created.map_or_else(
+        || "at an unknown time".to_string(),
+        |time| format!("at {}", format_local_time(time)),
+    );
+
+    if !confirmed {
+        if !io::stdin().is_terminal() {
+            return Err("Not restoring the snapshot without --confirm".into());
+        }
+        let question = format!("Replace the data store with the snapshot taken {}?", taken);
+        if !confirm(&question)? {
+            println!("Restore cancelled");
+            return Ok(());
+        }
+    }
+
+    for &file in &FILES {
+        let contents = match files.get(file) {
+            Some(contents) => Value::from(contents.clone()),
+            None => match store.get(&[NET, file]) {
+                Ok(Value::Object(ref values)) if !values.is_empty() => {
+                    Value::Object(IndexMap::new())
+                }
+                _ => continue,
+            },
+        };
+        store.set(&[NET, file], &contents)?;
+    }
+    println!("Restored the snapshot taken {}", taken);
+
+    Ok(())
+}
+
+/// Thresholds in effect, with the source each came from
+///
+/// Sources take precedence as they do when alerts are evaluated: the active
+/// profile over the limits of the connection, which replace the stored
+/// thresholds. Of the stored thresholds, those given in the environment are
+/// stored over those derived from the data plan.
+fn threshold_settings(opt: &Opt, store: &Store, keys: &Keys) -> Vec<Setting> {
+    let connection = if opt.nm_thresholds {
+        nm::limits(&opt.iface)
+    } else {
+        Vec::new()
+    };
+    let time = unix_time();
+    let threshold = Threshold::active(store, keys, &connection, &opt.profiles, time);
+    let profile = active_profile(&opt.profiles, time);
+    let plan = opt
+        .plan
+        .map_or_else(Vec::new, |plan| plan_thresholds(plan, opt.plan_mode));
+    let given = |limits: &[(Direction, Level, u64)], direction, level| {
+        limits.iter().any(|&(d, l, _)| d == direction && l == level)
+    };
+
+    let mut settings = Vec::new();
+    for &direction in &Direction::ALL {
+        let (warn, cut) = threshold.of(direction);
+        for &(level, value) in &[(Level::Warn, warn), (Level::Cut, cut)] {
+            let name = threshold_name(direction, level);
+            let var = format!("PEACH_MONITOR_{}", name.to_uppercase());
+            let source = match profile {
+                Some(profile) if given(&profile.thresholds, direction, level) => {
+                    Origin::Profile(profile.spec.clone())
+                }
+                _ if given(&connection, direction, level) => Origin::NetworkManager,
+                _ if env::var(&var).is_ok_and(|value| !value.trim().is_empty()) => {
+                    Origin::Environment(var)
+                }
+                _ if given(&plan, direction, level) => Origin::Plan,
+                _ if value.is_some() => Origin::DataStore,
+                _ => Origin::Unset,
+            };
+            settings.push(Setting {
+                name,
+                value: value.map(|val| format_bytes(to_bytes(val))),
+                source,
+            });
+        }
+    }
+
+    settings
+}
+
+/// Print the options and thresholds in effect, with the source each came from
+fn print_config(options: &[Setting], thresholds: &[Setting], format: ConfigFormat) {
+    match format {
+        ConfigFormat::Json => {
+            let list = |settings: &[Setting]| -> Vec<serde_json::Value> {
+                settings.iter().map(Setting::to_json).collect()
+            };
+            let config = json!({ "options": list(options), "thresholds": list(thresholds) });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&config).unwrap_or_default()
+            );
+        }
+        ConfigFormat::Text => {
+            let width = options
+                .iter()
+                .chain(thresholds)
+                .map(|setting| setting.name.len())
+                .max()
+                .unwrap_or(0);
+            for (title, settings) in &[("Options:", options), ("Thresholds:", thresholds)] {
+                println!("{}", title);
+                for setting in settings.iter() {
+                    println!(
+                        "  {:<width$}  {:<24}  ({})",
+                        setting.name,
+                        setting.value.as_deref().unwrap_or("-"),
+                        setting.source,
+                        width = width
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Print the differences in totals, thresholds and alert flags between two snapshots
+fn print_comparison(before: &Path, after: &Path, format: CompareFormat) -> Result<(), BoxError> {
+    let (earlier, later) = (read_snapshot(before)?, read_snapshot(after)?);
+    let (before_created, before_files) =
+        snapshot::validate(&earlier).map_err(|e| format!("{}: {}", before.display(), e))?;
+    let (after_created, after_files) =
+        snapshot::validate(&later).map_err(|e| format!("{}: {}", after.display(), e))?;
+    let changes = snapshot::compare(before_files, after_files);
+
+    match format {
+        CompareFormat::Json => {
+            let side = |path: &Path, created: Option<u64>| json!({ "file": path.display().to_string(), "created": created });
+            let changes: Vec<serde_json::Value> = changes
+                .iter()
+                .map(|(key, change)| {
+                    json!({
+                        "key": key,
+                        "before": change.before(),
+                        "after": change.after(),
+                        "difference": change.difference().map(|d| d as i64),
+                    })
+                })
+                .collect();
+            let comparison = json!({
+                "before": side(before, before_created),
+                "after": side(after, after_created),
+                "changes": changes,
+            });
+            println!("{}", serde_json::to_string_pretty(&comparison)?);
+        }
+        CompareFormat::Human => {
+            let taken = |created: Option<u64>| {
+                created.map_or_else(|| "unknown time".to_string(), format_local_time)
+            };
+            println!(
+                "Before:      {} ({})",
+                before.display(),
+                taken(before_created)
+            );
+            println!(
+                "After:       {} ({})",
+                after.display(),
+                taken(after_created)
+            );
+            println!();
+            if changes.is_empty() {
+                println!("No differences");
+            }
+            let width = changes.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+            for (key, change) in &changes {
+                println!(
+                    "{:<width$}  {}",
+                    key,
+                    describe_change(change),
+                    width = width
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Describe the change of a key between snapshots, e.g. `1,024 -> 2,048 (+1,024)`
+fn describe_change(change: &snapshot::Change) -> String {
+    let show = |value: &serde_json::Value| match value.as_u64() {
+        Some(number) => group_digits(number),
+        None => value.to_string(),
+    };
+    match change {
+        snapshot::Change::Added(after) => format!("added: {}", show(after)),
+        snapshot::Change::Removed(before) => format!("removed (was {})", show(before)),
+        snapshot::Change::Changed(before, after) => {
+            let mut described = format!("{} -> {}", show(before), show(after));
+            if let Some(difference) = change.difference() {
+                let sign = if difference < 0 { "-" } else { "+" };
+                let magnitude = group_digits(difference.unsigned_abs() as u64);
+                described.push_str(&format!(" ({}{})", sign, magnitude));
+            }
+            described
+        }
+    }
+}
+
+/// Back up and reinitialise corrupt data store files once confirmed
+fn repair_store(corrupt: Vec<PathBuf>) -> Result<(), BoxError> {
+    for path in &corrupt {
+        println!("Corrupt:     {}", path.display());
+    }
+    if !confirm("Back up and reinitialise the corrupt files? Their data will be lost")? {
+        return Err(NestError::Corrupt { paths: corrupt }.into());
+    }
+    for backup in integrity::back_up(&corrupt, unix_time())? {
+        println!("Backed up:   {}", backup.display());
+    }
+
+    Ok(())
+}
+
+/// Ensure the data directory can be written to by creating and removing a probe file
+fn check_writable(path: &Path) -> Result<(), NestError> {
+    let probe = path.join(".write-test");
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&probe)
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|source| NestError::NotWritable {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Log the latest traffic statistics and alert state without writing to the data store
+fn log_unpersisted(iface: &str, source: &Source, store: &Store, keys: &Keys, inclusive: bool) {
+    match Traffic::get(iface, source) {
+        Ok(traffic) => info!(
+            "{} traffic counters: {} bytes received, {} bytes transmitted",
+            iface,
+            group_digits(traffic.rx),
+            group_digits(traffic.tx)
+        ),
+        Err(e) => error!("{}", e),
+    }
+    let threshold = Threshold {
+        inclusive,
+        ..Threshold::get(store, keys)
+    };
+    for (direction, alert) in alert_totals(store, keys, &threshold) {
+        info!(
+            "{}: {}, {}: {}; persistence is disabled",
+            keys.alert(direction, Level::Warn)[0],
+            alert.warn,
+            keys.alert(direction, Level::Cut)[0],
+            alert.cut
+        );
+    }
+}
+
+/// Number of attempts made to create the data directory
+const DATA_DIR_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of data directory creation
+const DATA_DIR_RETRY_DELAY: time::Duration = time::Duration::from_millis(200);
+
+/// Warn once if the daemon will write frequently to the flash storage holding the root filesystem
+fn warn_flash_writes(path: &Path, opt: &Opt) {
+    let writes = writes_per_day(opt.interval);
+    if writes > FREQUENT_WRITES_PER_DAY && on_root_flash(path, opt.storage) {
+        warn!(
+            "The data store is on the same flash storage as the root filesystem and polling every {}s \
+             writes to it around {} times a day, which may shorten its life; consider a longer \
+             --interval (or pass --storage other to silence this warning)",
+            opt.interval,
+            group_digits(writes)
+        );
+    }
+}
+
+/// System directory holding the data store when there is no XDG data directory
+const FALLBACK_DATA_DIR: &str = "/var/lib/peachcloud";
+
+/// Directory holding the data store by default: the XDG data directory
+///
+/// A service run without a user session (e.g. a systemd system service) may
+/// have neither `$XDG_DATA_HOME` nor `$HOME` to find it from, in which case
+/// the system directory is used instead. Without them, the home directory
+/// would otherwise be looked up from the user database, which for a service
+/// run as root is that of root rather than a directory meant for services.
+fn default_data_dir() -> PathBuf {
+    let unset = |var| env::var_os(var).is_none_or(|value| value.is_empty());
+    if unset("XDG_DATA_HOME") && unset("HOME") {
+        warn!(
+            "Neither XDG_DATA_HOME nor HOME is set; using {} (pass --data-dir to choose another)",
+            FALLBACK_DATA_DIR
+        );
+        return PathBuf::from(FALLBACK_DATA_DIR);
+    }
+    match xdg::BaseDirectories::new() {
+        Ok(dirs) => dirs.get_data_home().join("peachcloud"),
+        Err(e) => {
+            warn!(
+                "No XDG data directory ({}); using {} (pass --data-dir to choose another)",
+                e, FALLBACK_DATA_DIR
+            );
+            PathBuf::from(FALLBACK_DATA_DIR)
+        }
+    }
+}
+
+/// Create the data directory, retrying while the filesystem may still be mounting
+///
+/// The default data directory is used unless another directory is given.
+fn create_data_dir(data_dir: Option<&Path>) -> Result<PathBuf, NestError> {
+    let path = data_dir.map_or_else(default_data_dir, Path::to_path_buf);
+    retry_with_backoff(DATA_DIR_ATTEMPTS, DATA_DIR_RETRY_DELAY, || {
+        fs::create_dir_all(&path).map(|_| path.clone())
+    })
+    .map_err(|source| NestError::DataDirectory { source })
+}
+
+/// Define the schema of the data store
+pub(crate) fn store_schema() -> Result<Schema, NestError> {
+    let files: serde_json::Map<String, serde_json::Value> = FILES
+        .iter()
+        .map(|file| (file.to_string(), json!("json")))
+        .collect();
+    let schema = json!({ NET: files }).try_into()?;
+
+    Ok(schema)
+}
+
+/// Store the data plan, window cap and anomaly factor given on the command line
+fn store_config(opt: &Opt, store: &Store, keys: &Keys) -> Result<(), NestError> {
+    // derive and store thresholds from the data plan
+    if let Some(plan) = opt.plan {
+        set_plan(store, keys, plan, opt.plan_mode)?;
+    }
+
+    // store the cap on usage within the trailing window
+    if let Some(cap) = opt.window_cap {
+        store.set(&[NET, NOTIFY, WINDOW_CUT], &Value::Uint(to_megabytes(cap)))?;
+    }
+
+    // store the day the billing cycle starts on
+    if let Some(day) = opt.cycle_day {
+        store.set(&[NET, NOTIFY, CYCLE_DAY], &Value::Uint(day as u64))?;
+    }
+
+    // store the factor above which a day's usage is anomalous
+    if let Some(factor) = opt.anomaly_factor {
+        store.set(&[NET, NOTIFY, ANOMALY_FACTOR], &Value::Float(factor))?;
+    }
+
+    // store the factor above which a day's upload ratio is anomalous
+    if let Some(factor) = opt.upload_factor {
+        store.set(&[NET, NOTIFY, UPLOAD_FACTOR], &Value::Float(factor))?;
+    }
+
+    Ok(())
+}
+
+/// Feed the readings of a trace through a scratch data store, printing each alert change
+///
+/// The scratch store starts from the thresholds of the real data store,
+/// overridden by any given on the command line, so that an alert
+/// configuration can be tested without touching the stored totals. Readings
+/// are processed as fast as possible, each at the time recorded in the trace.
+fn replay(opt: &Opt, keys: &Keys, trace_path: &Path) -> Result<(), BoxError> {
+    let readings = trace::load(trace_path)?;
+    let real = Store::new(&create_data_dir(opt.data_dir.as_deref())?, store_schema()?);
+
+    let path = std::env::temp_dir().join(format!("peach-monitor-replay-{}", process::id()));
+    fs::create_dir_all(&path).map_err(|source| NestError::DataDirectory { source })?;
+    let store = Store::new(&path, store_schema()?);
+    let result = replay_readings(opt, keys, &real, &store, &readings);
+
+    if let Err(e) = fs::remove_dir_all(&path) {
+        warn!(
+            "Failed to remove scratch data store {}: {}",
+            path.display(),
+            e
+        );
+    }
+    result
+}
+
+/// Configure a scratch data store like the real one, then replay readings against it
+fn replay_readings(
+    opt: &Opt,
+    keys: &Keys,
+    real: &Store,
+    store: &Store,
+    readings: &[Reading],
+) -> Result<(), BoxError> {
+    if let Ok(Value::Object(notify)) = real.get(&[NET, NOTIFY]) {
+        for (key, val) in &notify {
+            store
+                .set(&[NET, NOTIFY, key], val)
+                .map_err(NestError::from)?;
+        }
+    }
+    store_config(opt, store, keys)?;
+
+    for reading in readings {
+        let before = alert_flags(store, keys);
+        let delta = update_transmission_totals(opt, reading, store, keys, false)?;
+        if let Some(window) = opt.window {
+            record_sample(store, &delta, window, reading.time)?;
+        }
+        update_alerts(opt, store, keys, reading.time)?;
+
+        let after = alert_flags(store, keys);
+        for (flag, value) in hook::edges(&before, &after) {
+            let change = if value { "raised" } else { "cleared" };
+            println!(
+                "{} {} {} on {}",
+                format_local_time(reading.time),
+                flag,
+                change,
+                reading.iface
+            );
+        }
+    }
+
+    println!("Replayed {} readings", readings.len());
+    print_status(
+        store,
+        keys,
+        None,
+        &mut Pseudonyms::new(false),
+        opt.palette(),
+    );
+
+    Ok(())
+}
+
+/// Time each stage of the poll path over a number of cycles and print a summary
+fn bench(
+    iface: &str,
+    source: &Source,
+    keys: &Keys,
+    semantics: CounterSemantics,
+    cycles: u32,
+) -> Result<(), BoxError> {
+    let stages = time_stages(iface, source, keys, semantics, cycles)?;
+
+    println!("Ran {} poll cycles on {}", stages[0].count(), iface);
+    println!("{}", bench::header());
+    for timings in &stages {
+        println!("{}", timings.row());
+    }
+
+    Ok(())
+}
+
+/// Time each stage of the poll path (read, delta, totals and alerts) over a number of cycles
+///
+/// The cycles run against a scratch data store in the temporary directory,
+/// which is removed afterwards, so the stored totals are left untouched.
+fn time_stages(
+    iface: &str,
+    source: &Source,
+    keys: &Keys,
+    semantics: CounterSemantics,
+    cycles: u32,
+) -> Result<[Timings; 4], BoxError> {
+    let path = std::env::temp_dir().join(format!("peach-monitor-bench-{}", process::id()));
+    fs::create_dir_all(&path).map_err(|source| NestError::DataDirectory { source })?;
+    let store = Store::new(&path, store_schema()?);
+
+    let mut read = Timings::new("read");
+    let mut delta = Timings::new("delta");
+    let mut totals = Timings::new("totals");
+    let mut alerts = Timings::new("alerts");
+    let result = (0..cycles).try_for_each(|_| -> Result<(), BoxError> {
+        let reading = read.time(|| take_reading(iface, source, None))?;
+        let calculated = delta.time(|| {
+            let last = LastCounters::get(&store, keys, iface);
+            Delta::calculate(
+                iface,
+                &reading.traffic,
+                &store,
+                &last,
+                &reading.boot,
+                semantics,
+            )
+        });
+        totals.time(|| {
+            store_delta(&reading, &calculated, &store, keys, false)?;
+            history::record(
+                &store,
+                Local::now().date_naive(),
+                calculated.rx,
+                calculated.tx,
+                0,
+                None,
+            )
+        })?;
+        alerts
+            .time(|| set_alert_flags(&store, keys, &Threshold::get(&store, keys), |_| true, 1))?;
+
+        Ok(())
+    });
+
+    if let Err(e) = fs::remove_dir_all(&path) {
+        warn!(
+            "Failed to remove scratch data store {}: {}",
+            path.display(),
+            e
+        );
+    }
+    result?;
+
+    Ok([read, delta, totals, alerts])
+}
+
+fn run(opt: Opt, options: Vec<Setting>) -> Result<(), BoxError> {
+    let keys = opt.keys();
+    keys.check_alert_keys()?;
+    check_threshold_options(&opt)?;
+    // ensure-thresholds reads the same variables, only where its options are not given
+    let env_thresholds = match opt.cmd {
+        Some(Command::EnsureThresholds { .. }) => Vec::new(),
+        _ => env_thresholds(opt.max_threshold)?,
+    };
+    if opt.observe && opt.writes() {
+        return Err(
+            "--observe cannot be used with options or commands which write to the data store"
+                .into(),
+        );
+    }
+
+    // benchmarking uses a scratch data store rather than the real one
+    if let Some(Command::Bench { cycles }) = opt.cmd {
+        return bench(
+            &opt.iface,
+            &opt.source(),
+            &keys,
+            opt.counter_semantics,
+            cycles,
+        );
+    }
+
+    // replaying uses a scratch data store configured like the real one
+    if let Some(Command::Replay { ref trace }) = opt.cmd {
+        return replay(&opt, &keys, trace);
+    }
+
+    // comparing only reads the snapshot files
+    if let Some(Command::Compare {
+        ref before,
+        ref after,
+        format,
+    }) = opt.cmd
+    {
+        return print_comparison(before, after, format);
+    }
+
+    // per-host usage is read from the kernel rather than the data store
+    #[cfg(feature = "top-talkers")]
+    if let Some(Command::TopTalkers {
+        count,
+        by,
+        ref file,
+    }) = opt.cmd
+    {
+        return print_top_talkers(file, by, count);
+    }
+
+    // define the path
+    let path = create_data_dir(opt.data_dir.as_deref())?;
+
+    // create the data store
+    let store = Store::new(&path, store_schema()?);
+    let source = opt.source();
+
+    // diagnosing reports problems which would otherwise stop the run below
+    if let Some(Command::Doctor) = opt.cmd {
+        return doctor(&opt, &store, &keys, &path);
+    }
+
+    // corrupt files would read as empty and fail every write
+    let corrupt = integrity::check(&store, &path);
+    if !corrupt.is_empty() {
+        if !opt.repair {
+            return Err(NestError::Corrupt { paths: corrupt }.into());
+        }
+        repair_store(corrupt)?;
+    }
+    if opt.strict || opt.fail_fast {
+        check_types(&store, &keys)?;
+    }
+
+    if opt.adaptive_interval && (opt.min_interval == 0 || opt.min_interval > opt.max_interval) {
+        return Err("--min-interval must be at least 1 and no greater than --max-interval".into());
+    }
+    if opt.write_attempts == 0 {
+        return Err("--write-attempts must be at least 1".into());
+    }
+
+    // ensure the data store can be written to before making any changes
+    let mut persist = true;
+    if opt.observe {
+        // the daemon only reads, leaving the writes to the instance doing the accounting
+        info!("Observing the data store without writing to it");
+        persist = false;
+    } else if let Err(e) = check_writable(&path) {
+        if opt.writes() || opt.fail_fast {
+            return Err(e.into());
+        }
+        // the daemon remains useful for display purposes without persistence
+        if opt.daemon {
+            error!("{}; persistence is disabled", e);
+        }
+        persist = false;
+    }
+
+    if opt.daemon && persist {
+        warn_flash_writes(&path, &opt);
+    }
+
+    store_config(&opt, &store, &keys)?;
+    if !env_thresholds.is_empty() {
+        if !persist {
+            info!("Thresholds given in the environment are not stored without persistence");
+        } else if ensure_thresholds(&store, &keys, &env_thresholds)? {
+            info!("Stored the thresholds given in the environment");
+        }
+    }
+
+    // print the counters before a save updates them
+    if opt.debug_counters {
+        print_counters(&opt.iface, &source, &store, &keys, opt.counter_semantics)?;
+    }
+
+    // update network transmission totals
+    if opt.save {
+        let reading = take_reading(&opt.iface, &source, opt.record.as_deref())?;
+        let delta = update_transmission_totals(&opt, &reading, &store, &keys, false)?;
+        if let Some(window) = opt.window {
+            record_sample(&store, &delta, window, reading.time)?;
+        }
+        write_sinks(&opt.sinks(), &opt.iface, &delta, reading.time);
+    }
+
+    // update alert flags
+    if opt.update {
+        let flags = alert_flags(&store, &keys);
+
+        // test transmission totals against alert thresholds and set flags
+        let alerts = update_alerts(&opt, &store, &keys, unix_time())?;
+
+        // wait for the notifications so that their outcome is logged before exiting
+        let backends = opt.backends();
+        if !backends.is_empty() {
+            for handle in notify_alert_changes(&backends, &opt.iface, &store, &keys, &flags) {
+                let _ = handle.join();
+            }
+        }
+
+        if let Some(ref breach_file) = opt.breach_file {
+            update_breach_file(breach_file, &keys, &alerts)?;
+        }
+        if let Some(ref status_file) = opt.status_file {
+            update_status_file(status_file, &store, &keys, &alerts, unix_time())?;
+        }
+    }
+
+    match opt.cmd {
+        Some(Command::Status { round, anonymize }) => {
+            let names = &mut Pseudonyms::new(anonymize);
+            print_status(&store, &keys, round, names, opt.palette());
+        }
+        Some(Command::Summary) => print_summary(&store, &keys),
+        Some(Command::Dash) => run_dash(&store, &keys, opt.interval, opt.palette())?,
+        Some(Command::WatchAlerts { ref flag, timeout }) => watch_alerts(
+            &store,
+            &keys,
+            flag,
+            Duration::from_secs(opt.interval),
+            timeout,
+            opt.palette(),
+        )?,
+        Some(Command::TestAlert { ref flag }) => {
+            test_alert(&opt.backends(), &opt.iface, &store, &keys, flag)?
+        }
+        Some(Command::Pause) => set_paused(&store, true)?,
+        Some(Command::Resume) => set_paused(&store, false)?,
+        Some(Command::Metered) => set_metered(&store, true)?,
+        Some(Command::Unmetered) => set_metered(&store, false)?,
+        Some(Command::EnsureThresholds {
+            total_warn,
+            total_cut,
+            rx_warn,
+            rx_cut,
+            tx_warn,
+            tx_cut,
+        }) => {
+            let desired = [
+                (Direction::Combined, Level::Warn, total_warn),
+                (Direction::Combined, Level::Cut, total_cut),
+                (Direction::Rx, Level::Warn, rx_warn),
+                (Direction::Rx, Level::Cut, rx_cut),
+                (Direction::Tx, Level::Warn, tx_warn),
+                (Direction::Tx, Level::Cut, tx_cut),
+            ];
+            if ensure_thresholds(&store, &keys, &desired)? {
+                println!("changed");
+            } else {
+                println!("unchanged");
+            }
+        }
+        Some(Command::CheckKeys) => check_keys(&store, &keys)?,
+        Some(Command::Doctor) => (),
+        Some(Command::Repl) => repl(&opt, &store, &keys)?,
+        Some(Command::Checkpoint { ref name }) => set_checkpoint(&store, &keys, name)?,
+        Some(Command::Diff { ref name }) => print_diff(&store, &keys, name)?,
+        Some(Command::Reset {
+            confirm,
+            rx_only,
+            tx_only,
+        }) => reset_totals(&opt, &store, &keys, confirm, rx_only, tx_only)?,
+        Some(Command::History) => print_history(&opt, &store)?,
+        Some(Command::Cycles) => print_cycles(&store),
+        Some(Command::Export {
+            format,
+            from,
+            to,
+            include_identity,
+            anonymize,
+        }) => {
+            let identity = read_identity(&opt.iface, include_identity, anonymize)?;
+            export_history(&opt, &store, format, from, to, identity.as_ref())?
+        }
+        Some(Command::Snapshot {
+            ref output,
+            include_identity,
+            anonymize,
+        }) => {
+            let identity = read_identity(&opt.iface, include_identity, anonymize)?;
+            write_snapshot(&store, output.as_deref(), identity.as_ref())?
+        }
+        Some(Command::Restore {
+            ref snapshot,
+            confirm,
+        }) => restore_snapshot(snapshot, &store, confirm)?,
+        Some(Command::PruneHistory {
+            older_than,
+            dry_run,
+        }) => prune_history(&store, older_than, dry_run)?,
+        Some(Command::Config { format }) => {
+            let thresholds = threshold_settings(&opt, &store, &keys);
+            print_config(&options, &thresholds, format);
+        }
+        #[cfg(feature = "top-talkers")]
+        Some(Command::TopTalkers { .. }) => (),
+        Some(Command::Bench { .. })
+        | Some(Command::Replay { .. })
+        | Some(Command::Compare { .. })
+        | None => (),
+    }
+
+    // lazy writes of a single run are left to the kernel to write out
+    if !opt.daemon && persist && opt.durability == Durability::Sync {
+        flush(&path)?;
+    }
+
+    if opt.daemon {
+        // signals stop the daemon loop and start the shutdown timeout
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let (signal_tx, signal_rx) = mpsc::channel();
+        // the daemon also stops by itself when its first poll fails under fail-fast
+        let failed_tx = signal_tx.clone();
+
+        if let Some(runtime) = opt.max_runtime {
+            stop_after(runtime, stop_tx.clone(), signal_tx.clone());
+        }
+
+        ctrlc::set_handler(move || {
+            let _ = stop_tx.send(());
+            let _ = signal_tx.send(());
+        })
+        .expect("Error setting Ctrl-C handler");
+
+        let journal = if opt.journal {
+            match Journal::connect() {
+                Ok(journal) => Some(journal),
+                Err(e) => {
+                    warn!(
+                        "Failed to connect to the systemd journal: {}; falling back to regular logging",
+                        e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some(addr) = opt.metrics_addr {
+            // the server reads the store independently of the daemon loop
+            let store = Store::new(&path, store_schema()?);
+            let keys = keys.clone();
+            http::serve(addr, move |path| match path {
+                "/metrics" => Some(Response {
+                    content_type: metrics::CONTENT_TYPE,
+                    body: metrics_exposition(&store, &keys),
+                }),
+                _ => None,
+            })?;
+        }
+
+        if let Some(port) = opt.api_port {
+            // like the metrics server, the API reads the store independently
+            let store = Store::new(&path, store_schema()?);
+            let keys = keys.clone();
+            http::serve(SocketAddr::new(opt.api_bind, port), move |path| {
+                api_response(path, &store, &keys)
+            })?;
+        }
+
+        let timeout = opt.shutdown_timeout;
+        let (done_tx, done_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = run_daemon(
+                &opt,
+                &store,
+                &keys,
+                &path,
+                persist,
+                journal.as_ref(),
+                &stop_rx,
+            );
+            if result.is_err() {
+                let _ = failed_tx.send(());
+            }
+            let _ = done_tx.send(result);
+        });
+
+        // wait for SIGINT, SIGTERM or the maximum runtime, then give the final save a bounded time to finish
+        let _ = signal_rx.recv();
+        if !await_final_save(&done_rx, Duration::from_secs(timeout))? {
+            warn!(
+                "Final save did not finish within {}s; exiting without it",
+                timeout
+            );
+        }
+
+        println!("Terminating gracefully...");
+    }
+
+    Ok(())
+}
+
+/// Wait up to a timeout for the daemon to finish, returning whether it did
+///
+/// A daemon which failed, including in its final save, returns its error.
+fn await_final_save(
+    done: &Receiver<Result<(), String>>,
+    timeout: Duration,
+) -> Result<bool, String> {
+    match done.recv_timeout(timeout) {
+        Ok(result) => result.map(|()| true),
+        Err(RecvTimeoutError::Timeout) => Ok(false),
+        // the daemon is gone without reporting, so there is nothing to wait for
+        Err(RecvTimeoutError::Disconnected) => Ok(true),
+    }
+}
+
+/// Install the logger configured by `RUST_LOG`, letting through up to a number of lines per minute
+fn init_logger(max_rate: Option<u32>) -> Result<(), String> {
+    let logger = env_logger::Builder::from_default_env().build();
+    log::set_max_level(logger.filter());
+    let result = match max_rate {
+        Some(0) => return Err("--max-log-rate must be at least 1".to_string()),
+        Some(rate) => log::set_boxed_logger(Box::new(Throttled::new(logger, rate))),
+        None => log::set_boxed_logger(Box::new(logger)),
+    };
+
+    result.map_err(|e| format!("Failed to initialise the logger: {}", e))
+}
+
+/// Run the binary with the arguments it was started with, exiting on failure
+pub fn main() {
+    // parse cli arguments, keeping the matches to tell where each option came from
+    let matches = Opt::clap().get_matches();
+    let opt = Opt::from_clap(&matches);
+    let options = config::options(&option_specs(), &matches);
+
+    // initialise the logger
+    if let Err(e) = init_logger(opt.max_log_rate) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+
+    let result = run(opt, options);
+    // log the count of any lines held back by the rate limit
+    log::logger().flush();
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::{lock_env, scratch_dir, scratch_store};
+
+    fn opt(args: &[&str]) -> Opt {
+        let _env = lock_env();
+        Opt::from_iter(std::iter::once("peach-monitor").chain(args.iter().copied()))
+    }
+
+    fn keys(opt: &Opt) -> Keys {
+        Keys::new(
+            opt.schema,
+            opt.compat_flat_keys,
+            opt.threshold_keys.clone(),
+            opt.alert_keys.clone(),
+            opt.total_unit,
+        )
+    }
+
+    fn reading(time: u64, boot: Option<&str>, rx: u64, tx: u64) -> Reading {
+        Reading {
+            time,
+            iface: "eth9".to_string(),
+            boot: boot.map(str::to_string),
+            traffic: Traffic { rx, tx },
+        }
+    }
+
+    /// Save each reading in turn, returning the stored totals
+    fn replay(opt: &Opt, store: &Store, readings: &[Reading]) -> Totals {
+        let keys = keys(opt);
+        for reading in readings {
+            update_transmission_totals(opt, reading, store, &keys, false).unwrap();
+        }
+        Totals::get(store, &keys)
+    }
+
+    const TIME: u64 = 1_760_000_000;
+
+    #[test]
+    fn first_reading_is_a_baseline() {
+        let (opt, store) = (opt(&[]), scratch_store("first-reading"));
+        let delta = update_transmission_totals(
+            &opt,
+            &reading(TIME, Some("b"), 5000, 700),
+            &store,
+            &keys(&opt),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!((delta.rx, delta.tx), (0, 0));
+        let totals = Totals::get(&store, &keys(&opt));
+        assert_eq!((totals.rx, totals.tx, totals.total), (0, 0, 0));
+        assert_eq!(stored_total(&store, RX_LAST), Some(5000));
+        assert_eq!(stored_total(&store, TX_LAST), Some(700));
+        let seen = store.get(&[NET, TRAFFIC, FIRST_SEEN]).unwrap();
+        assert_eq!(seen, json!({ "eth9": TIME }).try_into().unwrap());
+    }
+
+    #[test]
+    fn unknown_boot_is_unchanged() {
+        let opt = opt(&[]);
+        let readings = |boot| {
+            vec![
+                reading(TIME, boot, 1000, 0),
+                reading(TIME + 60, boot, 2000, 0),
+                reading(TIME + 120, boot, 3000, 0),
+            ]
+        };
+
+        let unknown = replay(&opt, &scratch_store("unknown-boot"), &readings(None));
+        let known = replay(&opt, &scratch_store("known-boot"), &readings(Some("b")));
+        assert_eq!(unknown.rx, 2000);
+        assert_eq!(known.rx, 2000);
+    }
+
+    #[test]
+    fn unknown_boot_is_not_stored() {
+        let (opt, store) = (opt(&[]), scratch_store("boot-not-stored"));
+        replay(&opt, &store, &[reading(TIME, None, 1000, 0)]);
+        assert_eq!(store.get(&[NET, TRAFFIC, BOOT_ID]).ok(), None);
+
+        // the boot of the counters is kept while the boot of a reading is unknown
+        replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME + 60, Some("b"), 2000, 0),
+                reading(TIME + 120, None, 3000, 0),
+            ],
+        );
+        assert_eq!(stored_string(&store, BOOT_ID).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn known_boots_which_differ_are_a_reboot() {
+        let (opt, store) = (opt(&[]), scratch_store("reboot"));
+        let totals = replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME, Some("a"), 1000, 0),
+                reading(TIME + 60, Some("a"), 3000, 0),
+                reading(TIME + 120, Some("b"), 500, 0),
+            ],
+        );
+        assert_eq!(totals.rx, 2500);
+    }
+
+    #[test]
+    fn writable_data_directory() {
+        let dir = scratch_dir("writable");
+        check_writable(&dir).unwrap();
+        // the probe file is removed again
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn read_only_data_directory() {
+        // a directory under a regular file cannot be written, even by root
+        let file = scratch_dir("read-only").join("file");
+        fs::write(&file, "").unwrap();
+        let under_file = file.join("store");
+        assert!(matches!(
+            check_writable(&under_file),
+            Err(NestError::NotWritable { path, .. }) if path == under_file
+        ));
+        assert!(matches!(
+            check_writable(&file),
+            Err(NestError::NotWritable { .. })
+        ));
+
+        let missing = scratch_dir("read-only").join("missing");
+        assert!(matches!(
+            check_writable(&missing),
+            Err(NestError::NotWritable { path, .. }) if path == missing
+        ));
+    }
+
+    #[test]
+    fn daemon_polls_without_persistence() {
+        let dir = scratch_dir("unpersisted");
+        let (polls, file) = (dir.join("polls"), dir.join("file"));
+        fs::write(&file, "").unwrap();
+        let command = format!("echo >> {}; echo 1000 100", polls.display());
+        let opt = opt(&[
+            "--daemon",
+            "--iface",
+            "eth9",
+            "--stats-command",
+            &command,
+            "--interval",
+            "1",
+        ]);
+        let path = file.join("store");
+        let (keys, store) = (keys(&opt), Store::new(&path, store_schema().unwrap()));
+        let persist = check_writable(&path).is_ok();
+        assert!(!persist);
+
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let timer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(2500));
+            stop_tx.send(()).unwrap();
+        });
+        run_daemon(&opt, &store, &keys, &path, persist, None, &stop_rx).unwrap();
+        timer.join().unwrap();
+
+        // the counters are read at each interval, without being stored
+        assert!(fs::read_to_string(&polls).unwrap().lines().count() >= 2);
+        assert!(!path.exists());
+    }
+
+    const MEGABYTE: u64 = 1024 * 1024;
+
+    #[test]
+    fn thresholds_derived_from_a_directional_plan() {
+        assert_eq!(
+            plan_thresholds(50 * 1024 * MEGABYTE, PlanMode::Directional),
+            vec![
+                (Direction::Rx, Level::Warn, 40960),
+                (Direction::Rx, Level::Cut, 51200),
+                (Direction::Tx, Level::Warn, 40960),
+                (Direction::Tx, Level::Cut, 51200),
+            ]
+        );
+    }
+
+    #[test]
+    fn thresholds_derived_from_a_combined_plan() {
+        assert_eq!(
+            plan_thresholds(10 * MEGABYTE, PlanMode::Combined),
+            vec![
+                (Direction::Combined, Level::Warn, 8),
+                (Direction::Combined, Level::Cut, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_is_stored_with_its_thresholds() {
+        let (opt, store) = (opt(&[]), scratch_store("plan"));
+        set_plan(&store, &keys(&opt), 100 * MEGABYTE, PlanMode::Directional).unwrap();
+
+        let notify = |key| store.get(&[NET, NOTIFY, key]).ok();
+        assert_eq!(notify("rx_warn"), Some(Value::Uint(80)));
+        assert_eq!(notify("rx_cut"), Some(Value::Uint(100)));
+        assert_eq!(notify("tx_warn"), Some(Value::Uint(80)));
+        assert_eq!(notify("tx_cut"), Some(Value::Uint(100)));
+        assert_eq!(notify(PLAN), Some(Value::Uint(100)));
+        assert_eq!(
+            notify(PLAN_MODE),
+            Some(Value::String("directional".to_string()))
+        );
+    }
+
+    #[test]
+    fn breach_file_follows_the_cutoff_alerts() {
+        let keys = keys(&opt(&[]));
+        let path = scratch_dir("breach").join("breached");
+        let alerts = |rx_cut| {
+            vec![
+                (
+                    Direction::Rx,
+                    Alert {
+                        warn: true,
+                        cut: rx_cut,
+                    },
+                ),
+                (
+                    Direction::Tx,
+                    Alert {
+                        warn: true,
+                        cut: false,
+                    },
+                ),
+            ]
+        };
+
+        // a warning alone is no breach
+        update_breach_file(&path, &keys, &alerts(false)).unwrap();
+        assert!(!path.exists());
+
+        update_breach_file(&path, &keys, &alerts(true)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "rx_cut_alert\n");
+
+        update_breach_file(&path, &keys, &alerts(false)).unwrap();
+        assert!(!path.exists());
+    }
+
+    /// Store totals (bytes) counted on the monitored interface
+    fn set_totals(store: &Store, keys: &Keys, rx: u64, tx: u64) {
+        store
+            .set(
+                &[NET, TRAFFIC, IFACE_LAST],
+                &Value::String("eth9".to_string()),
+            )
+            .unwrap();
+        let totals = Totals {
+            rx,
+            tx,
+            total: rx + tx,
+            version: 1,
+        };
+        totals.set(store, keys, "eth9").unwrap();
+    }
+
+    #[test]
+    fn rounding_is_for_display_only() {
+        let opt = opt(&["--inclusive-thresholds"]);
+        let (keys, store) = (keys(&opt), scratch_store("round"));
+        let exact = 10 * MEGABYTE - 1000;
+        set_totals(&store, &keys, exact, 0);
+        store
+            .set(&[NET, NOTIFY, "total_cut"], &Value::Uint(10))
+            .unwrap();
+
+        // the rounded total would reach the cutoff, but the exact one is stored and compared
+        assert_eq!(round_bytes(exact, MEGABYTE), 10 * MEGABYTE);
+        assert_eq!(
+            stored_usage(&store, &keys, Direction::Combined),
+            Some(exact)
+        );
+        let alerts = update_alerts(&opt, &store, &keys, TIME).unwrap();
+        let (_, combined) = &alerts[2];
+        assert!(!combined.cut);
+        assert_eq!(
+            store.get(&[NET, ALERT, "total_cut_alert"]).ok(),
+            Some(Value::Bool(false))
+        );
+    }
+
+    #[test]
+    fn combined_threshold_trips_on_the_sum() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("combined"));
+        set_totals(&store, &keys, 6 * MEGABYTE, 6 * MEGABYTE);
+        for (key, val) in &[("rx_cut", 10), ("tx_cut", 10), ("total_cut", 10)] {
+            store.set(&[NET, NOTIFY, key], &Value::Uint(*val)).unwrap();
+        }
+
+        let alerts = update_alerts(&opt, &store, &keys, TIME).unwrap();
+        let cut = |direction| {
+            alerts
+                .iter()
+                .find(|(d, _)| *d == direction)
+                .map(|(_, alert)| alert.cut)
+        };
+        assert_eq!(cut(Direction::Rx), Some(false));
+        assert_eq!(cut(Direction::Tx), Some(false));
+        assert_eq!(cut(Direction::Combined), Some(true));
+        assert_eq!(
+            store.get(&[NET, ALERT, "total_cut_alert"]).ok(),
+            Some(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn data_directory_is_created() {
+        let path = scratch_dir("data-dir").join("nested").join("peachcloud");
+        assert_eq!(create_data_dir(Some(&path)).unwrap(), path);
+        assert!(path.is_dir());
+        // an existing directory is accepted as it is
+        assert_eq!(create_data_dir(Some(&path)).unwrap(), path);
+    }
+
+    #[test]
+    fn reported_delta_is_the_one_saved() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("counter-report"));
+        replay(&opt, &store, &[reading(TIME, Some("b"), 1_000_000, 2000)]);
+
+        let next = reading(TIME + 60, Some("b"), 1_234_567, 2500);
+        let last = LastCounters::get(&store, &keys, "eth9");
+        let reported = Delta::calculate(
+            "eth9",
+            &next.traffic,
+            &store,
+            &last,
+            &next.boot,
+            opt.counter_semantics,
+        );
+        assert_eq!(
+            counter_report("eth9", &next.traffic, &last, &reported),
+            "eth9: rx 1,234,567 (rx_last 1,000,000, delta 234,567), \
+             tx 2,500 (tx_last 2,000, delta 500); change since last reading"
+        );
+
+        let saved = update_transmission_totals(&opt, &next, &store, &keys, false).unwrap();
+        assert_eq!((saved.rx, saved.tx), (reported.rx, reported.tx));
+    }
+
+    /// Daemon options reading the counters of eth9 from a file, and the file
+    fn counters_file(name: &str, args: &[&str]) -> (Opt, PathBuf) {
+        let path = scratch_dir(name).join("counters");
+        let command = format!("cat {}", path.display());
+        let mut all = vec!["--iface", "eth9", "--stats-command", &command];
+        all.extend_from_slice(args);
+        (opt(&all), path)
+    }
+
+    #[test]
+    fn paused_intervals_are_not_counted() {
+        let (opt, counters) = counters_file("paused", &[]);
+        let (keys, store) = (keys(&opt), scratch_store("paused-store"));
+        let mut meter = RateMeter::new();
+        let mut grace = Grace::new(None, Instant::now());
+        let mut poll_at = |rx: u64, tx: u64| {
+            fs::write(&counters, format!("{} {}", rx, tx)).unwrap();
+            poll(
+                &opt,
+                &store,
+                &keys,
+                &Outputs::default(),
+                &mut meter,
+                &mut grace,
+                false,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(poll_at(1000, 100), None);
+        set_paused(&store, true).unwrap();
+        let paused = poll_at(5000, 300);
+        let paused_totals = Totals::get(&store, &keys);
+        set_paused(&store, false).unwrap();
+        poll_at(6000, 400);
+
+        // the rates still follow the link while paused
+        let rate = paused.unwrap();
+        assert!(rate.rx > 0 && rate.tx > 0);
+        assert_eq!((paused_totals.rx, paused_totals.tx), (0, 0));
+        // resuming counts from the reading taken while paused
+        let totals = Totals::get(&store, &keys);
+        assert_eq!((totals.rx, totals.tx), (1000, 100));
+    }
+
+    #[test]
+    fn combined_cutoff_alert_is_written_to_the_keys_of_the_layout() {
+        for (schema, path) in &[
+            ("v2", vec![NET, ALERT, "total_cut_alert"]),
+            ("legacy", vec![NET, ALERT, "cut_alert"]),
+            (
+                "per-interface",
+                vec![NET, INTERFACES, "eth9", "total_cut_alert"],
+            ),
+        ] {
+            let opt = opt(&["--schema", schema]);
+            let (keys, store) = (keys(&opt), scratch_store(&format!("layout-{}", schema)));
+            set_totals(&store, &keys, 8 * MEGABYTE, 4 * MEGABYTE);
+            let cut = keys.threshold(Direction::Combined, Level::Cut)[0];
+            store.set(&[NET, NOTIFY, cut], &Value::Uint(10)).unwrap();
+
+            update_alerts(&opt, &store, &keys, TIME).unwrap();
+            assert_eq!(store.get(path).ok(), Some(Value::Bool(true)), "{}", schema);
+            // the legacy key is mirrored under the v2 layout, and only there
+            let mirrored = store.get(&[NET, ALERT, "cut_alert"]).ok();
+            let expected = Some(Value::Bool(true)).filter(|_| *schema != "per-interface");
+            assert_eq!(mirrored, expected, "{}", schema);
+        }
+    }
+
+    #[test]
+    fn journal_fields_of_a_poll() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("journal-fields"));
+        set_totals(&store, &keys, 3000, 200);
+        let alerts = vec![
+            (
+                Direction::Rx,
+                Alert {
+                    warn: true,
+                    cut: false,
+                },
+            ),
+            (
+                Direction::Combined,
+                Alert {
+                    warn: true,
+                    cut: true,
+                },
+            ),
+        ];
+        let rate = Rate { rx: 1024, tx: 12 };
+
+        let fields = poll_fields("wlan0", &store, &keys, &alerts, Some(&rate));
+        let fields: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("IFACE", "wlan0"),
+                ("RX_BYTES", "3000"),
+                ("TX_BYTES", "200"),
+                ("TOTAL_BYTES", "3200"),
+                ("RX_RATE", "1024"),
+                ("TX_RATE", "12"),
+                ("RX_WARN_ALERT", "true"),
+                ("RX_CUT_ALERT", "false"),
+                ("TOTAL_WARN_ALERT", "true"),
+                ("TOTAL_CUT_ALERT", "true"),
+            ]
+        );
+    }
+
+    #[test]
+    fn bench_times_the_poll_path() {
+        let (opt, counters) = counters_file("bench", &[]);
+        fs::write(&counters, "1000 200").unwrap();
+        let stages =
+            time_stages("eth9", &opt.source(), &keys(&opt), opt.counter_semantics, 3).unwrap();
+
+        for timings in &stages {
+            assert_eq!(timings.count(), 3);
+            assert!(timings.min() <= timings.avg() && timings.avg() <= timings.max());
+            // reading the counters runs a command, which takes some time
+            assert!(timings.max() < Duration::from_secs(10));
+        }
+        assert!(stages[0].min() > Duration::default());
+    }
+
+    #[test]
+    fn command_counters_belong_to_no_boot() {
+        assert_eq!(counters_boot(&Source::Command("echo 1 2".into())), None);
+        assert_eq!(counters_boot(&Source::Proc), boot_id());
+    }
+
+    #[test]
+    fn host_reboot_is_not_a_command_counter_reset() {
+        let (opt, counters) = counters_file("command-boot", &[]);
+        let (keys, store) = (keys(&opt), scratch_store("command-boot-store"));
+        // a save made before the host rebooted, under the host's boot
+        replay(&opt, &store, &[reading(TIME, Some("earlier"), 50_000, 0)]);
+
+        fs::write(&counters, "51000 0").unwrap();
+        let reading = take_reading("eth9", &opt.source(), None).unwrap();
+        let delta = update_transmission_totals(&opt, &reading, &store, &keys, false).unwrap();
+        assert_eq!(delta.rx, 1000);
+    }
+
+    /// Totals received after readings of the receive counter a minute apart, under a semantics
+    fn received(semantics: &str, name: &str, counters: &[u64]) -> u64 {
+        let opt = opt(&["--counter-semantics", semantics]);
+        let readings: Vec<Reading> = counters
+            .iter()
+            .zip(0..)
+            .map(|(&rx, i)| reading(TIME + i * 60, Some("b"), rx, 0))
+            .collect();
+        replay(&opt, &scratch_store(name), &readings).rx
+    }
+
+    #[test]
+    fn per_link_counters_are_counted_from_zero_after_a_decrease() {
+        // the link bounced between the second and third readings
+        assert_eq!(
+            received("per-link", "per-link", &[1000, 5000, 300, 800]),
+            4800
+        );
+    }
+
+    #[test]
+    fn cumulative_counters_are_rebaselined_after_a_decrease() {
+        // the traffic before the decrease is not counted twice
+        assert_eq!(
+            received("cumulative", "cumulative", &[1000, 5000, 300, 800]),
+            4500
+        );
+    }
+
+    #[test]
+    fn semantics_agree_while_counters_increase() {
+        let counters = [1000, 2000, 2500];
+        assert_eq!(received("per-link", "increase-per-link", &counters), 1500);
+        assert_eq!(
+            received("cumulative", "increase-cumulative", &counters),
+            1500
+        );
+    }
+
+    #[test]
+    fn diff_since_a_checkpoint() {
+        let opt = opt(&[]);
+        let keys = keys(&opt);
+        let (store, unchecked) = (scratch_store("diff"), scratch_store("diff-unchecked"));
+        let before = [
+            reading(TIME, Some("b"), 1000, 100),
+            reading(TIME + 60, Some("b"), 3000, 300),
+        ];
+        let after = [
+            reading(TIME + 120, Some("b"), 3500, 350),
+            reading(TIME + 180, Some("b"), 7000, 400),
+        ];
+        replay(&opt, &store, &before);
+        set_checkpoint(&store, &keys, "update").unwrap();
+        let totals = replay(&opt, &store, &after);
+
+        let checkpoint = Checkpoint::load(&store, "update").unwrap();
+        assert_eq!(checkpoint.since(totals.rx, totals.tx), (4000, 100));
+        // the checkpoint leaves the totals as they would otherwise be
+        replay(&opt, &unchecked, &before);
+        assert_eq!(replay(&opt, &unchecked, &after), totals);
+    }
+
+    /// Daemon which takes some time over its final save, then reports the result of it
+    fn final_save(takes: Duration, result: Result<(), String>) -> Receiver<Result<(), String>> {
+        let (done_tx, done_rx) = mpsc::channel();
+        thread::spawn(move || {
+            thread::sleep(takes);
+            let _ = done_tx.send(result);
+        });
+        done_rx
+    }
+
+    #[test]
+    fn blocked_final_save_times_out() {
+        // e.g. a store write blocked on a hung filesystem
+        let done = final_save(Duration::from_secs(5), Ok(()));
+        let waited = Instant::now();
+        assert_eq!(
+            await_final_save(&done, Duration::from_millis(100)),
+            Ok(false)
+        );
+        assert!(waited.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn final_save_within_the_timeout() {
+        let done = final_save(Duration::from_millis(10), Ok(()));
+        assert_eq!(await_final_save(&done, Duration::from_secs(5)), Ok(true));
+
+        let failed = final_save(Duration::from_millis(10), Err("write failed".to_string()));
+        assert_eq!(
+            await_final_save(&failed, Duration::from_secs(5)),
+            Err("write failed".to_string())
+        );
+    }
+
+    #[test]
+    fn metrics_of_each_interface() {
+        let opt = opt(&["--schema", "per-interface"]);
+        let (keys, store) = (keys(&opt), scratch_store("metrics"));
+        for (iface, rx) in &[("wlan0", 5000), ("eth0", 700)] {
+            let reading = |time, rx, tx| Reading {
+                iface: iface.to_string(),
+                ..reading(time, Some("b"), rx, tx)
+            };
+            replay(
+                &opt,
+                &store,
+                &[reading(TIME, 0, 0), reading(TIME + 60, *rx, 100)],
+            );
+        }
+
+        let out = metrics_exposition(&store, &keys);
+        assert!(out.contains("peach_monitor_rx_bytes_total{iface=\"wlan0\"} 5000\n"));
+        assert!(out.contains("peach_monitor_rx_bytes_total{iface=\"eth0\"} 700\n"));
+        assert!(out.contains("peach_monitor_tx_bytes_total{iface=\"eth0\"} 100\n"));
+    }
+
+    #[test]
+    fn ensured_thresholds_report_changes() {
+        let (opt, store) = (opt(&[]), scratch_store("ensure"));
+        let keys = keys(&opt);
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+            .unwrap();
+        store
+            .set(&[NET, NOTIFY, "rx_warn"], &Value::Uint(4096))
+            .unwrap();
+
+        let rx_cut = |gigabytes: u64| {
+            [
+                (Direction::Rx, Level::Warn, None),
+                (Direction::Rx, Level::Cut, Some(gigabytes * 1024 * MEGABYTE)),
+            ]
+        };
+        assert!(!ensure_thresholds(&store, &keys, &rx_cut(5)).unwrap());
+        assert!(ensure_thresholds(&store, &keys, &rx_cut(6)).unwrap());
+        assert_eq!(
+            stored_threshold(&store, &keys, Direction::Rx, Level::Cut),
+            Some(6144)
+        );
+        assert_eq!(
+            stored_threshold(&store, &keys, Direction::Rx, Level::Warn),
+            Some(4096)
+        );
+        assert!(!ensure_thresholds(&store, &keys, &rx_cut(6)).unwrap());
+
+        // a cutoff below the warning in effect is refused without writing
+        assert!(ensure_thresholds(&store, &keys, &rx_cut(3)).is_err());
+        assert_eq!(
+            stored_threshold(&store, &keys, Direction::Rx, Level::Cut),
+            Some(6144)
+        );
+    }
+
+    #[test]
+    fn interface_from_the_environment() {
+        let parse = |args: &[&str]| {
+            Opt::from_iter(std::iter::once("peach-monitor").chain(args.iter().copied()))
+        };
+        let _env = lock_env();
+        env::set_var("PEACH_MONITOR_IFACE", "eth7");
+        let (from_env, given) = (parse(&[]), parse(&["--iface", "eth8"]));
+        env::remove_var("PEACH_MONITOR_IFACE");
+        let unset = parse(&[]);
+
+        assert_eq!(from_env.iface, "eth7");
+        assert_eq!(given.iface, "eth8");
+        assert_eq!(unset.iface, "wlan0");
+    }
+
+    #[test]
+    fn flat_keys_mirror_the_interface_entry() {
+        let opt = opt(&["--schema", "per-interface", "--compat-flat-keys"]);
+        let (keys, store) = (keys(&opt), scratch_store("compat-flat"));
+        init_keys(&store, &keys).unwrap();
+        replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME, Some("b"), 0, 0),
+                reading(TIME + 60, Some("b"), 5000, 700),
+            ],
+        );
+        let flags = [("rx_cut_alert", Value::Bool(true))];
+        let previous = [("rx_cut_alert", Value::Bool(false))];
+        write_alert_flags(&store, &keys, &flags, &previous, 1).unwrap();
+
+        let entry = interface_values(&store, "eth9");
+        for (file, key, value) in &[
+            (TRAFFIC, "rx", Value::Uint(5000)),
+            (TRAFFIC, "tx", Value::Uint(700)),
+            (TRAFFIC, "total", Value::Uint(5700)),
+            (ALERT, "rx_cut_alert", Value::Bool(true)),
+        ] {
+            assert_eq!(entry.get(*key), Some(value), "{}", key);
+            assert_eq!(store.get(&[NET, file, key]).unwrap(), *value, "{}", key);
+        }
+    }
+
+    #[test]
+    fn flat_keys_are_not_written_without_compatibility() {
+        let opt = opt(&["--schema", "per-interface"]);
+        let (keys, store) = (keys(&opt), scratch_store("compat-flat-off"));
+        init_keys(&store, &keys).unwrap();
+        replay(&opt, &store, &[reading(TIME, Some("b"), 0, 0)]);
+        assert!(interface_values(&store, "eth9").contains_key("rx"));
+        assert!(!matches!(
+            store.get(&[NET, TRAFFIC, "rx"]),
+            Ok(Value::Uint(_))
+        ));
+    }
+
+    #[test]
+    fn day_well_above_the_baseline_is_an_anomaly() {
+        let store = scratch_store("anomaly");
+        let day = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+        for (date, usage) in (1..=7).zip(&[900, 1100, 1000, 800, 1200, 1000, 1000]) {
+            history::record(&store, day(date), *usage, 0, 0, None).unwrap();
+        }
+        history::record(&store, day(8), 2500, 0, 0, None).unwrap();
+        update_baseline(&store, day(8), 7).unwrap();
+        assert_eq!(stored_total(&store, BASELINE), Some(1000));
+
+        // no factor, no evaluation
+        assert_eq!(set_anomaly_alert(&store, day(8)).unwrap(), None);
+        store
+            .set(&[NET, NOTIFY, ANOMALY_FACTOR], &Value::Float(3.0))
+            .unwrap();
+        assert_eq!(set_anomaly_alert(&store, day(8)).unwrap(), Some(false));
+        history::record(&store, day(8), 600, 0, 0, None).unwrap();
+        assert_eq!(set_anomaly_alert(&store, day(8)).unwrap(), Some(true));
+        assert_eq!(
+            store.get(&[NET, ALERT, ANOMALY_ALERT]).ok(),
+            Some(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn strict_mode_reports_values_of_the_wrong_type() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("strict"));
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+            .unwrap();
+        assert!(check_types(&store, &keys).is_ok());
+
+        store
+            .set(&[NET, NOTIFY, "tx_cut"], &Value::String("5GB".to_string()))
+            .unwrap();
+        store
+            .set(&[NET, ALERT, "rx_cut_alert"], &Value::Uint(1))
+            .unwrap();
+        match check_types(&store, &keys) {
+            Err(NestError::UnexpectedTypes { mismatches }) => assert_eq!(
+                mismatches,
+                [
+                    "net/notify/tx_cut: expected an unsigned integer, found a string",
+                    "net/alert/rx_cut_alert: expected a boolean, found an unsigned integer",
+                ]
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // leniently, the value is taken as unset
+        assert_eq!(
+            stored_threshold(&store, &keys, Direction::Tx, Level::Cut),
+            None
+        );
+        assert_eq!(
+            stored_threshold(&store, &keys, Direction::Rx, Level::Cut),
+            Some(5120)
+        );
+    }
+
+    #[test]
+    fn api_endpoints() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("api"));
+        set_totals(&store, &keys, 5000, 700);
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+            .unwrap();
+        store
+            .set(&[NET, NOTIFY, "total_warn"], &Value::Uint(8))
+            .unwrap();
+        store
+            .set(&[NET, ALERT, "rx_cut_alert"], &Value::Bool(false))
+            .unwrap();
+        let get = |path| {
+            let response = api_response(path, &store, &keys).unwrap();
+            assert_eq!(response.content_type, JSON_CONTENT_TYPE);
+            serde_json::from_str::<serde_json::Value>(&response.body).unwrap()
+        };
+
+        assert_eq!(
+            get("/totals"),
+            json!({"iface": "eth9", "rx": 5000, "tx": 700, "total": 5700})
+        );
+        assert_eq!(
+            get("/thresholds"),
+            json!({
+                "rx": {"warn": null, "cut": 5120 * MEGABYTE},
+                "tx": {"warn": null, "cut": null},
+                "total": {"warn": 8 * MEGABYTE, "cut": null},
+                "window_cut": null,
+            })
+        );
+        assert_eq!(get("/alerts"), json!({"rx_cut_alert": false}));
+
+        let status = get("/status");
+        assert_eq!(status["iface"], "eth9");
+        assert_eq!(status["totals"], get("/totals"));
+        assert_eq!(status["thresholds"], get("/thresholds"));
+        assert_eq!(status["alerts"], get("/alerts"));
+        assert_eq!(status["paused"], false);
+        assert_eq!(status["metered"], true);
+        assert_eq!(status["cycle"], serde_json::Value::Null);
+
+        assert!(api_response("/other", &store, &keys).is_none());
+    }
+
+    #[test]
+    fn threshold_read_from_a_configured_key() {
+        let opt = opt(&["--threshold-key", "total_warn=data_warn"]);
+        let (keys, store) = (keys(&opt), scratch_store("threshold-key"));
+        store
+            .set(&[NET, NOTIFY, "data_warn"], &Value::Uint(8))
+            .unwrap();
+        store.set(&[NET, NOTIFY, "warn"], &Value::Uint(8)).unwrap();
+        assert_eq!(
+            stored_threshold(&store, &keys, Direction::Combined, Level::Warn),
+            Some(8)
+        );
+        assert!(check_keys(&store, &keys).is_ok());
+        let desired = [(Direction::Combined, Level::Warn, Some(10 * MEGABYTE))];
+        assert!(ensure_thresholds(&store, &keys, &desired).unwrap());
+        assert_eq!(
+            store.get(&[NET, NOTIFY, "data_warn"]).unwrap(),
+            Value::Uint(10)
+        );
+
+        // the fallback key now differs, and a key of another tool is never read
+        store
+            .set(&[NET, NOTIFY, "data_cut"], &Value::Uint(12))
+            .unwrap();
+        match check_keys(&store, &keys) {
+            Err(NestError::KeyMismatch { problems }) => assert_eq!(
+                problems,
+                [
+                    "net/notify/warn differs from net/notify/data_warn, which is read instead",
+                    "net/notify/data_cut is never read \
+                     (map it with --threshold-key <threshold>=data_cut)",
+                ]
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn replayed_trace_reproduces_the_state() {
+        let opt = opt(&[]);
+        let keys = keys(&opt);
+        let trace = scratch_dir("replay-trace").join("trace.jsonl");
+        let live = scratch_store("replay-live");
+        live.set(&[NET, NOTIFY, "rx_warn"], &Value::Uint(4))
+            .unwrap();
+        live.set(&[NET, NOTIFY, "total_cut"], &Value::Uint(10))
+            .unwrap();
+
+        let readings = [
+            reading(TIME, Some("b1"), 0, 0),
+            reading(TIME + 60, Some("b1"), 3 * MEGABYTE, MEGABYTE),
+            reading(TIME + 120, Some("b1"), 5 * MEGABYTE, 2 * MEGABYTE),
+            // reboot
+            reading(TIME + 180, Some("b2"), 4 * MEGABYTE, MEGABYTE),
+        ];
+        for reading in &readings {
+            trace::append(&trace, reading).unwrap();
+            update_transmission_totals(&opt, reading, &live, &keys, false).unwrap();
+            update_alerts(&opt, &live, &keys, reading.time).unwrap();
+        }
+
+        let scratch = scratch_store("replay-scratch");
+        replay_readings(&opt, &keys, &live, &scratch, &trace::load(&trace).unwrap()).unwrap();
+        let (live_totals, replayed) = (Totals::get(&live, &keys), Totals::get(&scratch, &keys));
+        assert_eq!((replayed.rx, replayed.tx), (9 * MEGABYTE, 3 * MEGABYTE));
+        assert_eq!((live_totals.rx, live_totals.tx), (replayed.rx, replayed.tx));
+        assert_eq!(alert_flags(&scratch, &keys), alert_flags(&live, &keys));
+        assert_eq!(
+            alert_flags(&scratch, &keys)
+                .into_iter()
+                .filter(|(_, raised)| *raised)
+                .map(|(flag, _)| flag)
+                .collect::<Vec<_>>(),
+            ["rx_warn_alert", "total_cut_alert"]
+        );
+    }
+
+    #[test]
+    fn daemon_stops_after_its_runtime_with_a_final_save() {
+        let (opt, counters) = counters_file("max-runtime", &["--interval", "3600"]);
+        let path = scratch_dir("max-runtime-store");
+        let (keys, store) = (keys(&opt), Store::new(&path, store_schema().unwrap()));
+        fs::write(&counters, "1000 100").unwrap();
+
+        // the counters advance once the first poll has stored them
+        let watcher = Store::new(&path, store_schema().unwrap());
+        let advance = thread::spawn(move || {
+            while stored_total(&watcher, RX_LAST).is_none() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            fs::write(&counters, "5000 500").unwrap();
+        });
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let (signal_tx, signal_rx) = mpsc::channel();
+        let started = Instant::now();
+        let timer = stop_after(Duration::from_secs(1), stop_tx, signal_tx);
+
+        run_daemon(&opt, &store, &keys, &path, true, None, &stop_rx).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(30));
+        timer.join().unwrap();
+        advance.join().unwrap();
+        assert!(signal_rx.try_recv().is_ok());
+        let totals = Totals::get(&store, &keys);
+        assert_eq!((totals.rx, totals.tx), (4000, 400));
+    }
+
+    #[test]
+    fn summary_of_every_interface() {
+        let opt = opt(&["--schema", "per-interface"]);
+        let (keys, store) = (keys(&opt), scratch_store("summary"));
+        for (iface, rx, tx) in &[("wlan0", 5000, 500), ("eth0", 700, 70), ("wwan0", 0, 0)] {
+            let reading = |time, rx, tx| Reading {
+                iface: iface.to_string(),
+                ..reading(time, Some("b"), rx, tx)
+            };
+            replay(
+                &opt,
+                &store,
+                &[reading(TIME, 0, 0), reading(TIME + 60, *rx, *tx)],
+            );
+        }
+        let mut entry = interface_values(&store, "eth0");
+        entry.insert("total_cut_alert".to_string(), Value::Bool(true));
+        entry.insert("rx_warn_alert".to_string(), Value::Bool(true));
+        store
+            .set(&[NET, INTERFACES, "eth0"], &Value::Object(entry))
+            .unwrap();
+
+        let summaries = summarize(&store, &keys);
+        let usage: Vec<(&str, u64, u64)> = summaries
+            .iter()
+            .map(|summary| (summary.iface.as_str(), summary.rx, summary.tx))
+            .collect();
+        assert_eq!(
+            usage,
+            [("wlan0", 5000, 500), ("eth0", 700, 70), ("wwan0", 0, 0)]
+        );
+        assert_eq!(summaries[1].raised, ["rx_warn_alert", "total_cut_alert"]);
+        let over_cap: Vec<&str> = summaries
+            .iter()
+            .filter(|summary| summary.over_cap(&keys))
+            .map(|summary| summary.iface.as_str())
+            .collect();
+        assert_eq!(over_cap, ["eth0"]);
+        assert_eq!(
+            summaries.iter().map(|summary| summary.rx).sum::<u64>(),
+            5700
+        );
+    }
+
+    #[test]
+    fn receive_only_interface_raises_no_transmit_alerts() {
+        let opt = opt(&["--inclusive-thresholds"]);
+        let (keys, store) = (keys(&opt), scratch_store("rx-only"));
+        store
+            .set(&[NET, NOTIFY, "tx_cut"], &Value::Uint(0))
+            .unwrap();
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(0))
+            .unwrap();
+        // before any traffic, neither direction can be told apart
+        replay(&opt, &store, &[reading(TIME, Some("b"), 0, 0)]);
+        assert!(counted(&store, &keys, Direction::Tx));
+
+        replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME + 60, Some("b"), 5000, 0),
+                reading(TIME + 120, Some("b"), 9000, 0),
+            ],
+        );
+        update_alerts(&opt, &store, &keys, TIME + 120).unwrap();
+        assert!(counted(&store, &keys, Direction::Rx));
+        assert!(!counted(&store, &keys, Direction::Tx));
+        assert!(counted(&store, &keys, Direction::Combined));
+        assert_eq!(
+            store.get(&[NET, ALERT, "rx_cut_alert"]).ok(),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(
+            store.get(&[NET, ALERT, "tx_cut_alert"]).ok(),
+            Some(Value::Bool(false))
+        );
+    }
+
+    #[test]
+    fn flag_already_raised_returns_at_once() {
+        let (keys, store) = (keys(&opt(&[])), scratch_store("watch-raised"));
+        store
+            .set(&[NET, ALERT, "rx_cut_alert"], &Value::Bool(true))
+            .unwrap();
+        let started = Instant::now();
+        watch_alerts(
+            &store,
+            &keys,
+            "rx_cut_alert",
+            Duration::from_secs(60),
+            Some(Duration::from_secs(60)),
+            Palette::new(false),
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn watch_returns_once_the_flag_is_raised() {
+        let path = scratch_dir("watch-transition");
+        let (keys, store) = (keys(&opt(&[])), Store::new(&path, store_schema().unwrap()));
+        store
+            .set(&[NET, ALERT, "rx_cut_alert"], &Value::Bool(false))
+            .unwrap();
+        let raise = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            Store::new(&path, store_schema().unwrap())
+                .set(&[NET, ALERT, "rx_cut_alert"], &Value::Bool(true))
+                .unwrap();
+        });
+        watch_alerts(
+            &store,
+            &keys,
+            "rx_cut_alert",
+            Duration::from_millis(20),
+            Some(Duration::from_secs(30)),
+            Palette::new(false),
+        )
+        .unwrap();
+        raise.join().unwrap();
+    }
+
+    #[test]
+    fn watch_times_out() {
+        let (keys, store) = (keys(&opt(&[])), scratch_store("watch-timeout"));
+        let result = watch_alerts(
+            &store,
+            &keys,
+            "tx_warn_alert",
+            Duration::from_millis(200),
+            Some(Duration::from_secs(1)),
+            Palette::new(false),
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "tx_warn_alert was not raised within 1s"
+        );
+        assert!(watch_alerts(
+            &store,
+            &keys,
+            "no_alert",
+            Duration::ZERO,
+            None,
+            Palette::new(false)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn flags_not_evaluated_keep_their_values() {
+        let opt = opt(&["--alerts", "rx_cut_alert,total_cut_alert"]);
+        let (keys, store) = (keys(&opt), scratch_store("selected-alerts"));
+        for (key, megabytes) in &[("rx_warn", 1), ("rx_cut", 4), ("total_warn", 1)] {
+            store
+                .set(&[NET, NOTIFY, key], &Value::Uint(*megabytes))
+                .unwrap();
+        }
+        // stale values the warnings were left with
+        store
+            .set(&[NET, ALERT, "rx_warn_alert"], &Value::Bool(false))
+            .unwrap();
+        store
+            .set(&[NET, ALERT, "total_warn_alert"], &Value::Bool(true))
+            .unwrap();
+
+        for (rx, cut) in &[(2, false), (5, true), (0, false)] {
+            set_totals(&store, &keys, rx * MEGABYTE, 0);
+            update_alerts(&opt, &store, &keys, TIME).unwrap();
+            let flag = |key| store.get(&[NET, ALERT, key]).ok();
+            assert_eq!(flag("rx_cut_alert"), Some(Value::Bool(*cut)), "{} MB", rx);
+            assert_eq!(flag("rx_warn_alert"), Some(Value::Bool(false)));
+            assert_eq!(flag("total_warn_alert"), Some(Value::Bool(true)));
+        }
+        assert!(opt.evaluates(AlertFlag::Threshold(Direction::Combined, Level::Cut)));
+        assert!(!opt.evaluates(AlertFlag::Anomaly));
+    }
+
+    #[test]
+    fn history_entry_holds_the_thresholds_of_its_day() {
+        for (args, snapshot) in &[(&["--history-thresholds"][..], true), (&[][..], false)] {
+            let opt = opt(args);
+            let store = scratch_store(&format!("history-{}", snapshot));
+            store
+                .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+                .unwrap();
+            store
+                .set(&[NET, NOTIFY, "total_warn"], &Value::Uint(8192))
+                .unwrap();
+            replay(
+                &opt,
+                &store,
+                &[
+                    reading(TIME, Some("b"), 0, 0),
+                    reading(TIME + 60, Some("b"), 5000, 700),
+                ],
+            );
+            store
+                .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(6144))
+                .unwrap();
+            replay(&opt, &store, &[reading(TIME + 120, Some("b"), 6000, 800)]);
+
+            let day = &history::load(&store)[&local_date(TIME)];
+            assert_eq!((day.rx, day.tx), (6000, 800));
+            let expected: Vec<(String, u64)> = if *snapshot {
+                vec![
+                    ("rx_cut".to_string(), 6144),
+                    ("total_warn".to_string(), 8192),
+                ]
+            } else {
+                Vec::new()
+            };
+            assert_eq!(day.thresholds, expected);
+        }
+    }
+
+    #[test]
+    fn reset_only_once_confirmed() {
+        let opt = opt(&["--iface", "eth9"]);
+        let (keys, store) = (keys(&opt), scratch_store("reset"));
+        set_totals(&store, &keys, 5000, 700);
+        let totals = |store: &Store| {
+            let totals = Totals::get(store, &keys);
+            (totals.rx, totals.tx, totals.total)
+        };
+
+        // a terminal would prompt instead
+        if !io::stdin().is_terminal() {
+            assert!(reset_totals(&opt, &store, &keys, false, false, false).is_err());
+            assert_eq!(totals(&store), (5000, 700, 5700));
+        }
+
+        reset_totals(&opt, &store, &keys, true, false, true).unwrap();
+        assert_eq!(totals(&store), (5000, 0, 5000));
+        set_totals(&store, &keys, 5000, 700);
+        reset_totals(&opt, &store, &keys, true, true, false).unwrap();
+        assert_eq!(totals(&store), (0, 700, 700));
+        reset_totals(&opt, &store, &keys, true, false, false).unwrap();
+        assert_eq!(totals(&store), (0, 0, 0));
+    }
+
+    #[test]
+    fn lost_update_is_reapplied() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("lost-update"));
+        set_totals(&store, &keys, 1000, 100);
+        let base = Totals::get(&store, &keys);
+        let delta = Delta {
+            rx: 50,
+            tx: 5,
+            basis: "test",
+        };
+
+        // another process read the totals before this write and saves its own delta after it
+        let mut writes = 0;
+        add_totals_with(&store, &keys, &delta, |totals| {
+            writes += 1;
+            totals.set(&store, &keys, "eth9")?;
+            if writes == 1 {
+                base.add(300, 30).set(&store, &keys, "eth9")?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(writes, 2);
+        let totals = Totals::get(&store, &keys);
+        assert_eq!((totals.rx, totals.tx, totals.total), (1350, 135, 1485));
+        assert_eq!(totals.version, base.version + 2);
+    }
+
+    #[test]
+    fn later_save_is_not_a_lost_update() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("later-update"));
+        set_totals(&store, &keys, 1000, 100);
+        let delta = Delta {
+            rx: 50,
+            tx: 5,
+            basis: "test",
+        };
+
+        // another process saves its own delta on top of this write
+        let mut writes = 0;
+        add_totals_with(&store, &keys, &delta, |totals| {
+            writes += 1;
+            totals.set(&store, &keys, "eth9")?;
+            totals.add(300, 30).set(&store, &keys, "eth9")
+        })
+        .unwrap();
+        assert_eq!(writes, 1);
+        let totals = Totals::get(&store, &keys);
+        assert_eq!((totals.rx, totals.tx), (1350, 135));
+    }
+
+    #[test]
+    fn grafana_points_of_a_known_history() {
+        let opt = opt(&[]);
+        let store = scratch_store("grafana");
+        let day = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+        for date in 1..=3 {
+            history::record(&store, day(date), 1000 * date as u64, 10, 0, None).unwrap();
+        }
+
+        let days = exported_days(&opt, &store, Some(day(2)), None).unwrap();
+        assert_eq!(days, [(day(2), 2000, 10), (day(3), 3000, 10)]);
+        let identity = Identity {
+            kind: "pseudonym",
+            value: "device-1".to_string(),
+        };
+        let points = grafana_points(&days, Some(&identity));
+        let points = points.as_array().unwrap();
+        assert_eq!(points.len(), 2);
+        for (point, &(date, rx, tx)) in points.iter().zip(&days) {
+            let time = point["time"].as_i64().unwrap();
+            let local = Local.timestamp_millis_opt(time).unwrap();
+            assert_eq!((local.date_naive(), local.time()), (date, NaiveTime::MIN));
+            assert_eq!(
+                *point,
+                json!({"time": time, "rx": rx, "tx": tx, "identity": "device-1"})
+            );
+        }
+        assert_eq!(
+            grafana_points(&days[..1], None),
+            json!([{"time": local_midnight_millis(day(2)), "rx": 2000, "tx": 10}])
+        );
+        assert_eq!(
+            exported_days(&opt, &store, None, Some(day(1)))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    /// Findings of the doctor against a data store, with the counters given (if any)
+    fn diagnosed(
+        name: &str,
+        args: &[&str],
+        counters: Option<&str>,
+        setup: impl Fn(&Store, &Path),
+    ) -> Vec<Finding> {
+        let (opt, counters_path) = counters_file(&format!("doctor-{}", name), args);
+        if let Some(counters) = counters {
+            fs::write(&counters_path, counters).unwrap();
+        }
+        let path = scratch_dir(&format!("doctor-{}-store", name));
+        let (keys, store) = (keys(&opt), Store::new(&path, store_schema().unwrap()));
+        setup(&store, &path);
+        diagnose(&opt, &store, &keys, &path)
+    }
+
+    fn finding<'a>(findings: &'a [Finding], check: &str) -> &'a Finding {
+        findings
+            .iter()
+            .find(|finding| finding.check == check)
+            .unwrap()
+    }
+
+    #[test]
+    fn healthy_configuration_passes() {
+        let findings = diagnosed("healthy", &[], Some("1000 100"), |store, _| {
+            store
+                .set(&[NET, NOTIFY, "total_warn"], &Value::Uint(8))
+                .unwrap();
+            store
+                .set(&[NET, NOTIFY, "total_cut"], &Value::Uint(10))
+                .unwrap();
+            store
+                .set(&[NET, TRAFFIC, UPDATED], &Value::Uint(unix_time()))
+                .unwrap();
+        });
+        let checks: Vec<(&str, Outcome)> = findings
+            .iter()
+            .map(|finding| (finding.check, finding.outcome))
+            .collect();
+        assert_eq!(
+            checks,
+            [
+                ("Interface", Outcome::Pass),
+                ("Data directory", Outcome::Pass),
+                ("Store files", Outcome::Pass),
+                ("Stored types", Outcome::Pass),
+                ("Thresholds", Outcome::Pass),
+                ("Schema", Outcome::Pass),
+                ("Daemon", Outcome::Pass),
+            ]
+        );
+        assert_eq!(doctor::verdict(&findings), "healthy");
+    }
+
+    #[test]
+    fn broken_configurations_are_found() {
+        // counters which cannot be read, and no thresholds
+        let findings = diagnosed("unreadable", &[], None, |_, _| ());
+        assert_eq!(finding(&findings, "Interface").outcome, Outcome::Fail);
+        assert_eq!(
+            finding(&findings, "Thresholds").detail,
+            "none are set, so no alert can be raised"
+        );
+
+        // thresholds which are zero or out of order, stored under legacy keys
+        let findings = diagnosed("thresholds", &[], Some("1 1"), |store, _| {
+            store
+                .set(&[NET, NOTIFY, "rx_warn"], &Value::Uint(0))
+                .unwrap();
+            store.set(&[NET, NOTIFY, "warn"], &Value::Uint(10)).unwrap();
+            store.set(&[NET, NOTIFY, "cut"], &Value::Uint(10)).unwrap();
+        });
+        let thresholds = finding(&findings, "Thresholds");
+        assert_eq!(thresholds.outcome, Outcome::Warn);
+        assert_eq!(
+            thresholds.detail,
+            "rx_warn is 0, so its alert is always raised; total_warn is not below total_cut"
+        );
+        assert_eq!(
+            finding(&findings, "Schema").detail,
+            "total_warn is only stored under its legacy key warn; \
+             total_cut is only stored under its legacy key cut"
+        );
+
+        // a daemon which stopped polling, and values of the wrong type
+        let findings = diagnosed("stale", &["--schema", "legacy"], Some("1 1"), |store, _| {
+            store
+                .set(&[NET, TRAFFIC, UPDATED], &Value::Uint(TIME))
+                .unwrap();
+            store
+                .set(&[NET, NOTIFY, "cut"], &Value::String("10GB".to_string()))
+                .unwrap();
+        });
+        assert_eq!(finding(&findings, "Daemon").outcome, Outcome::Warn);
+        assert_eq!(finding(&findings, "Stored types").outcome, Outcome::Warn);
+        assert_eq!(finding(&findings, "Schema").outcome, Outcome::Warn);
+    }
+
+    #[test]
+    fn corrupt_store_file_fails() {
+        let findings = diagnosed("corrupt", &[], Some("1 1"), |store, path| {
+            store
+                .set(&[NET, NOTIFY, "total_cut"], &Value::Uint(10))
+                .unwrap();
+            // truncated part-way through a write
+            fs::write(integrity::file_path(path, NOTIFY), "{\"total_cut\": 1").unwrap();
+        });
+        assert_eq!(finding(&findings, "Store files").outcome, Outcome::Fail);
+        assert_eq!(doctor::verdict(&findings), "1 failure, 1 warning");
+    }
+
+    #[test]
+    fn failed_flag_writes_restore_the_previous_flags() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("alert-flags-failing"));
+        init_keys(&store, &keys).unwrap();
+        let flags = [
+            ("rx_warn_alert", Value::Bool(true)),
+            ("rx_cut_alert", Value::Bool(true)),
+            ("tx_warn_alert", Value::Bool(true)),
+            ("tx_cut_alert", Value::Bool(true)),
+        ];
+        let previous: Vec<_> = flags
+            .iter()
+            .map(|&(key, _)| (key, Value::Bool(false)))
+            .collect();
+        set_values(&store, &keys, "eth9", ALERT, &previous).unwrap();
+
+        // each attempt writes the first flag of the batch, then fails
+        let mut attempts = 0;
+        let result = write_alert_flags_with(&flags, &previous, 2, |values| {
+            if values == flags {
+                attempts += 1;
+                set_values(&store, &keys, "eth9", ALERT, &values[..1])?;
+                return Err(NestError::Corrupt { paths: Vec::new() });
+            }
+            set_values(&store, &keys, "eth9", ALERT, values)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 2);
+        for &(key, _) in &flags {
+            assert_eq!(stored_alert(&store, &keys, key), Some(false), "{}", key);
+        }
+    }
+
+    #[test]
+    fn flag_writes_are_retried_as_a_batch() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("alert-flags-retried"));
+        init_keys(&store, &keys).unwrap();
+        let flags = [
+            ("rx_warn_alert", Value::Bool(true)),
+            ("rx_cut_alert", Value::Bool(false)),
+        ];
+        let previous = [
+            ("rx_warn_alert", Value::Bool(false)),
+            ("rx_cut_alert", Value::Bool(true)),
+        ];
+        set_values(&store, &keys, "eth9", ALERT, &previous).unwrap();
+
+        // the first attempt fails part-way through the batch
+        let mut failed = false;
+        write_alert_flags_with(&flags, &previous, 3, |values| {
+            if !failed {
+                failed = true;
+                set_values(&store, &keys, "eth9", ALERT, &values[..1])?;
+                return Err(NestError::Corrupt { paths: Vec::new() });
+            }
+            set_values(&store, &keys, "eth9", ALERT, values)
+        })
+        .unwrap();
+
+        assert_eq!(stored_alert(&store, &keys, "rx_warn_alert"), Some(true));
+        assert_eq!(stored_alert(&store, &keys, "rx_cut_alert"), Some(false));
+    }
+
+    #[test]
+    fn unmetered_usage_is_neither_counted_nor_alerted_on() {
+        let opt = opt(&["--inclusive-thresholds"]);
+        let (keys, store) = (keys(&opt), scratch_store("unmetered"));
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(1))
+            .unwrap();
+        replay(&opt, &store, &[reading(TIME, Some("b"), 0, 0)]);
+
+        set_metered(&store, false).unwrap();
+        let totals = replay(
+            &opt,
+            &store,
+            &[reading(TIME + 60, Some("b"), 5 * MEGABYTE, 100)],
+        );
+        update_alerts(&opt, &store, &keys, TIME + 60).unwrap();
+        assert_eq!((totals.rx, totals.tx), (0, 0));
+        assert_eq!(
+            store.get(&[NET, ALERT, "rx_cut_alert"]).ok(),
+            Some(Value::Bool(false))
+        );
+
+        // only the usage after the connection is metered again is counted
+        set_metered(&store, true).unwrap();
+        let totals = replay(
+            &opt,
+            &store,
+            &[reading(TIME + 120, Some("b"), 7 * MEGABYTE, 300)],
+        );
+        update_alerts(&opt, &store, &keys, TIME + 120).unwrap();
+        assert_eq!((totals.rx, totals.tx), (2 * MEGABYTE, 200));
+        assert_eq!(
+            store.get(&[NET, ALERT, "rx_cut_alert"]).ok(),
+            Some(Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn overhead_is_added_to_each_delta_before_accumulating() {
+        let opt = opt(&["--overhead-percent", "2.5"]);
+        let store = scratch_store("overhead");
+        let totals = replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME, Some("b"), 0, 0),
+                reading(TIME + 60, Some("b"), 10_000, 2000),
+                reading(TIME + 120, Some("b"), 10_001, 2002),
+            ],
+        );
+
+        // 10000 * 1.025, then 1 * 1.025 and 2 * 1.025 rounded
+        assert_eq!((totals.rx, totals.tx), (10_251, 2052));
+        assert_eq!(totals.total, 12_303);
+    }
+
+    #[test]
+    fn interactive_commands_change_the_store() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("repl"));
+        init_keys(&store, &keys).unwrap();
+        set_totals(&store, &keys, 7000, 3000);
+
+        for line in &[
+            "set net/notify/rx_cut 5000",
+            "set net/traffic/iface_last eth9",
+            "get net/notify/rx_cut",
+            "",
+            "reset --confirm --rx-only",
+        ] {
+            run_input(&opt, &store, &keys, repl::parse(line).unwrap()).unwrap();
+        }
+
+        assert_eq!(
+            store.get(&[NET, NOTIFY, "rx_cut"]).unwrap(),
+            Value::Uint(5000)
+        );
+        assert_eq!(
+            store.get(&[NET, TRAFFIC, IFACE_LAST]).unwrap(),
+            Value::String("eth9".to_string())
+        );
+        let totals = Totals::get(&store, &keys);
+        assert_eq!((totals.rx, totals.tx), (0, 3000));
+    }
+
+    #[test]
+    fn interactive_commands_cannot_write_in_observe_mode() {
+        let opt = opt(&["--observe"]);
+        let (keys, store) = (keys(&opt), scratch_store("repl-observe"));
+        init_keys(&store, &keys).unwrap();
+
+        let input = repl::parse("set net/notify/rx_cut 5000").unwrap();
+        assert!(run_input(&opt, &store, &keys, input).is_err());
+        assert_ne!(
+            store.get(&[NET, NOTIFY, "rx_cut"]).ok(),
+            Some(Value::Uint(5000))
+        );
+    }
+
+    #[test]
+    fn warn_only_never_raises_cut_flags() {
+        let opt = opt(&["--warn-only"]);
+        let (keys, store) = (keys(&opt), scratch_store("warn-only"));
+        for (key, megabytes) in &[
+            ("rx_warn", 1),
+            ("rx_cut", 2),
+            ("total_warn", 1),
+            ("total_cut", 2),
+        ] {
+            store
+                .set(&[NET, NOTIFY, key], &Value::Uint(*megabytes))
+                .unwrap();
+        }
+        set_totals(&store, &keys, 5 * MEGABYTE, 0);
+
+        update_alerts(&opt, &store, &keys, TIME).unwrap();
+        for (key, raised) in &[
+            ("rx_warn_alert", true),
+            ("total_warn_alert", true),
+            ("rx_cut_alert", false),
+            ("total_cut_alert", false),
+        ] {
+            assert_eq!(
+                store.get(&[NET, ALERT, key]).ok(),
+                Some(Value::Bool(*raised)),
+                "{}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn interface_key_follows_the_monitored_interface() {
+        let opt = opt(&["--schema", "per-interface"]);
+        let (keys, store) = (keys(&opt), scratch_store("iface-key"));
+        init_keys(&store, &keys).unwrap();
+        replay(&opt, &store, &[reading(TIME, Some("b"), 0, 0)]);
+        assert_eq!(stored_string(&store, IFACE).as_deref(), Some("eth9"));
+
+        // another interface is monitored, e.g. after a change of uplink
+        let other = Reading {
+            iface: "wwan0".to_string(),
+            ..reading(TIME + 60, Some("b"), 0, 0)
+        };
+        replay(&opt, &store, &[other]);
+        assert_eq!(stored_string(&store, IFACE).as_deref(), Some("wwan0"));
+        assert_eq!(
+            store.get(&[NET, TRAFFIC, IFACES]).unwrap(),
+            Value::Array(vec![
+                Value::String("eth9".to_string()),
+                Value::String("wwan0".to_string())
+            ])
+        );
+        assert_eq!(api_status(&store, &keys)["iface"], "wwan0");
+    }
+
+    #[test]
+    fn deltas_below_the_minimum_are_dropped() {
+        let opt = opt(&["--min-delta", "1000"]);
+        let store = scratch_store("min-delta");
+        let totals = replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME, Some("b"), 0, 0),
+                // 600 bytes, dropped
+                reading(TIME + 60, Some("b"), 400, 200),
+                // 1000 bytes, counted
+                reading(TIME + 120, Some("b"), 1200, 400),
+                // 999 bytes, dropped
+                reading(TIME + 180, Some("b"), 2000, 599),
+                reading(TIME + 240, Some("b"), 7000, 1599),
+            ],
+        );
+
+        assert_eq!((totals.rx, totals.tx), (800 + 5000, 200 + 1000));
+    }
+
+    #[test]
+    fn snapshot_round_trip_preserves_the_store() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("snapshot"));
+        init_keys(&store, &keys).unwrap();
+        replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME, Some("b"), 0, 0),
+                reading(TIME + 60, Some("b"), 5000, 700),
+            ],
+        );
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+            .unwrap();
+        store
+            .set(&[NET, ALERT, "rx_cut_alert"], &Value::Bool(true))
+            .unwrap();
+        history::record(&store, local_date(TIME), 5000, 700, 0, None).unwrap();
+        let path = scratch_dir("snapshot-file").join("state.json");
+        write_snapshot(&store, Some(&path), None).unwrap();
+
+        // a replacement device, holding state of its own which is replaced
+        let restored = scratch_store("snapshot-restored");
+        set_totals(&restored, &keys, 1, 1);
+        restore_snapshot(&path, &restored, true).unwrap();
+        for &file in &FILES {
+            assert_eq!(
+                restored.get(&[NET, file]).ok(),
+                store.get(&[NET, file]).ok(),
+                "{}",
+                file
+            );
+        }
+        assert_eq!(Totals::get(&restored, &keys), Totals::get(&store, &keys));
+    }
+
+    #[test]
+    fn delta_mode_writes_each_delta_without_accumulating() {
+        let opt = opt(&["--delta-mode"]);
+        let store = scratch_store("delta-mode");
+        let saves = [
+            reading(TIME, Some("b"), 0, 0),
+            reading(TIME + 60, Some("b"), 5000, 700),
+            reading(TIME + 120, Some("b"), 5300, 900),
+        ];
+
+        replay(&opt, &store, &saves[..2]);
+        assert_eq!(stored_total(&store, RX_DELTA), Some(5000));
+        assert_eq!(stored_total(&store, TX_DELTA), Some(700));
+        let totals = replay(&opt, &store, &saves[2..]);
+        assert_eq!(stored_total(&store, RX_DELTA), Some(300));
+        assert_eq!(stored_total(&store, TX_DELTA), Some(200));
+        assert_eq!(stored_total(&store, DELTA_TIME), Some(TIME + 120));
+        assert_eq!((totals.rx, totals.tx), (0, 0));
+    }
+
+    #[test]
+    fn unlikely_thresholds_are_rejected() {
+        for (args, error) in &[
+            (
+                &["ensure-thresholds", "--rx-cut", "512KB"][..],
+                "Invalid rx_cut: 512KB is below 1MB, the smallest threshold which can be stored",
+            ),
+            (
+                &["ensure-thresholds", "--total-cut", "5000GB"][..],
+                "Invalid total_cut: 4.9TB is larger than 2TB (raise --max-threshold if this is intended)",
+            ),
+            (
+                &["--max-threshold", "100GB", "--plan", "200GB"][..],
+                "Invalid plan: 200GB is larger than 100GB (raise --max-threshold if this is intended)",
+            ),
+            (
+                &["--profile", "window=00:00-06:00,total_warn=8GB,total_cut=5GB"][..],
+                "Invalid profile window=00:00-06:00,total_warn=8GB,total_cut=5GB: total_warn (8GB) must be below total_cut (5GB)",
+            ),
+        ] {
+            assert_eq!(check_threshold_options(&opt(args)).unwrap_err(), *error);
+        }
+        assert!(
+            check_threshold_options(&opt(&["ensure-thresholds", "--total-cut", "50GB"])).is_ok()
+        );
+    }
+
+    #[test]
+    fn warning_at_or_above_the_cutoff_is_not_stored() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("ensure-levels"));
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+            .unwrap();
+
+        // the stored cutoff stays in effect alongside the warning given
+        let desired = [(Direction::Rx, Level::Warn, Some(5120 * MEGABYTE))];
+        assert!(ensure_thresholds(&store, &keys, &desired).is_err());
+        assert_eq!(
+            stored_threshold(&store, &keys, Direction::Rx, Level::Warn),
+            None
+        );
+    }
+
+    #[test]
+    fn status_holds_the_days_of_the_cycle() {
+        let store = scratch_store("cycle-days");
+        store
+            .set(&[NET, NOTIFY, CYCLE_DAY], &Value::Uint(15))
+            .unwrap();
+
+        let cycle = api_cycle(&store);
+        let today = Local::now().date_naive();
+        let expected = Cycle::containing(today, 15);
+        assert_eq!(cycle["start"], expected.start.to_string());
+        assert_eq!(cycle["elapsed_days"], expected.elapsed(today));
+        assert_eq!(cycle["remaining_days"], expected.remaining(today));
+        assert_eq!(
+            cycle["elapsed_days"].as_i64().unwrap() + cycle["remaining_days"].as_i64().unwrap(),
+            expected.length()
+        );
+    }
+
+    /// Contents of each file under a directory, by path
+    fn directory_contents(dir: &Path) -> std::collections::BTreeMap<PathBuf, Vec<u8>> {
+        let mut contents = std::collections::BTreeMap::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                contents.extend(directory_contents(&path));
+            } else {
+                contents.insert(path.clone(), fs::read(&path).unwrap());
+            }
+        }
+        contents
+    }
+
+    #[test]
+    fn observe_mode_never_writes_to_the_store() {
+        let dir = scratch_dir("observe");
+        let store = Store::new(&dir, store_schema().unwrap());
+        let accounting = opt(&[]);
+        init_keys(&store, &keys(&accounting)).unwrap();
+        replay(
+            &accounting,
+            &store,
+            &[
+                reading(TIME, Some("b"), 0, 0),
+                reading(TIME + 60, Some("b"), 5000, 700),
+            ],
+        );
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+            .unwrap();
+        store
+            .set(&[NET, NOTIFY, CYCLE_DAY], &Value::Uint(15))
+            .unwrap();
+        let before = directory_contents(&dir);
+
+        let data_dir = dir.to_str().unwrap();
+        for command in &[
+            "status",
+            "summary",
+            "history",
+            "cycles",
+            "check-keys",
+            "doctor",
+        ] {
+            let _ = run(
+                opt(&["--observe", "--data-dir", data_dir, command]),
+                Vec::new(),
+            );
+        }
+        assert_eq!(directory_contents(&dir), before);
+
+        // operations which write are refused outright
+        for args in &[
+            &["--save"][..],
+            &["--plan", "50GB"],
+            &["pause"],
+            &["reset", "--confirm"],
+        ] {
+            let args = [&["--observe", "--data-dir", data_dir][..], args].concat();
+            assert!(run(opt(&args), Vec::new()).is_err(), "{:?}", args);
+        }
+        assert_eq!(directory_contents(&dir), before);
+    }
+
+    #[test]
+    fn total_equal_to_a_threshold_only_reaches_it_when_inclusive() {
+        let at = 10 * MEGABYTE;
+        let exclusive = Alert::evaluate(at, Some(10), Some(10), false);
+        assert!(!exclusive.warn && !exclusive.cut);
+        let inclusive = Alert::evaluate(at, Some(10), Some(10), true);
+        assert!(inclusive.warn && inclusive.cut);
+        // either way a larger total reaches it and a smaller one does not
+        for &inclusive in &[false, true] {
+            assert!(Alert::evaluate(at + 1, None, Some(10), inclusive).cut);
+            assert!(!Alert::evaluate(at - 1, None, Some(10), inclusive).cut);
+        }
+
+        for (args, raised) in &[(&[][..], false), (&["--inclusive-thresholds"][..], true)] {
+            let opt = opt(args);
+            let (keys, store) = (keys(&opt), scratch_store("inclusive"));
+            set_totals(&store, &keys, at, 0);
+            store
+                .set(&[NET, NOTIFY, "total_cut"], &Value::Uint(10))
+                .unwrap();
+            update_alerts(&opt, &store, &keys, TIME).unwrap();
+            assert_eq!(
+                store.get(&[NET, ALERT, "total_cut_alert"]).ok(),
+                Some(Value::Bool(*raised)),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn totals_restart_at_each_boot_with_since_boot() {
+        let opt = opt(&["--since-boot"]);
+        let store = scratch_store("since-boot");
+        let totals = replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME, Some("a"), 0, 0),
+                reading(TIME + 60, Some("a"), 5000, 700),
+                // the same boot, whose traffic keeps adding up
+                reading(TIME + 120, Some("a"), 6000, 900),
+            ],
+        );
+        assert_eq!((totals.rx, totals.tx), (6000, 900));
+
+        // a new boot leaves only the traffic counted since it
+        let totals = replay(&opt, &store, &[reading(TIME + 180, Some("b"), 300, 40)]);
+        assert_eq!((totals.rx, totals.tx, totals.total), (300, 40, 340));
+        let totals = replay(&opt, &store, &[reading(TIME + 240, Some("b"), 800, 90)]);
+        assert_eq!((totals.rx, totals.tx), (800, 90));
+    }
+
+    #[test]
+    fn cycle_summary_line_of_a_poll() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("cycle-summary"));
+        set_totals(&store, &keys, 5000, 700);
+        let alerts = [
+            (
+                Direction::Rx,
+                Alert {
+                    warn: true,
+                    cut: true,
+                },
+            ),
+            (
+                Direction::Tx,
+                Alert {
+                    warn: false,
+                    cut: false,
+                },
+            ),
+            (
+                Direction::Combined,
+                Alert {
+                    warn: true,
+                    cut: false,
+                },
+            ),
+        ];
+
+        assert_eq!(
+            cycle_summary(TIME, "eth9", &store, &keys, &alerts, Some(&Rate { rx: 83, tx: 11 })),
+            "time=1760000000 iface=eth9 rx=5000 tx=700 rx_rate=83 tx_rate=11 alerts=rx_warn,rx_cut,total_warn"
+        );
+        assert_eq!(
+            cycle_summary(TIME, "eth9", &store, &keys, &alerts[1..2], None),
+            "time=1760000000 iface=eth9 rx=5000 tx=700 rx_rate=- tx_rate=- alerts=none"
+        );
+    }
+
+    #[test]
+    fn test_alert_notifies_without_changing_the_store() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("test-alert"));
+        set_totals(&store, &keys, 5000, 700);
+        let dir = scratch_dir("test-alert-script");
+        let (script, received) = (dir.join("alert"), dir.join("received"));
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$PM_FLAG $PM_VALUE $PM_IFACE\" >> {}\n",
+                received.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let backends = [Backend::Script(script)];
+
+        test_alert(&backends, "eth9", &store, &keys, "rx_cut_alert").unwrap();
+        assert_eq!(
+            fs::read_to_string(&received).unwrap(),
+            "rx_cut_alert true eth9\nrx_cut_alert false eth9\n"
+        );
+        assert!(store.get(&[NET, ALERT, "rx_cut_alert"]).is_err());
+        assert_eq!(Totals::get(&store, &keys).rx, 5000);
+
+        // a flag which is really raised is left raised with the backends
+        fs::remove_file(&received).unwrap();
+        store
+            .set(&[NET, ALERT, "rx_cut_alert"], &Value::Bool(true))
+            .unwrap();
+        test_alert(&backends, "eth9", &store, &keys, "rx_cut_alert").unwrap();
+        assert_eq!(
+            fs::read_to_string(&received).unwrap(),
+            "rx_cut_alert true eth9\n"
+        );
+
+        assert!(test_alert(&backends, "eth9", &store, &keys, "rx_over_alert").is_err());
+        assert!(test_alert(&[], "eth9", &store, &keys, "rx_cut_alert").is_err());
+    }
+
+    #[test]
+    fn thresholds_from_the_environment_are_stored() {
+        let dir = scratch_dir("env-thresholds");
+        let data_dir = dir.to_str().unwrap();
+        let parse = |args: &[&str]| {
+            Opt::from_iter(["peach-monitor", "--data-dir", data_dir].iter().chain(args))
+        };
+
+        // the environment is held until the variables are removed again
+        let env = lock_env();
+        env::set_var("PEACH_MONITOR_RX_CUT", "5GB");
+        env::set_var("PEACH_MONITOR_TOTAL_WARN", " 40GB");
+        env::set_var("PEACH_MONITOR_TX_WARN", "");
+        let stored = run(parse(&["status"]), Vec::new());
+        env::set_var("PEACH_MONITOR_TX_CUT", "lots");
+        let invalid = env_thresholds(2 * 1024 * 1024 * MEGABYTE);
+        env::set_var("PEACH_MONITOR_TX_CUT", "5000GB");
+        let too_large = run(parse(&["status"]), Vec::new());
+        for var in &["RX_CUT", "TOTAL_WARN", "TX_WARN", "TX_CUT"] {
+            env::remove_var(format!("PEACH_MONITOR_{}", var));
+        }
+        drop(env);
+
+        stored.unwrap();
+        let (keys, store) = (keys(&opt(&[])), Store::new(&dir, store_schema().unwrap()));
+        let threshold = Threshold::get(&store, &keys);
+        assert_eq!(threshold.of(Direction::Rx), (None, Some(5120)));
+        assert_eq!(threshold.of(Direction::Tx), (None, None));
+        assert_eq!(threshold.of(Direction::Combined), (Some(40960), None));
+        assert!(invalid
+            .unwrap_err()
+            .starts_with("Invalid PEACH_MONITOR_TX_CUT: "));
+        assert!(too_large.is_err());
+        assert_eq!(
+            Threshold::get(&store, &keys).of(Direction::Tx),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn total_which_is_not_a_number_of_bytes_is_evaluated_as_zero() {
+        let opt = opt(&["--inclusive-thresholds"]);
+        let keys = keys(&opt);
+        for (name, value) in &[
+            ("null", Value::Null),
+            ("string", Value::String("5GB".to_string())),
+            ("negative", Value::Int(-5)),
+            ("fraction", Value::Float(5000.5)),
+        ] {
+            let store = scratch_store(&format!("invalid-total-{}", name));
+            set_totals(&store, &keys, 5 * MEGABYTE, 0);
+            store
+                .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(1))
+                .unwrap();
+            update_alerts(&opt, &store, &keys, TIME).unwrap();
+            assert_eq!(
+                store.get(&[NET, ALERT, "rx_cut_alert"]).ok(),
+                Some(Value::Bool(true))
+            );
+
+            // the alert raised against the earlier total is cleared
+            store.set(&[NET, TRAFFIC, "rx"], value).unwrap();
+            assert_eq!(
+                alert_usage(&store, &keys, Direction::Rx),
+                Some(0),
+                "{}",
+                name
+            );
+            update_alerts(&opt, &store, &keys, TIME).unwrap();
+            assert_eq!(
+                store.get(&[NET, ALERT, "rx_cut_alert"]).ok(),
+                Some(Value::Bool(false)),
+                "{}",
+                name
+            );
+        }
+
+        // a whole number stored as another kind is read as its value
+        let store = scratch_store("float-total");
+        store
+            .set(&[NET, TRAFFIC, "rx"], &Value::Float(5000.0))
+            .unwrap();
+        assert_eq!(alert_usage(&store, &keys, Direction::Rx), Some(5000));
+    }
+
+    #[test]
+    fn grace_period_holds_back_the_flags_it_started_with() {
+        let start = Instant::now();
+        let mut grace = Grace::new(Some(Duration::from_secs(60)), start);
+        let (cleared, raised) = (vec![("rx_cut_alert", false)], vec![("rx_cut_alert", true)]);
+
+        assert_eq!(grace.before(start, cleared.clone()), None);
+        assert_eq!(
+            grace.before(start + Duration::from_secs(59), raised.clone()),
+            None
+        );
+        // changes are notified from the flags stored when the period started
+        assert_eq!(
+            grace.before(start + Duration::from_secs(60), raised.clone()),
+            Some(cleared)
+        );
+        assert_eq!(
+            grace.before(start + Duration::from_secs(61), raised.clone()),
+            Some(raised.clone())
+        );
+
+        let mut none = Grace::new(None, start);
+        assert_eq!(none.before(start, raised.clone()), Some(raised));
+    }
+
+    #[test]
+    fn notifications_are_held_back_during_the_grace_period() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("grace-script");
+        let (script, received) = (dir.join("alert"), dir.join("received"));
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$PM_FLAG $PM_VALUE\" >> {}\n",
+                received.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let (opt, counters) = counters_file(
+            "grace",
+            &[
+                "--on-alert",
+                script.to_str().unwrap(),
+                "--startup-grace",
+                "1h",
+            ],
+        );
+        let (keys, store) = (keys(&opt), scratch_store("grace-store"));
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(1))
+            .unwrap();
+        let mut meter = RateMeter::new();
+        let mut grace = Grace::new(opt.startup_grace, Instant::now());
+        // the flag is raised in the store, but not notified
+        for &rx in &[0, 5 * MEGABYTE] {
+            fs::write(&counters, format!("{} 0", rx)).unwrap();
+            poll(
+                &opt,
+                &store,
+                &keys,
+                &Outputs::default(),
+                &mut meter,
+                &mut grace,
+                false,
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            store.get(&[NET, ALERT, "rx_cut_alert"]).ok(),
+            Some(Value::Bool(true))
+        );
+
+        // once the period is over, the flag raised during it is notified
+        grace.until = Some(Instant::now());
+        poll(
+            &opt,
+            &store,
+            &keys,
+            &Outputs::default(),
+            &mut meter,
+            &mut grace,
+            false,
+        )
+        .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !received.exists() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(
+            fs::read_to_string(&received).unwrap(),
+            "rx_cut_alert true\n"
+        );
+    }
+
+    #[test]
+    fn reset_archives_the_cycle_it_ends() {
+        let (archiving, none) = (
+            opt(&["--iface", "eth9"]),
+            opt(&["--iface", "eth9", "--keep-cycles", "0"]),
+        );
+        let (keys, store) = (keys(&archiving), scratch_store("reset-archive"));
+        set_totals(&store, &keys, 5000, 700);
+
+        // resetting one direction does not end the cycle
+        reset_totals(&archiving, &store, &keys, true, false, true).unwrap();
+        assert!(archive::load(&store).is_empty());
+        reset_totals(&archiving, &store, &keys, true, false, false).unwrap();
+        let totals = Totals::get(&store, &keys);
+        assert_eq!((totals.rx, totals.tx, totals.total), (0, 0, 0));
+        let cycles = archive::load(&store);
+        let today = Local::now().date_naive();
+        assert_eq!(cycles.keys().collect::<Vec<_>>(), [&today]);
+        assert_eq!((cycles[&today].rx, cycles[&today].tx), (5000, 0));
+
+        let store = scratch_store("reset-no-archive");
+        set_totals(&store, &keys, 5000, 700);
+        reset_totals(&none, &store, &keys, true, false, false).unwrap();
+        assert!(archive::load(&store).is_empty());
+    }
+
+    #[test]
+    fn traffic_while_the_daemon_was_stopped_follows_catch_up() {
+        for &(catch_up, counted) in &[("on-restart", 5000 + 2000 + 100), ("off", 5000 + 100)] {
+            let opt = opt(&["--catch-up", catch_up]);
+            let (keys, store) = (keys(&opt), scratch_store(&format!("catch-up-{}", catch_up)));
+            replay(
+                &opt,
+                &store,
+                &[
+                    reading(TIME, Some("b"), 0, 0),
+                    reading(TIME + 60, Some("b"), 5000, 0),
+                ],
+            );
+
+            // the daemon is stopped for an hour, then its first poll follows a restart
+            let first = reading(TIME + 3660, Some("b"), 7000, 0);
+            update_transmission_totals(&opt, &first, &store, &keys, true).unwrap();
+            let totals = replay(&opt, &store, &[reading(TIME + 3720, Some("b"), 7100, 0)]);
+            assert_eq!(totals.rx, counted, "{}", catch_up);
+        }
+    }
+
+    #[test]
+    fn missing_keys_are_initialised_and_existing_ones_kept() {
+        let store = scratch_store("get-or-init");
+        let init = |key, default| get_or_init(&store, TRAFFIC, key, default).unwrap();
+
+        // both a missing file and a key missing from a file are initialised
+        assert_eq!(init("rx", Value::Uint(0)), Value::Uint(0));
+        assert_eq!(init("paused", Value::Bool(false)), Value::Bool(false));
+        assert_eq!(
+            store.get(&[NET, TRAFFIC, "paused"]).unwrap(),
+            Value::Bool(false)
+        );
+
+        store
+            .set(&[NET, TRAFFIC, "rx"], &Value::Uint(5000))
+            .unwrap();
+        assert_eq!(init("rx", Value::Uint(0)), Value::Uint(5000));
+        // whatever its type
+        store
+            .set(&[NET, TRAFFIC, "tx"], &Value::String("700".to_string()))
+            .unwrap();
+        assert_eq!(init("tx", Value::Uint(0)), Value::String("700".to_string()));
+        assert_eq!(store.get(&[NET, TRAFFIC, "rx"]).unwrap(), Value::Uint(5000));
+    }
+
+    #[test]
+    fn initialised_keys_leave_thresholds_undefined() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("init-keys"));
+        store
+            .set(&[NET, ALERT, "rx_cut_alert"], &Value::Bool(true))
+            .unwrap();
+        init_keys(&store, &keys).unwrap();
+
+        assert_eq!(stored_total(&store, "total"), Some(0));
+        assert_eq!(stored_total(&store, TOTALS_VERSION), Some(0));
+        assert_eq!(stored_alert(&store, &keys, "tx_warn_alert"), Some(false));
+        assert_eq!(stored_alert(&store, &keys, "rx_cut_alert"), Some(true));
+        assert!(store.get(&[NET, NOTIFY]).is_err());
+    }
+
+    #[test]
+    fn totals_in_kilobytes_round_trip_exactly() {
+        let opt = opt(&["--total-unit", "kb"]);
+        let (keys, store) = (keys(&opt), scratch_store("kilobytes"));
+        let totals = replay(
+            &opt,
+            &store,
+            &[
+                reading(TIME, Some("b"), 0, 0),
+                reading(TIME + 60, Some("b"), 5000, 700),
+                reading(TIME + 120, Some("b"), 10_000, 1400),
+            ],
+        );
+
+        assert_eq!((totals.rx, totals.tx, totals.total), (10_000, 1400, 11_400));
+        assert_eq!(stored_usage(&store, &keys, Direction::Rx), Some(10_000));
+        for (key, stored) in &[
+            ("rx", 9),
+            ("rx_carry", 10_000 % 1024),
+            ("tx", 1),
+            ("tx_carry", 1400 - 1024),
+            ("total", 11),
+            ("total_carry", 11_400 % 1024),
+        ] {
+            assert_eq!(stored_total(&store, key), Some(*stored), "{}", key);
+        }
+        assert_eq!(stored_string(&store, TOTAL_UNIT).as_deref(), Some("kb"));
+    }
+
+    #[test]
+    fn thresholds_are_compared_with_the_exact_total_in_kilobytes() {
+        let opt = opt(&["--total-unit", "kb", "--inclusive-thresholds"]);
+        let keys = keys(&opt);
+        for &(rx, raised) in &[(5 * MEGABYTE - 1, false), (5 * MEGABYTE, true)] {
+            let store = scratch_store("kilobytes-cap");
+            store
+                .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5))
+                .unwrap();
+            replay(
+                &opt,
+                &store,
+                &[
+                    reading(TIME, Some("b"), 0, 0),
+                    reading(TIME + 60, Some("b"), rx, 0),
+                ],
+            );
+
+            // one byte short of the cap is a whole kilobyte short once stored in kilobytes
+            assert_eq!(stored_total(&store, "rx"), Some(rx / 1024));
+            update_alerts(&opt, &store, &keys, TIME + 60).unwrap();
+            assert_eq!(
+                store.get(&[NET, ALERT, "rx_cut_alert"]).ok(),
+                Some(Value::Bool(raised)),
+                "{}",
+                rx
+            );
+        }
+    }
+
+    #[test]
+    fn failed_first_poll_stops_the_daemon_under_fail_fast() {
+        // the counters file is never written, so reading the interface fails
+        let (opt, _) = counters_file(
+            "fail-fast",
+            &["--daemon", "--fail-fast", "--interval", "3600"],
+        );
+        let path = scratch_dir("fail-fast-store");
+        let (keys, store) = (keys(&opt), Store::new(&path, store_schema().unwrap()));
+        let (_stop_tx, stop_rx) = mpsc::channel();
+
+        let error = run_daemon(&opt, &store, &keys, &path, true, None, &stop_rx).unwrap_err();
+        assert!(error.starts_with("First poll failed: "), "{}", error);
+    }
+
+    #[test]
+    fn failed_first_poll_is_logged_without_fail_fast() {
+        let (opt, _) = counters_file("no-fail-fast", &["--daemon", "--interval", "3600"]);
+        let path = scratch_dir("no-fail-fast-store");
+        let (keys, store) = (keys(&opt), Store::new(&path, store_schema().unwrap()));
+        let (stop_tx, stop_rx) = mpsc::channel();
+        stop_tx.send(()).unwrap();
+
+        // the daemon carries on polling, here until it is stopped
+        run_daemon(&opt, &store, &keys, &path, true, None, &stop_rx).unwrap();
+    }
+
+    #[test]
+    fn connection_limits_replace_the_stored_thresholds() {
+        let opt = opt(&[]);
+        let (keys, store) = (keys(&opt), scratch_store("nm-limits"));
+        for (key, megabytes) in &[("total_cut", 10240), ("rx_warn", 4096)] {
+            store
+                .set(&[NET, NOTIFY, key], &Value::Uint(*megabytes))
+                .unwrap();
+        }
+
+        let connection = [(Direction::Combined, Level::Cut, 50 * 1024 * MEGABYTE)];
+        let threshold = Threshold::active(&store, &keys, &connection, &[], TIME);
+        assert_eq!(threshold.of(Direction::Combined), (None, Some(51200)));
+        assert_eq!(threshold.of(Direction::Rx), (Some(4096), None));
+
+        // without limits from NetworkManager the stored thresholds apply
+        let threshold = Threshold::active(&store, &keys, &[], &[], TIME);
+        assert_eq!(threshold.of(Direction::Combined), (None, Some(10240)));
+    }
+
+    #[test]
+    fn status_file_follows_each_poll() {
+        let status = scratch_dir("status-file").join("status");
+        let (opt, counters) = counters_file(
+            "status-file-counters",
+            &["--status-file", status.to_str().unwrap()],
+        );
+        let (keys, store) = (keys(&opt), scratch_store("status-file-store"));
+        for (key, megabytes) in &[("rx_warn", 1), ("rx_cut", 5)] {
+            store
+                .set(&[NET, NOTIFY, key], &Value::Uint(*megabytes))
+                .unwrap();
+        }
+        let mut meter = RateMeter::new();
+        let mut grace = Grace::new(None, Instant::now());
+
+        for &(rx, state) in &[(0, "ok"), (2 * MEGABYTE, "warn"), (6 * MEGABYTE, "cut")] {
+            fs::write(&counters, format!("{} 1000", rx)).unwrap();
+            poll(
+                &opt,
+                &store,
+                &keys,
+                &Outputs::default(),
+                &mut meter,
+                &mut grace,
+                false,
+            )
+            .unwrap();
+
+            let contents = fs::read_to_string(&status).unwrap();
+            let lines: Vec<&str> = contents.lines().collect();
+            assert_eq!(
+                lines[..4],
+                [
+                    state.to_string(),
+                    format!("rx={}", rx),
+                    "tx=0".to_string(),
+                    format!("total={}", rx)
+                ],
+                "{}",
+                state
+            );
+            assert!(lines[4].starts_with("time="));
+            assert_eq!(lines.len(), 5);
+        }
+        // the temporary file is renamed into place
+        assert!(!status.with_extension("tmp").exists());
+    }
+
+    /// Data store of a cycle starting on the 1st, whose days up to one each used a number of megabytes
+    fn cycle_history(name: &str, until: NaiveDate, megabytes: u64) -> Store {
+        let store = scratch_store(name);
+        store
+            .set(&[NET, NOTIFY, CYCLE_DAY], &Value::Uint(1))
+            .unwrap();
+        // the days of the previous cycle, which are not projected from
+        let mut day = NaiveDate::from_ymd_opt(2026, 9, 20).unwrap();
+        while day <= until {
+            let used = if day < NaiveDate::from_ymd_opt(2026, 10, 1).unwrap() {
+                1024 * MEGABYTE
+            } else {
+                megabytes * MEGABYTE
+            };
+            history::record(&store, day, used, 0, 0, None).unwrap();
+            day = day.succ_opt().unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn projection_of_the_usage_of_the_cycle() {
+        let inclusive = opt(&["--inclusive-thresholds", "--projection-clear-margin", "0"]);
+        let opt = opt(&["--projection-clear-margin", "0"]);
+        let today = NaiveDate::from_ymd_opt(2026, 10, 11).unwrap();
+        let store = cycle_history("projection", today, 100);
+        // a lifetime total spanning several cycles is not what is projected
+        set_totals(&store, &keys(&opt), 100 * 1024 * MEGABYTE, 0);
+
+        // 10 days of 100MB, today's 100MB, and 100MB a day for the 20 days after it
+        let project = |opt: &Opt, cut| set_projection_alert(opt, &store, Some(cut), today).unwrap();
+        assert_eq!(project(&opt, 3100), Some(false));
+        assert_eq!(project(&inclusive, 3100), Some(true));
+        assert_eq!(project(&opt, 3101), Some(false));
+        assert_eq!(
+            store.get(&[NET, ALERT, PROJECTION_OVER]).unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(project(&opt, 3099), Some(true));
+        assert_eq!(
+            store.get(&[NET, ALERT, PROJECTION_OVER]).unwrap(),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn projection_waits_for_the_minimum_days() {
+        let opt = opt(&["--projection-min-days", "3"]);
+        let today = NaiveDate::from_ymd_opt(2026, 10, 3).unwrap();
+        let store = cycle_history("projection-min-days", today, 1024);
+
+        // two days elapsed, at a pace far over the cutoff
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(5120), today).unwrap(),
+            Some(false)
+        );
+        let tomorrow = today.succ_opt().unwrap();
+        history::record(&store, tomorrow, 1024 * MEGABYTE, 0, 0, None).unwrap();
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(5120), tomorrow).unwrap(),
+            Some(true)
+        );
+
+        // nothing is projected without a cutoff
+        assert_eq!(
+            set_projection_alert(&opt, &store, None, tomorrow).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn data_dir_falls_back_without_xdg_variables() {
+        let _env = lock_env();
+        let saved: Vec<_> = ["HOME", "XDG_DATA_HOME"]
+            .iter()
+            .map(|var| (*var, env::var_os(var)))
+            .collect();
+        env::remove_var("HOME");
+        env::remove_var("XDG_DATA_HOME");
+        let fallback = default_data_dir();
+        env::set_var("HOME", "");
+        let empty = default_data_dir();
+        env::set_var("XDG_DATA_HOME", "/srv/data");
+        let xdg = default_data_dir();
+        for (var, value) in saved {
+            match value {
+                Some(value) => env::set_var(var, value),
+                None => env::remove_var(var),
+            }
+        }
+
+        assert_eq!(fallback, Path::new(FALLBACK_DATA_DIR));
+        assert_eq!(empty, Path::new(FALLBACK_DATA_DIR));
+        assert_eq!(xdg, Path::new("/srv/data/peachcloud"));
+    }
+
+    #[test]
+    fn renamed_alert_flags_are_written_and_read_back() {
+        let opt = opt(&["--alert-key", "total_cut_alert=data_cut"]);
+        let (keys, store) = (keys(&opt), scratch_store("alert-key"));
+        init_keys(&store, &keys).unwrap();
+        set_totals(&store, &keys, 6 * MEGABYTE, 6 * MEGABYTE);
+        store
+            .set(&[NET, NOTIFY, "total_cut"], &Value::Uint(10))
+            .unwrap();
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5))
+            .unwrap();
+        update_alerts(&opt, &store, &keys, TIME).unwrap();
+
+        let alert = |key| store.get(&[NET, ALERT, key]).ok();
+        assert_eq!(alert("data_cut"), Some(Value::Bool(true)));
+        assert_eq!(alert("total_cut_alert"), None);
+        assert_eq!(alert("cut_alert"), None);
+        assert_eq!(alert("rx_cut_alert"), Some(Value::Bool(true)));
+
+        let alerts = api_alerts(&store, &keys);
+        assert_eq!(alerts["data_cut"], true);
+        assert_eq!(alerts["rx_cut_alert"], true);
+        assert_eq!(alerts["total_warn_alert"], false);
+        assert!(alerts.get("total_cut_alert").is_none());
+        assert_eq!(
+            alert_flags(&store, &keys)
+                .iter()
+                .find(|(flag, _)| *flag == "data_cut"),
+            Some(&("data_cut", true))
+        );
+    }
+
+    #[test]
+    fn daily_moving_average_is_stored_once_per_day() {
+        let store = scratch_store("daily-ema");
+        let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        for (d, usage) in &[(1, 1000), (2, 2000), (3, 500)] {
+            history::record(&store, day(*d), *usage, 0, 0, None).unwrap();
+        }
+
+        update_daily_ema(&store, day(3), 0.5).unwrap();
+        assert_eq!(stored_total(&store, DAILY_EMA), Some(1500));
+        assert_eq!(stored_ema_date(&store), Some(day(2)));
+        // the same day again leaves it as it is, the next one folds in the day before
+        update_daily_ema(&store, day(3), 0.5).unwrap();
+        assert_eq!(stored_total(&store, DAILY_EMA), Some(1500));
+        update_daily_ema(&store, day(4), 0.5).unwrap();
+        assert_eq!(stored_total(&store, DAILY_EMA), Some(1000));
+        assert_eq!(stored_ema_date(&store), Some(day(3)));
+    }
+
+    #[test]
+    fn projection_on_the_moving_average() {
+        let opt = opt(&["--forecast-basis", "ema", "--projection-clear-margin", "0"]);
+        let today = NaiveDate::from_ymd_opt(2026, 10, 11).unwrap();
+        let store = cycle_history("projection-ema", today, 100);
+        // without a moving average there is nothing to project from
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(1), today).unwrap(),
+            None
+        );
+
+        // 1100MB so far, and 10MB a day for the 20 days after today
+        store
+            .set(&[NET, TRAFFIC, DAILY_EMA], &Value::Uint(10 * MEGABYTE))
+            .unwrap();
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(1300), today).unwrap(),
+            Some(false)
+        );
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(1299), today).unwrap(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn upload_anomaly_against_the_ratio_baseline() {
+        let store = scratch_store("upload-anomaly");
+        let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        for d in 1..=3 {
+            history::record(&store, day(d), 100 * MEGABYTE, 10 * MEGABYTE, 0, None).unwrap();
+        }
+        // nothing is flagged until a factor and a baseline are stored
+        assert_eq!(set_upload_anomaly(&store, day(3), 0).unwrap(), None);
+        store
+            .set(&[NET, NOTIFY, UPLOAD_FACTOR], &Value::Float(3.0))
+            .unwrap();
+        assert_eq!(set_upload_anomaly(&store, day(3), 0).unwrap(), None);
+        update_ratio_baseline(&store, day(3), 7).unwrap();
+        assert_eq!(
+            store.get(&[NET, TRAFFIC, RATIO_BASELINE]).unwrap(),
+            Value::Float(0.1)
+        );
+
+        // each case is evaluated on a day of its own, after those of the baseline
+        for (today, (rx, tx, min_tx, raised)) in (4..).zip(&[
+            // at the baseline, then up to the factor
+            (100, 10, 0, false),
+            (100, 30, 0, false),
+            (100, 31, 0, true),
+            // too little transmitted to count
+            (100, 31, 40, false),
+            // transmitting without receiving
+            (0, 50, 40, true),
+            (0, 0, 0, false),
+        ]) {
+            history::record(&store, day(today), rx * MEGABYTE, tx * MEGABYTE, 0, None).unwrap();
+            assert_eq!(
+                set_upload_anomaly(&store, day(today), min_tx * MEGABYTE).unwrap(),
+                Some(*raised),
+                "{} {}",
+                rx,
+                tx
+            );
+            assert_eq!(
+                store.get(&[NET, ALERT, UPLOAD_ANOMALY]).unwrap(),
+                Value::Bool(*raised)
+            );
+        }
+    }
+
+    #[test]
+    fn projection_alert_clears_below_the_margin() {
+        let (flickering, opt) = (
+            opt(&["--forecast-basis", "ema", "--projection-clear-margin", "0"]),
+            opt(&["--forecast-basis", "ema"]),
+        );
+        let today = NaiveDate::from_ymd_opt(2026, 10, 11).unwrap();
+        let store = cycle_history("projection-margin", today, 100);
+        // 1100MB so far, and the moving average for the 20 days after today
+        let project = |opt: &Opt, average| {
+            store
+                .set(&[NET, TRAFFIC, DAILY_EMA], &Value::Uint(average * MEGABYTE))
+                .unwrap();
+            set_projection_alert(opt, &store, Some(2000), today)
+                .unwrap()
+                .unwrap()
+        };
+
+        // a projection oscillating around the cutoff of 2000MB, which clears below 1900MB
+        let raised: Vec<bool> = [46, 44, 46, 40, 39, 44, 46]
+            .iter()
+            .map(|&average| project(&opt, average))
+            .collect();
+        assert_eq!(raised, [true, true, true, true, false, false, true]);
+
+        // without a margin the alert follows each crossing
+        let raised: Vec<bool> = [46, 44, 46]
+            .iter()
+            .map(|&average| project(&flickering, average))
+            .collect();
+        assert_eq!(raised, [true, false, true]);
+    }
+
+    #[test]
+    fn thresholds_are_listed_with_their_sources() {
+        let opt = opt(&[
+            "--plan",
+            "10GB",
+            "--plan-mode",
+            "combined",
+            "--profile",
+            "window=00:00-24:00,tx_warn=1GB",
+        ]);
+        let (keys, store) = (keys(&opt), scratch_store("threshold-sources"));
+        set_plan(&store, &keys, 10 * 1024 * MEGABYTE, PlanMode::Combined).unwrap();
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(100))
+            .unwrap();
+
+        let settings = {
+            let _env = lock_env();
+            env::set_var("PEACH_MONITOR_TOTAL_WARN", "9GB");
+            let settings = threshold_settings(&opt, &store, &keys);
+            env::remove_var("PEACH_MONITOR_TOTAL_WARN");
+            settings
+        };
+        let source = |name: &str| {
+            let setting = settings
+                .iter()
+                .find(|setting| setting.name == name)
+                .unwrap();
+            (setting.value.clone(), setting.source.clone())
+        };
+        assert_eq!(source("rx_warn"), (None, Origin::Unset));
+        assert_eq!(
+            source("rx_cut"),
+            (Some("100MB".to_string()), Origin::DataStore)
+        );
+        assert_eq!(
+            source("tx_warn"),
+            (
+                Some("1GB".to_string()),
+                Origin::Profile("window=00:00-24:00,tx_warn=1GB".to_string())
+            )
+        );
+        // the variable is stored over the threshold derived from the plan
+        assert_eq!(
+            source("total_warn").1,
+            Origin::Environment("PEACH_MONITOR_TOTAL_WARN".to_string())
+        );
+        assert_eq!(
+            source("total_cut"),
+            (Some("10GB".to_string()), Origin::Plan)
+        );
+    }
+
+    #[test]
+    fn polls_write_to_the_sinks_despite_failures() {
+        use crate::sink::tests::Recorder;
+
+        let (opt, counters) = counters_file("sinks-counters", &[]);
+        let (keys, store) = (keys(&opt), scratch_store("sinks-store"));
+        let recorder = Recorder::new("recorder", false);
+        let records = recorder.records.clone();
+        let outputs = Outputs {
+            journal: None,
+            sinks: vec![Box::new(Recorder::new("locked", true)), Box::new(recorder)],
+        };
+        let mut meter = RateMeter::new();
+        let mut grace = Grace::new(None, Instant::now());
+
+        for &(rx, tx) in &[(1000, 100), (6000, 800)] {
+            fs::write(&counters, format!("{} {}", rx, tx)).unwrap();
+            poll(&opt, &store, &keys, &outputs, &mut meter, &mut grace, false).unwrap();
+        }
+        let deltas: Vec<(u64, u64)> = records
+            .borrow()
+            .iter()
+            .map(|&(_, _, rx, tx)| (rx, tx))
+            .collect();
+        assert_eq!(deltas.last(), Some(&(5000, 700)));
+        assert_eq!(records.borrow().len(), 2);
+        assert_eq!(records.borrow()[0].1, opt.iface);
+        // the data store is written whatever the backends do
+        assert_eq!(
+            stored_usage(&store, &keys, Direction::Rx),
+            Some(deltas.iter().map(|d| d.0).sum())
+        );
+    }
+
+    #[test]
+    fn daily_budget_of_the_cycle() {
+        let opt = opt(&[]);
+        let today = NaiveDate::from_ymd_opt(2026, 10, 11).unwrap();
+        let store = cycle_history("daily-budget", today, 100);
+        // a lifetime total spanning several cycles does not count against the budget
+        set_totals(&store, &keys(&opt), 100 * 1024 * MEGABYTE, 0);
+        assert_eq!(set_budget_alert(&opt, &store, None, today).unwrap(), None);
+
+        // 2100MB left after the 10 days of 100MB before today, over the 21 days left,
+        // which today's 100MB does not exceed
+        assert_eq!(
+            set_budget_alert(&opt, &store, Some(3100), today).unwrap(),
+            Some(false)
+        );
+        assert_eq!(stored_total(&store, DAILY_BUDGET), Some(100 * MEGABYTE));
+        history::record(&store, today, 1, 0, 0, None).unwrap();
+        assert_eq!(
+            set_budget_alert(&opt, &store, Some(3100), today).unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            store.get(&[NET, ALERT, BUDGET_OVER]).unwrap(),
+            Value::Bool(true)
+        );
+        // today's usage does not change the budget
+        assert_eq!(stored_total(&store, DAILY_BUDGET), Some(100 * MEGABYTE));
+
+        // on the last day, all that remains of the cap
+        let last = NaiveDate::from_ymd_opt(2026, 10, 31).unwrap();
+        assert_eq!(
+            set_budget_alert(&opt, &store, Some(3100), last).unwrap(),
+            Some(false)
+        );
+        assert_eq!(
+            stored_total(&store, DAILY_BUDGET),
+            Some(2000 * MEGABYTE - 1)
+        );
+        // and nothing once it is used up
+        assert_eq!(
+            set_budget_alert(&opt, &store, Some(1000), last).unwrap(),
+            Some(false)
+        );
+        assert_eq!(stored_total(&store, DAILY_BUDGET), Some(0));
+    }
+
+    #[test]
+    fn projection_on_the_moving_average_waits_for_the_minimum_days() {
+        let opt = opt(&["--forecast-basis", "ema"]);
+        let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        // a heavy first day of the cycle, well above the moving average
+        let store = cycle_history("projection-ema-min-days", day(1), 5000);
+        store
+            .set(&[NET, TRAFFIC, DAILY_EMA], &Value::Uint(10 * MEGABYTE))
+            .unwrap();
+        store
+            .set(&[NET, ALERT, PROJECTION_OVER], &Value::Bool(true))
+            .unwrap();
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(2000), day(1)).unwrap(),
+            Some(false)
+        );
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(2000), day(3)).unwrap(),
+            Some(false)
+        );
+        // once 3 days of the cycle have elapsed
+        assert_eq!(
+            set_projection_alert(&opt, &store, Some(2000), day(4)).unwrap(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn summary_names_renamed_flags_by_their_keys() {
+        let opt = opt(&[
+            "--schema",
+            "per-interface",
+            "--alert-key",
+            "total_cut_alert=data_cut",
+        ]);
+        let (keys, store) = (keys(&opt), scratch_store("summary-renamed"));
+        let mut entry = IndexMap::new();
+        entry.insert("data_cut".to_string(), Value::Bool(true));
+        entry.insert("total_cut_alert".to_string(), Value::Bool(true));
+        store
+            .set(&[NET, INTERFACES, "eth0"], &Value::Object(entry))
+            .unwrap();
+
+        let summaries = summarize(&store, &keys);
+        // the key of the layout is no longer that of the flag
+        assert_eq!(summaries[0].raised, ["data_cut"]);
+        assert!(summaries[0].over_cap(&keys));
+    }
+
+    #[test]
+    fn zero_threshold_reads_as_the_alert_it_raises() {
+        let (opt, counters) = counters_file("zero-threshold", &[]);
+        let (keys, store) = (keys(&opt), scratch_store("zero-threshold-store"));
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(0))
+            .unwrap();
+        let mut meter = RateMeter::new();
+        let mut grace = Grace::new(None, Instant::now());
+        for rx in &[1000, 2000] {
+            fs::write(&counters, format!("{} 100", rx)).unwrap();
+            poll(
+                &opt,
+                &store,
+                &keys,
+                &Outputs::default(),
+                &mut meter,
+                &mut grace,
+                false,
+            )
+            .unwrap();
+        }
+
+        let state = load_state(&store, &keys);
+        assert!(state.rx.cut_alert);
+        assert_eq!(state.rx.cut, Some(0));
+        assert_eq!(
+            state.rx.cut,
+            stored_threshold(&store, &keys, Direction::Rx, Level::Cut).map(to_bytes)
+        );
+    }
+
+    #[test]
+    fn options_are_listed_as_defined() {
+        let mut help = Vec::new();
+        Opt::clap().write_long_help(&mut help).unwrap();
+        let help = String::from_utf8(help).unwrap();
+        let flags = help.find("FLAGS:").unwrap();
+        let subcommands = help.find("SUBCOMMANDS:").unwrap();
+        // each option is listed by its names, ahead of its more indented description
+        let mut defined: Vec<&str> = help[flags..subcommands]
+            .lines()
+            .filter(|line| line.starts_with("    -") || line.starts_with("        --"))
+            .filter_map(|line| line.split(' ').find(|w| w.starts_with("--")))
+            .map(|long| long.trim_end_matches(','))
+            .filter(|&long| long != "--help" && long != "--version")
+            .collect();
+        let mut listed: Vec<String> = option_specs()
+            .iter()
+            .map(|spec| format!("--{}", spec.long.unwrap_or(spec.name)))
+            .collect();
+        defined.sort_unstable();
+        listed.sort_unstable();
+        assert_eq!(defined, listed);
+
+        // options left to their defaults are resolved as such
+        let _env = lock_env();
+        for spec in option_specs() {
+            if let Some(var) = spec.env {
+                assert!(env::var_os(var).is_none(), "{}", var);
+            }
+        }
+        let matches = Opt::clap()
+            .get_matches_from_safe(["peach-monitor"])
+            .unwrap();
+        for setting in config::options(&option_specs(), &matches) {
+            let expected = match setting.value {
+                Some(_) => Origin::Default,
+                None => Origin::Unset,
+            };
+            assert_eq!(setting.source, expected, "{}", setting.name);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Colouring of alert states in terminal output.
+//!
+//! Only the human-readable status and watch views are coloured; output
+//! meant for other programs (JSON, metrics, exports) never is. Colour is
+//! enabled automatically when stdout is a terminal and `NO_COLOR` is unset
+//! (see <https://no-color.org>).
+
+use std::str::FromStr;
+
+/// Whether to colour output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorChoice {
+    /// Colour output written to a terminal, unless `NO_COLOR` is set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output should be coloured, given whether it goes to a
+    /// terminal and whether `NO_COLOR` is set (to a non-empty value)
+    pub fn enabled(self, terminal: bool, no_color: bool) -> bool {
+        match self {
+            ColorChoice::Auto => terminal && !no_color,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Unknown color choice: {}", s)),
+        }
+    }
+}
+
+/// Colour of an alert state
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    /// A cutoff has been reached
+    Red,
+    /// A warning has been reached
+    Yellow,
+    /// No alert is raised
+    Green,
+}
+
+impl Color {
+    /// ANSI escape code which selects the colour
+    fn code(self) -> &'static str {
+        match self {
+            Color::Red => "\x1b[31m",
+            Color::Yellow => "\x1b[33m",
+            Color::Green => "\x1b[32m",
+        }
+    }
+}
+
+/// ANSI escape code which resets the colour
+const RESET: &str = "\x1b[0m";
+
+/// Colours text, or passes it through unchanged when colour is disabled
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub fn new(enabled: bool) -> Palette {
+        Palette { enabled }
+    }
+
+    /// Return the text to display in a colour
+    pub fn paint(&self, text: &str, color: Color) -> String {
+        if self.enabled {
+            format!("{}{}{}", color.code(), text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn automatic_colour_needs_a_terminal_without_no_color() {
+        assert!(ColorChoice::Auto.enabled(true, false));
+        assert!(!ColorChoice::Auto.enabled(false, false));
+        assert!(!ColorChoice::Auto.enabled(true, true));
+        assert!(ColorChoice::Always.enabled(false, true));
+        assert!(!ColorChoice::Never.enabled(true, false));
+    }
+
+    #[test]
+    fn disabled_palette_writes_no_escape_codes() {
+        assert_eq!(Palette::new(false).paint("cut", Color::Red), "cut");
+        assert_eq!(
+            Palette::new(true).paint("cut", Color::Red),
+            "\x1b[31mcut\x1b[0m"
+        );
+        let never = ColorChoice::Never.enabled(true, false);
+        let no_color = ColorChoice::Auto.enabled(true, true);
+        for enabled in [never, no_color] {
+            assert!(!Palette::new(enabled)
+                .paint("warn", Color::Yellow)
+                .contains('\x1b'));
+        }
+    }
+
+    #[test]
+    fn choices() {
+        assert_eq!("auto".parse(), Ok(ColorChoice::Auto));
+        assert_eq!("always".parse(), Ok(ColorChoice::Always));
+        assert_eq!("never".parse(), Ok(ColorChoice::Never));
+        assert!("yes".parse::<ColorChoice>().is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Effective configuration of the `config` command.
+//!
+//! An option can be given on the command line, fall back to an environment
+//! variable, or be left to its default, and thresholds can come from the
+//! data store, a data plan, the environment, NetworkManager or a profile.
+//! Each setting is resolved with the source it came from, so that a value
+//! which is not the expected one can be traced to where it was given.
+
+use std::{env, fmt};
+
+use serde_json::{json, Value};
+use structopt::clap::ArgMatches;
+
+/// Where a setting came from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    CommandLine,
+    /// Environment variable, by name
+    Environment(String),
+    Default,
+    /// Option which is not given and has no default
+    Unset,
+    DataStore,
+    /// Thresholds derived from the data plan
+    Plan,
+    NetworkManager,
+    /// Threshold profile, by specification
+    Profile(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::CommandLine => write!(f, "command line"),
+            Origin::Environment(var) => write!(f, "environment {}", var),
+            Origin::Default => write!(f, "default"),
+            Origin::Unset => write!(f, "unset"),
+            Origin::DataStore => write!(f, "data store"),
+            Origin::Plan => write!(f, "plan"),
+            Origin::NetworkManager => write!(f, "NetworkManager"),
+            Origin::Profile(spec) => write!(f, "profile {}", spec),
+        }
+    }
+}
+
+/// Value of a setting, and its source
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setting {
+    /// Option (e.g. `--iface`) or threshold (e.g. `total_cut`)
+    pub name: String,
+    /// Value as given, or `None` for an option which is unset
+    pub value: Option<String>,
+    pub source: Origin,
+}
+
+impl Setting {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "value": self.value,
+            "source": self.source.to_string(),
+        })
+    }
+}
+
+/// Kind of value an option takes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// Given or not
+    Flag,
+    Value,
+    /// Repeatable, with a value each time it is given
+    Values,
+}
+
+/// Option of the command line, as listed by `config`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spec {
+    /// Name the option is matched by, as it is given (e.g. `data-dir`)
+    pub name: &'static str,
+    /// Long name, where it is not the name (e.g. `profile` for `profiles`)
+    pub long: Option<&'static str>,
+    pub kind: Kind,
+    /// Environment variable the option falls back to
+    pub env: Option<&'static str>,
+    /// Default value, as it would be given
+    pub default: Option<&'static str>,
+}
+
+impl Spec {
+    pub const fn flag(name: &'static str) -> Spec {
+        Spec {
+            name,
+            long: None,
+            kind: Kind::Flag,
+            env: None,
+            default: None,
+        }
+    }
+
+    pub const fn value(name: &'static str) -> Spec {
+        Spec {
+            kind: Kind::Value,
+            ..Spec::flag(name)
+        }
+    }
+
+    pub const fn values(name: &'static str) -> Spec {
+        Spec {
+            kind: Kind::Values,
+            ..Spec::flag(name)
+        }
+    }
+
+    pub const fn long(self, long: &'static str) -> Spec {
+        Spec {
+            long: Some(long),
+            ..self
+        }
+    }
+
+    pub const fn env(self, var: &'static str) -> Spec {
+        Spec {
+            env: Some(var),
+            ..self
+        }
+    }
+
+    pub const fn default(self, value: &'static str) -> Spec {
+        Spec {
+            default: Some(value),
+            ..self
+        }
+    }
+}
+
+/// Resolve options from the arguments matched against them
+///
+/// Options are listed in the order given, with their values as given rather
+/// than as parsed (e.g. `50GB`), and flags as `true` or `false`. An option
+/// which is not given on the command line comes from its variable while
+/// that is set, and otherwise from its default when it holds the default.
+pub fn options(specs: &[Spec], matches: &ArgMatches) -> Vec<Setting> {
+    specs
+        .iter()
+        .map(|spec| {
+            let value = match spec.kind {
+                Kind::Flag => Some(matches.is_present(spec.name).to_string()),
+                // the parser adds the value of the variable after those given on
+                // the command line, where only the first value is used
+                Kind::Values => matches
+                    .values_of(spec.name)
+                    .map(|values| values.collect::<Vec<_>>().join(", ")),
+                Kind::Value => matches.value_of(spec.name).map(str::to_string),
+            };
+            let default = match spec.kind {
+                Kind::Flag => Some("false"),
+                _ => spec.default,
+            };
+            let source = match spec.env {
+                _ if matches.occurrences_of(spec.name) > 0 => Origin::CommandLine,
+                Some(var) if env::var_os(var).is_some() => Origin::Environment(var.to_string()),
+                _ if value.is_some() && value.as_deref() == default => Origin::Default,
+                _ => Origin::Unset,
+            };
+            Setting {
+                name: format!("--{}", spec.long.unwrap_or(spec.name)),
+                value,
+                source,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    use structopt::clap::{App, Arg};
+
+    use crate::tests::lock_env;
+
+    const VAR: &str = "PEACH_MONITOR_CONFIG_TEST_IFACE";
+
+    /// Application with an option of each kind of `SPECS`, reading the environment as it is built
+    fn app() -> App<'static, 'static> {
+        App::new("peach-monitor")
+            .arg(Arg::with_name("verbose").long("verbose"))
+            .arg(
+                Arg::with_name("iface")
+                    .long("iface")
+                    .takes_value(true)
+                    .env(VAR)
+                    .default_value("wlan0"),
+            )
+            .arg(Arg::with_name("plan").long("plan").takes_value(true))
+            .arg(
+                Arg::with_name("alert")
+                    .long("alert")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1),
+            )
+    }
+
+    const SPECS: [Spec; 4] = [
+        Spec::flag("verbose"),
+        Spec::value("iface").env(VAR).default("wlan0"),
+        Spec::value("plan"),
+        Spec::values("alert"),
+    ];
+
+    fn settings(args: &[&str]) -> Vec<Setting> {
+        let matches = app()
+            .get_matches_from_safe(std::iter::once("peach-monitor").chain(args.iter().copied()))
+            .unwrap();
+        options(&SPECS, &matches)
+    }
+
+    fn setting(name: &str, value: Option<&str>, source: Origin) -> Setting {
+        Setting {
+            name: name.to_string(),
+            value: value.map(str::to_string),
+            source,
+        }
+    }
+
+    #[test]
+    fn options_are_listed_with_their_sources() {
+        let _env = lock_env();
+        env::remove_var(VAR);
+        assert_eq!(
+            settings(&[
+                "--verbose",
+                "--alert",
+                "rx_cut_alert",
+                "--alert",
+                "tx_cut_alert"
+            ]),
+            [
+                setting("--verbose", Some("true"), Origin::CommandLine),
+                setting("--iface", Some("wlan0"), Origin::Default),
+                setting("--plan", None, Origin::Unset),
+                setting(
+                    "--alert",
+                    Some("rx_cut_alert, tx_cut_alert"),
+                    Origin::CommandLine
+                ),
+            ]
+        );
+        assert_eq!(
+            settings(&[])[0],
+            setting("--verbose", Some("false"), Origin::Default)
+        );
+    }
+
+    #[test]
+    fn command_line_takes_precedence_over_the_environment_over_the_default() {
+        let _env = lock_env();
+        env::set_var(VAR, "wwan0");
+        let (from_env, given) = (settings(&[]), settings(&["--iface", "eth0"]));
+        env::remove_var(VAR);
+        let default = settings(&[]);
+
+        let iface = |settings: &[Setting]| settings[1].clone();
+        assert_eq!(
+            iface(&from_env),
+            setting(
+                "--iface",
+                Some("wwan0"),
+                Origin::Environment(VAR.to_string())
+            )
+        );
+        assert_eq!(
+            iface(&given),
+            setting("--iface", Some("eth0"), Origin::CommandLine)
+        );
+        assert_eq!(
+            iface(&default),
+            setting("--iface", Some("wlan0"), Origin::Default)
+        );
+    }
+
+    #[test]
+    fn settings_as_json() {
+        let setting = setting(
+            "--iface",
+            Some("eth0"),
+            Origin::Environment(VAR.to_string()),
+        );
+        assert_eq!(
+            setting.to_json(),
+            json!({
+                "name": "--iface",
+                "value": "eth0",
+                "source": format!("environment {}", VAR),
+            })
+        );
+        assert_eq!(
+            Origin::Profile("window=02:00-06:00".to_string()).to_string(),
+            "profile window=02:00-06:00"
+        );
+    }
+
+    #[test]
+    fn options_are_resolved_by_their_specs() {
+        let _env = lock_env();
+        env::remove_var(VAR);
+        let matches = app()
+            .get_matches_from_safe(["peach-monitor", "--plan", "10GB"])
+            .unwrap();
+        // a value which is not the listed default is not taken for it
+        let specs = [
+            Spec::value("iface").long("interface").default("eth0"),
+            Spec::value("plan").long("data-plan"),
+        ];
+        assert_eq!(
+            options(&specs, &matches),
+            [
+                setting("--interface", Some("wlan0"), Origin::Unset),
+                setting("--data-plan", Some("10GB"), Origin::CommandLine),
+            ]
+        );
+    }
+}
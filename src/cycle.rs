@@ -0,0 +1,193 @@
+//! Billing cycles which start on a fixed day of each month.
+//!
+//! Nothing about the cycle is stored beyond the day it starts on: the
+//! current cycle, and how far into it today is, are worked out from the
+//! date whenever they are needed. A cycle day beyond the end of a month
+//! (e.g. the 31st) starts that month's cycle on its last day instead.
+
+use chrono::{Datelike, NaiveDate};
+
+/// Billing cycle, from its first day up to (but excluding) the first day of the next
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cycle {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+/// Parse the day of the month cycles start on (1 to 31)
+pub fn parse_day(input: &str) -> Result<u32, String> {
+    match input.trim().parse::<u32>() {
+        Ok(day) if (1..=31).contains(&day) => Ok(day),
+        _ => Err(format!(
+            "Invalid cycle day: {} (expected a day of the month from 1 to 31)",
+            input
+        )),
+    }
+}
+
+/// Date a cycle starts in a month, on the cycle day or the last day of a shorter month
+fn start_in(year: i32, month: u32, day: u32) -> NaiveDate {
+    (1..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .expect("every month has a first day")
+}
+
+/// Month before or after a month, by year and month
+fn shift_month(year: i32, month: u32, forward: bool) -> (i32, u32) {
+    match (month, forward) {
+        (12, true) => (year + 1, 1),
+        (1, false) => (year - 1, 12),
+        (month, true) => (year, month + 1),
+        (month, false) => (year, month - 1),
+    }
+}
+
+impl Cycle {
+    /// The cycle which a date falls in, for cycles starting on a day of the month
+    pub fn containing(date: NaiveDate, day: u32) -> Cycle {
+        let (year, month) = (date.year(), date.month());
+        let this_month = start_in(year, month, day);
+        if date >= this_month {
+            let (year, month) = shift_month(year, month, true);
+            Cycle {
+                start: this_month,
+                end: start_in(year, month, day),
+            }
+        } else {
+            let (year, month) = shift_month(year, month, false);
+            Cycle {
+                start: start_in(year, month, day),
+                end: this_month,
+            }
+        }
+    }
+
+    /// Days of the cycle before a date, which is 0 on its first day
+    pub fn elapsed(&self, date: NaiveDate) -> i64 {
+        (date - self.start).num_days()
+    }
+
+    /// Days of the cycle from a date (included) until the next cycle starts
+    pub fn remaining(&self, date: NaiveDate) -> i64 {
+        (self.end - date).num_days()
+    }
+
+    /// Total (bytes) projected for the end of the cycle, with the usage of
+    /// the days after a date at a daily rate (bytes per day)
+    ///
+    /// The usage of the date itself is already counted in the total, so the
+    /// rate is only extended over the days of the cycle after it.
+    pub fn projection(&self, date: NaiveDate, total: u64, daily: u64) -> u64 {
+        let after = self.remaining(date) - 1;
+        if after <= 0 {
+            return total;
+        }
+        total.saturating_add(daily.saturating_mul(after as u64))
+    }
+
+    /// Combined usage (bytes) which can be spent each day from a date (included)
+    /// to the end of the cycle, to stay within a cap
+    ///
+    /// This is what remains of the cap after the usage of the days before the
+    /// date, spread over the days left, so that on the last day it is all that
+    /// remains. Once the cap has been used up, nothing remains to spend.
+    pub fn daily_budget(&self, date: NaiveDate, cap: u64, used_before: u64) -> u64 {
+        cap.saturating_sub(used_before) / self.remaining(date).max(1) as u64
+    }
+
+    /// Number of days in the cycle
+    pub fn length(&self) -> i64 {
+        (self.end - self.start).num_days()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn days_of_a_mid_month_cycle() {
+        let cycle = Cycle::containing(date(2026, 10, 14), 15);
+        assert_eq!(
+            cycle,
+            Cycle {
+                start: date(2026, 9, 15),
+                end: date(2026, 10, 15),
+            }
+        );
+        assert_eq!(cycle.length(), 30);
+        assert_eq!(cycle.elapsed(date(2026, 10, 14)), 29);
+        assert_eq!(cycle.remaining(date(2026, 10, 14)), 1);
+
+        // the cycle has just restarted
+        let cycle = Cycle::containing(date(2026, 10, 15), 15);
+        assert_eq!(cycle.start, date(2026, 10, 15));
+        assert_eq!(cycle.elapsed(date(2026, 10, 15)), 0);
+        assert_eq!(cycle.remaining(date(2026, 10, 15)), 31);
+
+        // across the end of the year
+        let cycle = Cycle::containing(date(2027, 1, 2), 15);
+        assert_eq!(
+            (cycle.start, cycle.end),
+            (date(2026, 12, 15), date(2027, 1, 15))
+        );
+        assert_eq!(
+            cycle.elapsed(date(2027, 1, 2)) + cycle.remaining(date(2027, 1, 2)),
+            31
+        );
+    }
+
+    #[test]
+    fn cycle_day_beyond_the_end_of_a_month() {
+        let cycle = Cycle::containing(date(2026, 3, 1), 31);
+        assert_eq!(
+            (cycle.start, cycle.end),
+            (date(2026, 2, 28), date(2026, 3, 31))
+        );
+        let cycle = Cycle::containing(date(2028, 2, 29), 30);
+        assert_eq!(
+            (cycle.start, cycle.end),
+            (date(2028, 2, 29), date(2028, 3, 30))
+        );
+    }
+
+    #[test]
+    fn cycle_days_are_parsed() {
+        assert_eq!(parse_day(" 15"), Ok(15));
+        assert!(parse_day("0").is_err());
+        assert!(parse_day("32").is_err());
+        assert!(parse_day("first").is_err());
+    }
+
+    #[test]
+    fn projection_extends_the_daily_rate_over_the_days_after_a_date() {
+        let cycle = Cycle::containing(date(2026, 10, 11), 1);
+        assert_eq!(cycle.remaining(date(2026, 10, 11)), 21);
+        // today's usage is in the total, so the rate covers the 20 days after it
+        assert_eq!(cycle.projection(date(2026, 10, 11), 10_500, 1000), 30_500);
+        // nothing is left to project on the last day
+        assert_eq!(cycle.projection(date(2026, 10, 31), 10_500, 1000), 10_500);
+    }
+
+    #[test]
+    fn daily_budget_across_a_cycle() {
+        let cycle = Cycle::containing(date(2026, 10, 1), 1);
+        // 31 days, 3100 bytes
+        assert_eq!(cycle.daily_budget(date(2026, 10, 1), 3100, 0), 100);
+        // on pace, the budget stays the same
+        assert_eq!(cycle.daily_budget(date(2026, 10, 11), 3100, 1000), 100);
+        // ahead of pace it shrinks, behind it grows
+        assert_eq!(cycle.daily_budget(date(2026, 10, 11), 3100, 1630), 70);
+        assert_eq!(cycle.daily_budget(date(2026, 10, 11), 3100, 100), 142);
+        // on the last day it is all that remains
+        assert_eq!(cycle.daily_budget(date(2026, 10, 31), 3100, 2500), 600);
+        // once the cap is used up there is nothing to spend
+        assert_eq!(cycle.daily_budget(date(2026, 10, 20), 3100, 3100), 0);
+        assert_eq!(cycle.daily_budget(date(2026, 10, 20), 3100, 5000), 0);
+    }
+}
@@ -0,0 +1,298 @@
+//! Live terminal dashboard of the `dash` command.
+//!
+//! The dashboard is redrawn at each interval from a `State` read from the
+//! data store. Drawing it takes two steps: `Model::build` works out what to
+//! show (a gauge per direction, a sparkline of recent rates and the raised
+//! alerts) and `Model::render` lays that out as text, so that the first
+//! step has nothing to do with the terminal.
+
+use crate::color::{Color, Palette};
+use crate::units::{format_bytes, format_rate, usage_gauge};
+use crate::window::Sample;
+
+/// Number of rates shown by the sparkline, the most recent last
+const SPARKLINE_WIDTH: usize = 40;
+
+/// Bars of the sparkline, from the lowest rate to the highest
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Total and thresholds (bytes) of a direction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Usage {
+    pub label: &'static str,
+    pub total: u64,
+    pub warn: Option<u64>,
+    pub cut: Option<u64>,
+}
+
+/// What the dashboard is drawn from, as read from the data store
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct State {
+    pub iface: String,
+    /// Local time the state was read
+    pub time: String,
+    pub usage: Vec<Usage>,
+    /// Receive and transmit rates of the last daemon poll (bytes per second)
+    pub rate: Option<(u64, u64)>,
+    pub samples: Vec<Sample>,
+    /// Alert flags which are raised
+    pub alerts: Vec<String>,
+}
+
+/// Total of a direction, and how much of its threshold it has used
+#[derive(Debug, PartialEq)]
+pub struct Gauge {
+    pub label: &'static str,
+    /// Total, and the threshold it is measured against (e.g. `12GB of 50GB`)
+    pub total: String,
+    /// Bar of the share of the threshold used, if there is one
+    pub bar: Option<String>,
+    pub color: Color,
+}
+
+/// Contents of the dashboard
+#[derive(Debug, PartialEq)]
+pub struct Model {
+    pub title: String,
+    pub gauges: Vec<Gauge>,
+    pub rate: Option<String>,
+    /// Sparkline of the combined rates of recent samples, with the highest rate
+    pub sparkline: Option<(String, String)>,
+    pub alerts: Vec<String>,
+}
+
+impl Gauge {
+    /// Measure a total against its cutoff, or its warning threshold if it has no cutoff
+    fn build(usage: &Usage) -> Gauge {
+        let exceeds = |threshold: Option<u64>| threshold.is_some_and(|bytes| usage.total > bytes);
+        let color = if exceeds(usage.cut) {
+            Color::Red
+        } else if exceeds(usage.warn) {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        let (total, bar) = match usage.cut.or(usage.warn) {
+            Some(cap) => {
+                let mut total = format!("{} of {}", format_bytes(usage.total), format_bytes(cap));
+                if let (Some(warn), Some(_)) = (usage.warn, usage.cut) {
+                    total.push_str(&format!(", warning at {}", format_bytes(warn)));
+                }
+                (total, Some(usage_gauge(usage.total, cap)))
+            }
+            None => (format_bytes(usage.total), None),
+        };
+
+        Gauge {
+            label: usage.label,
+            total,
+            bar,
+            color,
+        }
+    }
+}
+
+/// Combined rates (bytes per second) between successive samples, oldest first
+///
+/// The first sample is skipped, since the time its usage was counted over
+/// is not known, as are samples taken at the same second as the one before.
+fn sample_rates(samples: &[Sample]) -> Vec<u64> {
+    samples
+        .windows(2)
+        .filter_map(|pair| {
+            let seconds = pair[1].time.checked_sub(pair[0].time).filter(|&s| s > 0)?;
+            Some((pair[1].rx + pair[1].tx) / seconds)
+        })
+        .collect()
+}
+
+/// Draw rates as a sparkline, scaled to the highest of them
+fn sparkline(rates: &[u64]) -> String {
+    let peak = rates.iter().copied().max().unwrap_or(0);
+    rates
+        .iter()
+        .map(|&rate| {
+            let level = if peak == 0 {
+                0
+            } else {
+                (rate as u128 * (BARS.len() - 1) as u128 / peak as u128) as usize
+            };
+            BARS[level]
+        })
+        .collect()
+}
+
+impl Model {
+    /// Work out the contents of the dashboard from a state
+    pub fn build(state: &State) -> Model {
+        let rates = sample_rates(&state.samples);
+        let recent = &rates[rates.len().saturating_sub(SPARKLINE_WIDTH)..];
+        let sparkline = if recent.is_empty() {
+            None
+        } else {
+            let peak = recent.iter().copied().max().unwrap_or(0);
+            Some((sparkline(recent), format_rate(peak)))
+        };
+
+        Model {
+            title: format!("peach-monitor: {} at {}", state.iface, state.time),
+            gauges: state.usage.iter().map(Gauge::build).collect(),
+            rate: state.rate.map(|(rx, tx)| {
+                format!(
+                    "received {}, transmitted {}",
+                    format_rate(rx),
+                    format_rate(tx)
+                )
+            }),
+            sparkline,
+            alerts: state.alerts.clone(),
+        }
+    }
+
+    /// Lay out the dashboard as lines of text
+    pub fn render(&self, palette: Palette) -> String {
+        let mut lines = vec![self.title.clone(), String::new()];
+        for gauge in &self.gauges {
+            let line = match gauge.bar {
+                Some(ref bar) => format!("{:<12} {} {}", gauge.label, bar, gauge.total),
+                None => format!("{:<12} {}", gauge.label, gauge.total),
+            };
+            lines.push(palette.paint(&line, gauge.color));
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "{:<12} {}",
+            "Rate",
+            self.rate.as_deref().unwrap_or("not measured yet")
+        ));
+        lines.push(match self.sparkline {
+            Some((ref sparkline, ref peak)) => {
+                format!("{:<12} {} (peak {})", "Recent", sparkline, peak)
+            }
+            None => format!(
+                "{:<12} no samples (recorded by the daemon with --window)",
+                "Recent"
+            ),
+        });
+        lines.push(String::new());
+        if self.alerts.is_empty() {
+            lines.push(palette.paint("No alerts raised", Color::Green));
+        } else {
+            let alerts = self.alerts.join(", ");
+            lines.push(palette.paint(&format!("Raised: {}", alerts), Color::Red));
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEGABYTE: u64 = 1024 * 1024;
+
+    fn state() -> State {
+        let usage = |label, total, warn, cut| Usage {
+            label,
+            total,
+            warn,
+            cut,
+        };
+        State {
+            iface: "eth9".to_string(),
+            time: "2026-10-14 12:00".to_string(),
+            usage: vec![
+                usage(
+                    "Received",
+                    6 * MEGABYTE,
+                    Some(4 * MEGABYTE),
+                    Some(8 * MEGABYTE),
+                ),
+                usage("Transmitted", MEGABYTE, None, None),
+                usage("Total", 7 * MEGABYTE, None, Some(5 * MEGABYTE)),
+            ],
+            rate: Some((1536, 200)),
+            samples: vec![
+                Sample {
+                    time: 100,
+                    rx: 9000,
+                    tx: 0,
+                },
+                Sample {
+                    time: 110,
+                    rx: 1000,
+                    tx: 0,
+                },
+                Sample {
+                    time: 120,
+                    rx: 4000,
+                    tx: 1000,
+                },
+                Sample {
+                    time: 120,
+                    rx: 1,
+                    tx: 0,
+                },
+                Sample {
+                    time: 130,
+                    rx: 0,
+                    tx: 0,
+                },
+            ],
+            alerts: vec!["total_cut_alert".to_string()],
+        }
+    }
+
+    #[test]
+    fn model_of_a_state() {
+        let model = Model::build(&state());
+
+        assert_eq!(model.title, "peach-monitor: eth9 at 2026-10-14 12:00");
+        assert_eq!(
+            model.gauges,
+            [
+                Gauge {
+                    label: "Received",
+                    total: "6MB of 8MB, warning at 4MB".to_string(),
+                    bar: Some("[#######---] 75%".to_string()),
+                    color: Color::Yellow,
+                },
+                Gauge {
+                    label: "Transmitted",
+                    total: "1MB".to_string(),
+                    bar: None,
+                    color: Color::Green,
+                },
+                Gauge {
+                    label: "Total",
+                    total: "7MB of 5MB".to_string(),
+                    bar: Some("[##########] 100%".to_string()),
+                    color: Color::Red,
+                },
+            ]
+        );
+        assert_eq!(
+            model.rate.as_deref(),
+            Some("received 1.5KB/s, transmitted 200B/s")
+        );
+        // the first sample, and the one taken at the same second as the one before, give no rate
+        assert_eq!(
+            model.sparkline,
+            Some(("▂█▁".to_string(), "500B/s".to_string()))
+        );
+        assert_eq!(model.alerts, ["total_cut_alert"]);
+    }
+
+    #[test]
+    fn model_of_an_empty_state() {
+        let model = Model::build(&State::default());
+        assert!(model.gauges.is_empty());
+        assert_eq!(model.rate, None);
+        assert_eq!(model.sparkline, None);
+
+        let rendered = model.render(Palette::new(false));
+        assert!(rendered.contains("Rate         not measured yet"));
+        assert!(rendered.ends_with("No alerts raised"));
+    }
+}
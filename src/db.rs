@@ -0,0 +1,136 @@
+//! SQLite storage of usage samples.
+//!
+//! The nest data store holds the current totals and flags, while this
+//! database keeps every sample so that history can be queried efficiently.
+//! Only compiled with the `sqlite` feature.
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection};
+
+use crate::sink::{Record, Sink};
+
+/// Schema of the samples table
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS samples (
+    ts INTEGER NOT NULL,
+    iface TEXT NOT NULL,
+    rx INTEGER NOT NULL,
+    tx INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS samples_ts ON samples (ts);";
+
+/// Connection to the samples database
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// Open the database at a path, creating it and its schema if needed
+    pub fn open(path: &Path) -> rusqlite::Result<Database> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Database { conn })
+    }
+
+    /// Insert the usage (bytes) counted for an interface at a time (unix seconds)
+    pub fn insert(&self, ts: u64, iface: &str, rx: u64, tx: u64) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO samples (ts, iface, rx, tx) VALUES (?1, ?2, ?3, ?4)",
+            params![ts as i64, iface, rx as i64, tx as i64],
+        )?;
+
+        Ok(())
+    }
+
+    /// Usage (bytes) per local date, oldest first
+    pub fn daily(&self) -> rusqlite::Result<Vec<(String, u64, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date(ts, 'unixepoch', 'localtime') AS day, SUM(rx), SUM(tx)
+             FROM samples GROUP BY day ORDER BY day",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)? as u64,
+                row.get::<_, i64>(2)? as u64,
+            ))
+        })?;
+
+        rows.collect()
+    }
+}
+
+/// Database recording the usage counted by each save, as a backend
+pub struct Samples {
+    path: PathBuf,
+}
+
+impl Samples {
+    pub fn new(path: &Path) -> Samples {
+        Samples {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl Sink for Samples {
+    fn name(&self) -> String {
+        format!("sqlite:{}", self.path.display())
+    }
+
+    /// Open the database and insert the usage as a sample
+    fn write(&self, record: &Record) -> Result<(), String> {
+        Database::open(&self.path)
+            .and_then(|db| db.insert(record.time, record.iface, record.rx, record.tx))
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::scratch_dir;
+
+    #[test]
+    fn schema_is_created_once() {
+        let path = scratch_dir("db-schema").join("samples.db");
+        Database::open(&path).unwrap();
+        let db = Database::open(&path).unwrap();
+        let mut names: Vec<String> = db
+            .conn
+            .prepare("SELECT name FROM sqlite_master ORDER BY name")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        names.sort();
+        assert_eq!(names, vec!["samples", "samples_ts"]);
+    }
+
+    #[test]
+    fn samples_round_trip() {
+        let path = scratch_dir("db-round-trip").join("samples.db");
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.daily().unwrap(), vec![]);
+
+        let samples = Samples::new(&path);
+        let time = 1_760_000_000;
+        samples
+            .write(&Record {
+                time,
+                iface: "eth0",
+                rx: 1000,
+                tx: 200,
+            })
+            .unwrap();
+        db.insert(time + 1, "eth0", 3000, 400).unwrap();
+
+        let daily = db.daily().unwrap();
+        assert_eq!(daily.len(), 1);
+        assert_eq!((daily[0].1, daily[0].2), (4000, 600));
+        assert_eq!(samples.name(), format!("sqlite:{}", path.display()));
+    }
+}
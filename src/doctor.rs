@@ -0,0 +1,96 @@
+//! Findings of the `doctor` command.
+//!
+//! Each check of the configuration and data store yields a finding which
+//! passes, warns of something likely to be unintended or fails outright.
+//! The findings are printed as a checklist followed by an overall verdict.
+
+use std::fmt;
+
+/// Outcome of a check
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Pass => "pass",
+            Outcome::Warn => "warn",
+            Outcome::Fail => "fail",
+        }
+    }
+}
+
+/// Result of a check, with a description of what was found
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub check: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Finding {
+    pub fn pass(check: &'static str, detail: impl Into<String>) -> Finding {
+        Finding {
+            check,
+            outcome: Outcome::Pass,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn warn(check: &'static str, detail: impl Into<String>) -> Finding {
+        Finding {
+            check,
+            outcome: Outcome::Warn,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn fail(check: &'static str, detail: impl Into<String>) -> Finding {
+        Finding {
+            check,
+            outcome: Outcome::Fail,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    /// Line of the checklist, e.g. `[warn] Thresholds: none are set`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {}",
+            self.outcome.as_str(),
+            self.check,
+            self.detail
+        )
+    }
+}
+
+/// Overall verdict on a set of findings, e.g. `1 failure, 2 warnings`
+pub fn verdict(findings: &[Finding]) -> String {
+    let count = |outcome| {
+        findings
+            .iter()
+            .filter(|finding| finding.outcome == outcome)
+            .count()
+    };
+    let plural = |count: usize, noun: &str| match count {
+        1 => format!("1 {}", noun),
+        _ => format!("{} {}s", count, noun),
+    };
+    match (count(Outcome::Fail), count(Outcome::Warn)) {
+        (0, 0) => "healthy".to_string(),
+        (0, warnings) => plural(warnings, "warning"),
+        (failures, 0) => plural(failures, "failure"),
+        (failures, warnings) => format!(
+            "{}, {}",
+            plural(failures, "failure"),
+            plural(warnings, "warning")
+        ),
+    }
+}
@@ -0,0 +1,139 @@
+//! Flushing of data store writes to the storage medium.
+//!
+//! Writes to the data store only reach the kernel's page cache, which
+//! writes them out in its own time; a power cut before then loses them. The
+//! files can instead be flushed (`fsync`) after every poll, which is slow on
+//! flash storage and wears it, or at a longer interval, which bounds the
+//! usage a power cut can lose while the writes of the polls between flushes
+//! are left to the page cache to combine.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::integrity::file_path;
+use crate::keys::{FILES, NET};
+
+/// When data store writes are flushed to the storage medium
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Durability {
+    /// Flush after every poll
+    Sync,
+    /// Flush at an interval, and when the daemon stops
+    Lazy,
+}
+
+impl FromStr for Durability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Durability, String> {
+        match s {
+            "sync" => Ok(Durability::Sync),
+            "lazy" => Ok(Durability::Lazy),
+            _ => Err(format!("Unknown durability: {}", s)),
+        }
+    }
+}
+
+/// Flush the files of the data store, and the directory holding them, to the storage medium
+///
+/// Files which have not been written yet are skipped.
+pub fn flush(data_dir: &Path) -> io::Result<()> {
+    for file in &FILES {
+        let path = file_path(data_dir, file);
+        if path.exists() {
+            File::open(&path)?.sync_all()?;
+        }
+    }
+    // the directory entries of files created since the last flush
+    File::open(data_dir.join(NET))?.sync_all()
+}
+
+/// Decides when the daemon flushes the data store
+#[derive(Debug)]
+pub struct Flusher {
+    durability: Durability,
+    interval: Duration,
+    last: Instant,
+}
+
+impl Flusher {
+    pub fn new(durability: Durability, interval: Duration, now: Instant) -> Flusher {
+        Flusher {
+            durability,
+            interval,
+            last: now,
+        }
+    }
+
+    /// Whether the writes of a poll made at a time should be flushed, which
+    /// restarts the interval if they should
+    pub fn due(&mut self, now: Instant) -> bool {
+        let due = match self.durability {
+            Durability::Sync => true,
+            Durability::Lazy => now.duration_since(self.last) >= self.interval,
+        };
+        if due {
+            self.last = now;
+        }
+
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_flushes_every_poll() {
+        let start = Instant::now();
+        let mut flusher = Flusher::new(Durability::Sync, Duration::from_secs(300), start);
+        for secs in &[0, 1, 2, 60] {
+            assert!(flusher.due(start + Duration::from_secs(*secs)), "{}", secs);
+        }
+    }
+
+    #[test]
+    fn lazy_flushes_once_an_interval() {
+        let start = Instant::now();
+        let mut flusher = Flusher::new(Durability::Lazy, Duration::from_secs(300), start);
+        // the polls in between are batched into the next flush
+        for &(secs, due) in &[
+            (60, false),
+            (299, false),
+            (300, true),
+            (360, false),
+            (600, true),
+        ] {
+            assert_eq!(
+                flusher.due(start + Duration::from_secs(secs)),
+                due,
+                "{}",
+                secs
+            );
+        }
+    }
+
+    #[test]
+    fn written_files_are_flushed() {
+        let dir = crate::tests::scratch_dir("flush");
+        let store = nest::Store::new(&dir, crate::cli::store_schema().unwrap());
+        store
+            .set(&[NET, crate::keys::TRAFFIC, "rx"], &nest::Value::Uint(5000))
+            .unwrap();
+        flush(&dir).unwrap();
+
+        // a data directory which holds no data store cannot be flushed
+        assert!(flush(&dir.join("missing")).is_err());
+    }
+
+    #[test]
+    fn durabilities_are_parsed() {
+        assert_eq!("sync".parse(), Ok(Durability::Sync));
+        assert_eq!("lazy".parse(), Ok(Durability::Lazy));
+        assert!("eventual".parse::<Durability>().is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! Parsing of human-readable durations.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Duration units and their lengths (seconds)
+const UNITS: [(&str, u64); 5] = [
+    ("w", 7 * 24 * 60 * 60),
+    ("d", 24 * 60 * 60),
+    ("h", 60 * 60),
+    ("m", 60),
+    ("s", 1),
+];
+
+/// Error returned when a duration string cannot be parsed
+#[derive(Debug)]
+pub struct ParseDurationError(String);
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid duration: {} (expected a whole number with an s, m, h, d or w suffix)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+/// Parse a human-readable duration (e.g. `60d`, `24h`, `90s`)
+pub fn parse_duration(input: &str) -> Result<Duration, ParseDurationError> {
+    let err = || ParseDurationError(input.to_string());
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(err)?;
+    let (number, unit) = trimmed.split_at(split);
+    let seconds = UNITS
+        .iter()
+        .find(|(name, _)| *name == unit.trim())
+        .map(|(_, seconds)| *seconds)
+        .ok_or_else(err)?;
+
+    let value: u64 = number.parse().map_err(|_| err())?;
+    value
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_with_units() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("24h").unwrap(), Duration::from_secs(86400));
+        assert_eq!(
+            parse_duration("60d").unwrap(),
+            Duration::from_secs(60 * 86400)
+        );
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            Duration::from_secs(14 * 86400)
+        );
+        assert_eq!(
+            parse_duration(" 3 h ").unwrap(),
+            Duration::from_secs(3 * 3600)
+        );
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for input in &["", "60", "d", "1.5h", "-1d", "5y", "99999999999999999999w"] {
+            assert!(parse_duration(input).is_err(), "{:?} parsed", input);
+        }
+    }
+}
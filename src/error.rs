@@ -1,15 +1,126 @@
 //! Basic error handling for network and nest.
 
-use std::error;
+use std::path::PathBuf;
+use std::{error, fmt, io};
 
 pub type BoxError = Box<dyn error::Error>;
 
 #[derive(Debug)]
 pub enum NetworkError {
+    /// Network statistics could not be read
+    Read(io::Error),
+    /// Network statistics contained a line which could not be parsed
+    Malformed { line: String },
+    /// The requested interface is not present in the network statistics
+    Interface { iface: String },
+    /// The stats command could not be run
+    Command { command: String, source: io::Error },
+    /// The stats command failed or printed output which could not be parsed
+    Output { command: String, reason: String },
+    /// The counters could not be read from an SNMP agent
+    Snmp { host: String, reason: String },
+    /// The class counters could not be read from nftables
+    Nft { table: String, reason: String },
+}
 
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetworkError::Read(ref err) => {
+                write!(f, "Failed to read network statistics: {}", err)
+            }
+            NetworkError::Malformed { ref line } => {
+                write!(f, "Malformed network statistics: {}", line.trim())
+            }
+            NetworkError::Interface { ref iface } => {
+                write!(f, "No network statistics found for interface {}", iface)
+            }
+            NetworkError::Command {
+                ref command,
+                ref source,
+            } => write!(f, "Failed to run stats command `{}`: {}", command, source),
+            NetworkError::Output {
+                ref command,
+                ref reason,
+            } => write!(f, "Stats command `{}` failed: {}", command, reason),
+            NetworkError::Snmp {
+                ref host,
+                ref reason,
+            } => write!(
+                f,
+                "Failed to read counters from SNMP agent {}: {}",
+                host, reason
+            ),
+            NetworkError::Nft {
+                ref table,
+                ref reason,
+            } => write!(
+                f,
+                "Failed to read counters from nftables table {}: {}",
+                table, reason
+            ),
+        }
+    }
 }
 
+impl error::Error for NetworkError {}
+
 #[derive(Debug)]
 pub enum NestError {
+    /// The data directory could not be created
+    DataDirectory { source: io::Error },
+    /// The data directory cannot be written to
+    NotWritable { path: PathBuf, source: io::Error },
+    /// An operation on the data store failed
+    Store(Box<nest::Error>),
+    /// Files of the data store exist but cannot be read
+    Corrupt { paths: Vec<PathBuf> },
+    /// Values in the data store have an unexpected type (strict mode)
+    UnexpectedTypes { mismatches: Vec<String> },
+    /// Thresholds are stored under keys which are not read as expected
+    KeyMismatch { problems: Vec<String> },
+}
+
+impl fmt::Display for NestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NestError::DataDirectory { ref source } => {
+                write!(f, "Failed to create data directory: {}", source)
+            }
+            NestError::NotWritable {
+                ref path,
+                ref source,
+            } => write!(
+                f,
+                "Data directory is not writable: {}: {}",
+                path.display(),
+                source
+            ),
+            NestError::Store(ref err) => write!(f, "Data store error: {}", err),
+            NestError::Corrupt { ref paths } => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(
+                    f,
+                    "Data store is corrupt: {} (run with --repair to back up and reinitialise)",
+                    paths.join(", ")
+                )
+            }
+            NestError::UnexpectedTypes { ref mismatches } => write!(
+                f,
+                "Unexpected value types in the data store: {}",
+                mismatches.join("; ")
+            ),
+            NestError::KeyMismatch { ref problems } => {
+                write!(f, "Threshold keys do not match: {}", problems.join("; "))
+            }
+        }
+    }
+}
+
+impl error::Error for NestError {}
 
+impl From<nest::Error> for NestError {
+    fn from(err: nest::Error) -> NestError {
+        NestError::Store(Box::new(err))
+    }
 }
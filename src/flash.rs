@@ -0,0 +1,109 @@
+//! Detection of frequent data store writes to flash storage.
+//!
+//! SD cards and eMMC modules wear out after a limited number of writes, so
+//! polling at short intervals on a device which boots from flash can shorten
+//! its life. This is only used to warn the user; writes are never limited.
+
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Seconds in a day
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Approximate number of file writes made by each poll
+///
+/// Every key set in the data store rewrites its json file: the totals and
+/// counters in `traffic.json` and the alert flags in `alert.json`.
+const WRITES_PER_POLL: u64 = 15;
+
+/// Number of writes per day above which flash storage is considered at risk
+pub const FREQUENT_WRITES_PER_DAY: u64 = 5000;
+
+/// How the storage holding the data store is identified
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Storage {
+    /// Detect flash storage from the block device holding the data store
+    Auto,
+    /// Treat the data store as being on flash storage
+    Flash,
+    /// Treat the data store as not being on flash storage
+    Other,
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Storage, String> {
+        match s {
+            "auto" => Ok(Storage::Auto),
+            "flash" => Ok(Storage::Flash),
+            "other" => Ok(Storage::Other),
+            _ => Err(format!("Unknown storage: {}", s)),
+        }
+    }
+}
+
+/// Estimate the number of file writes per day made when polling at an interval (seconds)
+pub fn writes_per_day(interval: u64) -> u64 {
+    (SECS_PER_DAY / interval.max(1)) * WRITES_PER_POLL
+}
+
+/// Name of the disk (e.g. `mmcblk0`) holding a path
+///
+/// Partitions are resolved to their parent disk so that paths on separate
+/// partitions of the same card compare equal.
+fn disk(path: &Path) -> Option<String> {
+    let dev = fs::metadata(path).ok()?.dev();
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let sys = fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+    let disk = if sys.join("partition").exists() {
+        sys.parent()?
+    } else {
+        &sys
+    };
+
+    disk.file_name()?.to_str().map(str::to_string)
+}
+
+/// Whether a path is on the same SD card or eMMC module as the root filesystem
+pub fn on_root_flash(path: &Path, storage: Storage) -> bool {
+    match storage {
+        Storage::Flash => true,
+        Storage::Other => false,
+        Storage::Auto => match (disk(path), disk(Path::new("/"))) {
+            (Some(data), Some(root)) => data == root && data.starts_with("mmcblk"),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_frequency_at_intervals() {
+        assert_eq!(writes_per_day(60), 1440 * WRITES_PER_POLL);
+        assert_eq!(writes_per_day(3600), 24 * WRITES_PER_POLL);
+        assert_eq!(writes_per_day(SECS_PER_DAY), WRITES_PER_POLL);
+        // an interval of zero is taken to be a second
+        assert_eq!(writes_per_day(0), writes_per_day(1));
+    }
+
+    #[test]
+    fn frequent_writes_threshold() {
+        // the default interval of a minute is frequent, an interval of five minutes is not
+        assert!(writes_per_day(60) > FREQUENT_WRITES_PER_DAY);
+        assert!(writes_per_day(300) < FREQUENT_WRITES_PER_DAY);
+    }
+
+    #[test]
+    fn storage_given_explicitly() {
+        let path = Path::new("/");
+        assert!(on_root_flash(path, Storage::Flash));
+        assert!(!on_root_flash(path, Storage::Other));
+    }
+}
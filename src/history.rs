@@ -0,0 +1,411 @@
+//! Daily usage history.
+//!
+//! Usage is recorded per local date in `net/history`, with each entry
+//! holding the bytes received and transmitted that day (e.g.
+//! `"2020-09-14": { "rx": 1024, "tx": 512 }`). Entries may also hold a
+//! snapshot of the thresholds (megabytes) in effect that day, by name (e.g.
+//! `"thresholds": { "total_cut": 51200 }`).
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use indexmap::IndexMap;
+use nest::{Store, Value};
+
+use crate::error::NestError;
+use crate::keys::{HISTORY, NET};
+
+/// Format of the date keys of history entries
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Key of the thresholds snapshot of an entry
+const THRESHOLDS: &str = "thresholds";
+
+/// Usage on a single day (bytes)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Day {
+    pub rx: u64,
+    pub tx: u64,
+    /// Thresholds (megabytes) in effect that day, if recorded
+    pub thresholds: Vec<(String, u64)>,
+}
+
+impl Day {
+    fn from_value(value: &Value) -> Day {
+        let entry = value.as_object();
+        let counter = |key| match entry.and_then(|day| day.get(key)) {
+            Some(Value::Uint(val)) => *val,
+            _ => 0,
+        };
+        let thresholds = match entry.and_then(|day| day.get(THRESHOLDS)) {
+            Some(Value::Object(thresholds)) => thresholds
+                .iter()
+                .filter_map(|(name, val)| match val {
+                    Value::Uint(val) => Some((name.clone(), *val)),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Day {
+            rx: counter("rx"),
+            tx: counter("tx"),
+            thresholds,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        let mut day = IndexMap::new();
+        day.insert("rx".to_string(), Value::Uint(self.rx));
+        day.insert("tx".to_string(), Value::Uint(self.tx));
+        if !self.thresholds.is_empty() {
+            let thresholds = self
+                .thresholds
+                .iter()
+                .map(|(name, val)| (name.clone(), Value::Uint(*val)))
+                .collect();
+            day.insert(THRESHOLDS.to_string(), Value::Object(thresholds));
+        }
+        Value::Object(day)
+    }
+}
+
+/// Usage history, ordered by date
+pub type History = BTreeMap<NaiveDate, Day>;
+
+/// Retrieve the usage history from the data store
+///
+/// Entries whose keys are not valid dates are skipped.
+pub fn load(store: &Store) -> History {
+    match store.get(&[NET, HISTORY]) {
+        Ok(Value::Object(entries)) => entries
+            .iter()
+            .filter_map(|(date, day)| {
+                NaiveDate::parse_from_str(date, DATE_FORMAT)
+                    .ok()
+                    .map(|date| (date, Day::from_value(day)))
+            })
+            .collect(),
+        _ => History::new(),
+    }
+}
+
+/// Replace the usage history in the data store
+fn save(store: &Store, history: &History) -> Result<(), NestError> {
+    let entries = history
+        .iter()
+        .map(|(date, day)| (date.format(DATE_FORMAT).to_string(), day.to_value()))
+        .collect();
+    store.set(&[NET, HISTORY], &Value::Object(entries))?;
+
+    Ok(())
+}
+
+/// Add usage (bytes) to the history entry for a date
+///
+/// The first record of each date marks a day boundary, at which entries
+/// older than `retention` days are pruned (a retention of 0 keeps the
+/// history indefinitely). Given thresholds replace the snapshot of the
+/// entry, so that it holds those in effect at its last record. Returns
+/// whether the record began a new day.
+pub fn record(
+    store: &Store,
+    date: NaiveDate,
+    rx: u64,
+    tx: u64,
+    retention: u32,
+    thresholds: Option<&[(String, u64)]>,
+) -> Result<bool, NestError> {
+    let key = date.format(DATE_FORMAT).to_string();
+    let (day, new_day) = match store.get(&[NET, HISTORY, &key]) {
+        Ok(value @ Value::Object(_)) => (Day::from_value(&value), false),
+        _ => (Day::default(), true),
+    };
+
+    // avoid rewriting the history when there is nothing to add
+    if !new_day && rx == 0 && tx == 0 {
+        return Ok(false);
+    }
+
+    let day = Day {
+        rx: day.rx + rx,
+        tx: day.tx + tx,
+        thresholds: thresholds.map_or(day.thresholds, <[_]>::to_vec),
+    };
+    store.set(&[NET, HISTORY, &key], &day.to_value())?;
+
+    if new_day && retention > 0 {
+        prune(
+            store,
+            date - chrono::Duration::days(retention.into()),
+            false,
+        )?;
+    }
+
+    Ok(new_day)
+}
+
+/// Average combined usage (bytes) of the days before a date
+///
+/// Only the entries of the `days` days before the date are averaged, so that
+/// the baseline follows changes in usage; `None` is returned if there are none.
+pub fn baseline(history: &History, date: NaiveDate, days: u32) -> Option<u64> {
+    let start = date - chrono::Duration::days(days.into());
+    let usage: Vec<u64> = history
+        .range(start..date)
+        .map(|(_, day)| day.rx + day.tx)
+        .collect();
+    if usage.is_empty() {
+        return None;
+    }
+
+    Some(usage.iter().sum::<u64>() / usage.len() as u64)
+}
+
+/// Ratio of transmitted to received traffic over the days before a date
+///
+/// As for the baseline, only the entries of the `days` days before the date
+/// count. `None` is returned if they received nothing, which leaves the
+/// ratio undefined.
+pub fn upload_ratio(history: &History, date: NaiveDate, days: u32) -> Option<f64> {
+    let start = date - chrono::Duration::days(days.into());
+    let (rx, tx) = history
+        .range(start..date)
+        .fold((0, 0), |(rx, tx), (_, day)| (rx + day.rx, tx + day.tx));
+
+    ratio(rx, tx)
+}
+
+/// Ratio of transmitted to received traffic, if anything was received
+pub fn ratio(rx: u64, tx: u64) -> Option<f64> {
+    if rx == 0 {
+        return None;
+    }
+
+    Some(tx as f64 / rx as f64)
+}
+
+/// Exponential moving average of daily usage (bytes), updated with the usage of a day
+///
+/// The day weighs `alpha` of the new average, and the first day is taken as
+/// the average.
+pub fn ema_step(average: Option<u64>, usage: u64, alpha: f64) -> u64 {
+    match average {
+        Some(average) => {
+            let average = average as f64;
+            (average + alpha * (usage as f64 - average))
+                .round()
+                .max(0.0) as u64
+        }
+        None => usage,
+    }
+}
+
+/// Fold the combined usage of the days before a date into a moving average
+///
+/// `state` holds the average and the last day already folded into it; only
+/// the entries after that day are folded, oldest first, so that updating
+/// the average again on the same date changes nothing. Returns the new state,
+/// or `None` if there is neither an earlier average nor a day to fold.
+pub fn update_ema(
+    history: &History,
+    state: Option<(u64, NaiveDate)>,
+    date: NaiveDate,
+    alpha: f64,
+) -> Option<(u64, NaiveDate)> {
+    history
+        .range(..date)
+        .filter(|(day, _)| state.is_none_or(|(_, last)| **day > last))
+        .fold(state, |state, (day, usage)| {
+            let average = ema_step(
+                state.map(|(average, _)| average),
+                usage.rx + usage.tx,
+                alpha,
+            );
+            Some((average, *day))
+        })
+}
+
+/// Dates of the history entries older than a cutoff date
+pub fn older_than(history: &History, cutoff: NaiveDate) -> Vec<NaiveDate> {
+    history.range(..cutoff).map(|(date, _)| *date).collect()
+}
+
+/// Remove history entries older than a cutoff date
+///
+/// Returns the dates of the entries removed (or which would be removed, for
+/// a dry run) and the number of entries remaining.
+pub fn prune(
+    store: &Store,
+    cutoff: NaiveDate,
+    dry_run: bool,
+) -> Result<(Vec<NaiveDate>, usize), NestError> {
+    let mut history = load(store);
+    let pruned = older_than(&history, cutoff);
+    let remaining = history.len() - pruned.len();
+
+    if !dry_run && !pruned.is_empty() {
+        history = history.split_off(&cutoff);
+        save(store, &history)?;
+    }
+
+    Ok((pruned, remaining))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::scratch_store;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    /// Store holding a day of usage on each of the given days of October
+    fn store_with_days(name: &str, days: &[u32]) -> Store {
+        let store = scratch_store(name);
+        for &day in days {
+            record(&store, date(day), 1000 * day as u64, 10, 0, None).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn dry_run_prunes_nothing() {
+        let store = store_with_days("history-dry-run", &[1, 2, 3, 10, 11]);
+        let (pruned, remaining) = prune(&store, date(10), true).unwrap();
+        assert_eq!(pruned, [date(1), date(2), date(3)]);
+        assert_eq!(remaining, 2);
+        assert_eq!(load(&store).len(), 5);
+    }
+
+    #[test]
+    fn prune_removes_the_older_entries() {
+        let store = store_with_days("history-prune", &[1, 2, 3, 10, 11]);
+        let (pruned, remaining) = prune(&store, date(3), false).unwrap();
+        assert_eq!(pruned, [date(1), date(2)]);
+        assert_eq!(remaining, 3);
+        let dates: Vec<NaiveDate> = load(&store).keys().copied().collect();
+        assert_eq!(dates, [date(3), date(10), date(11)]);
+    }
+
+    #[test]
+    fn records_add_up_within_a_day() {
+        let store = scratch_store("history-record");
+        assert!(record(&store, date(5), 100, 10, 0, None).unwrap());
+        assert!(!record(&store, date(5), 50, 5, 0, None).unwrap());
+        assert!(record(&store, date(6), 1, 1, 0, None).unwrap());
+        let history = load(&store);
+        assert_eq!((history[&date(5)].rx, history[&date(5)].tx), (150, 15));
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn retention_prunes_at_the_day_boundary() {
+        let store = store_with_days("history-retention", &[1, 2, 3]);
+        record(&store, date(4), 0, 0, 2, None).unwrap();
+        let dates: Vec<NaiveDate> = load(&store).keys().copied().collect();
+        assert_eq!(dates, [date(2), date(3), date(4)]);
+    }
+
+    #[test]
+    fn baseline_of_the_days_before() {
+        let history = load(&store_with_days("history-baseline", &[1, 5, 8, 9]));
+        assert_eq!(baseline(&history, date(10), 2), Some((8010 + 9010) / 2));
+        assert_eq!(
+            baseline(&history, date(10), 5),
+            Some((5010 + 8010 + 9010) / 3)
+        );
+        // the day itself is not part of its baseline
+        assert_eq!(baseline(&history, date(9), 1), Some(8010));
+        assert_eq!(baseline(&history, date(5), 3), None);
+    }
+
+    fn series(usage: &[u64]) -> History {
+        usage
+            .iter()
+            .enumerate()
+            .map(|(i, &rx)| {
+                let day = Day {
+                    rx,
+                    tx: 0,
+                    thresholds: Vec::new(),
+                };
+                (date(i as u32 + 1), day)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn moving_average_of_a_daily_series() {
+        let mut average = None;
+        let mut averages = Vec::new();
+        for &usage in &[1000, 2000, 0, 1000] {
+            average = Some(ema_step(average, usage, 0.3));
+            averages.extend(average);
+        }
+        assert_eq!(averages, [1000, 1300, 910, 937]);
+        assert_eq!(ema_step(Some(100), 400, 1.0), 400);
+        assert_eq!(ema_step(Some(100), 400, 0.0), 100);
+    }
+
+    #[test]
+    fn moving_average_folds_each_day_once() {
+        let history = series(&[100, 200, 0, 400]);
+        // the day of the date itself is not over, and is left out
+        assert_eq!(
+            update_ema(&history, None, date(4), 0.5),
+            Some((75, date(3)))
+        );
+        assert_eq!(
+            update_ema(&history, None, date(5), 0.5),
+            Some((238, date(4)))
+        );
+
+        // days already folded are not folded again
+        let state = update_ema(&history, None, date(3), 0.5);
+        assert_eq!(state, Some((150, date(2))));
+        assert_eq!(update_ema(&history, state, date(3), 0.5), state);
+        assert_eq!(
+            update_ema(&history, state, date(5), 0.5),
+            Some((238, date(4)))
+        );
+
+        assert_eq!(update_ema(&History::new(), None, date(5), 0.5), None);
+        assert_eq!(update_ema(&History::new(), state, date(5), 0.5), state);
+    }
+
+    fn day(rx: u64, tx: u64) -> Day {
+        Day {
+            rx,
+            tx,
+            thresholds: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn upload_ratio_of_the_days_before_a_date() {
+        let history: History = vec![
+            (date(1), day(1000, 9000)),
+            (date(2), day(1000, 100)),
+            (date(3), day(3000, 300)),
+            (date(4), day(1000, 5000)),
+        ]
+        .into_iter()
+        .collect();
+
+        // the day itself and those before the window are left out
+        assert_eq!(upload_ratio(&history, date(4), 2), Some(0.1));
+        assert_eq!(upload_ratio(&history, date(4), 3), Some(9400.0 / 5000.0));
+        assert_eq!(upload_ratio(&history, date(1), 7), None);
+        assert_eq!(upload_ratio(&history, date(20), 7), None);
+    }
+
+    #[test]
+    fn ratio_is_undefined_without_received_traffic() {
+        assert_eq!(ratio(2000, 500), Some(0.25));
+        assert_eq!(ratio(2000, 0), Some(0.0));
+        assert_eq!(ratio(0, 500), None);
+    }
+}
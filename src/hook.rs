@@ -0,0 +1,81 @@
+//! Detection of alert flag changes and the environment passed to alert scripts.
+
+/// Flags which changed between two readings of the alert flags
+///
+/// A flag missing from the earlier reading is treated as unset, so that a
+/// flag raised for the first time counts as a rising edge.
+pub fn edges<'a>(before: &[(&str, bool)], after: &[(&'a str, bool)]) -> Vec<(&'a str, bool)> {
+    after
+        .iter()
+        .filter(|(flag, value)| {
+            let previous = before
+                .iter()
+                .find(|(name, _)| name == flag)
+                .is_some_and(|(_, previous)| *previous);
+            previous != *value
+        })
+        .copied()
+        .collect()
+}
+
+/// Environment passed to the alert script for a flag change
+pub fn alert_env(
+    flag: &str,
+    value: bool,
+    iface: &str,
+    rx: u64,
+    tx: u64,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("PM_FLAG", flag.to_string()),
+        ("PM_VALUE", value.to_string()),
+        ("PM_IFACE", iface.to_string()),
+        ("PM_RX", rx.to_string()),
+        ("PM_TX", tx.to_string()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_flags_are_edges() {
+        let before = [("rx_warn_alert", true), ("rx_cut_alert", false)];
+        let after = [
+            ("rx_warn_alert", true),
+            ("rx_cut_alert", true),
+            ("tx_warn_alert", false),
+        ];
+        assert_eq!(edges(&before, &after), [("rx_cut_alert", true)]);
+    }
+
+    #[test]
+    fn falling_edges() {
+        let before = [("total_cut_alert", true)];
+        let after = [("total_cut_alert", false)];
+        assert_eq!(edges(&before, &after), [("total_cut_alert", false)]);
+    }
+
+    #[test]
+    fn flag_raised_for_the_first_time_is_a_rising_edge() {
+        assert_eq!(
+            edges(&[], &[("anomaly_alert", true), ("budget_over", false)]),
+            [("anomaly_alert", true)]
+        );
+    }
+
+    #[test]
+    fn environment_of_a_change() {
+        assert_eq!(
+            alert_env("tx_cut_alert", true, "wlan0", 1234, 567),
+            [
+                ("PM_FLAG", "tx_cut_alert".to_string()),
+                ("PM_VALUE", "true".to_string()),
+                ("PM_IFACE", "wlan0".to_string()),
+                ("PM_RX", "1234".to_string()),
+                ("PM_TX", "567".to_string()),
+            ]
+        );
+    }
+}
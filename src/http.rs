@@ -0,0 +1,215 @@
+//! Minimal HTTP support for exposing the monitor state and calling webhooks.
+//!
+//! Only `GET` requests are answered, one connection at a time, which is
+//! plenty for a scraper or dashboard polling every few seconds. Requests
+//! can also be posted to plain `http://` URLs.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::{debug, warn};
+
+/// Body and content type of a successful response
+pub struct Response {
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+/// Listen on an address and answer requests on a background thread
+///
+/// The handler receives the request path and returns `None` for paths it
+/// does not serve, which are answered with `404 Not Found`.
+pub fn serve<F>(addr: SocketAddr, handler: F) -> io::Result<JoinHandle<()>>
+where
+    F: Fn(&str) -> Option<Response> + Send + 'static,
+{
+    let listener = TcpListener::bind(addr)?;
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| respond(stream, &handler));
+            if let Err(e) = result {
+                warn!("Failed to answer HTTP request on {}: {}", addr, e);
+            }
+        }
+    }))
+}
+
+/// Read a request from a connection and write the response
+fn respond<F>(mut stream: TcpStream, handler: &F) -> io::Result<()>
+where
+    F: Fn(&str) -> Option<Response>,
+{
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // discard the headers, which are not used
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    debug!("HTTP request: {}", request_line.trim_end());
+    let mut parts = request_line.split_whitespace();
+    let (status, response) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => {
+            // ignore any query string
+            let path = target.split('?').next().unwrap_or(target);
+            match handler(path) {
+                Some(response) => ("200 OK", response),
+                None => ("404 Not Found", text("Not found\n")),
+            }
+        }
+        (Some(_), Some(_)) => ("405 Method Not Allowed", text("Method not allowed\n")),
+        _ => ("400 Bad Request", text("Bad request\n")),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// Plain text response body
+fn text(body: &str) -> Response {
+    Response {
+        content_type: "text/plain; charset=utf-8",
+        body: body.to_string(),
+    }
+}
+
+/// Time allowed for connecting to, writing to and reading from a server
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Host, port and path of an `http://` URL
+pub fn parse_url(url: &str) -> Result<(String, u16, String), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Unsupported URL: {} (only http:// URLs are supported)", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| format!("Invalid port in URL: {}", url))?,
+        ),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(format!("Missing host in URL: {}", url));
+    }
+
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+/// Post a body to an `http://` URL, returning the status code of the response
+pub fn post(url: &str, content_type: &str, body: &str) -> io::Result<u16> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+    let (host, port, path) = parse_url(url).map_err(invalid)?;
+    let addr = (host.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| invalid(format!("No address found for {}", host)))?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, CLIENT_TIMEOUT)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+
+    // only the status line of the response is needed
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid HTTP response: {}", status_line.trim_end()),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+    use std::net::Shutdown;
+
+    /// Send a request to `respond` over a local connection, returning the raw response
+    fn exchange(request: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        respond(server, &|path: &str| match path {
+            "/metrics" => Some(Response {
+                content_type: "text/plain",
+                body: "up 1\n".to_string(),
+            }),
+            _ => None,
+        })
+        .unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn served_path() {
+        let response = exchange("GET /metrics?x=1 HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\
+             Connection: close\r\n\r\nup 1\n"
+        );
+    }
+
+    #[test]
+    fn unserved_path_and_method() {
+        let response = exchange("GET /other HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = exchange("POST /metrics HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        let response = exchange("\r\n");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            parse_url("http://hooks.local:8080/notify/peach").unwrap(),
+            ("hooks.local".to_string(), 8080, "/notify/peach".to_string())
+        );
+        assert_eq!(
+            parse_url("http://10.0.0.1").unwrap(),
+            ("10.0.0.1".to_string(), 80, "/".to_string())
+        );
+        assert!(parse_url("https://hooks.local/").is_err());
+        assert!(parse_url("http://hooks.local:port/").is_err());
+        assert!(parse_url("http://:80/").is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! Stable identifiers of a device, for telling apart merged exports.
+//!
+//! The identifier is the MAC address of the monitored interface, read from
+//! `/sys/class/net/<iface>/address`. Interfaces without a hardware address
+//! (e.g. `ppp0` or a tunnel, which read as none or all zeros) fall back to
+//! the machine ID of systemd (`/etc/machine-id`). Shared output can carry a
+//! pseudonym derived from the identifier instead, which still tells devices
+//! apart without revealing their addresses.
+
+use std::fs;
+use std::path::Path;
+
+/// Directory of the network interfaces, each with its hardware address
+const SYS_CLASS_NET: &str = "/sys/class/net";
+/// Machine ID, identifying the installation rather than an interface
+const MACHINE_ID: &str = "/etc/machine-id";
+
+/// Identifier of a device
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    /// What the identifier is: `mac`, `machine-id` or `pseudonym`
+    pub kind: &'static str,
+    pub value: String,
+}
+
+impl Identity {
+    /// Read the identifier of the device through an interface
+    pub fn read(iface: &str) -> Result<Identity, String> {
+        Identity::read_from(Path::new(SYS_CLASS_NET), Path::new(MACHINE_ID), iface)
+    }
+
+    /// Read the identifier of the device through an interface, from the
+    /// directory of the interfaces and the machine ID file given
+    fn read_from(interfaces: &Path, machine_id: &Path, iface: &str) -> Result<Identity, String> {
+        let address = interfaces.join(iface).join("address");
+        if let Some(mac) = read_value(&address).filter(|mac| !is_unset_mac(mac)) {
+            return Ok(Identity {
+                kind: "mac",
+                value: mac,
+            });
+        }
+        match read_value(machine_id) {
+            Some(id) => Ok(Identity {
+                kind: "machine-id",
+                value: id,
+            }),
+            None => Err(format!(
+                "No identifier for {}: it has no MAC address and {} cannot be read",
+                iface,
+                machine_id.display()
+            )),
+        }
+    }
+
+    /// Pseudonym standing in for the identifier (e.g. `id-5b1f0e3c9a27d644`)
+    ///
+    /// The pseudonym is a hash of the identifier, so a device keeps the same
+    /// pseudonym from one export to the next.
+    pub fn pseudonym(&self) -> Identity {
+        Identity {
+            kind: "pseudonym",
+            value: format!("id-{:016x}", fnv1a(self.value.as_bytes())),
+        }
+    }
+}
+
+/// Trimmed contents of a file, unless it cannot be read or is empty
+fn read_value(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(contents.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Whether a MAC address is all zeros, as read for interfaces without one
+fn is_unset_mac(mac: &str) -> bool {
+    mac.chars().all(|c| c == '0' || c == ':')
+}
+
+/// 64-bit FNV-1a hash, which unlike the standard library's hashers is stable
+/// across releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::scratch_dir;
+
+    #[test]
+    fn identified_by_the_mac_address_then_the_machine_id() {
+        let dir = scratch_dir("identity");
+        let (interfaces, machine_id) = (dir.join("net"), dir.join("machine-id"));
+        for (iface, address) in &[
+            ("wlan0", "b8:27:eb:12:34:56\n"),
+            ("ppp0", "00:00:00:00:00:00\n"),
+        ] {
+            fs::create_dir_all(interfaces.join(iface)).unwrap();
+            fs::write(interfaces.join(iface).join("address"), address).unwrap();
+        }
+        let read = |iface| Identity::read_from(&interfaces, &machine_id, iface);
+
+        let mac = Identity {
+            kind: "mac",
+            value: "b8:27:eb:12:34:56".to_string(),
+        };
+        assert_eq!(read("wlan0"), Ok(mac));
+        assert_eq!(
+            read("ppp0"),
+            Err(format!(
+                "No identifier for ppp0: it has no MAC address and {} cannot be read",
+                machine_id.display()
+            ))
+        );
+
+        fs::write(&machine_id, "5b1f0e3c9a27d6440e1f2a3b4c5d6e7f\n").unwrap();
+        let id = Identity {
+            kind: "machine-id",
+            value: "5b1f0e3c9a27d6440e1f2a3b4c5d6e7f".to_string(),
+        };
+        // interfaces without an address, or which are unknown, fall back to the machine ID
+        assert_eq!(read("ppp0"), Ok(id.clone()));
+        assert_eq!(read("wg0"), Ok(id));
+    }
+
+    #[test]
+    fn pseudonyms_are_stable_and_distinct() {
+        let identity = |value: &str| Identity {
+            kind: "mac",
+            value: value.to_string(),
+        };
+        let pseudonym = identity("b8:27:eb:12:34:56").pseudonym();
+        assert_eq!(pseudonym.kind, "pseudonym");
+        assert_eq!(pseudonym.value.len(), "id-".len() + 16);
+        assert_eq!(pseudonym, identity("b8:27:eb:12:34:56").pseudonym());
+        assert_ne!(pseudonym, identity("b8:27:eb:12:34:57").pseudonym());
+        assert!(!pseudonym.value.contains("b8:27"));
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
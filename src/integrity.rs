@@ -0,0 +1,199 @@
+//! Detection and repair of corrupt data store files.
+//!
+//! A file truncated by a power cut or a full disk cannot be parsed, which
+//! would otherwise make reads appear empty and every write fail. Corrupt
+//! files are moved aside as backups so that the store can start afresh.
+//!
+//! Values of an unexpected type (e.g. a threshold stored as a string) are
+//! normally treated as unset; they can also be reported for strict checks.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use log::error;
+use nest::{Store, Value};
+
+use crate::keys::{FILES, NET};
+
+/// Path of a file of the data store within `net`
+pub fn file_path(data_dir: &Path, file: &str) -> PathBuf {
+    data_dir.join(NET).join(format!("{}.json", file))
+}
+
+/// Paths of the data store files which exist but cannot be read
+///
+/// The reason each file cannot be read is logged.
+pub fn check(store: &Store, data_dir: &Path) -> Vec<PathBuf> {
+    FILES
+        .iter()
+        .filter_map(|file| {
+            let path = file_path(data_dir, file);
+            if !path.exists() {
+                // missing files are created by the first write
+                return None;
+            }
+            match store.get(&[NET, file]) {
+                Ok(_) => None,
+                Err(e) => {
+                    error!("Corrupt data store file {}: {}", path.display(), e);
+                    Some(path)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Move corrupt files aside, so that they are recreated empty by the next write
+///
+/// Each file is renamed with a `.corrupt-<time>` suffix (unix seconds) and
+/// the paths of the backups are returned.
+pub fn back_up(paths: &[PathBuf], time: u64) -> io::Result<Vec<PathBuf>> {
+    paths
+        .iter()
+        .map(|path| {
+            let mut backup = path.clone().into_os_string();
+            backup.push(format!(".corrupt-{}", time));
+            let backup = PathBuf::from(backup);
+            fs::rename(path, &backup)?;
+            Ok(backup)
+        })
+        .collect()
+}
+
+/// Type expected of a stored value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Uint,
+    Float,
+    Bool,
+    String,
+    Array,
+    Object,
+}
+
+impl Kind {
+    fn matches(self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (Kind::Uint, Value::Uint(_))
+                | (Kind::Float, Value::Float(_))
+                | (Kind::Bool, Value::Bool(_))
+                | (Kind::String, Value::String(_))
+                | (Kind::Array, Value::Array(_))
+                | (Kind::Object, Value::Object(_))
+        )
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::Uint => "an unsigned integer",
+            Kind::Float => "a floating-point number",
+            Kind::Bool => "a boolean",
+            Kind::String => "a string",
+            Kind::Array => "an array",
+            Kind::Object => "an object",
+        }
+    }
+}
+
+/// Description of the type of a stored value
+fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Int(_) => "a negative integer",
+        Value::Uint(_) => "an unsigned integer",
+        Value::Float(_) => "a floating-point number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Describe the values of an object whose type differs from that expected of their key
+///
+/// Keys which are not set, or not expected, are not reported. Each mismatch
+/// is described with the path of its key (e.g. `net/notify/rx_cut: expected
+/// an unsigned integer, found a string`).
+pub fn mismatched_types(
+    path: &str,
+    values: &IndexMap<String, Value>,
+    expected: &[(&str, Kind)],
+) -> Vec<String> {
+    expected
+        .iter()
+        .filter_map(|&(key, kind)| match values.get(key) {
+            Some(value) if !kind.matches(value) => Some(format!(
+                "{}/{}: expected {}, found {}",
+                path,
+                key,
+                kind.describe(),
+                describe(value)
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cli::store_schema;
+    use crate::keys::{NOTIFY, TRAFFIC};
+    use crate::tests::scratch_dir;
+
+    #[test]
+    fn corrupt_file_is_backed_up_and_recreated() {
+        let data_dir = scratch_dir("integrity");
+        let store = Store::new(&data_dir, store_schema().unwrap());
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(5120))
+            .unwrap();
+        store
+            .set(&[NET, TRAFFIC, "rx"], &Value::Uint(1000))
+            .unwrap();
+        assert!(check(&store, &data_dir).is_empty());
+
+        // truncated part-way through a write
+        let notify = file_path(&data_dir, NOTIFY);
+        fs::write(&notify, "{\"rx_cut\": 51").unwrap();
+        assert_eq!(check(&store, &data_dir), vec![notify.clone()]);
+
+        let backups = back_up(std::slice::from_ref(&notify), 1_760_000_000).unwrap();
+        let backup = data_dir.join(NET).join("notify.json.corrupt-1760000000");
+        assert_eq!(backups, vec![backup.clone()]);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "{\"rx_cut\": 51");
+        assert!(!notify.exists());
+
+        // the other files are kept, and the corrupt one starts afresh
+        assert!(check(&store, &data_dir).is_empty());
+        assert_eq!(store.get(&[NET, TRAFFIC, "rx"]).unwrap(), Value::Uint(1000));
+        store
+            .set(&[NET, NOTIFY, "rx_cut"], &Value::Uint(2048))
+            .unwrap();
+        assert_eq!(
+            store.get(&[NET, NOTIFY, "rx_cut"]).unwrap(),
+            Value::Uint(2048)
+        );
+    }
+
+    #[test]
+    fn values_of_unexpected_types() {
+        let mut values = IndexMap::new();
+        values.insert("rx_cut".to_string(), Value::String("5GB".to_string()));
+        values.insert("tx_cut".to_string(), Value::Uint(5120));
+        values.insert("other".to_string(), Value::Bool(true));
+        let expected = [
+            ("rx_cut", Kind::Uint),
+            ("tx_cut", Kind::Uint),
+            ("rx_warn", Kind::Uint),
+        ];
+        assert_eq!(
+            mismatched_types("net/notify", &values, &expected),
+            ["net/notify/rx_cut: expected an unsigned integer, found a string"]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Structured logging to the systemd journal.
+//!
+//! Entries are sent over the journal's native protocol so that each field
+//! can be filtered on with `journalctl` (e.g. `journalctl RX_CUT_ALERT=true`).
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+
+/// Socket on which journald accepts native protocol entries
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Identifier under which entries are logged
+const SYSLOG_IDENTIFIER: &str = "peach-monitor";
+
+/// Connection to the systemd journal
+pub struct Journal {
+    socket: UnixDatagram,
+}
+
+impl Journal {
+    /// Connect to the journal, failing if journald is not running
+    pub fn connect() -> io::Result<Journal> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(JOURNAL_SOCKET)?;
+
+        Ok(Journal { socket })
+    }
+
+    /// Send an entry made up of a message and additional fields
+    ///
+    /// Field names must consist of uppercase letters, digits and underscores.
+    pub fn send(&self, message: &str, fields: &[(String, String)]) -> io::Result<()> {
+        let mut entry = Vec::new();
+        append_field(&mut entry, "MESSAGE", message);
+        append_field(&mut entry, "SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER);
+        for (name, value) in fields {
+            append_field(&mut entry, name, value);
+        }
+        self.socket.send(&entry)?;
+
+        Ok(())
+    }
+}
+
+/// Append a field to an entry in the native protocol format
+///
+/// Values containing newlines are length-prefixed, as the protocol requires.
+fn append_field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_in_the_native_format() {
+        let mut entry = Vec::new();
+        append_field(&mut entry, "RX_BYTES", "3000");
+        assert_eq!(entry, b"RX_BYTES=3000\n");
+    }
+
+    #[test]
+    fn multiline_values_are_length_prefixed() {
+        let mut entry = Vec::new();
+        append_field(&mut entry, "MESSAGE", "a\nb");
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert_eq!(entry, expected);
+    }
+}
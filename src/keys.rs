@@ -0,0 +1,660 @@
+//! Mapping of logical data store concepts onto key paths.
+//!
+//! Totals, thresholds and alert flags are looked up here rather than being
+//! hardcoded where they are used, so that the layout written to the data
+//! store can be switched to match the version of a downstream consumer
+//! (such as `peach-web`). The keys of individual thresholds can also be
+//! overridden to match where another tool (such as `peach-config`) writes
+//! them.
+
+use std::str::FromStr;
+
+/// Namespace of all network data
+pub const NET: &str = "net";
+/// Traffic totals and interface counters
+pub const TRAFFIC: &str = "traffic";
+/// User-defined thresholds and data plan
+pub const NOTIFY: &str = "notify";
+/// Alert flags
+pub const ALERT: &str = "alert";
+/// Checkpoints of the usage totals, keyed by name
+pub const CHECKPOINT: &str = "checkpoint";
+/// Daily usage, keyed by date
+pub const HISTORY: &str = "history";
+/// Usage samples for the trailing window
+pub const SAMPLES: &str = "samples";
+/// Totals and alert flags of each interface, keyed by interface (per-interface layout)
+pub const INTERFACES: &str = "interfaces";
+/// Final totals of completed billing cycles, keyed by the date they ended
+pub const CYCLES: &str = "cycles";
+
+/// Files of the data store within `net`
+pub const FILES: [&str; 8] = [
+    CHECKPOINT, HISTORY, SAMPLES, INTERFACES, CYCLES, TRAFFIC, NOTIFY, ALERT,
+];
+
+/// Raw received counter at the previous save (within `net/traffic`)
+pub const RX_LAST: &str = "rx_last";
+/// Raw transmitted counter at the previous save (within `net/traffic`)
+pub const TX_LAST: &str = "tx_last";
+/// Interface the stored counters belong to (within `net/traffic`)
+pub const IFACE_LAST: &str = "iface_last";
+/// Interface being monitored, for consumers to label the totals with (within `net/traffic`)
+pub const IFACE: &str = "iface";
+/// Interfaces holding totals, under the per-interface layout (within `net/traffic`)
+pub const IFACES: &str = "ifaces";
+/// Boot the stored counters belong to (within `net/traffic`)
+pub const BOOT_ID: &str = "boot_id";
+/// Time each interface was first seen (within `net/traffic`)
+pub const FIRST_SEEN: &str = "first_seen";
+/// Whether accounting is paused (within `net/traffic`)
+pub const PAUSED: &str = "paused";
+/// Whether the connection is metered, counted as such unless set to false (within `net/traffic`)
+pub const METERED: &str = "metered";
+/// Receive rate over the last daemon poll in bytes per second (within `net/traffic`)
+pub const RX_RATE: &str = "rx_rate";
+/// Transmit rate over the last daemon poll in bytes per second (within `net/traffic`)
+pub const TX_RATE: &str = "tx_rate";
+/// Time the rates were last updated in unix seconds (within `net/traffic`)
+pub const UPDATED: &str = "updated";
+/// Average daily usage over the days before today in bytes (within `net/traffic`)
+pub const BASELINE: &str = "baseline";
+/// Ratio of transmitted to received traffic over the days before today (within `net/traffic`)
+pub const RATIO_BASELINE: &str = "ratio_baseline";
+/// Exponential moving average of daily usage in bytes (within `net/traffic`)
+pub const DAILY_EMA: &str = "daily_ema";
+/// Last day folded into the moving average, as `YYYY-MM-DD` (within `net/traffic`)
+pub const EMA_DATE: &str = "ema_date";
+/// Combined usage in bytes which can be spent each day for the rest of the billing cycle
+/// (within `net/traffic`)
+pub const DAILY_BUDGET: &str = "daily_budget";
+/// Received traffic counted by the last save in bytes, in delta mode (within `net/traffic`)
+pub const RX_DELTA: &str = "rx_delta";
+/// Transmitted traffic counted by the last save in bytes, in delta mode (within `net/traffic`)
+pub const TX_DELTA: &str = "tx_delta";
+/// Time of the reading the deltas were counted up to in unix seconds (within `net/traffic`)
+pub const DELTA_TIME: &str = "delta_time";
+/// Number of writes made to the totals, to detect conflicting writes (within `net/traffic`)
+pub const TOTALS_VERSION: &str = "totals_version";
+/// Unit of the totals once stored in a unit other than bytes (within `net/traffic`)
+pub const TOTAL_UNIT: &str = "total_unit";
+
+/// Size of the data plan in megabytes (within `net/notify`)
+pub const PLAN: &str = "plan";
+/// Traffic directions covered by the data plan (within `net/notify`)
+pub const PLAN_MODE: &str = "plan_mode";
+/// Day of the month the billing cycle starts on (within `net/notify`)
+pub const CYCLE_DAY: &str = "cycle_day";
+/// Cap on combined usage within the trailing window in megabytes (within `net/notify`)
+pub const WINDOW_CUT: &str = "window_cut";
+/// Multiple of the baseline above which a day's usage is anomalous (within `net/notify`)
+pub const ANOMALY_FACTOR: &str = "anomaly_factor";
+/// Factor by which today's upload ratio must exceed the ratio baseline to be anomalous
+/// (within `net/notify`)
+pub const UPLOAD_FACTOR: &str = "upload_factor";
+
+/// Whether usage within the trailing window exceeds its cap (within `net/alert`)
+pub const WINDOW_CUT_ALERT: &str = "window_cut_alert";
+/// Whether today's usage exceeds the baseline by the anomaly factor (within `net/alert`)
+pub const ANOMALY_ALERT: &str = "anomaly_alert";
+/// Whether the combined total is projected to exceed its cutoff by the end of the billing cycle
+/// (within `net/alert`)
+pub const PROJECTION_OVER: &str = "projection_over";
+/// Whether today's ratio of transmitted to received traffic is anomalous (within `net/alert`)
+pub const UPLOAD_ANOMALY: &str = "upload_anomaly";
+/// Whether today's usage exceeds the daily budget (within `net/alert`)
+pub const BUDGET_OVER: &str = "budget_over";
+
+/// Direction of network traffic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Rx,
+    Tx,
+    /// Received and transmitted traffic together
+    Combined,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 3] = [Direction::Rx, Direction::Tx, Direction::Combined];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Direction::Rx => "rx",
+            Direction::Tx => "tx",
+            Direction::Combined => "total",
+        }
+    }
+}
+
+/// Severity of an alert threshold
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Warn,
+    Cut,
+}
+
+impl Level {
+    pub const ALL: [Level; 2] = [Level::Warn, Level::Cut];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Warn => "warn",
+            Level::Cut => "cut",
+        }
+    }
+}
+
+/// Name of a threshold (e.g. `total_warn`), as used when overriding its key
+pub fn threshold_name(direction: Direction, level: Level) -> String {
+    format!("{}_{}", direction.as_str(), level.as_str())
+}
+
+/// Alert flag, by the name of its canonical key under the `v2` layout
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlertFlag {
+    /// Flag of a threshold (e.g. `total_cut_alert`)
+    Threshold(Direction, Level),
+    /// Flag of the trailing window cap (`window_cut_alert`)
+    WindowCut,
+    /// Flag of the usage anomaly (`anomaly_alert`)
+    Anomaly,
+    /// Flag of the usage projected for the billing cycle (`projection_over`)
+    Projection,
+    /// Flag of the upload ratio anomaly (`upload_anomaly`)
+    UploadAnomaly,
+    /// Flag of the daily budget (`budget_over`)
+    Budget,
+}
+
+impl FromStr for AlertFlag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AlertFlag, String> {
+        match s {
+            WINDOW_CUT_ALERT => return Ok(AlertFlag::WindowCut),
+            ANOMALY_ALERT => return Ok(AlertFlag::Anomaly),
+            PROJECTION_OVER => return Ok(AlertFlag::Projection),
+            UPLOAD_ANOMALY => return Ok(AlertFlag::UploadAnomaly),
+            BUDGET_OVER => return Ok(AlertFlag::Budget),
+            _ => (),
+        }
+        for &direction in &Direction::ALL {
+            for &level in &Level::ALL {
+                if format!("{}_alert", threshold_name(direction, level)) == s {
+                    return Ok(AlertFlag::Threshold(direction, level));
+                }
+            }
+        }
+
+        Err(format!(
+            "Unknown alert flag: {} (expected rx_warn_alert, rx_cut_alert, tx_warn_alert, \
+             tx_cut_alert, total_warn_alert, total_cut_alert, window_cut_alert, anomaly_alert, \
+             projection_over, upload_anomaly or budget_over)",
+            s
+        ))
+    }
+}
+
+/// Key used for a threshold in place of those of the layout
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdKey {
+    pub direction: Direction,
+    pub level: Level,
+    pub key: String,
+}
+
+impl FromStr for ThresholdKey {
+    type Err = String;
+
+    /// Parse the name of a threshold and the key to use for it (e.g. `total_warn=data_warn`)
+    fn from_str(s: &str) -> Result<ThresholdKey, String> {
+        let (name, key) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <threshold>=<key>: {}", s))?;
+        if key.is_empty() {
+            return Err(format!("Missing key for threshold {}", name));
+        }
+        for &direction in &Direction::ALL {
+            for &level in &Level::ALL {
+                if threshold_name(direction, level) == name {
+                    return Ok(ThresholdKey {
+                        direction,
+                        level,
+                        key: key.to_string(),
+                    });
+                }
+            }
+        }
+
+        Err(format!(
+            "Unknown threshold: {} (expected rx_warn, rx_cut, tx_warn, tx_cut, \
+             total_warn or total_cut)",
+            name
+        ))
+    }
+}
+
+/// Key used for the alert flag of a threshold in place of those of the layout
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertKey {
+    pub direction: Direction,
+    pub level: Level,
+    pub key: String,
+}
+
+impl FromStr for AlertKey {
+    type Err = String;
+
+    /// Parse the canonical name of an alert flag and the key to use for it (e.g.
+    /// `total_cut_alert=data_cut`)
+    fn from_str(s: &str) -> Result<AlertKey, String> {
+        let (name, key) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <flag>=<key>: {}", s))?;
+        if key.is_empty() {
+            return Err(format!("Missing key for alert flag {}", name));
+        }
+        match name.parse()? {
+            AlertFlag::Threshold(direction, level) => Ok(AlertKey {
+                direction,
+                level,
+                key: key.to_string(),
+            }),
+            _ => Err(format!(
+                "Only the alert flags of thresholds can be renamed: {}",
+                name
+            )),
+        }
+    }
+}
+
+/// Named layouts of the data store keys
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// Directional and combined keys, with the combined alert flags mirrored
+    /// to the keys of the legacy layout
+    V2,
+    /// Layout of earlier versions, where combined traffic uses the `warn`,
+    /// `cut`, `warn_alert` and `cut_alert` keys
+    Legacy,
+    /// Keys of the V2 layout, with the totals and alert flags held per
+    /// interface in `net/interfaces` rather than in `net/traffic` and
+    /// `net/alert`
+    PerInterface,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        match s {
+            "v2" => Ok(Layout::V2),
+            "legacy" => Ok(Layout::Legacy),
+            "per-interface" => Ok(Layout::PerInterface),
+            _ => Err(format!("Unknown schema: {}", s)),
+        }
+    }
+}
+
+/// Unit the totals are stored in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TotalUnit {
+    Bytes,
+    /// Whole kilobytes, with the bytes below a kilobyte carried separately
+    Kilobytes,
+}
+
+impl TotalUnit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TotalUnit::Bytes => "bytes",
+            TotalUnit::Kilobytes => "kb",
+        }
+    }
+
+    /// Number of bytes in the unit
+    pub fn bytes(self) -> u64 {
+        match self {
+            TotalUnit::Bytes => 1,
+            TotalUnit::Kilobytes => 1024,
+        }
+    }
+}
+
+impl FromStr for TotalUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TotalUnit, String> {
+        match s {
+            "bytes" => Ok(TotalUnit::Bytes),
+            "kb" => Ok(TotalUnit::Kilobytes),
+            _ => Err(format!("Unknown total unit: {}", s)),
+        }
+    }
+}
+
+/// Key names for totals, thresholds and alert flags under a layout
+#[derive(Clone)]
+pub struct Keys {
+    layout: Layout,
+    compat_flat: bool, // mirror per-interface values to the flat keys
+    threshold_keys: Vec<ThresholdKey>,
+    alert_keys: Vec<AlertKey>,
+    unit: TotalUnit, // unit the totals are written in
+}
+
+impl Keys {
+    pub fn new(
+        layout: Layout,
+        compat_flat: bool,
+        threshold_keys: Vec<ThresholdKey>,
+        alert_keys: Vec<AlertKey>,
+        unit: TotalUnit,
+    ) -> Keys {
+        Keys {
+            layout,
+            compat_flat,
+            threshold_keys,
+            alert_keys,
+            unit,
+        }
+    }
+
+    /// Check that renamed alert flags are not written to the key of another flag
+    ///
+    /// A flag may only be renamed once, and its new key must not be one of
+    /// the keys of another flag (renamed or not), so that no two flags
+    /// overwrite each other.
+    pub fn check_alert_keys(&self) -> Result<(), String> {
+        let flag_name = |direction, level| format!("{}_alert", threshold_name(direction, level));
+        let mut flags = Vec::new();
+        for &direction in &Direction::ALL {
+            for &level in &Level::ALL {
+                let renamed = self
+                    .alert_keys
+                    .iter()
+                    .filter(|a| a.direction == direction && a.level == level)
+                    .count();
+                if renamed > 1 {
+                    return Err(format!(
+                        "Alert flag {} is renamed more than once",
+                        flag_name(direction, level)
+                    ));
+                }
+                flags.push((flag_name(direction, level), self.alert(direction, level)));
+            }
+        }
+        for &flag in &[
+            WINDOW_CUT_ALERT,
+            ANOMALY_ALERT,
+            PROJECTION_OVER,
+            UPLOAD_ANOMALY,
+            BUDGET_OVER,
+        ] {
+            flags.push((flag.to_string(), vec![flag]));
+        }
+
+        for renamed in &self.alert_keys {
+            let name = flag_name(renamed.direction, renamed.level);
+            let collision = flags
+                .iter()
+                .find(|(other, keys)| *other != name && keys.contains(&renamed.key.as_str()));
+            if let Some((other, _)) = collision {
+                return Err(format!(
+                    "Alert flag {} cannot be renamed to {}, a key of {}",
+                    name, renamed.key, other
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unit the totals are written in
+    pub fn unit(&self) -> TotalUnit {
+        self.unit
+    }
+
+    /// Whether totals and alert flags are held per interface
+    pub fn per_interface(&self) -> bool {
+        self.layout == Layout::PerInterface
+    }
+
+    /// Whether totals and alert flags are written to `net/traffic` and `net/alert`
+    ///
+    /// Under the per-interface layout they are only mirrored there, for the
+    /// monitored interface, when compatibility with flat keys is requested.
+    pub fn flat(&self) -> bool {
+        !self.per_interface() || self.compat_flat
+    }
+
+    /// Key of the stored total (bytes) for a direction (within `net/traffic`)
+    pub fn total(&self, direction: Direction) -> &'static str {
+        direction.as_str()
+    }
+
+    /// Key of the bytes below a whole unit of a stored total for a direction (within `net/traffic`)
+    pub fn carry(&self, direction: Direction) -> &'static str {
+        match direction {
+            Direction::Rx => "rx_carry",
+            Direction::Tx => "tx_carry",
+            Direction::Combined => "total_carry",
+        }
+    }
+
+    /// Keys of a threshold (within `net/notify`)
+    ///
+    /// Thresholds are written to the first key and read from the first key
+    /// which holds a value. An overridden key comes first, followed by the
+    /// keys of the layout so that values already stored are still read.
+    pub fn threshold(&self, direction: Direction, level: Level) -> Vec<&str> {
+        let mut keys: Vec<&str> = self
+            .threshold_keys
+            .iter()
+            .filter(|t| t.direction == direction && t.level == level)
+            .map(|t| t.key.as_str())
+            .collect();
+        for &key in self.layout_threshold(direction, level) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+
+    /// Keys of a threshold under the layout, without overrides
+    fn layout_threshold(&self, direction: Direction, level: Level) -> &'static [&'static str] {
+        match (self.layout, direction, level) {
+            (_, Direction::Rx, Level::Warn) => &["rx_warn"],
+            (_, Direction::Rx, Level::Cut) => &["rx_cut"],
+            (_, Direction::Tx, Level::Warn) => &["tx_warn"],
+            (_, Direction::Tx, Level::Cut) => &["tx_cut"],
+            (Layout::V2 | Layout::PerInterface, Direction::Combined, Level::Warn) => {
+                &["total_warn", "warn"]
+            }
+            (Layout::V2 | Layout::PerInterface, Direction::Combined, Level::Cut) => {
+                &["total_cut", "cut"]
+            }
+            (Layout::Legacy, Direction::Combined, Level::Warn) => &["warn"],
+            (Layout::Legacy, Direction::Combined, Level::Cut) => &["cut"],
+        }
+    }
+
+    /// Keys an alert flag is written to (within `net/alert`)
+    ///
+    /// A renamed flag is only written to its new key, which stands in for
+    /// the keys of the layout. Otherwise the first key is that of the layout
+    /// (e.g. `total_cut_alert`), which the flag is read from and named by,
+    /// followed by any keys it is mirrored to (e.g. `cut_alert`).
+    pub fn alert(&self, direction: Direction, level: Level) -> Vec<&str> {
+        match self
+            .alert_keys
+            .iter()
+            .find(|a| a.direction == direction && a.level == level)
+        {
+            Some(renamed) => vec![renamed.key.as_str()],
+            None => self.layout_alert(direction, level).to_vec(),
+        }
+    }
+
+    /// Keys of an alert flag under the layout, without renames
+    fn layout_alert(&self, direction: Direction, level: Level) -> &'static [&'static str] {
+        match (self.layout, direction, level) {
+            (_, Direction::Rx, Level::Warn) => &["rx_warn_alert"],
+            (_, Direction::Rx, Level::Cut) => &["rx_cut_alert"],
+            (_, Direction::Tx, Level::Warn) => &["tx_warn_alert"],
+            (_, Direction::Tx, Level::Cut) => &["tx_cut_alert"],
+            (Layout::V2 | Layout::PerInterface, Direction::Combined, Level::Warn) => {
+                &["total_warn_alert", "warn_alert"]
+            }
+            (Layout::V2 | Layout::PerInterface, Direction::Combined, Level::Cut) => {
+                &["total_cut_alert", "cut_alert"]
+            }
+            (Layout::Legacy, Direction::Combined, Level::Warn) => &["warn_alert"],
+            (Layout::Legacy, Direction::Combined, Level::Cut) => &["cut_alert"],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(layout: Layout) -> Keys {
+        Keys::new(layout, false, Vec::new(), Vec::new(), TotalUnit::Bytes)
+    }
+
+    #[test]
+    fn combined_keys_under_each_layout() {
+        let v2 = keys(Layout::V2);
+        assert_eq!(
+            v2.threshold(Direction::Combined, Level::Cut),
+            ["total_cut", "cut"]
+        );
+        assert_eq!(
+            v2.alert(Direction::Combined, Level::Cut),
+            ["total_cut_alert", "cut_alert"]
+        );
+
+        let legacy = keys(Layout::Legacy);
+        assert_eq!(legacy.threshold(Direction::Combined, Level::Cut), ["cut"]);
+        assert_eq!(legacy.alert(Direction::Combined, Level::Cut), ["cut_alert"]);
+
+        let per_interface = keys(Layout::PerInterface);
+        assert_eq!(
+            per_interface.threshold(Direction::Combined, Level::Warn),
+            ["total_warn", "warn"]
+        );
+        assert!(per_interface.per_interface());
+        assert!(!per_interface.flat());
+    }
+
+    #[test]
+    fn directional_keys_are_the_same_under_each_layout() {
+        for &layout in &[Layout::V2, Layout::Legacy, Layout::PerInterface] {
+            let keys = keys(layout);
+            assert_eq!(keys.threshold(Direction::Rx, Level::Warn), ["rx_warn"]);
+            assert_eq!(keys.alert(Direction::Tx, Level::Cut), ["tx_cut_alert"]);
+            assert_eq!(keys.total(Direction::Combined), "total");
+        }
+    }
+
+    #[test]
+    fn layout_names() {
+        assert_eq!("v2".parse(), Ok(Layout::V2));
+        assert_eq!("legacy".parse(), Ok(Layout::Legacy));
+        assert_eq!("per-interface".parse(), Ok(Layout::PerInterface));
+        assert!("v3".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn overridden_threshold_key_comes_first() {
+        let key: ThresholdKey = "total_warn=data_warn".parse().unwrap();
+        assert_eq!(
+            key,
+            ThresholdKey {
+                direction: Direction::Combined,
+                level: Level::Warn,
+                key: "data_warn".to_string(),
+            }
+        );
+        let keys = Keys::new(Layout::V2, false, vec![key], Vec::new(), TotalUnit::Bytes);
+        assert_eq!(
+            keys.threshold(Direction::Combined, Level::Warn),
+            ["data_warn", "total_warn", "warn"]
+        );
+        assert_eq!(
+            keys.threshold(Direction::Combined, Level::Cut),
+            ["total_cut", "cut"]
+        );
+
+        assert!("total_warn".parse::<ThresholdKey>().is_err());
+        assert!("total_warn=".parse::<ThresholdKey>().is_err());
+        assert!("data_warn=total_warn".parse::<ThresholdKey>().is_err());
+    }
+
+    fn renamed(renames: &[&str]) -> Keys {
+        let alert_keys = renames.iter().map(|s| s.parse().unwrap()).collect();
+        Keys::new(Layout::V2, false, Vec::new(), alert_keys, TotalUnit::Bytes)
+    }
+
+    #[test]
+    fn alert_keys_are_parsed() {
+        assert_eq!(
+            "total_cut_alert=data_cut".parse(),
+            Ok(AlertKey {
+                direction: Direction::Combined,
+                level: Level::Cut,
+                key: "data_cut".to_string(),
+            })
+        );
+        assert!("total_cut_alert".parse::<AlertKey>().is_err());
+        assert!("total_cut_alert=".parse::<AlertKey>().is_err());
+        assert!("window_cut_alert=window".parse::<AlertKey>().is_err());
+    }
+
+    #[test]
+    fn renamed_alert_flag_replaces_the_keys_of_the_layout() {
+        let keys = renamed(&["total_cut_alert=data_cut"]);
+        assert_eq!(keys.alert(Direction::Combined, Level::Cut), ["data_cut"]);
+        assert_eq!(
+            keys.alert(Direction::Combined, Level::Warn),
+            ["total_warn_alert", "warn_alert"]
+        );
+        assert_eq!(keys.alert(Direction::Rx, Level::Cut), ["rx_cut_alert"]);
+        assert_eq!(keys.check_alert_keys(), Ok(()));
+    }
+
+    #[test]
+    fn colliding_alert_keys_are_refused() {
+        for (renames, error) in &[
+            (
+                &["rx_cut_alert=a", "rx_cut_alert=b"][..],
+                "Alert flag rx_cut_alert is renamed more than once",
+            ),
+            (
+                &["rx_cut_alert=cut", "tx_cut_alert=cut"][..],
+                "Alert flag rx_cut_alert cannot be renamed to cut, a key of tx_cut_alert",
+            ),
+            (
+                &["rx_cut_alert=tx_cut_alert"][..],
+                "Alert flag rx_cut_alert cannot be renamed to tx_cut_alert, a key of tx_cut_alert",
+            ),
+            (
+                &["rx_cut_alert=cut_alert"][..],
+                "Alert flag rx_cut_alert cannot be renamed to cut_alert, a key of total_cut_alert",
+            ),
+            (
+                &["total_warn_alert=projection_over"][..],
+                "Alert flag total_warn_alert cannot be renamed to projection_over, a key of projection_over",
+            ),
+        ] {
+            assert_eq!(renamed(renames).check_alert_keys(), Err(error.to_string()));
+        }
+        // a flag may keep its own canonical key
+        assert_eq!(
+            renamed(&["total_cut_alert=total_cut_alert"]).check_alert_keys(),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,726 @@
+//! Helpers shared by the `peach-monitor` binary and crates built on its data
+//! store, such as user interfaces displaying the usage of a connection. The
+//! binary itself is the `cli` module, which the daemon and `Monitor` share.
+//!
+//! Totals and thresholds are given in bytes, as thresholds read from the
+//! data store (in megabytes) are converted before they are compared.
+//!
+//! The keys of the data store are those of the `keys` module, which the
+//! binary writes with. `load_state` reads the whole state of a data store at
+//! once, under the same `Keys` (schema, overridden threshold keys, renamed
+//! alert flags and unit of the totals) as the daemon, so that consumers need
+//! not match the stored values themselves. `Monitor` runs the polls of the
+//! daemon itself, passing the state after each of them to a closure.
+
+use std::ffi::OsString;
+use std::thread;
+
+use indexmap::IndexMap;
+use nest::{Store, Value};
+
+mod anonymize;
+mod archive;
+mod bench;
+mod checkpoint;
+pub mod cli;
+mod color;
+mod config;
+mod cycle;
+mod dash;
+#[cfg(feature = "sqlite")]
+mod db;
+mod doctor;
+mod durability;
+mod duration;
+mod error;
+mod flash;
+mod history;
+mod hook;
+mod http;
+mod identity;
+mod integrity;
+mod journal;
+pub mod keys;
+mod metered;
+mod metrics;
+mod nft;
+mod nm;
+mod notification;
+mod profile;
+mod rate;
+mod repl;
+mod retry;
+mod schedule;
+mod shaping;
+mod sink;
+mod snapshot;
+mod snmp;
+#[cfg(feature = "top-talkers")]
+mod talkers;
+mod throttle;
+mod trace;
+mod traffic;
+mod units;
+mod window;
+
+use crate::keys::{
+    Direction, Keys, Level, TotalUnit, ALERT, ANOMALY_ALERT, BUDGET_OVER, DAILY_BUDGET, IFACE,
+    IFACE_LAST, INTERFACES, METERED, NET, NOTIFY, PAUSED, PROJECTION_OVER, RX_RATE, TOTAL_UNIT,
+    TRAFFIC, TX_RATE, UPDATED, UPLOAD_ANOMALY, WINDOW_CUT_ALERT,
+};
+
+/// Bytes in a megabyte, the unit thresholds are stored in
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Convert a megabyte value (e.g. a threshold) to bytes
+pub fn to_bytes(megabytes: u64) -> u64 {
+    megabytes.saturating_mul(MEGABYTE)
+}
+
+/// Convert a total into bytes, given the unit recorded alongside it and the bytes it carried
+///
+/// Totals without a recorded unit are in bytes. A total in kilobytes is
+/// completed by the bytes below a kilobyte carried with it, so that no
+/// usage is lost to the coarser unit.
+pub fn total_bytes(total: u64, unit: Option<&Value>, carry: Option<&Value>) -> u64 {
+    let unit = match unit {
+        Some(Value::String(unit)) => unit.parse().unwrap_or(TotalUnit::Bytes),
+        _ => TotalUnit::Bytes,
+    };
+    let carry = match carry {
+        Some(Value::Uint(carry)) if unit != TotalUnit::Bytes => *carry,
+        _ => 0,
+    };
+    total.saturating_mul(unit.bytes()).saturating_add(carry)
+}
+
+/// Bytes left before a total reaches the warning and cutoff thresholds of its direction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Remaining {
+    pub warn: Option<u64>,
+    pub cut: Option<u64>,
+}
+
+/// Bytes left before a total reaches its warning and cutoff thresholds
+///
+/// A threshold which is not defined, or is zero (disabled), yields `None`.
+/// A threshold which the total has already reached yields `Some(0)`.
+pub fn bytes_to_thresholds(total: u64, warn: Option<u64>, cut: Option<u64>) -> Remaining {
+    let remaining = |threshold: Option<u64>| {
+        threshold
+            .filter(|&bytes| bytes > 0)
+            .map(|bytes| bytes.saturating_sub(total))
+    };
+
+    Remaining {
+        warn: remaining(warn),
+        cut: remaining(cut),
+    }
+}
+
+/// Total, thresholds and alert flags of a direction, in bytes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DirectionState {
+    pub total: u64,
+    pub warn: Option<u64>,
+    pub cut: Option<u64>,
+    pub warn_alert: bool,
+    pub cut_alert: bool,
+}
+
+impl DirectionState {
+    /// Bytes left before the total reaches its thresholds
+    pub fn remaining(&self) -> Remaining {
+        bytes_to_thresholds(self.total, self.warn, self.cut)
+    }
+}
+
+/// State of the monitor as read from its data store
+///
+/// Keys which are missing (or hold a value of another type) read as a total
+/// of 0, no threshold, an unset flag or no time, except that a connection
+/// is metered unless it has been marked otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorState {
+    /// Name of the monitored interface
+    pub iface: Option<String>,
+    pub rx: DirectionState,
+    pub tx: DirectionState,
+    /// Received and transmitted traffic together
+    pub total: DirectionState,
+    pub window_cut_alert: bool,
+    pub anomaly_alert: bool,
+    pub projection_over: bool,
+    pub upload_anomaly: bool,
+    pub budget_over: bool,
+    /// Combined usage which can be spent each day for the rest of the billing cycle (bytes)
+    pub daily_budget: Option<u64>,
+    /// Receive and transmit rates of the last daemon poll (bytes per second)
+    pub rx_rate: Option<u64>,
+    pub tx_rate: Option<u64>,
+    /// Time the rates were last updated (unix seconds)
+    pub updated: Option<u64>,
+    pub paused: bool,
+    pub metered: bool,
+}
+
+/// Retrieve a stored threshold (megabytes) from the first of its keys which holds a value
+pub fn stored_threshold(
+    store: &Store,
+    keys: &Keys,
+    direction: Direction,
+    level: Level,
+) -> Option<u64> {
+    keys.threshold(direction, level)
+        .iter()
+        .find_map(|key| match store.get(&[NET, NOTIFY, key]) {
+            Ok(Value::Uint(val)) => Some(val),
+            _ => None,
+        })
+}
+
+/// Retrieve a stored network traffic total or counter (bytes)
+pub fn stored_total(store: &Store, key: &str) -> Option<u64> {
+    match store.get(&[NET, TRAFFIC, key]) {
+        Ok(Value::Uint(total)) => Some(total),
+        _ => None,
+    }
+}
+
+/// Retrieve the totals and alert flags stored for an interface (per-interface layout)
+pub fn interface_values(store: &Store, iface: &str) -> IndexMap<String, Value> {
+    match store.get(&[NET, INTERFACES, iface]) {
+        Ok(Value::Object(values)) => values,
+        _ => IndexMap::new(),
+    }
+}
+
+/// Retrieve a total or alert flag of the monitored interface
+///
+/// Under the flat layouts the value is read from a file of `net` instead.
+pub fn stored_value(store: &Store, keys: &Keys, file: &str, key: &str) -> Option<Value> {
+    if keys.per_interface() {
+        let iface = stored_string(store, IFACE_LAST)?;
+        interface_values(store, &iface).swap_remove(key)
+    } else {
+        store.get(&[NET, file, key]).ok()
+    }
+}
+
+/// Retrieve the stored usage total (bytes) for a direction
+pub fn stored_usage(store: &Store, keys: &Keys, direction: Direction) -> Option<u64> {
+    match stored_value(store, keys, TRAFFIC, keys.total(direction)) {
+        Some(Value::Uint(total)) => Some(stored_bytes(store, keys, direction, total)),
+        _ => None,
+    }
+}
+
+/// Convert a stored total of a direction into bytes, by the unit it was stored in
+pub fn stored_bytes(store: &Store, keys: &Keys, direction: Direction, total: u64) -> u64 {
+    let value = |key| stored_value(store, keys, TRAFFIC, key);
+    total_bytes(
+        total,
+        value(TOTAL_UNIT).as_ref(),
+        value(keys.carry(direction)).as_ref(),
+    )
+}
+
+/// Retrieve a stored alert flag by one of its keys
+pub fn stored_alert(store: &Store, keys: &Keys, key: &str) -> Option<bool> {
+    match stored_value(store, keys, ALERT, key) {
+        Some(Value::Bool(alert)) => Some(alert),
+        _ => None,
+    }
+}
+
+/// Retrieve a stored string value
+pub fn stored_string(store: &Store, key: &str) -> Option<String> {
+    match store.get(&[NET, TRAFFIC, key]) {
+        Ok(Value::String(val)) => Some(val),
+        _ => None,
+    }
+}
+
+/// Whether the connection is metered, which it is unless marked otherwise
+pub fn is_metered(store: &Store) -> bool {
+    !matches!(store.get(&[NET, TRAFFIC, METERED]), Ok(Value::Bool(false)))
+}
+
+/// Read the state of the monitor from its data store, under the keys the daemon writes
+///
+/// Totals are converted to bytes from the unit they were stored in, and a
+/// missing combined total is the sum of the directions. Values are read with
+/// the same readers as the daemon: thresholds from the first of their keys
+/// holding a number (such as an overridden key, then the keys of the
+/// layout), so that a threshold of 0 is read as such, its alert always being
+/// raised, and the alert flags of thresholds from their renamed keys, if any.
+pub fn load_state(store: &Store, keys: &Keys) -> MonitorState {
+    let usage = |direction| stored_usage(store, keys, direction);
+    let (rx, tx) = (
+        usage(Direction::Rx).unwrap_or(0),
+        usage(Direction::Tx).unwrap_or(0),
+    );
+    let direction = |direction, total| {
+        let threshold = |level| stored_threshold(store, keys, direction, level).map(to_bytes);
+        let alert =
+            |level| stored_alert(store, keys, keys.alert(direction, level)[0]).unwrap_or(false);
+        DirectionState {
+            total,
+            warn: threshold(Level::Warn),
+            cut: threshold(Level::Cut),
+            warn_alert: alert(Level::Warn),
+            cut_alert: alert(Level::Cut),
+        }
+    };
+    let flag = |file, key| matches!(store.get(&[NET, file, key]), Ok(Value::Bool(true)));
+
+    MonitorState {
+        iface: stored_string(store, IFACE).or_else(|| stored_string(store, IFACE_LAST)),
+        rx: direction(Direction::Rx, rx),
+        tx: direction(Direction::Tx, tx),
+        total: direction(
+            Direction::Combined,
+            usage(Direction::Combined).unwrap_or(rx + tx),
+        ),
+        window_cut_alert: flag(ALERT, WINDOW_CUT_ALERT),
+        anomaly_alert: flag(ALERT, ANOMALY_ALERT),
+        projection_over: flag(ALERT, PROJECTION_OVER),
+        upload_anomaly: flag(ALERT, UPLOAD_ANOMALY),
+        budget_over: flag(ALERT, BUDGET_OVER),
+        daily_budget: stored_total(store, DAILY_BUDGET),
+        rx_rate: stored_total(store, RX_RATE),
+        tx_rate: stored_total(store, TX_RATE),
+        updated: stored_total(store, UPDATED),
+        paused: flag(TRAFFIC, PAUSED),
+        metered: is_metered(store),
+    }
+}
+
+/// Whether a `Monitor` keeps polling after calling its closure
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Polls a data store as the daemon does, passing the state after each poll to a closure
+///
+/// A consumer streaming live data (e.g. to a websocket) runs the polls
+/// itself, in place of the daemon, so that it is handed the state as soon
+/// as each poll has written it. Each poll takes a reading of the counters,
+/// adds the usage since the last one to the totals, stores the rates and
+/// sets the alert flags, with the same code as the daemon's poll, but
+/// leaves notifications, backends (`--db`) and the breach and status files
+/// to the daemon.
+pub struct Monitor {
+    polls: cli::Polls,
+}
+
+impl Monitor {
+    /// Poll the data store given by the options of the binary (e.g. `--data-dir` and `--iface`)
+    ///
+    /// The options are those of `peach-monitor` other than its command, and
+    /// the polls are made every `--interval` seconds.
+    pub fn new<I, S>(args: I) -> Result<Monitor, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let polls =
+            cli::Polls::open(args.into_iter().map(Into::into)).map_err(|e| e.to_string())?;
+        Ok(Monitor { polls })
+    }
+
+    /// Run a single poll, returning the state it left in the data store
+    pub fn poll(&mut self) -> Result<MonitorState, String> {
+        self.polls.poll().map_err(|e| e.to_string())
+    }
+
+    /// Poll at the interval and call a closure with the state after each
+    /// poll, until it returns `Flow::Stop` or a poll fails
+    ///
+    /// The first poll is made at once, and the interval is counted from the
+    /// end of each call of the closure. A failed poll ends the loop and is
+    /// returned, without the closure being called.
+    pub fn run_with<F>(&mut self, mut callback: F) -> Result<(), String>
+    where
+        F: FnMut(&MonitorState) -> Flow,
+    {
+        loop {
+            let state = self.poll()?;
+            if callback(&state) == Flow::Stop {
+                return Ok(());
+            }
+            thread::sleep(self.polls.interval());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use std::sync::{Mutex, MutexGuard};
+    use std::{env, fs, process};
+
+    use crate::keys::Layout;
+
+    /// Empty directory of its own under the temporary directory
+    pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("peach-monitor-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    /// Empty data store in a directory of its own
+    pub(crate) fn scratch_store(name: &str) -> Store {
+        Store::new(scratch_dir(name), cli::store_schema().unwrap())
+    }
+
+    /// Held while the environment is read or changed, as tests run in parallel
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Lock the environment, whether or not a test panicked while holding it
+    pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+        ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[test]
+    fn bytes_left_before_each_threshold() {
+        let (warn, cut) = (Some(8 * MEGABYTE), Some(10 * MEGABYTE));
+        for &(total, expected) in &[
+            // below the warning
+            (
+                5 * MEGABYTE,
+                Remaining {
+                    warn: Some(3 * MEGABYTE),
+                    cut: Some(5 * MEGABYTE),
+                },
+            ),
+            // between the warning and the cutoff
+            (
+                9 * MEGABYTE,
+                Remaining {
+                    warn: Some(0),
+                    cut: Some(MEGABYTE),
+                },
+            ),
+            // over the cutoff
+            (
+                12 * MEGABYTE,
+                Remaining {
+                    warn: Some(0),
+                    cut: Some(0),
+                },
+            ),
+        ] {
+            assert_eq!(bytes_to_thresholds(total, warn, cut), expected, "{}", total);
+        }
+    }
+
+    #[test]
+    fn disabled_thresholds_leave_nothing_to_count_down() {
+        assert_eq!(
+            bytes_to_thresholds(5000, Some(0), None),
+            Remaining {
+                warn: None,
+                cut: None
+            }
+        );
+    }
+
+    fn keys(layout: Layout, thresholds: &[&str], alerts: &[&str]) -> Keys {
+        Keys::new(
+            layout,
+            false,
+            thresholds.iter().map(|s| s.parse().unwrap()).collect(),
+            alerts.iter().map(|s| s.parse().unwrap()).collect(),
+            TotalUnit::Bytes,
+        )
+    }
+
+    fn set(store: &Store, file: &str, values: &[(&str, Value)]) {
+        for (key, value) in values {
+            store.set(&[NET, file, key], value).unwrap();
+        }
+    }
+
+    #[test]
+    fn state_of_a_populated_store() {
+        let store = scratch_store("state-populated");
+        set(
+            &store,
+            TRAFFIC,
+            &[
+                (IFACE, Value::String("wlan0".to_string())),
+                ("rx", Value::Uint(8 * 1024)),
+                ("rx_carry", Value::Uint(100)),
+                ("tx", Value::Uint(1024)),
+                (TOTAL_UNIT, Value::String("kb".to_string())),
+                (RX_RATE, Value::Uint(2000)),
+                (UPDATED, Value::Uint(1_760_000_000)),
+                (METERED, Value::Bool(false)),
+            ],
+        );
+        set(
+            &store,
+            NOTIFY,
+            &[
+                ("rx_warn", Value::Uint(4)),
+                ("rx_cut", Value::Uint(0)),
+                ("data_cap", Value::Uint(20)),
+                ("total_cut", Value::Uint(10)),
+                ("warn", Value::Uint(15)),
+            ],
+        );
+        set(
+            &store,
+            ALERT,
+            &[
+                ("rx_warn_alert", Value::Bool(true)),
+                ("total_cut_alert", Value::Bool(true)),
+                ("data_cut", Value::Bool(false)),
+                (BUDGET_OVER, Value::Bool(true)),
+            ],
+        );
+        let keys = keys(
+            Layout::V2,
+            &["total_cut=data_cap"],
+            &["total_cut_alert=data_cut"],
+        );
+
+        let state = load_state(&store, &keys);
+        assert_eq!(state.iface.as_deref(), Some("wlan0"));
+        assert_eq!(
+            state.rx,
+            DirectionState {
+                total: 8 * MEGABYTE + 100,
+                warn: Some(4 * MEGABYTE),
+                cut: Some(0),
+                warn_alert: true,
+                cut_alert: false,
+            }
+        );
+        assert_eq!(state.tx.total, MEGABYTE);
+        // the overridden key comes before those of the layout, and the legacy key is a fallback
+        assert_eq!(state.total.total, 9 * MEGABYTE + 100);
+        assert_eq!(state.total.cut, Some(20 * MEGABYTE));
+        assert_eq!(state.total.warn, Some(15 * MEGABYTE));
+        // the renamed flag stands in for the keys of the layout
+        assert!(!state.total.cut_alert);
+        assert!(state.budget_over && !state.projection_over);
+        assert_eq!((state.rx_rate, state.tx_rate), (Some(2000), None));
+        assert_eq!(state.updated, Some(1_760_000_000));
+        assert!(!state.metered && !state.paused);
+        assert_eq!(
+            state.rx.remaining(),
+            Remaining {
+                warn: Some(0),
+                cut: None
+            }
+        );
+    }
+
+    #[test]
+    fn state_of_the_monitored_interface() {
+        let store = scratch_store("state-per-interface");
+        set(
+            &store,
+            TRAFFIC,
+            &[
+                (IFACE_LAST, Value::String("wlan0".to_string())),
+                ("rx", Value::Uint(1)),
+            ],
+        );
+        let entry = |rx, alert| {
+            let mut values = IndexMap::new();
+            values.insert("rx".to_string(), Value::Uint(rx));
+            values.insert("total_cut_alert".to_string(), Value::Bool(alert));
+            Value::Object(values)
+        };
+        set(
+            &store,
+            INTERFACES,
+            &[("wlan0", entry(5000, true)), ("eth0", entry(9000, false))],
+        );
+
+        let state = load_state(&store, &keys(Layout::PerInterface, &[], &[]));
+        assert_eq!(state.iface.as_deref(), Some("wlan0"));
+        assert_eq!(state.rx.total, 5000);
+        assert_eq!(state.total.total, 5000);
+        assert!(state.total.cut_alert);
+
+        // the flat layouts read net/traffic instead
+        assert_eq!(load_state(&store, &keys(Layout::V2, &[], &[])).rx.total, 1);
+    }
+
+    #[test]
+    fn state_of_an_empty_store() {
+        let store = scratch_store("state-empty");
+        let none = DirectionState::default();
+        for keys in &[
+            keys(Layout::V2, &[], &[]),
+            keys(Layout::Legacy, &[], &[]),
+            keys(Layout::PerInterface, &[], &[]),
+        ] {
+            assert_eq!(
+                load_state(&store, keys),
+                MonitorState {
+                    iface: None,
+                    rx: none.clone(),
+                    tx: none.clone(),
+                    total: none.clone(),
+                    window_cut_alert: false,
+                    anomaly_alert: false,
+                    projection_over: false,
+                    upload_anomaly: false,
+                    budget_over: false,
+                    daily_budget: None,
+                    rx_rate: None,
+                    tx_rate: None,
+                    updated: None,
+                    paused: false,
+                    metered: true,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn totals_are_converted_to_bytes() {
+        let kb = Value::String("kb".to_string());
+        assert_eq!(
+            total_bytes(10, Some(&kb), Some(&Value::Uint(100))),
+            10 * 1024 + 100
+        );
+        assert_eq!(total_bytes(10, None, Some(&Value::Uint(100))), 10);
+        assert_eq!(total_bytes(u64::MAX, Some(&kb), None), u64::MAX);
+        assert_eq!(to_bytes(3), 3 * MEGABYTE);
+    }
+
+    /// Monitor polling the counters of a file, and the file
+    fn counters_monitor(name: &str, args: &[&str]) -> (Monitor, PathBuf) {
+        let dir = scratch_dir(name);
+        let counters = dir.join("counters");
+        fs::write(&counters, "1000 100").unwrap();
+        let command = format!("cat {}", counters.display());
+        let data_dir = dir.join("store");
+        let mut all = vec![
+            "--iface",
+            "eth9",
+            "--stats-command",
+            &command,
+            "--data-dir",
+            data_dir.to_str().unwrap(),
+            "--interval",
+            "0",
+        ];
+        all.extend_from_slice(args);
+        let _env = lock_env();
+        (Monitor::new(all).unwrap(), counters)
+    }
+
+    #[test]
+    fn callback_is_called_after_each_poll() {
+        let (mut monitor, counters) = counters_monitor("monitor-callback", &["--plan", "1MB"]);
+        let mut states = Vec::new();
+        monitor
+            .run_with(|state| {
+                states.push(state.clone());
+                // the counters advance by 1MB received and 100KB transmitted between polls
+                let polls = states.len() as u64;
+                let (rx, tx) = (1000 + polls * MEGABYTE, 100 + polls * 100 * 1024);
+                fs::write(&counters, format!("{} {}", rx, tx)).unwrap();
+                if states.len() < 3 {
+                    Flow::Continue
+                } else {
+                    Flow::Stop
+                }
+            })
+            .unwrap();
+
+        let totals: Vec<(u64, u64)> = states
+            .iter()
+            .map(|state| (state.rx.total, state.tx.total))
+            .collect();
+        assert_eq!(
+            totals,
+            [(0, 0), (MEGABYTE, 100 * 1024), (2 * MEGABYTE, 200 * 1024)]
+        );
+        // each poll sets the alerts of the plan, and stores the rates since the one before
+        let cut_alerts: Vec<bool> = states.iter().map(|state| state.total.cut_alert).collect();
+        assert_eq!(cut_alerts, [false, true, true]);
+        assert_eq!(states[0].total.cut, Some(MEGABYTE));
+        assert!(states[0].rx_rate.is_none() && states[0].updated.is_some());
+        assert!(states[1..].iter().all(|state| state.rx_rate.is_some()));
+        assert!(states[2].updated >= states[0].updated);
+
+        // the loop can be run again, carrying on from the polls before
+        assert_eq!(monitor.poll().unwrap().rx.total, 3 * MEGABYTE);
+    }
+
+    #[test]
+    fn stopping_ends_the_loop_at_once() {
+        let (mut monitor, counters) = counters_monitor("monitor-stop", &[]);
+        let mut calls = 0;
+        monitor
+            .run_with(|_| {
+                calls += 1;
+                Flow::Stop
+            })
+            .unwrap();
+        assert_eq!(calls, 1);
+        fs::write(&counters, "3000 200").unwrap();
+        let state = monitor.poll().unwrap();
+        assert_eq!((state.rx.total, state.tx.total), (2000, 100));
+    }
+
+    #[test]
+    fn failed_poll_ends_the_loop() {
+        let (mut monitor, counters) = counters_monitor("monitor-failed", &[]);
+        let mut calls = 0;
+        let result = monitor.run_with(|_| {
+            calls += 1;
+            if calls == 2 {
+                fs::remove_file(&counters).unwrap();
+            }
+            Flow::Continue
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn monitor_fails_on_invalid_options() {
+        let _env = lock_env();
+        assert!(Monitor::new(["--interval", "soon"]).is_err());
+        assert!(Monitor::new(["--alert-key", "total_cut_alert=warn_alert"]).is_err());
+    }
+
+    #[test]
+    fn thresholds_are_read_as_the_daemon_reads_them() {
+        let store = scratch_store("state-thresholds");
+        set(
+            &store,
+            NOTIFY,
+            &[
+                // a threshold of 0 is reached at once rather than disabled
+                ("rx_cut", Value::Uint(0)),
+                // a key holding something other than a number is skipped
+                ("data_cap", Value::String("20".to_string())),
+                ("total_cut", Value::Uint(10)),
+                ("total_warn", Value::Bool(true)),
+                ("warn", Value::Uint(8)),
+            ],
+        );
+        let keys = keys(Layout::V2, &["total_cut=data_cap"], &[]);
+        let state = load_state(&store, &keys);
+        assert_eq!(state.rx.cut, Some(0));
+        // though there is nothing to count down to it
+        assert_eq!(state.rx.remaining().cut, None);
+        assert_eq!(state.total.cut, Some(10 * MEGABYTE));
+        assert_eq!(state.total.warn, Some(8 * MEGABYTE));
+    }
+}
@@ -0,0 +1,45 @@
+//! Detection of whether the connection of an interface is metered.
+//!
+//! NetworkManager records whether each device's connection is metered
+//! (`GENERAL.METERED`), either as configured or guessed from the connection
+//! (e.g. a phone's hotspot), which `nmcli` can report.
+
+use std::process::Command;
+
+use log::warn;
+
+/// Parse NetworkManager's metered property (`yes`, `no`, `yes (guessed)`, ...)
+///
+/// `unknown` and anything unexpected yield `None`.
+fn parse(output: &str) -> Option<bool> {
+    match output.split_whitespace().next()? {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether NetworkManager reports the connection of an interface as metered
+///
+/// Returns `None` if `nmcli` cannot be run or does not know, which is logged.
+pub fn detect(iface: &str) -> Option<bool> {
+    let output = Command::new("nmcli")
+        .args(["--get-values", "GENERAL.METERED", "device", "show", iface])
+        .output();
+    let metered = match output {
+        Ok(output) if output.status.success() => parse(&String::from_utf8_lossy(&output.stdout)),
+        Ok(output) => {
+            warn!("nmcli failed to show {}: {}", iface, output.status);
+            return None;
+        }
+        Err(e) => {
+            warn!("Failed to run nmcli: {}", e);
+            return None;
+        }
+    };
+    if metered.is_none() {
+        warn!("NetworkManager does not know whether {} is metered", iface);
+    }
+
+    metered
+}
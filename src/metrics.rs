@@ -0,0 +1,172 @@
+//! OpenMetrics exposition of the usage totals, thresholds and alert flags.
+
+use std::fmt::Write;
+
+/// Content type of the exposition format
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Usage totals (bytes) of a monitored interface
+pub struct InterfaceTotals {
+    pub iface: String,
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// A threshold (bytes) or alert flag, identified by its direction and level
+pub struct Labelled<T> {
+    pub direction: &'static str,
+    pub level: &'static str,
+    pub value: T,
+}
+
+/// Escape a label value (backslashes, double quotes and newlines)
+fn escape_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render the metrics in the OpenMetrics text format
+///
+/// Totals are labelled by interface, while thresholds and alert flags are
+/// labelled by direction (`rx`, `tx` or `total`) and level (`warn` or `cut`)
+/// so that dashboards can draw the cap alongside usage.
+pub fn exposition(
+    ifaces: &[InterfaceTotals],
+    thresholds: &[Labelled<u64>],
+    alerts: &[Labelled<bool>],
+) -> String {
+    let mut out = String::new();
+
+    let counters = [
+        ("rx", "Bytes received since monitoring started"),
+        ("tx", "Bytes transmitted since monitoring started"),
+    ];
+    for (direction, help) in &counters {
+        let _ = writeln!(out, "# TYPE peach_monitor_{}_bytes counter", direction);
+        let _ = writeln!(out, "# UNIT peach_monitor_{}_bytes bytes", direction);
+        let _ = writeln!(out, "# HELP peach_monitor_{}_bytes {}.", direction, help);
+        for iface in ifaces {
+            let value = if *direction == "rx" {
+                iface.rx
+            } else {
+                iface.tx
+            };
+            let _ = writeln!(
+                out,
+                "peach_monitor_{}_bytes_total{{iface=\"{}\"}} {}",
+                direction,
+                escape_label(&iface.iface),
+                value
+            );
+        }
+    }
+
+    out.push_str("# TYPE peach_monitor_threshold_bytes gauge\n");
+    out.push_str("# UNIT peach_monitor_threshold_bytes bytes\n");
+    out.push_str("# HELP peach_monitor_threshold_bytes Configured alert thresholds.\n");
+    for threshold in thresholds {
+        let _ = writeln!(
+            out,
+            "peach_monitor_threshold_bytes{{direction=\"{}\",level=\"{}\"}} {}",
+            threshold.direction, threshold.level, threshold.value
+        );
+    }
+
+    out.push_str("# TYPE peach_monitor_alert gauge\n");
+    out.push_str("# HELP peach_monitor_alert Whether an alert flag is set (1) or not (0).\n");
+    for alert in alerts {
+        let _ = writeln!(
+            out,
+            "peach_monitor_alert{{direction=\"{}\",level=\"{}\"}} {}",
+            alert.direction, alert.level, alert.value as u8
+        );
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_interfaces_with_labels() {
+        let ifaces = [
+            InterfaceTotals {
+                iface: "wlan0".to_string(),
+                rx: 1234567,
+                tx: 89012,
+            },
+            InterfaceTotals {
+                iface: "eth0".to_string(),
+                rx: 10,
+                tx: 20,
+            },
+        ];
+        let thresholds = [Labelled {
+            direction: "total",
+            level: "cut",
+            value: 53687091200,
+        }];
+        let alerts = [
+            Labelled {
+                direction: "total",
+                level: "cut",
+                value: false,
+            },
+            Labelled {
+                direction: "rx",
+                level: "warn",
+                value: true,
+            },
+        ];
+
+        assert_eq!(
+            exposition(&ifaces, &thresholds, &alerts),
+            "# TYPE peach_monitor_rx_bytes counter\n\
+             # UNIT peach_monitor_rx_bytes bytes\n\
+             # HELP peach_monitor_rx_bytes Bytes received since monitoring started.\n\
+             peach_monitor_rx_bytes_total{iface=\"wlan0\"} 1234567\n\
+             peach_monitor_rx_bytes_total{iface=\"eth0\"} 10\n\
+             # TYPE peach_monitor_tx_bytes counter\n\
+             # UNIT peach_monitor_tx_bytes bytes\n\
+             # HELP peach_monitor_tx_bytes Bytes transmitted since monitoring started.\n\
+             peach_monitor_tx_bytes_total{iface=\"wlan0\"} 89012\n\
+             peach_monitor_tx_bytes_total{iface=\"eth0\"} 20\n\
+             # TYPE peach_monitor_threshold_bytes gauge\n\
+             # UNIT peach_monitor_threshold_bytes bytes\n\
+             # HELP peach_monitor_threshold_bytes Configured alert thresholds.\n\
+             peach_monitor_threshold_bytes{direction=\"total\",level=\"cut\"} 53687091200\n\
+             # TYPE peach_monitor_alert gauge\n\
+             # HELP peach_monitor_alert Whether an alert flag is set (1) or not (0).\n\
+             peach_monitor_alert{direction=\"total\",level=\"cut\"} 0\n\
+             peach_monitor_alert{direction=\"rx\",level=\"warn\"} 1\n\
+             # EOF\n"
+        );
+    }
+
+    #[test]
+    fn label_escaping() {
+        assert_eq!(escape_label("wlan0"), "wlan0");
+        assert_eq!(escape_label("a\"b"), "a\\\"b");
+        assert_eq!(escape_label("a\\b"), "a\\\\b");
+        assert_eq!(escape_label("a\nb"), "a\\nb");
+
+        let ifaces = [InterfaceTotals {
+            iface: "we\"ird\\".to_string(),
+            rx: 1,
+            tx: 2,
+        }];
+        let out = exposition(&ifaces, &[], &[]);
+        assert!(out.contains("peach_monitor_rx_bytes_total{iface=\"we\\\"ird\\\\\"} 1\n"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Traffic counters of nftables, by traffic class.
+//!
+//! Where firewall rules tag traffic with a class (e.g. by its DSCP mark, or
+//! by whether it leaves the local network) and count each class in named
+//! counters, only the classes which are metered need be accounted. The
+//! counters of a class are named `<class>_rx` and `<class>_tx`, in a table
+//! given with the settings, and are read with `nft -j list counters`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::process::Command;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+/// Suffixes of the counter names of a class, for received and transmitted traffic
+const RX_SUFFIX: &str = "_rx";
+const TX_SUFFIX: &str = "_tx";
+
+/// Traffic classes which are accounted
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selection {
+    /// Every class counted in the table
+    All,
+    /// Only the given classes
+    Only(Vec<String>),
+    /// Every class counted in the table but the given ones
+    Except(Vec<String>),
+}
+
+/// Table holding the class counters, and the classes to account, e.g.
+/// `table=peach,class=metered`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Counters {
+    family: String,
+    pub table: String,
+    selection: Selection,
+}
+
+impl FromStr for Counters {
+    type Err = String;
+
+    /// Parse comma-separated settings: `table=<name>` (required), `family=<family>`
+    /// (`inet` by default), then either `class=<class>` or `exclude=<class>`,
+    /// each of which may be repeated
+    fn from_str(s: &str) -> Result<Counters, String> {
+        let (mut family, mut table) = ("inet".to_string(), None);
+        let (mut only, mut except) = (Vec::new(), Vec::new());
+        for setting in s.split(',') {
+            let (name, value) = setting.split_once('=').ok_or_else(|| {
+                format!(
+                    "Invalid nftables setting: {} (expected name=value)",
+                    setting
+                )
+            })?;
+            let value = value.to_string();
+            match name {
+                "family" => family = value,
+                "table" => table = Some(value),
+                "class" => only.push(value),
+                "exclude" => except.push(value),
+                _ => return Err(format!("Unknown nftables setting: {}", name)),
+            }
+        }
+
+        let table = table.ok_or_else(|| format!("Missing table in nftables settings: {}", s))?;
+        let selection = match (only.is_empty(), except.is_empty()) {
+            (true, true) => Selection::All,
+            (false, true) => Selection::Only(only),
+            (true, false) => Selection::Except(except),
+            (false, false) => {
+                return Err("nftables classes are either selected (class=) or excluded \
+                    (exclude=), not both"
+                    .to_string())
+            }
+        };
+
+        Ok(Counters {
+            family,
+            table,
+            selection,
+        })
+    }
+}
+
+impl Counters {
+    /// Read the received and transmitted bytes of the accounted classes
+    pub fn get(&self) -> Result<(u64, u64), String> {
+        let output = Command::new("nft")
+            .args(["-j", "list", "counters", "table", &self.family, &self.table])
+            .output()
+            .map_err(|e| format!("failed to run nft: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "nft exited with {}: {}",
+                output.status,
+                stderr.trim()
+            ));
+        }
+
+        let counters = parse_counters(&String::from_utf8_lossy(&output.stdout))?;
+        select(&counters, &self.selection)
+    }
+}
+
+/// Parse the named counters (bytes) listed by `nft -j list counters`
+fn parse_counters(output: &str) -> Result<BTreeMap<String, u64>, String> {
+    let listing: Value =
+        serde_json::from_str(output).map_err(|e| format!("invalid nft output: {}", e))?;
+    let objects = listing["nftables"]
+        .as_array()
+        .ok_or("invalid nft output: no nftables array")?;
+
+    let mut counters = BTreeMap::new();
+    for counter in objects.iter().filter_map(|object| object.get("counter")) {
+        let name = counter["name"].as_str();
+        let bytes = counter["bytes"].as_u64();
+        match (name, bytes) {
+            (Some(name), Some(bytes)) => {
+                counters.insert(name.to_string(), bytes);
+            }
+            _ => return Err(format!("invalid nft counter: {}", counter)),
+        }
+    }
+
+    Ok(counters)
+}
+
+/// Sum the received and transmitted counters of the selected classes
+///
+/// Counters whose names do not end in `_rx` or `_tx` are not classes and are
+/// ignored. A class selected by name must be counted in both directions, so
+/// that a misspelt class or missing rule is reported rather than read as no
+/// traffic.
+fn select(counters: &BTreeMap<String, u64>, selection: &Selection) -> Result<(u64, u64), String> {
+    let classes: BTreeSet<&str> = counters
+        .keys()
+        .filter_map(|name| {
+            name.strip_suffix(RX_SUFFIX)
+                .or_else(|| name.strip_suffix(TX_SUFFIX))
+        })
+        .collect();
+    let selected: BTreeSet<&str> = match selection {
+        Selection::All => classes,
+        Selection::Except(excluded) => classes
+            .into_iter()
+            .filter(|class| !excluded.iter().any(|name| name == class))
+            .collect(),
+        Selection::Only(included) => {
+            for class in included {
+                for suffix in &[RX_SUFFIX, TX_SUFFIX] {
+                    let name = format!("{}{}", class, suffix);
+                    if !counters.contains_key(&name) {
+                        return Err(format!("no counter named {} for class {}", name, class));
+                    }
+                }
+            }
+            included.iter().map(String::as_str).collect()
+        }
+    };
+
+    let total = |suffix: &str| -> u64 {
+        selected
+            .iter()
+            .filter_map(|class| counters.get(&format!("{}{}", class, suffix)))
+            .sum()
+    };
+
+    Ok((total(RX_SUFFIX), total(TX_SUFFIX)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of `nft -j list counters` for the metered, lan and dscp46 classes
+    const LISTING: &str = r#"{"nftables": [
+        {"metainfo": {"version": "1.0.6", "json_schema_version": 1}},
+        {"counter": {"family": "inet", "name": "metered_rx", "table": "peach", "packets": 10, "bytes": 5000}},
+        {"counter": {"family": "inet", "name": "metered_tx", "table": "peach", "packets": 5, "bytes": 700}},
+        {"counter": {"family": "inet", "name": "lan_rx", "table": "peach", "packets": 90, "bytes": 90000}},
+        {"counter": {"family": "inet", "name": "lan_tx", "table": "peach", "packets": 80, "bytes": 80000}},
+        {"counter": {"family": "inet", "name": "dscp46_rx", "table": "peach", "packets": 3, "bytes": 300}},
+        {"counter": {"family": "inet", "name": "dscp46_tx", "table": "peach", "packets": 2, "bytes": 40}},
+        {"counter": {"family": "inet", "name": "dropped", "table": "peach", "packets": 1, "bytes": 1}}
+    ]}"#;
+
+    fn classes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn selected_classes_are_summed() {
+        let counters = parse_counters(LISTING).unwrap();
+        assert_eq!(counters.len(), 7);
+
+        for (selection, expected) in &[
+            (Selection::All, (95_300, 80_740)),
+            (Selection::Only(classes(&["metered"])), (5000, 700)),
+            (
+                Selection::Only(classes(&["metered", "dscp46"])),
+                (5300, 740),
+            ),
+            (Selection::Except(classes(&["lan"])), (5300, 740)),
+            (Selection::Except(classes(&["unknown"])), (95_300, 80_740)),
+        ] {
+            assert_eq!(
+                select(&counters, selection),
+                Ok(*expected),
+                "{:?}",
+                selection
+            );
+        }
+        assert_eq!(
+            select(&counters, &Selection::Only(classes(&["metred"]))),
+            Err("no counter named metred_rx for class metred".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_listings_are_errors() {
+        assert!(parse_counters("not json").is_err());
+        assert!(parse_counters(r#"{"counters": []}"#).is_err());
+        assert!(parse_counters(r#"{"nftables": [{"counter": {"name": "lan_rx"}}]}"#).is_err());
+    }
+
+    #[test]
+    fn settings_are_parsed() {
+        assert_eq!(
+            "table=peach,class=metered,class=dscp46".parse(),
+            Ok(Counters {
+                family: "inet".to_string(),
+                table: "peach".to_string(),
+                selection: Selection::Only(classes(&["metered", "dscp46"])),
+            })
+        );
+        assert_eq!(
+            "family=ip,table=peach,exclude=lan".parse(),
+            Ok(Counters {
+                family: "ip".to_string(),
+                table: "peach".to_string(),
+                selection: Selection::Except(classes(&["lan"])),
+            })
+        );
+        for invalid in &[
+            "class=metered",
+            "table=peach,class=metered,exclude=lan",
+            "table=peach,mark=1",
+        ] {
+            assert!(invalid.parse::<Counters>().is_err(), "{}", invalid);
+        }
+    }
+}
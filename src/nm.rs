@@ -0,0 +1,204 @@
+//! Data limits of NetworkManager connections.
+//!
+//! NetworkManager does not define data limits of its own, but keeps any user
+//! data attached to a connection profile (its `user` setting) without
+//! interpreting it. Limits are read from keys of that data named after a
+//! threshold (e.g. `peach-monitor.total_cut` = `50GB`), from the profile of
+//! the connection active on an interface. The profile is looked up over
+//! D-Bus with systemd's `busctl`, whose JSON output is parsed here.
+
+use std::process::Command;
+
+use log::{debug, warn};
+use serde_json::Value;
+
+use crate::keys::{threshold_name, Direction, Level};
+use crate::units::parse_size;
+
+/// D-Bus service of NetworkManager
+const SERVICE: &str = "org.freedesktop.NetworkManager";
+
+/// Prefix of the keys of the connection's user data holding limits
+const KEY_PREFIX: &str = "peach-monitor.";
+
+/// Run `busctl` against NetworkManager, returning its JSON output
+fn busctl(args: &[&str]) -> Result<Value, String> {
+    let output = Command::new("busctl")
+        .args(["--system", "--json=short"])
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run busctl: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "busctl exited with {}: {}",
+            output.status,
+            stderr.trim()
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("invalid busctl output: {}", e))
+}
+
+/// Object path of a reply or property, which `busctl` gives alone or as the only value of a call
+fn parse_path(reply: &Value) -> Result<String, String> {
+    let data = match reply["data"] {
+        Value::Array(ref values) => values.first(),
+        ref value => Some(value),
+    };
+    match data.and_then(Value::as_str) {
+        // the root path stands for no object, e.g. a device without an active connection
+        Some("/") | None => Err(format!("no object path in {}", reply)),
+        Some(path) => Ok(path.to_string()),
+    }
+}
+
+/// Limits in the user data of a connection's settings (as returned by `GetSettings`)
+///
+/// Keys which do not name a threshold, and values which are not sizes, are
+/// logged and skipped.
+fn parse_limits(settings: &Value) -> Vec<(Direction, Level, u64)> {
+    let data = &settings["data"][0]["user"]["data"]["data"];
+    let entries = match data.as_object() {
+        Some(entries) => entries,
+        None => return Vec::new(),
+    };
+
+    let mut limits = Vec::new();
+    for (key, value) in entries {
+        let name = match key.strip_prefix(KEY_PREFIX) {
+            Some(name) => name,
+            None => continue,
+        };
+        let threshold = Direction::ALL
+            .iter()
+            .flat_map(|&direction| Level::ALL.iter().map(move |&level| (direction, level)))
+            .find(|&(direction, level)| threshold_name(direction, level) == name);
+        let bytes = value.as_str().map(parse_size);
+        match (threshold, bytes) {
+            (Some((direction, level)), Some(Ok(bytes))) => limits.push((direction, level, bytes)),
+            (None, _) => warn!("Unknown threshold in NetworkManager user data: {}", key),
+            (_, _) => warn!(
+                "Invalid size in NetworkManager user data: {} = {}",
+                key, value
+            ),
+        }
+    }
+
+    limits
+}
+
+/// Limits set on the connection active on an interface
+///
+/// An interface without an active connection, or whose profile cannot be
+/// read, has none, which is logged.
+pub fn limits(iface: &str) -> Vec<(Direction, Level, u64)> {
+    let settings = busctl(&[
+        "call",
+        SERVICE,
+        "/org/freedesktop/NetworkManager",
+        SERVICE,
+        "GetDeviceByIpIface",
+        "s",
+        iface,
+    ])
+    .and_then(|reply| parse_path(&reply))
+    .and_then(|device| {
+        let interface = "org.freedesktop.NetworkManager.Device";
+        busctl(&[
+            "get-property",
+            SERVICE,
+            &device,
+            interface,
+            "ActiveConnection",
+        ])
+    })
+    .and_then(|reply| parse_path(&reply))
+    .and_then(|active| {
+        let interface = "org.freedesktop.NetworkManager.Connection.Active";
+        busctl(&["get-property", SERVICE, &active, interface, "Connection"])
+    })
+    .and_then(|reply| parse_path(&reply))
+    .and_then(|profile| {
+        let interface = "org.freedesktop.NetworkManager.Settings.Connection";
+        busctl(&["call", SERVICE, &profile, interface, "GetSettings"])
+    });
+
+    match settings {
+        Ok(settings) => {
+            let limits = parse_limits(&settings);
+            debug!("NetworkManager limits of {}: {:?}", iface, limits);
+            limits
+        }
+        Err(e) => {
+            warn!(
+                "Failed to read the NetworkManager connection of {}: {}",
+                iface, e
+            );
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn object_paths_of_replies() {
+        // a method call gives its values in an array, a property its value alone
+        let call = json!({"type": "o", "data": ["/org/freedesktop/NetworkManager/Devices/3"]});
+        let property =
+            json!({"type": "o", "data": "/org/freedesktop/NetworkManager/ActiveConnection/5"});
+        assert_eq!(
+            parse_path(&call).as_deref(),
+            Ok("/org/freedesktop/NetworkManager/Devices/3")
+        );
+        assert_eq!(
+            parse_path(&property).as_deref(),
+            Ok("/org/freedesktop/NetworkManager/ActiveConnection/5")
+        );
+        // a device without an active connection
+        assert!(parse_path(&json!({"type": "o", "data": "/"})).is_err());
+        assert!(parse_path(&json!({"type": "o", "data": []})).is_err());
+    }
+
+    #[test]
+    fn limits_in_the_user_data_of_a_connection() {
+        let settings = json!({
+            "type": "a{sa{sv}}",
+            "data": [{
+                "connection": {"id": {"type": "s", "data": "Hotspot"}},
+                "user": {"data": {"type": "a{ss}", "data": {
+                    "peach-monitor.total_cut": "50GB",
+                    "peach-monitor.rx_warn": "40GB",
+                    "peach-monitor.tx_over": "1GB",
+                    "peach-monitor.total_warn": "lots",
+                    "org.gnome.other": "1"
+                }}}
+            }]
+        });
+        let mut limits = parse_limits(&settings);
+        limits.sort_by_key(|&(_, _, bytes)| bytes);
+
+        let gigabytes = 1024 * 1024 * 1024;
+        assert_eq!(
+            limits,
+            [
+                (Direction::Rx, Level::Warn, 40 * gigabytes),
+                (Direction::Combined, Level::Cut, 50 * gigabytes),
+            ]
+        );
+    }
+
+    #[test]
+    fn connection_without_user_data_has_no_limits() {
+        let settings = json!({
+            "type": "a{sa{sv}}",
+            "data": [{"connection": {"id": {"type": "s", "data": "Home"}}}]
+        });
+        assert!(parse_limits(&settings).is_empty());
+    }
+}
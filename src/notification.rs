@@ -0,0 +1,330 @@
+//! Notification backends triggered when alert flags change.
+//!
+//! Any number of backends can be configured. Each change is delivered to
+//! every backend on its own thread, so that a backend which fails or hangs
+//! does not prevent the others from firing.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::thread::{self, JoinHandle};
+
+use log::{error, info};
+use serde_json::json;
+
+use crate::error::BoxError;
+use crate::hook;
+use crate::http;
+use crate::journal::Journal;
+use crate::units::format_bytes;
+
+/// A change of an alert flag
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub flag: String,
+    pub value: bool,
+    pub iface: String,
+    pub rx: u64, // received total (bytes)
+    pub tx: u64, // transmitted total (bytes)
+}
+
+impl Event {
+    /// One-line description of the change (e.g. `rx_cut_alert raised on wlan0`)
+    fn summary(&self) -> String {
+        let change = if self.value { "raised" } else { "cleared" };
+        format!("{} {} on {}", self.flag, change, self.iface)
+    }
+
+    /// Description of the totals at the time of the change
+    fn details(&self) -> String {
+        format!(
+            "{} received, {} transmitted",
+            format_bytes(self.rx),
+            format_bytes(self.tx)
+        )
+    }
+}
+
+/// Destination of notifications
+#[derive(Clone, Debug, PartialEq)]
+pub enum Backend {
+    /// Desktop notification through `notify-send`
+    Desktop,
+    /// JSON body posted to an `http://` URL
+    Webhook(String),
+    /// Email to an address through `sendmail`
+    Email(String),
+    /// Script receiving the change in environment variables
+    Script(PathBuf),
+    /// Entry in the systemd journal
+    Journal,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    /// Parse a backend (`notify-send`, `journal`, `webhook=<url>`, `email=<address>` or `script=<path>`)
+    fn from_str(s: &str) -> Result<Backend, String> {
+        let (kind, target) = match s.split_once('=') {
+            Some((kind, target)) => (kind, Some(target)),
+            None => (s, None),
+        };
+        match (kind, target) {
+            ("notify-send", None) => Ok(Backend::Desktop),
+            ("journal", None) => Ok(Backend::Journal),
+            ("webhook", Some(url)) => {
+                http::parse_url(url)?;
+                Ok(Backend::Webhook(url.to_string()))
+            }
+            ("email", Some(address)) if !address.is_empty() => {
+                Ok(Backend::Email(address.to_string()))
+            }
+            ("script", Some(path)) if !path.is_empty() => Ok(Backend::Script(PathBuf::from(path))),
+            _ => Err(format!(
+                "Unknown notification backend: {} (expected notify-send, journal, \
+                 webhook=<url>, email=<address> or script=<path>)",
+                s
+            )),
+        }
+    }
+}
+
+impl Backend {
+    /// Short description used in log messages
+    fn describe(&self) -> String {
+        match self {
+            Backend::Desktop => "notify-send".to_string(),
+            Backend::Webhook(url) => format!("webhook {}", url),
+            Backend::Email(address) => format!("email to {}", address),
+            Backend::Script(path) => format!("alert script {}", path.display()),
+            Backend::Journal => "journal".to_string(),
+        }
+    }
+
+    /// Deliver a notification of an event
+    fn send(&self, event: &Event) -> Result<(), BoxError> {
+        match self {
+            Backend::Desktop => run(Command::new("notify-send")
+                .arg("peach-monitor")
+                .arg(format!("{} ({})", event.summary(), event.details()))),
+            Backend::Webhook(url) => {
+                let body = json!({
+                    "flag": event.flag,
+                    "value": event.value,
+                    "iface": event.iface,
+                    "rx": event.rx,
+                    "tx": event.tx,
+                });
+                match http::post(url, "application/json", &body.to_string())? {
+                    200..=299 => Ok(()),
+                    status => Err(format!("server responded with status {}", status).into()),
+                }
+            }
+            Backend::Email(address) => {
+                let message = format!(
+                    "To: {}\nSubject: peach-monitor: {}\n\n{}\n",
+                    address,
+                    event.summary(),
+                    event.details()
+                );
+                let mut child = Command::new("sendmail")
+                    .arg("-t")
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(message.as_bytes())?;
+                }
+                exit_status(child.wait()?)
+            }
+            Backend::Script(path) => run(Command::new(path).envs(hook::alert_env(
+                &event.flag,
+                event.value,
+                &event.iface,
+                event.rx,
+                event.tx,
+            ))),
+            Backend::Journal => {
+                let fields = [
+                    ("FLAG".to_string(), event.flag.clone()),
+                    ("VALUE".to_string(), event.value.to_string()),
+                    ("IFACE".to_string(), event.iface.clone()),
+                ];
+                Journal::connect()?.send(&event.summary(), &fields)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Run a command to completion, failing if it exits unsuccessfully
+fn run(command: &mut Command) -> Result<(), BoxError> {
+    exit_status(command.status()?)
+}
+
+/// Turn an unsuccessful exit status into an error
+fn exit_status(status: std::process::ExitStatus) -> Result<(), BoxError> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("exited with {}", status).into())
+    }
+}
+
+/// Deliver an event to each backend in the background, logging the outcome
+///
+/// The returned handles may be joined to wait for delivery to finish.
+pub fn dispatch(backends: &[Backend], event: &Event) -> Vec<JoinHandle<()>> {
+    backends
+        .iter()
+        .map(|backend| {
+            let backend = backend.clone();
+            let event = event.clone();
+            thread::spawn(move || match backend.send(&event) {
+                Ok(()) => info!("Notified {} of {}", backend.describe(), event.summary()),
+                Err(e) => error!(
+                    "Failed to notify {} of {}: {}",
+                    backend.describe(),
+                    event.summary(),
+                    e
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Deliver an event to each backend in turn, waiting for each to finish
+///
+/// Returns the description of each backend along with the outcome of its delivery.
+pub fn deliver(backends: &[Backend], event: &Event) -> Vec<(String, Result<(), BoxError>)> {
+    backends
+        .iter()
+        .map(|backend| (backend.describe(), backend.send(event)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::mpsc;
+
+    use crate::tests::scratch_dir;
+
+    fn event() -> Event {
+        Event {
+            flag: "rx_cut_alert".to_string(),
+            value: true,
+            iface: "eth9".to_string(),
+            rx: 5000,
+            tx: 700,
+        }
+    }
+
+    /// Accept one webhook request, answering `204 No Content` and sending on its body
+    fn webhook() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (body_tx, body_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                header.clear();
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            body_tx.send(String::from_utf8(body).unwrap()).unwrap();
+        });
+        (url, body_rx)
+    }
+
+    #[test]
+    fn backend_names() {
+        assert_eq!("notify-send".parse(), Ok(Backend::Desktop));
+        assert_eq!("journal".parse(), Ok(Backend::Journal));
+        assert_eq!(
+            "email=ops@example.org".parse(),
+            Ok(Backend::Email("ops@example.org".to_string()))
+        );
+        assert_eq!(
+            "script=/etc/peach/alert".parse(),
+            Ok(Backend::Script(PathBuf::from("/etc/peach/alert")))
+        );
+        assert!("webhook=https://example.org/".parse::<Backend>().is_err());
+        assert!("email=".parse::<Backend>().is_err());
+        assert!("sms".parse::<Backend>().is_err());
+    }
+
+    #[test]
+    fn every_backend_receives_the_event() {
+        let dir = scratch_dir("notification");
+        let script = dir.join("alert");
+        let received = dir.join("received");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$PM_FLAG $PM_VALUE $PM_IFACE\" > {}\n",
+                received.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let (url, body) = webhook();
+
+        // a backend which fails does not keep the others from firing
+        let backends = [
+            Backend::Script(dir.join("missing")),
+            Backend::Script(script),
+            Backend::Webhook(url),
+        ];
+        for handle in dispatch(&backends, &event()) {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(
+            fs::read_to_string(&received).unwrap(),
+            "rx_cut_alert true eth9\n"
+        );
+        let body: serde_json::Value = serde_json::from_str(&body.recv().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({"flag": "rx_cut_alert", "value": true, "iface": "eth9", "rx": 5000, "tx": 700})
+        );
+    }
+
+    #[test]
+    fn outcome_of_each_delivery() {
+        let (url, _body) = webhook();
+        let missing = scratch_dir("notification-missing").join("missing");
+        let outcomes = deliver(
+            &[
+                Backend::Script(missing.clone()),
+                Backend::Webhook(url.clone()),
+            ],
+            &event(),
+        );
+        let described: Vec<&str> = outcomes.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            described,
+            [
+                format!("alert script {}", missing.display()),
+                format!("webhook {}", url)
+            ]
+        );
+        assert!(outcomes[0].1.is_err());
+        assert!(outcomes[1].1.is_ok());
+    }
+}
@@ -0,0 +1,224 @@
+//! Threshold profiles for times of day and days of the week.
+//!
+//! Some data plans meter traffic differently at certain times, such as an
+//! unmetered off-peak window at night or a larger allowance at weekends. A
+//! profile covers a daily window on a set of days and either overrides some
+//! of the thresholds while it is active or marks its traffic as unmetered.
+
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDateTime, Timelike, Weekday};
+
+use crate::keys::{threshold_name, Direction, Level};
+use crate::units::parse_size;
+
+/// Minutes in a day, the end of a window which runs until midnight
+const DAY: u32 = 24 * 60;
+
+/// Threshold profile, e.g. `days=sat-sun,window=00:00-24:00,total_cut=80GB`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub spec: String,
+    days: [bool; 7], // indexed by days from Monday
+    start: u32,      // minutes after midnight
+    end: u32,        // minutes after midnight, no later than `start` across midnight
+    /// Thresholds (bytes) overridden while the profile is active
+    pub thresholds: Vec<(Direction, Level, u64)>,
+    pub unmetered: bool,
+}
+
+/// Parse a time of day (`HH:MM`, up to `24:00`) into minutes after midnight
+fn parse_time(input: &str) -> Result<u32, String> {
+    let err = || format!("Invalid time: {} (expected HH:MM)", input);
+    let (hours, minutes) = input.split_once(':').ok_or_else(err)?;
+    let hours: u32 = hours.parse().map_err(|_| err())?;
+    let minutes: u32 = minutes.parse().map_err(|_| err())?;
+    let time = hours * 60 + minutes;
+    if minutes >= 60 || time > DAY {
+        return Err(err());
+    }
+
+    Ok(time)
+}
+
+/// Parse a day of the week or a range of days (e.g. `sat` or `mon-fri`)
+///
+/// Ranges wrap around the end of the week, so `fri-mon` covers four days.
+fn parse_days(input: &str) -> Result<[bool; 7], String> {
+    let day = |name: &str| {
+        name.parse::<Weekday>()
+            .map(|day| day.num_days_from_monday() as usize)
+            .map_err(|_| format!("Invalid day: {}", name))
+    };
+    let (first, last) = match input.split_once('-') {
+        Some((first, last)) => (day(first)?, day(last)?),
+        None => (day(input)?, day(input)?),
+    };
+
+    let mut days = [false; 7];
+    let mut index = first;
+    loop {
+        days[index] = true;
+        if index == last {
+            break;
+        }
+        index = (index + 1) % 7;
+    }
+
+    Ok(days)
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    /// Parse comma-separated settings: `window=<HH:MM>-<HH:MM>` (required),
+    /// `days=<day>[-<day>]`, `unmetered` and thresholds such as `total_warn=40GB`
+    fn from_str(s: &str) -> Result<Profile, String> {
+        let mut profile = Profile {
+            spec: s.to_string(),
+            days: [true; 7],
+            start: 0,
+            end: 0,
+            thresholds: Vec::new(),
+            unmetered: false,
+        };
+        let mut window = None;
+        for setting in s.split(',') {
+            match setting.split_once('=') {
+                Some(("window", range)) => {
+                    let (start, end) = range.split_once('-').ok_or_else(|| {
+                        format!("Invalid window: {} (expected HH:MM-HH:MM)", range)
+                    })?;
+                    window = Some((parse_time(start)?, parse_time(end)?));
+                }
+                Some(("days", days)) => profile.days = parse_days(days)?,
+                Some((name, size)) => {
+                    let (direction, level) = Direction::ALL
+                        .iter()
+                        .flat_map(|&direction| {
+                            Level::ALL.iter().map(move |&level| (direction, level))
+                        })
+                        .find(|&(direction, level)| threshold_name(direction, level) == name)
+                        .ok_or_else(|| format!("Unknown profile setting: {}", name))?;
+                    let bytes = parse_size(size).map_err(|e| e.to_string())?;
+                    profile.thresholds.push((direction, level, bytes));
+                }
+                None if setting == "unmetered" => profile.unmetered = true,
+                None => return Err(format!("Unknown profile setting: {}", setting)),
+            }
+        }
+
+        let (start, end) = window.ok_or_else(|| format!("Missing window in profile: {}", s))?;
+        profile.start = start;
+        profile.end = end;
+        Ok(profile)
+    }
+}
+
+impl Profile {
+    /// Whether the profile is active at a (local) time
+    ///
+    /// A window which crosses midnight belongs to the day it starts on, so
+    /// `days=fri,window=22:00-02:00` is active early on Saturday morning.
+    pub fn is_active(&self, time: &NaiveDateTime) -> bool {
+        let minute = time.hour() * 60 + time.minute();
+        let day = time.weekday().num_days_from_monday() as usize;
+        if self.start < self.end {
+            self.days[day] && (self.start..self.end).contains(&minute)
+        } else if minute >= self.start {
+            self.days[day]
+        } else {
+            // the morning part of a window which started the day before
+            minute < self.end && self.days[(day + 6) % 7]
+        }
+    }
+}
+
+/// The first of the profiles which is active at a (local) time
+pub fn active<'a>(profiles: &'a [Profile], time: &NaiveDateTime) -> Option<&'a Profile> {
+    profiles.iter().find(|profile| profile.is_active(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    /// Local time on a day of the week starting Monday 12 October 2026 (0 for Monday)
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 12 + day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn window_within_a_day() {
+        let profile: Profile = "window=02:00-06:00,unmetered".parse().unwrap();
+        assert!(profile.unmetered);
+        assert!(!profile.is_active(&at(0, 1, 59)));
+        assert!(profile.is_active(&at(0, 2, 0)));
+        assert!(profile.is_active(&at(3, 5, 59)));
+        // the end of the window is not part of it
+        assert!(!profile.is_active(&at(0, 6, 0)));
+        assert!(!profile.is_active(&at(0, 12, 0)));
+    }
+
+    #[test]
+    fn window_across_midnight_belongs_to_the_day_it_starts() {
+        let profile: Profile = "days=fri,window=22:00-02:00,total_cut=80GB"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            profile.thresholds,
+            [(Direction::Combined, Level::Cut, 80 * 1024 * 1024 * 1024)]
+        );
+        assert!(!profile.is_active(&at(4, 21, 59)));
+        assert!(profile.is_active(&at(4, 22, 0)));
+        assert!(profile.is_active(&at(4, 23, 59)));
+        assert!(profile.is_active(&at(5, 0, 0)));
+        assert!(profile.is_active(&at(5, 1, 59)));
+        assert!(!profile.is_active(&at(5, 2, 0)));
+        // not the night starting on Saturday, nor the early hours of Friday
+        assert!(!profile.is_active(&at(5, 22, 0)));
+        assert!(!profile.is_active(&at(4, 1, 0)));
+    }
+
+    #[test]
+    fn weekend_all_day() {
+        let profile: Profile = "days=sat-sun,window=00:00-24:00".parse().unwrap();
+        assert!(!profile.is_active(&at(4, 23, 59)));
+        assert!(profile.is_active(&at(5, 0, 0)));
+        assert!(profile.is_active(&at(6, 23, 59)));
+        assert!(!profile.is_active(&at(7, 0, 0)));
+    }
+
+    #[test]
+    fn first_active_profile_is_selected() {
+        let profiles: Vec<Profile> = [
+            "window=02:00-06:00,unmetered",
+            "window=00:00-24:00,rx_cut=1GB",
+        ]
+        .iter()
+        .map(|spec| spec.parse().unwrap())
+        .collect();
+        assert!(active(&profiles, &at(0, 3, 0)).unwrap().unmetered);
+        assert!(!active(&profiles, &at(0, 6, 0)).unwrap().unmetered);
+        assert_eq!(active(&profiles[..1], &at(0, 6, 0)), None);
+    }
+
+    #[test]
+    fn invalid_profiles() {
+        assert!("days=sat".parse::<Profile>().is_err());
+        assert!("window=25:00-02:00".parse::<Profile>().is_err());
+        assert!("window=02:60-03:00".parse::<Profile>().is_err());
+        assert!("window=02:00-03:00,days=someday"
+            .parse::<Profile>()
+            .is_err());
+        assert!("window=02:00-03:00,peak".parse::<Profile>().is_err());
+        assert!("window=02:00-03:00,total_cut=lots"
+            .parse::<Profile>()
+            .is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Transfer rates between daemon polls.
+//!
+//! Rates are measured against the monotonic clock, so that the wall clock
+//! being stepped (e.g. by NTP) between polls cannot produce a negative or
+//! wildly inflated rate. Wall-clock time is only used to tell people when
+//! the rates were last updated.
+
+use std::time::Instant;
+
+/// Transfer rates (bytes per second)
+#[derive(Debug, PartialEq)]
+pub struct Rate {
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Measures transfer rates from the traffic counted by successive polls
+#[derive(Debug, Default)]
+pub struct RateMeter {
+    last: Option<Instant>,
+}
+
+impl RateMeter {
+    pub fn new() -> RateMeter {
+        RateMeter::default()
+    }
+
+    /// Record the traffic (bytes) counted by a poll at an instant
+    ///
+    /// Returns the rates over the time since the previous poll, or `None`
+    /// for the first poll (whose traffic may have been counted over any
+    /// length of time) and when no time has elapsed.
+    pub fn record(&mut self, now: Instant, rx: u64, tx: u64) -> Option<Rate> {
+        let last = self.last.replace(now)?;
+        let elapsed = now.saturating_duration_since(last).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        let per_second = |bytes: u64| (bytes as f64 / elapsed).round() as u64;
+
+        Some(Rate {
+            rx: per_second(rx),
+            tx: per_second(tx),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn rates_over_the_monotonic_interval() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new();
+        assert_eq!(meter.record(start, 5000, 500), None);
+        assert_eq!(
+            meter.record(start + Duration::from_secs(60), 6000, 600),
+            Some(Rate { rx: 100, tx: 10 })
+        );
+        // no time elapsed
+        assert_eq!(
+            meter.record(start + Duration::from_secs(60), 6000, 600),
+            None
+        );
+    }
+
+    #[test]
+    fn clock_step_does_not_skew_the_rate() {
+        // polls a minute apart by the monotonic clock, whatever the wall clock did between them
+        let start = Instant::now();
+        let mut meter = RateMeter::new();
+        meter.record(start, 0, 0);
+        assert_eq!(
+            meter.record(start + Duration::from_secs(60), 60_000, 6000),
+            Some(Rate { rx: 1000, tx: 100 })
+        );
+        // an instant before the last, which the monotonic clock never gives, counts as no time
+        assert_eq!(meter.record(start, 60_000, 6000), None);
+    }
+}
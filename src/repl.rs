@@ -0,0 +1,165 @@
+//! Commands of the interactive `repl` mode.
+//!
+//! Each line read in the mode holds one command operating on the live data
+//! store, e.g. `get net/traffic/rx` or `set net/notify/rx_cut 5000`. Values
+//! are parsed as JSON, falling back to a string for a bare word.
+
+/// Commands and their arguments, shown by `help`
+pub const HELP: &str = "\
+get <path>                       print the value at a path (e.g. net/traffic/rx)
+set <path> <value>               store a value (JSON, or a bare string) at a path
+status                           print the status of the stored totals and flags
+reset [--confirm] [--rx-only|--tx-only]
+                                 zero the usage totals of the interface
+help                             print this help
+quit                             leave (as does end of input)";
+
+/// Command read from a line of input
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Get(Vec<String>),
+    Set(Vec<String>, serde_json::Value),
+    Status,
+    Reset {
+        confirm: bool,
+        rx_only: bool,
+        tx_only: bool,
+    },
+    Help,
+    Quit,
+    /// A blank line, which does nothing
+    Empty,
+}
+
+/// Split a path (e.g. `net/traffic/rx`) into its keys
+fn parse_path(path: &str) -> Result<Vec<String>, String> {
+    let keys: Vec<String> = path
+        .trim_matches('/')
+        .split('/')
+        .map(String::from)
+        .collect();
+    if keys.iter().any(|key| key.is_empty()) {
+        return Err(format!("Invalid path: {}", path));
+    }
+
+    Ok(keys)
+}
+
+/// Parse a line of input into a command
+pub fn parse(line: &str) -> Result<Input, String> {
+    let line = line.trim();
+    let (command, args) = match line.split_once(char::is_whitespace) {
+        Some((command, args)) => (command, args.trim()),
+        None => (line, ""),
+    };
+
+    match command {
+        "" => Ok(Input::Empty),
+        "get" if !args.is_empty() && !args.contains(char::is_whitespace) => {
+            Ok(Input::Get(parse_path(args)?))
+        }
+        "get" => Err("Usage: get <path>".to_string()),
+        "set" => {
+            let (path, value) = args
+                .split_once(char::is_whitespace)
+                .ok_or("Usage: set <path> <value>")?;
+            let path = parse_path(path)?;
+            // the data store only sets values up to one key deep within a file
+            if path.len() > 3 {
+                return Err(format!(
+                    "Cannot set {}: only keys directly within a file (e.g. net/notify/rx_cut) can be set",
+                    path.join("/")
+                ));
+            }
+            let value = value.trim();
+            let value = serde_json::from_str(value)
+                .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+            Ok(Input::Set(path, value))
+        }
+        "status" if args.is_empty() => Ok(Input::Status),
+        "reset" => {
+            let (mut confirm, mut rx_only, mut tx_only) = (false, false, false);
+            for arg in args.split_whitespace() {
+                match arg {
+                    "--confirm" => confirm = true,
+                    "--rx-only" => rx_only = true,
+                    "--tx-only" => tx_only = true,
+                    _ => return Err(format!("Unknown reset option: {}", arg)),
+                }
+            }
+            if rx_only && tx_only {
+                return Err("--rx-only cannot be used with --tx-only".to_string());
+            }
+            Ok(Input::Reset {
+                confirm,
+                rx_only,
+                tx_only,
+            })
+        }
+        "help" if args.is_empty() => Ok(Input::Help),
+        "quit" | "exit" if args.is_empty() => Ok(Input::Quit),
+        "status" | "help" | "quit" | "exit" => Err(format!("{} takes no arguments", command)),
+        _ => Err(format!("Unknown command: {} (try help)", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        use serde_json::json;
+
+        let path = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        for (line, input) in vec![
+            ("", Input::Empty),
+            (
+                "  get /net/traffic/rx/ ",
+                Input::Get(path(&["net", "traffic", "rx"])),
+            ),
+            (
+                "set net/notify/rx_cut 5000",
+                Input::Set(path(&["net", "notify", "rx_cut"]), json!(5000)),
+            ),
+            (
+                "set net/traffic/iface_last  eth0",
+                Input::Set(path(&["net", "traffic", "iface_last"]), json!("eth0")),
+            ),
+            (
+                "set net/alert/rx_cut_alert true",
+                Input::Set(path(&["net", "alert", "rx_cut_alert"]), json!(true)),
+            ),
+            ("status", Input::Status),
+            (
+                "reset --confirm --tx-only",
+                Input::Reset {
+                    confirm: true,
+                    rx_only: false,
+                    tx_only: true,
+                },
+            ),
+            ("help", Input::Help),
+            ("exit", Input::Quit),
+        ] {
+            assert_eq!(parse(line), Ok(input), "{}", line);
+        }
+    }
+
+    #[test]
+    fn invalid_commands_are_errors() {
+        for line in &[
+            "get",
+            "get net/traffic rx",
+            "get net//rx",
+            "set net/notify/rx_cut",
+            "set net/interfaces/eth0/rx 5",
+            "status now",
+            "reset --rx-only --tx-only",
+            "reset --force",
+            "delete net/traffic/rx",
+        ] {
+            assert!(parse(line).is_err(), "{}", line);
+        }
+    }
+}
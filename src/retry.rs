@@ -0,0 +1,78 @@
+//! Retrying of operations which may fail transiently.
+
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+
+/// Run an operation until it succeeds or the attempts are exhausted
+///
+/// The delay between attempts starts at `delay` and doubles after each
+/// failure. The error from the final attempt is returned if none succeed.
+pub fn retry_with_backoff<T, E, F>(attempts: u32, delay: Duration, mut op: F) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Result<T, E>,
+{
+    let mut delay = delay;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Ok(val) => return Ok(val),
+            Err(e) if attempt < attempts => {
+                warn!(
+                    "Attempt {} of {} failed: {}; retrying in {:?}",
+                    attempt, attempts, e, delay
+                );
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn succeeds_after_failures() {
+        let calls = Cell::new(0);
+        let result: Result<&str, String> = retry_with_backoff(3, Duration::from_millis(1), || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(format!("failure {}", calls.get()))
+            } else {
+                Ok("created")
+            }
+        });
+        assert_eq!(result, Ok("created"));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn returns_the_last_error_once_attempts_are_exhausted() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = retry_with_backoff(2, Duration::from_millis(1), || {
+            calls.set(calls.get() + 1);
+            Err(format!("failure {}", calls.get()))
+        });
+        assert_eq!(result, Err("failure 2".to_string()));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn no_retry_after_success() {
+        let calls = Cell::new(0);
+        let result: Result<u32, String> = retry_with_backoff(5, Duration::from_secs(60), || {
+            calls.set(calls.get() + 1);
+            Ok(7)
+        });
+        assert_eq!(result, Ok(7));
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -0,0 +1,201 @@
+//! Adaptive scheduling of daemon polls.
+//!
+//! Rather than polling at a fixed interval, the daemon can poll often while
+//! traffic is heading towards a threshold and back off while it is idle or
+//! far from any limit, saving power and flash writes on quiet devices.
+//!
+//! Intervals can also be jittered, so that a fleet of devices started at the
+//! same time does not keep polling (and notifying) in step, or aligned to
+//! wall-clock boundaries, so that polls fall at tidy times (e.g. every five
+//! minutes on the minute) which compare across devices.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Usage heading towards a threshold which has not been crossed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Approach {
+    pub remaining: u64, // bytes left before the threshold
+    pub rate: u64,      // bytes per second in the threshold's direction
+}
+
+/// Interval before the next poll, bounded between `min` and `max`
+///
+/// The interval is half the shortest time in which any threshold could be
+/// reached at the current rates, so that a crossing is reported soon after
+/// it happens. Without active traffic towards a threshold the interval
+/// backs off to `max`.
+pub fn next_interval(min: Duration, max: Duration, approaches: &[Approach]) -> Duration {
+    approaches
+        .iter()
+        .filter(|approach| approach.rate > 0)
+        .map(|approach| Duration::from_secs(approach.remaining / approach.rate / 2))
+        .min()
+        .unwrap_or(max)
+        .clamp(min, max)
+}
+
+/// Interval varied by `offset` (from -1 to 1) times a fraction of itself
+///
+/// With offsets spread evenly over the range, the jittered intervals stay
+/// within `fraction` of the interval and average out to it.
+pub fn jitter(interval: Duration, fraction: f64, offset: f64) -> Duration {
+    interval.mul_f64(1.0 + fraction * offset.clamp(-1.0, 1.0))
+}
+
+/// Random offset spread evenly from -1 to 1, for jittering an interval
+///
+/// The randomly keyed hasher of the standard library is enough to spread
+/// polls out without a dependency on a random number generator.
+pub fn random_offset() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+/// Time from `now` (since the unix epoch) until the next local wall-clock boundary of an interval
+///
+/// Boundaries are the multiples of the interval since local midnight of the
+/// epoch, `offset` being the seconds the local time is ahead of UTC, so that
+/// e.g. hourly polls fall on the local hour. At a boundary, the next one is
+/// a whole interval away.
+pub fn until_boundary(now: Duration, offset: i64, interval: Duration) -> Duration {
+    let interval = interval.as_nanos() as i128;
+    if interval == 0 {
+        return Duration::from_secs(0);
+    }
+    let local = now.as_nanos() as i128 + i128::from(offset) * 1_000_000_000;
+    Duration::from_nanos((interval - local.rem_euclid(interval)) as u64)
+}
+
+/// Shortest wait before the first aligned poll after the start
+const FIRST_ALIGNED_WAIT: Duration = Duration::from_secs(1);
+
+/// Time until the boundary of an interval the next poll is aligned to
+///
+/// This is the next boundary, except that before the first aligned poll a
+/// boundary less than a second away is skipped, so that it does not measure
+/// rates over next to no time since the poll made at the start.
+pub fn aligned_wait(now: Duration, offset: i64, interval: Duration, first: bool) -> Duration {
+    let wait = until_boundary(now, offset, interval);
+    if first && wait < FIRST_ALIGNED_WAIT {
+        wait + interval
+    } else {
+        wait
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Duration = Duration::from_secs(10);
+    const MAX: Duration = Duration::from_secs(600);
+
+    #[test]
+    fn idle_backs_off_to_the_maximum() {
+        assert_eq!(next_interval(MIN, MAX, &[]), MAX);
+        let idle = Approach {
+            remaining: 1000,
+            rate: 0,
+        };
+        assert_eq!(next_interval(MIN, MAX, &[idle]), MAX);
+    }
+
+    #[test]
+    fn half_the_time_to_the_nearest_threshold() {
+        let far = Approach {
+            remaining: 10_000_000,
+            rate: 1000,
+        };
+        let near = Approach {
+            remaining: 120_000,
+            rate: 1000,
+        };
+        assert_eq!(next_interval(MIN, MAX, &[far]), MAX);
+        assert_eq!(
+            next_interval(MIN, MAX, &[far, near]),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn threshold_about_to_be_reached_polls_at_the_minimum() {
+        let close = Approach {
+            remaining: 500,
+            rate: 1000,
+        };
+        assert_eq!(next_interval(MIN, MAX, &[close]), MIN);
+    }
+
+    #[test]
+    fn jittered_intervals_stay_within_the_fraction() {
+        let interval = Duration::from_secs(60);
+        assert_eq!(jitter(interval, 0.1, -1.0), Duration::from_secs(54));
+        assert_eq!(jitter(interval, 0.1, 1.0), Duration::from_secs(66));
+        assert_eq!(jitter(interval, 0.1, 0.0), interval);
+        // offsets out of range are clamped
+        assert_eq!(jitter(interval, 0.1, 5.0), Duration::from_secs(66));
+
+        let (low, high) = (Duration::from_secs(54), Duration::from_secs(66));
+        let jittered: Vec<Duration> = (0..1000)
+            .map(|_| jitter(interval, 0.1, random_offset()))
+            .collect();
+        assert!(jittered.iter().all(|&wait| low <= wait && wait <= high));
+        // the jitter averages out to the interval
+        let average = jittered.iter().sum::<Duration>() / jittered.len() as u32;
+        assert!(average > Duration::from_secs(58) && average < Duration::from_secs(62));
+    }
+
+    #[test]
+    fn random_offsets_are_spread_over_the_range() {
+        let offsets: Vec<f64> = (0..1000).map(|_| random_offset()).collect();
+        assert!(offsets.iter().all(|offset| (-1.0..1.0).contains(offset)));
+        assert!(offsets.iter().any(|&offset| offset < -0.5));
+        assert!(offsets.iter().any(|&offset| offset > 0.5));
+    }
+
+    #[test]
+    fn waits_until_the_next_boundary() {
+        let secs = Duration::from_secs;
+        let five_minutes = secs(300);
+        // 2025-10-09 12:03:20 UTC
+        let now = secs(1_760_011_400);
+        assert_eq!(until_boundary(now, 0, five_minutes), secs(100));
+        // at a boundary the next one is a whole interval away
+        assert_eq!(
+            until_boundary(secs(1_760_011_500), 0, five_minutes),
+            five_minutes
+        );
+        assert_eq!(
+            until_boundary(now + Duration::from_millis(250), 0, five_minutes),
+            Duration::from_millis(99_750)
+        );
+
+        // hours are counted from the local midnight, here half an hour off UTC
+        let hour = secs(3600);
+        assert_eq!(until_boundary(now, 0, hour), secs(3400));
+        assert_eq!(until_boundary(now, 1800, hour), secs(1600));
+        assert_eq!(until_boundary(now, -1800, hour), secs(1600));
+        assert_eq!(until_boundary(now, 0, secs(0)), secs(0));
+    }
+
+    #[test]
+    fn first_aligned_wait_skips_a_boundary_just_ahead() {
+        let interval = Duration::from_secs(60);
+        let just_before = Duration::from_millis(1_760_011_439_500);
+        assert_eq!(
+            aligned_wait(just_before, 0, interval, true),
+            Duration::from_millis(60_500)
+        );
+        assert_eq!(
+            aligned_wait(just_before, 0, interval, false),
+            Duration::from_millis(500)
+        );
+        let even = Duration::from_secs(1_760_011_410);
+        assert_eq!(
+            aligned_wait(even, 0, interval, true),
+            Duration::from_secs(30)
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Temporary shaping of an interface while its rate stays above a limit.
+//!
+//! Volume cutoffs only act once a cap is used up, by which time a single
+//! device may have saturated a shared metered uplink for hours. Shaping acts
+//! on the rate instead: the combined rate of each daemon poll is smoothed
+//! with an exponentially weighted moving average, so that a short burst does
+//! not count as sustained throughput, and only the crossings of the smoothed
+//! rate (its edges) run a command: the shaping command as it rises above the
+//! limit, and the restoring command as it falls back to the restore rate.
+
+use std::process::Command;
+
+/// Crossing of the smoothed rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    /// The smoothed rate rose above the limit: the interface is to be shaped
+    Rising,
+    /// The smoothed rate fell back to the restore rate: the interface is to be restored
+    Falling,
+}
+
+/// Tracks the smoothed rate and whether the interface is shaped
+#[derive(Debug)]
+pub struct Shaper {
+    /// Rate (bytes per second) above which the interface is shaped
+    limit: u64,
+    /// Rate (bytes per second) at or below which a shaped interface is restored
+    restore: u64,
+    /// Weight of each new rate in the average, above 0 and below 1
+    smoothing: f64,
+    smoothed: Option<f64>,
+    shaped: bool,
+}
+
+impl Shaper {
+    /// Track rates against a limit, restoring the interface at a lower rate (if any)
+    ///
+    /// A restore rate above the limit is lowered to it.
+    pub fn new(limit: u64, restore: Option<u64>, smoothing: f64) -> Shaper {
+        Shaper {
+            limit,
+            restore: restore.map_or(limit, |restore| restore.min(limit)),
+            smoothing,
+            smoothed: None,
+            shaped: false,
+        }
+    }
+
+    /// Smoothed rate (bytes per second), once a rate has been recorded
+    pub fn smoothed(&self) -> Option<u64> {
+        self.smoothed.map(|rate| rate.round() as u64)
+    }
+
+    /// Whether the interface is shaped
+    pub fn is_shaped(&self) -> bool {
+        self.shaped
+    }
+
+    /// Record the combined rate (bytes per second) of a poll
+    ///
+    /// The first rate is taken as the average. Returns the edge crossed by
+    /// the smoothed rate, if any: while the interface stays shaped (or
+    /// unshaped) nothing is returned, so that the commands only run once
+    /// per crossing.
+    pub fn record(&mut self, rate: u64) -> Option<Edge> {
+        let rate = rate as f64;
+        let smoothed = match self.smoothed {
+            Some(average) => average + self.smoothing * (rate - average),
+            None => rate,
+        };
+        self.smoothed = Some(smoothed);
+
+        if !self.shaped && smoothed > self.limit as f64 {
+            self.shaped = true;
+            Some(Edge::Rising)
+        } else if self.shaped && smoothed <= self.restore as f64 {
+            self.shaped = false;
+            Some(Edge::Falling)
+        } else {
+            None
+        }
+    }
+}
+
+/// Run a shaping or restoring command with `sh -c`
+///
+/// The interface name and the limit (bytes per second) are passed in the
+/// `PM_IFACE` and `PM_RATE` environment variables.
+pub fn run(command: &str, iface: &str, limit: u64) -> Result<(), String> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("PM_IFACE", iface)
+        .env("PM_RATE", limit.to_string())
+        .status()
+        .map_err(|e| format!("failed to run {}: {}", command, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", command, status));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_of_the_smoothed_rate() {
+        let mut shaper = Shaper::new(100, Some(50), 0.5);
+        assert_eq!(shaper.record(80), None);
+        assert_eq!(shaper.smoothed(), Some(80));
+        assert_eq!(shaper.record(200), Some(Edge::Rising));
+        assert_eq!(shaper.smoothed(), Some(140));
+        assert!(shaper.is_shaped());
+        // staying above the limit is not another edge
+        assert_eq!(shaper.record(300), None);
+
+        // falling below the limit but not to the restore rate keeps it shaped
+        assert_eq!(shaper.record(0), None);
+        assert_eq!(shaper.record(0), None);
+        assert_eq!(shaper.smoothed(), Some(55));
+        assert_eq!(shaper.record(0), Some(Edge::Falling));
+        assert!(!shaper.is_shaped());
+        assert_eq!(shaper.record(0), None);
+    }
+
+    #[test]
+    fn short_bursts_are_smoothed_out() {
+        let mut shaper = Shaper::new(100, None, 0.25);
+        assert_eq!(shaper.record(0), None);
+        assert_eq!(shaper.record(300), None);
+        assert_eq!(shaper.record(0), None);
+        assert!(!shaper.is_shaped());
+    }
+
+    #[test]
+    fn restore_rate_is_at_most_the_limit() {
+        let mut shaper = Shaper::new(100, Some(200), 1.0);
+        assert_eq!(shaper.record(150), Some(Edge::Rising));
+        assert_eq!(shaper.record(120), None);
+        assert_eq!(shaper.record(100), Some(Edge::Falling));
+        // the limit itself is not above it
+        assert_eq!(shaper.record(100), None);
+    }
+}
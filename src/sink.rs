@@ -0,0 +1,145 @@
+//! Backends recording the usage counted by each save.
+//!
+//! The nest data store holds the totals which alerts are evaluated against
+//! and is always written first, since the usage counted by a save is only
+//! known once its totals are stored, and a failure to store them fails the
+//! save. It is therefore not a `Sink` itself. The usage is then written to
+//! each of the other backends registered (e.g. SQLite databases), which are
+//! built once rather than for each save. A backend which fails, such as a
+//! database locked by another process, is logged and skipped, so that it
+//! neither keeps the others from being written nor fails the save.
+
+use log::error;
+
+/// Usage (bytes) counted by a save
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record<'a> {
+    /// Time of the reading (unix seconds)
+    pub time: u64,
+    pub iface: &'a str,
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Backend which the usage counted by each save is written to
+pub trait Sink {
+    /// Name of the backend in log messages (e.g. `sqlite:/var/lib/samples.db`)
+    fn name(&self) -> String;
+
+    /// Write the usage counted by a save
+    fn write(&self, record: &Record) -> Result<(), String>;
+}
+
+/// Write the usage counted by a save to each backend, returning how many were written
+///
+/// Every backend is written in turn whatever the outcome of those before it.
+pub fn write_all(sinks: &[Box<dyn Sink>], record: &Record) -> usize {
+    let mut written = 0;
+    for sink in sinks {
+        match sink.write(record) {
+            Ok(()) => written += 1,
+            Err(e) => error!("Failed to write usage to {}: {}", sink.name(), e),
+        }
+    }
+
+    written
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Time, interface, received and transmitted usage of each record written
+    pub(crate) type Records = Rc<RefCell<Vec<(u64, String, u64, u64)>>>;
+
+    /// Backend keeping the records written to it, or failing each write
+    pub(crate) struct Recorder {
+        pub name: &'static str,
+        pub records: Records,
+        pub fails: bool,
+    }
+
+    impl Recorder {
+        pub fn new(name: &'static str, fails: bool) -> Recorder {
+            Recorder {
+                name,
+                records: Rc::new(RefCell::new(Vec::new())),
+                fails,
+            }
+        }
+    }
+
+    impl Sink for Recorder {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn write(&self, record: &Record) -> Result<(), String> {
+            if self.fails {
+                return Err("database is locked".to_string());
+            }
+            let Record {
+                time,
+                iface,
+                rx,
+                tx,
+            } = *record;
+            self.records
+                .borrow_mut()
+                .push((time, iface.to_string(), rx, tx));
+            Ok(())
+        }
+    }
+
+    const RECORD: Record = Record {
+        time: 1_760_000_000,
+        iface: "wlan0",
+        rx: 5000,
+        tx: 700,
+    };
+
+    #[test]
+    fn writes_reach_every_backend() {
+        let (first, second) = (
+            Recorder::new("first", false),
+            Recorder::new("second", false),
+        );
+        let records = [first.records.clone(), second.records.clone()];
+        let sinks: Vec<Box<dyn Sink>> = vec![Box::new(first), Box::new(second)];
+
+        assert_eq!(write_all(&sinks, &RECORD), 2);
+        assert_eq!(write_all(&sinks, &Record { rx: 10, ..RECORD }), 2);
+        for records in &records {
+            assert_eq!(
+                *records.borrow(),
+                [
+                    (1_760_000_000, "wlan0".to_string(), 5000, 700),
+                    (1_760_000_000, "wlan0".to_string(), 10, 700),
+                ]
+            );
+        }
+        assert_eq!(write_all(&[], &RECORD), 0);
+    }
+
+    #[test]
+    fn failing_backend_is_isolated() {
+        let (before, after) = (
+            Recorder::new("before", false),
+            Recorder::new("after", false),
+        );
+        let records = [before.records.clone(), after.records.clone()];
+        let sinks: Vec<Box<dyn Sink>> = vec![
+            Box::new(before),
+            Box::new(Recorder::new("locked", true)),
+            Box::new(after),
+        ];
+
+        assert_eq!(write_all(&sinks, &RECORD), 2);
+        for records in &records {
+            assert_eq!(records.borrow().len(), 1);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Snapshots of the full monitor state, for backups and device migration.
+//!
+//! A snapshot holds the contents of each file of the data store within
+//! `net` (totals, thresholds, alert flags, history, ...), tagged with its
+//! format and version so that a file which is not a snapshot, or one
+//! written by an incompatible version, is refused before anything is
+//! restored from it.
+//!
+//! Two snapshots can also be compared, e.g. to see how much was used between
+//! two backups or to check a migration. Their totals, thresholds and alert
+//! flags are compared key by key, with nested objects (such as the totals of
+//! each interface) flattened into dotted paths like `traffic.ifaces.wlan0.rx`.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+
+use crate::identity::Identity;
+use crate::keys::{ALERT, FILES, NOTIFY, TRAFFIC};
+
+/// Identifies a file as a snapshot
+const FORMAT: &str = "peach-monitor-snapshot";
+/// Version of the snapshot layout, raised on incompatible changes
+const VERSION: u64 = 1;
+
+/// Build a snapshot from the files of the data store, keyed by name, taken at a time (unix seconds)
+///
+/// The identifier of the device, if given, is recorded alongside (e.g.
+/// `"identity": { "kind": "mac", "value": "b8:27:eb:12:34:56" }`), and is
+/// not restored.
+pub fn create(files: Map<String, Value>, created: u64, identity: Option<&Identity>) -> Value {
+    let mut snapshot = json!({
+        "format": FORMAT,
+        "version": VERSION,
+        "created": created,
+        "net": files,
+    });
+    if let Some(identity) = identity {
+        snapshot["identity"] = json!({ "kind": identity.kind, "value": identity.value });
+    }
+
+    snapshot
+}
+
+/// Check a snapshot, returning the time it was taken (unix seconds) and the
+/// files of the data store it holds
+pub fn validate(snapshot: &Value) -> Result<(Option<u64>, &Map<String, Value>), String> {
+    if snapshot.get("format").and_then(Value::as_str) != Some(FORMAT) {
+        return Err("Not a peach-monitor snapshot".to_string());
+    }
+    match snapshot.get("version").and_then(Value::as_u64) {
+        Some(VERSION) => (),
+        Some(version) => {
+            return Err(format!(
+                "Unsupported snapshot version: {} (expected {})",
+                version, VERSION
+            ))
+        }
+        None => return Err("Snapshot has no version".to_string()),
+    }
+
+    let files = snapshot
+        .get("net")
+        .and_then(Value::as_object)
+        .ok_or("Snapshot holds no data store files")?;
+    for (file, contents) in files {
+        if !FILES.contains(&file.as_str()) {
+            return Err(format!("Unknown data store file in snapshot: {}", file));
+        }
+        if !contents.is_object() {
+            return Err(format!(
+                "Data store file {} in snapshot is not an object",
+                file
+            ));
+        }
+    }
+
+    Ok((snapshot.get("created").and_then(Value::as_u64), files))
+}
+
+/// Files of the data store compared between snapshots: totals, thresholds and alert flags
+const COMPARED: [&str; 3] = [TRAFFIC, NOTIFY, ALERT];
+
+/// Difference of a key between two snapshots
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// The key is only in the later snapshot
+    Added(Value),
+    /// The key is only in the earlier snapshot
+    Removed(Value),
+    /// The key holds a different value in each snapshot
+    Changed(Value, Value),
+}
+
+impl Change {
+    /// Value in the earlier snapshot, if any
+    pub fn before(&self) -> Option<&Value> {
+        match self {
+            Change::Added(_) => None,
+            Change::Removed(before) | Change::Changed(before, _) => Some(before),
+        }
+    }
+
+    /// Value in the later snapshot, if any
+    pub fn after(&self) -> Option<&Value> {
+        match self {
+            Change::Removed(_) => None,
+            Change::Added(after) | Change::Changed(_, after) => Some(after),
+        }
+    }
+
+    /// Difference between two numbers (later minus earlier), e.g. of a total
+    pub fn difference(&self) -> Option<i128> {
+        match self {
+            Change::Changed(before, after) => {
+                Some(i128::from(after.as_u64()?) - i128::from(before.as_u64()?))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Values of the compared files of a snapshot, keyed by dotted path
+fn flatten(files: &Map<String, Value>) -> BTreeMap<String, Value> {
+    fn walk(path: String, value: &Value, values: &mut BTreeMap<String, Value>) {
+        match value {
+            Value::Object(entries) => {
+                for (key, value) in entries {
+                    walk(format!("{}.{}", path, key), value, values);
+                }
+            }
+            value => {
+                values.insert(path, value.clone());
+            }
+        }
+    }
+
+    let mut values = BTreeMap::new();
+    for &file in &COMPARED {
+        if let Some(contents) = files.get(file) {
+            walk(file.to_string(), contents, &mut values);
+        }
+    }
+    values
+}
+
+/// Keys whose values differ between the files of two snapshots, by dotted path
+///
+/// Keys holding the same value in both are left out.
+pub fn compare(before: &Map<String, Value>, after: &Map<String, Value>) -> Vec<(String, Change)> {
+    let (mut before, after) = (flatten(before), flatten(after));
+    let mut changes: Vec<(String, Change)> = Vec::new();
+    for (path, value) in after {
+        match before.remove(&path) {
+            None => changes.push((path, Change::Added(value))),
+            Some(previous) if previous != value => {
+                changes.push((path, Change::Changed(previous, value)))
+            }
+            Some(_) => (),
+        }
+    }
+    changes.extend(
+        before
+            .into_iter()
+            .map(|(path, value)| (path, Change::Removed(value))),
+    );
+    changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn created_snapshot_is_valid() {
+        let mut files = Map::new();
+        files.insert(TRAFFIC.to_string(), json!({"rx": 5000, "tx": 700}));
+        let snapshot = create(files.clone(), 1_760_000_000, None);
+
+        assert_eq!(validate(&snapshot), Ok((Some(1_760_000_000), &files)));
+        assert!(snapshot.get("identity").is_none());
+    }
+
+    #[test]
+    fn invalid_snapshots_are_refused() {
+        let valid = || create(Map::new(), 1_760_000_000, None);
+        let mut newer = valid();
+        newer["version"] = json!(VERSION + 1);
+        let mut unversioned = valid();
+        unversioned.as_object_mut().unwrap().remove("version");
+        let mut unknown = valid();
+        unknown["net"] = json!({"passwords": {}});
+        let mut scalar = valid();
+        scalar["net"] = json!({ TRAFFIC: 5000 });
+
+        for (snapshot, error) in &[
+            (json!({"rx": 5000}), "Not a peach-monitor snapshot"),
+            (newer, "Unsupported snapshot version: 2 (expected 1)"),
+            (unversioned, "Snapshot has no version"),
+            (unknown, "Unknown data store file in snapshot: passwords"),
+            (
+                scalar,
+                "Data store file traffic in snapshot is not an object",
+            ),
+        ] {
+            assert_eq!(validate(snapshot).unwrap_err(), *error);
+        }
+    }
+
+    fn files(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn snapshots_are_compared_key_by_key() {
+        let before = files(json!({
+            "traffic": {"rx": 5000, "tx": 700, "ifaces": {"wlan0": {"rx": 5000}}},
+            "notify": {"total_cut": 1024, "rx_warn": 512},
+            "alert": {"total_warn": false},
+            "history": {"2026-10-01": {"rx": 5000}},
+        }));
+        let after = files(json!({
+            "traffic": {"rx": 9000, "tx": 700, "ifaces": {"wlan0": {"rx": 6000}, "eth0": {"rx": 3000}}},
+            "notify": {"total_cut": 2048},
+            "alert": {"total_warn": true},
+            "history": {"2026-10-02": {"rx": 4000}},
+        }));
+
+        assert_eq!(
+            compare(&before, &after),
+            vec![
+                (
+                    "alert.total_warn".to_string(),
+                    Change::Changed(json!(false), json!(true))
+                ),
+                ("notify.rx_warn".to_string(), Change::Removed(json!(512))),
+                (
+                    "notify.total_cut".to_string(),
+                    Change::Changed(json!(1024), json!(2048))
+                ),
+                (
+                    "traffic.ifaces.eth0.rx".to_string(),
+                    Change::Added(json!(3000))
+                ),
+                (
+                    "traffic.ifaces.wlan0.rx".to_string(),
+                    Change::Changed(json!(5000), json!(6000))
+                ),
+                (
+                    "traffic.rx".to_string(),
+                    Change::Changed(json!(5000), json!(9000))
+                ),
+            ]
+        );
+        assert!(compare(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn missing_files_are_compared_as_empty() {
+        let before = files(json!({"traffic": {"rx": 5000}}));
+        let after = files(json!({"alert": {"total_cut": true}}));
+        assert_eq!(
+            compare(&before, &after),
+            vec![
+                ("alert.total_cut".to_string(), Change::Added(json!(true))),
+                ("traffic.rx".to_string(), Change::Removed(json!(5000))),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_of_numbers_have_a_difference() {
+        let grown = Change::Changed(json!(5000), json!(9000));
+        assert_eq!(grown.difference(), Some(4000));
+        assert_eq!(
+            Change::Changed(json!(9000), json!(5000)).difference(),
+            Some(-4000)
+        );
+        assert_eq!(
+            Change::Changed(json!(false), json!(true)).difference(),
+            None
+        );
+        assert_eq!(Change::Added(json!(3000)).difference(), None);
+
+        assert_eq!(grown.before(), Some(&json!(5000)));
+        assert_eq!(grown.after(), Some(&json!(9000)));
+        assert_eq!(Change::Added(json!(3000)).before(), None);
+        assert_eq!(Change::Removed(json!(512)).after(), None);
+    }
+
+    #[test]
+    fn identity_is_recorded_in_the_snapshot() {
+        let identity = Identity {
+            kind: "mac",
+            value: "b8:27:eb:12:34:56".to_string(),
+        };
+        let snapshot = create(Map::new(), 1_760_000_000, Some(&identity));
+        assert_eq!(
+            snapshot["identity"],
+            json!({ "kind": "mac", "value": "b8:27:eb:12:34:56" })
+        );
+        assert!(validate(&snapshot).is_ok());
+    }
+}
@@ -0,0 +1,255 @@
+//! Traffic counters of a router's interface, polled over SNMP.
+//!
+//! Where the metered connection terminates on a separate router, its
+//! counters are read from the router with net-snmp's `snmpget`. Only the
+//! 64-bit counters of the interface extensions table (`ifHCInOctets` and
+//! `ifHCOutOctets`) are read: the 32-bit `ifInOctets` and `ifOutOctets`
+//! wrap after 4GB, within minutes on a fast link, and a wrap between two
+//! polls could not be told apart from a reset of the counters.
+
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Received bytes of an interface, by index (`IF-MIB::ifHCInOctets`)
+const IF_HC_IN_OCTETS: &str = "1.3.6.1.2.1.31.1.1.1.6";
+/// Transmitted bytes of an interface, by index (`IF-MIB::ifHCOutOctets`)
+const IF_HC_OUT_OCTETS: &str = "1.3.6.1.2.1.31.1.1.1.10";
+
+/// Credentials an agent is queried with
+#[derive(Clone, PartialEq)]
+enum Security {
+    /// SNMPv2c community
+    Community(String),
+    /// SNMPv3 user, with authentication and privacy protocols and passphrases if required
+    User {
+        name: String,
+        auth: Option<(String, String)>,
+        privacy: Option<(String, String)>,
+    },
+}
+
+/// SNMP agent and the index of the interface to read, e.g. `host=192.168.1.1,index=2`
+#[derive(Clone, PartialEq)]
+pub struct Agent {
+    /// Host of the agent, optionally with a port (e.g. `192.168.1.1:161`)
+    pub host: String,
+    index: u32,
+    security: Security,
+}
+
+impl fmt::Debug for Agent {
+    /// Describes the agent without its credentials, which should never be logged
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Agent {{ host: {}, index: {} }}", self.host, self.index)
+    }
+}
+
+impl FromStr for Agent {
+    type Err = String;
+
+    /// Parse comma-separated settings: `host=<host>` and `index=<ifIndex>`
+    /// (required), then either `community=<community>` (SNMPv2c, `public` by
+    /// default) or `user=<name>` (SNMPv3) with `auth=<passphrase>` and
+    /// `priv=<passphrase>` as required, whose protocols default to SHA and AES
+    /// unless given with `auth-protocol=` and `priv-protocol=`
+    fn from_str(s: &str) -> Result<Agent, String> {
+        let (mut host, mut index, mut community, mut user) = (None, None, None, None);
+        let (mut auth, mut privacy) = (None, None);
+        let (mut auth_protocol, mut priv_protocol) = ("SHA".to_string(), "AES".to_string());
+        for setting in s.split(',') {
+            let (name, value) = setting.split_once('=').ok_or_else(|| {
+                format!("Invalid SNMP setting: {} (expected name=value)", setting)
+            })?;
+            let value = value.to_string();
+            match name {
+                "host" => host = Some(value),
+                "index" => {
+                    index = Some(
+                        value
+                            .parse::<u32>()
+                            .map_err(|_| format!("Invalid SNMP interface index: {}", value))?,
+                    )
+                }
+                "community" => community = Some(value),
+                "user" => user = Some(value),
+                "auth" => auth = Some(value),
+                "priv" => privacy = Some(value),
+                "auth-protocol" => auth_protocol = value,
+                "priv-protocol" => priv_protocol = value,
+                _ => return Err(format!("Unknown SNMP setting: {}", name)),
+            }
+        }
+
+        let host = host.ok_or_else(|| format!("Missing host in SNMP agent: {}", s))?;
+        let index = index.ok_or_else(|| format!("Missing index in SNMP agent: {}", s))?;
+        let security = match (user, community) {
+            (Some(_), Some(_)) => {
+                return Err("An SNMP agent takes either a community or a user, not both".to_string())
+            }
+            (Some(name), None) => {
+                if privacy.is_some() && auth.is_none() {
+                    return Err("SNMPv3 privacy requires authentication (auth=)".to_string());
+                }
+                Security::User {
+                    name,
+                    auth: auth.map(|pass| (auth_protocol, pass)),
+                    privacy: privacy.map(|pass| (priv_protocol, pass)),
+                }
+            }
+            (None, community) => {
+                if auth.is_some() || privacy.is_some() {
+                    return Err("auth= and priv= require an SNMPv3 user (user=)".to_string());
+                }
+                Security::Community(community.unwrap_or_else(|| "public".to_string()))
+            }
+        };
+
+        Ok(Agent {
+            host,
+            index,
+            security,
+        })
+    }
+}
+
+impl Agent {
+    /// Arguments of `snmpget` selecting the protocol version and credentials
+    fn security_args(&self) -> Vec<&str> {
+        match self.security {
+            Security::Community(ref community) => vec!["-v2c", "-c", community],
+            Security::User {
+                ref name,
+                ref auth,
+                ref privacy,
+            } => {
+                let level = match (auth, privacy) {
+                    (None, _) => "noAuthNoPriv",
+                    (Some(_), None) => "authNoPriv",
+                    (Some(_), Some(_)) => "authPriv",
+                };
+                let mut args = vec!["-v3", "-l", level, "-u", name];
+                if let Some((protocol, pass)) = auth {
+                    args.extend(&["-a", protocol, "-A", pass]);
+                }
+                if let Some((protocol, pass)) = privacy {
+                    args.extend(&["-x", protocol, "-X", pass]);
+                }
+                args
+            }
+        }
+    }
+
+    /// Identifies the counters of the agent, standing in for the boot they belong to
+    ///
+    /// The counters of a router do not reset when the host polling it is
+    /// rebooted. A reboot of the router itself lowers its counters, which
+    /// is handled as any other decrease under the counter semantics.
+    pub fn counters_id(&self) -> String {
+        format!("snmp:{}/{}", self.host, self.index)
+    }
+
+    /// Read the received and transmitted byte counters of the interface
+    pub fn get(&self) -> Result<(u64, u64), String> {
+        let output = Command::new("snmpget")
+            .args(self.security_args())
+            // print the values alone, one per line, in the order requested
+            .arg("-Oqv")
+            .arg(&self.host)
+            .arg(format!("{}.{}", IF_HC_IN_OCTETS, self.index))
+            .arg(format!("{}.{}", IF_HC_OUT_OCTETS, self.index))
+            .output()
+            .map_err(|e| format!("failed to run snmpget: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "snmpget exited with {}: {}",
+                output.status,
+                stderr.trim()
+            ));
+        }
+
+        parse_counters(&String::from_utf8_lossy(&output.stdout), self.index)
+    }
+}
+
+/// Parse the values `snmpget -Oqv` printed for the received and transmitted counters
+fn parse_counters(output: &str, index: u32) -> Result<(u64, u64), String> {
+    let values: Vec<&str> = output.lines().map(str::trim).collect();
+    let counter = |name: &str, value: Option<&&str>| match value {
+        Some(value) if value.starts_with("No Such") => Err(format!(
+            "the agent has no {} for interface index {} ({}); 64-bit counters are required",
+            name, index, value
+        )),
+        Some(value) => value
+            .parse::<u64>()
+            .map_err(|_| format!("invalid {} value: {}", name, value)),
+        None => Err(format!("no value for {}", name)),
+    };
+
+    Ok((
+        counter("ifHCInOctets", values.first())?,
+        counter("ifHCOutOctets", values.get(1))?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_are_read_from_the_output() {
+        assert_eq!(parse_counters("5000\n700\n", 2), Ok((5000, 700)));
+        // 64-bit counters carry on past the 4GB at which 32-bit ones wrap
+        let past_wrap = u64::from(u32::MAX) + 1_000_000;
+        assert_eq!(
+            parse_counters(&format!("{}\n{}\n", past_wrap, u64::MAX), 2),
+            Ok((past_wrap, u64::MAX))
+        );
+
+        for (output, error) in &[
+            (
+                "No Such Instance currently exists at this OID\n700\n",
+                "the agent has no ifHCInOctets for interface index 2 \
+                 (No Such Instance currently exists at this OID); 64-bit counters are required",
+            ),
+            ("5000\n-1\n", "invalid ifHCOutOctets value: -1"),
+            ("5000\n", "no value for ifHCOutOctets"),
+        ] {
+            assert_eq!(parse_counters(output, 2).unwrap_err(), *error);
+        }
+    }
+
+    #[test]
+    fn agents_are_parsed() {
+        let agent: Agent = "host=192.168.1.1:161,index=2".parse().unwrap();
+        assert_eq!(agent.host, "192.168.1.1:161");
+        assert_eq!(agent.security_args(), ["-v2c", "-c", "public"]);
+        assert_eq!(agent.counters_id(), "snmp:192.168.1.1:161/2");
+
+        let agent: Agent =
+            "host=router,index=3,user=monitor,auth=secret,priv=hidden,priv-protocol=DES"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            agent.security_args(),
+            [
+                "-v3", "-l", "authPriv", "-u", "monitor", "-a", "SHA", "-A", "secret", "-x", "DES",
+                "-X", "hidden"
+            ]
+        );
+        assert_eq!(format!("{:?}", agent), "Agent { host: router, index: 3 }");
+
+        for invalid in &[
+            "index=2",
+            "host=router",
+            "host=router,index=eth0",
+            "host=router,index=2,community=public,user=monitor",
+            "host=router,index=2,user=monitor,priv=hidden",
+            "host=router,index=2,auth=secret",
+            "host=router,index=2,port=161",
+        ] {
+            assert!(invalid.parse::<Agent>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Per-host usage read from the connection tracking table.
+//!
+//! With connection accounting enabled (`net.netfilter.nf_conntrack_acct=1`),
+//! each tracked connection carries byte counts for its original and reply
+//! directions, as listed in `/proc/net/nf_conntrack` or by
+//! `conntrack -L -o extended`. Only compiled with the `top-talkers` feature.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// A tracked connection and the bytes transferred in each direction
+#[derive(Debug, PartialEq)]
+pub struct Flow {
+    pub src: String,
+    pub dst: String,
+    pub orig_bytes: u64,  // sent from the source to the destination
+    pub reply_bytes: u64, // sent from the destination back to the source
+}
+
+/// End of the connections to group usage by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Grouping {
+    /// Remote end, to which each connection was made
+    Destination,
+    /// Local end, from which each connection was made
+    Source,
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Grouping, String> {
+        match s {
+            "destination" => Ok(Grouping::Destination),
+            "source" => Ok(Grouping::Source),
+            _ => Err(format!("Unknown grouping: {}", s)),
+        }
+    }
+}
+
+/// Parse a connection from a line of the tracking table
+///
+/// The first `src`, `dst` and `bytes` fields belong to the original
+/// direction and the second `bytes` field to the reply direction. Lines
+/// without byte counts (accounting disabled) or addresses yield `None`.
+fn parse_flow(line: &str) -> Option<Flow> {
+    let mut src = None;
+    let mut dst = None;
+    let mut bytes = Vec::with_capacity(2);
+    for field in line.split_whitespace() {
+        match field.split_once('=') {
+            Some(("src", addr)) if src.is_none() => src = Some(addr),
+            Some(("dst", addr)) if dst.is_none() => dst = Some(addr),
+            Some(("bytes", count)) => bytes.push(count.parse().ok()?),
+            _ => (),
+        }
+    }
+
+    Some(Flow {
+        src: src?.to_string(),
+        dst: dst?.to_string(),
+        orig_bytes: *bytes.first()?,
+        reply_bytes: bytes.get(1).copied().unwrap_or(0),
+    })
+}
+
+/// Parse the connections of a tracking table listing
+///
+/// Lines which cannot be parsed are skipped; an error is returned if the
+/// listing holds connections but none of them carry byte counts.
+pub fn parse(listing: &str) -> Result<Vec<Flow>, String> {
+    let lines: Vec<&str> = listing.lines().filter(|l| l.contains("src=")).collect();
+    let flows: Vec<Flow> = lines.iter().filter_map(|line| parse_flow(line)).collect();
+    if flows.is_empty() && !lines.is_empty() {
+        return Err("Connections carry no byte counts; enable accounting with \
+                    `sysctl net.netfilter.nf_conntrack_acct=1`"
+            .to_string());
+    }
+
+    Ok(flows)
+}
+
+/// Total bytes (both directions) per host, largest first, limited to a count
+///
+/// Hosts with equal usage are ordered by address so that output is stable.
+pub fn rank(flows: &[Flow], grouping: Grouping, count: usize) -> Vec<(String, u64)> {
+    let mut totals: HashMap<&str, u64> = HashMap::new();
+    for flow in flows {
+        let host = match grouping {
+            Grouping::Destination => &flow.dst,
+            Grouping::Source => &flow.src,
+        };
+        *totals.entry(host).or_insert(0) += flow.orig_bytes + flow.reply_bytes;
+    }
+
+    let mut ranked: Vec<(String, u64)> = totals
+        .into_iter()
+        .map(|(host, bytes)| (host.to_string(), bytes))
+        .collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(count);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = "\
+ipv4     2 tcp      6 431999 ESTABLISHED src=192.168.1.10 dst=93.184.216.34 sport=51000 dport=443 packets=10 bytes=1200 src=93.184.216.34 dst=192.168.1.10 sport=443 dport=51000 packets=40 bytes=48000 [ASSURED] mark=0 use=1
+ipv4     2 udp      17 29 src=192.168.1.11 dst=1.1.1.1 sport=40000 dport=53 packets=1 bytes=60 src=1.1.1.1 dst=192.168.1.11 sport=53 dport=40000 packets=1 bytes=120 mark=0 use=1
+ipv4     2 tcp      6 300 ESTABLISHED src=192.168.1.11 dst=93.184.216.34 sport=52000 dport=443 packets=5 bytes=800 src=93.184.216.34 dst=192.168.1.11 sport=443 dport=52000 packets=9 bytes=10000 [ASSURED] mark=0 use=1
+ipv4     2 tcp      6 300 ESTABLISHED src=192.168.1.12 dst=10.0.0.1 sport=53000 dport=22 packets=5 bytes=abc src=10.0.0.1 dst=192.168.1.12 sport=22 dport=53000 packets=9 bytes=10 mark=0 use=1
+";
+
+    #[test]
+    fn flows_of_a_listing() {
+        let flows = parse(LISTING).unwrap();
+        // the line with a malformed count is skipped
+        assert_eq!(flows.len(), 3);
+        assert_eq!(
+            flows[0],
+            Flow {
+                src: "192.168.1.10".to_string(),
+                dst: "93.184.216.34".to_string(),
+                orig_bytes: 1200,
+                reply_bytes: 48000,
+            }
+        );
+    }
+
+    #[test]
+    fn listing_without_accounting() {
+        let listing = "ipv4 2 tcp 6 300 ESTABLISHED src=192.168.1.10 dst=10.0.0.1 sport=1 dport=2 \
+                       src=10.0.0.1 dst=192.168.1.10 sport=2 dport=1 mark=0 use=1\n";
+        assert!(parse(listing).is_err());
+        assert_eq!(parse("").unwrap(), []);
+    }
+
+    #[test]
+    fn hosts_ranked_by_usage() {
+        let flows = parse(LISTING).unwrap();
+        assert_eq!(
+            rank(&flows, Grouping::Destination, 5),
+            [
+                ("93.184.216.34".to_string(), 60000),
+                ("1.1.1.1".to_string(), 180)
+            ]
+        );
+        assert_eq!(
+            rank(&flows, Grouping::Source, 5),
+            [
+                ("192.168.1.10".to_string(), 49200),
+                ("192.168.1.11".to_string(), 10980)
+            ]
+        );
+        assert_eq!(rank(&flows, Grouping::Source, 1).len(), 1);
+    }
+
+    #[test]
+    fn ties_are_ordered_by_address() {
+        let flow = |dst: &str| Flow {
+            src: "192.168.1.10".to_string(),
+            dst: dst.to_string(),
+            orig_bytes: 100,
+            reply_bytes: 0,
+        };
+        let hosts: Vec<String> = rank(
+            &[flow("10.0.0.2"), flow("10.0.0.1")],
+            Grouping::Destination,
+            2,
+        )
+        .into_iter()
+        .map(|(host, _)| host)
+        .collect();
+        assert_eq!(hosts, ["10.0.0.1", "10.0.0.2"]);
+    }
+}
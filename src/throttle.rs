@@ -0,0 +1,298 @@
+//! Rate limiting of log lines.
+//!
+//! A daemon stuck in an error loop, or polling a flapping link, can log the
+//! same lines over and over, flooding the journal (which may live on flash).
+//! With a limit, lines are let through from a token bucket holding up to a
+//! minute's worth of lines and refilled at the limit. Lines logged while the
+//! bucket is empty are held back and counted, and the count is logged ahead
+//! of the next line let through, as `last message repeated N times` for the
+//! lines repeating the last line logged and as a number of lines suppressed
+//! for the others.
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+use log::{Level, Log, Metadata, Record};
+
+/// Token bucket of log lines
+#[derive(Debug)]
+struct Bucket {
+    /// Lines which can be logged at once, as many as are let through a minute
+    capacity: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    /// Let through up to a number of lines per minute, starting full
+    fn new(per_minute: u32, now: Instant) -> Bucket {
+        Bucket {
+            capacity: per_minute as f64,
+            tokens: per_minute as f64,
+            refilled: now,
+        }
+    }
+
+    /// Take a token for a line, unless the bucket is empty
+    fn take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.capacity / 60.0).min(self.capacity);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Line logged, as compared to those which follow it
+#[derive(Debug, PartialEq)]
+struct Line {
+    level: Level,
+    target: String,
+    module_path: Option<String>,
+    message: String,
+}
+
+/// Lines held back since the last line let through
+#[derive(Debug, Default)]
+struct Held {
+    /// Lines repeating the last line logged
+    repeated: u64,
+    /// Lines differing from it
+    other: u64,
+}
+
+#[derive(Debug)]
+struct State {
+    bucket: Bucket,
+    last: Option<Line>,
+    held: Held,
+}
+
+/// Logger letting through a limited number of lines per minute to another
+pub struct Throttled<L> {
+    inner: L,
+    state: Mutex<State>,
+}
+
+impl<L: Log> Throttled<L> {
+    pub fn new(inner: L, per_minute: u32) -> Throttled<L> {
+        Throttled::starting(inner, per_minute, Instant::now())
+    }
+
+    /// Let through a number of lines per minute, with the bucket full at a time
+    fn starting(inner: L, per_minute: u32, now: Instant) -> Throttled<L> {
+        Throttled {
+            inner,
+            state: Mutex::new(State {
+                bucket: Bucket::new(per_minute, now),
+                last: None,
+                held: Held::default(),
+            }),
+        }
+    }
+
+    /// Log the count of the lines held back since the last line let through, if any
+    ///
+    /// The count is logged at the level and from the module of the last line.
+    fn summarize(&self, state: &mut State) {
+        let held = std::mem::take(&mut state.held);
+        let last = match state.last {
+            Some(ref last) => last,
+            None => return,
+        };
+        let summary = match (held.repeated, held.other) {
+            (0, 0) => return,
+            (repeated, 0) => format!("last message repeated {} times", repeated),
+            (0, other) => format!("{} log lines suppressed by --max-log-rate", other),
+            (repeated, other) => format!(
+                "last message repeated {} times; {} other log lines suppressed by --max-log-rate",
+                repeated, other
+            ),
+        };
+        self.inner.log(
+            &Record::builder()
+                .level(last.level)
+                .target(&last.target)
+                .module_path(last.module_path.as_deref())
+                .args(format_args!("{}", summary))
+                .build(),
+        );
+    }
+}
+
+impl<L: Log> Log for Throttled<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.log_at(record, Instant::now());
+    }
+
+    /// Log the count of any lines held back, then flush the other logger
+    fn flush(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            self.summarize(&mut state);
+        }
+        self.inner.flush();
+    }
+}
+
+impl<L: Log> Throttled<L> {
+    /// Log a line at a time, unless the bucket is empty
+    fn log_at(&self, record: &Record, now: Instant) {
+        if !self.inner.enabled(record.metadata()) {
+            return;
+        }
+        let line = Line {
+            level: record.level(),
+            target: record.target().to_string(),
+            module_path: record.module_path().map(str::to_string),
+            message: record.args().to_string(),
+        };
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if !state.bucket.take(now) {
+            if state.last.as_ref() == Some(&line) {
+                state.held.repeated += 1;
+            } else {
+                state.held.other += 1;
+            }
+            return;
+        }
+        self.summarize(&mut state);
+        self.inner.log(record);
+        state.last = Some(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Logger keeping the lines logged to it, as `<level> <message>`
+    #[derive(Clone, Default)]
+    struct Lines(Arc<Mutex<Vec<String>>>);
+
+    impl Lines {
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl Log for Lines {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Info
+        }
+
+        fn log(&self, record: &Record) {
+            let line = format!("{} {}", record.level(), record.args());
+            self.0.lock().unwrap().push(line);
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn log(logger: &Throttled<Lines>, level: Level, message: &str, now: Instant) {
+        logger.log_at(
+            &Record::builder()
+                .level(level)
+                .target("peach_monitor")
+                .args(format_args!("{}", message))
+                .build(),
+            now,
+        );
+    }
+
+    #[test]
+    fn bucket_refills_at_the_rate() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(2, start);
+        assert!(bucket.take(start));
+        assert!(bucket.take(start));
+        assert!(!bucket.take(start));
+        // a line every 30 seconds
+        assert!(!bucket.take(start + Duration::from_secs(29)));
+        assert!(bucket.take(start + Duration::from_secs(30)));
+        assert!(!bucket.take(start + Duration::from_secs(30)));
+        // an idle bucket only fills up to a minute's worth
+        let later = start + Duration::from_secs(3600);
+        assert!(bucket.take(later) && bucket.take(later));
+        assert!(!bucket.take(later));
+    }
+
+    #[test]
+    fn repeated_lines_beyond_the_rate_are_coalesced() {
+        let (lines, start) = (Lines::default(), Instant::now());
+        let logger = Throttled::starting(lines.clone(), 2, start);
+        for _ in 0..7 {
+            log(&logger, Level::Error, "Failed to read counters", start);
+        }
+        assert_eq!(lines.take(), ["ERROR Failed to read counters"; 2]);
+
+        // the count is logged ahead of the next line let through
+        log(
+            &logger,
+            Level::Warn,
+            "Link is down",
+            start + Duration::from_secs(30),
+        );
+        assert_eq!(
+            lines.take(),
+            ["ERROR last message repeated 5 times", "WARN Link is down"]
+        );
+        // nothing was held back since
+        logger.flush();
+        assert!(lines.take().is_empty());
+    }
+
+    #[test]
+    fn other_lines_are_counted_apart() {
+        let (lines, start) = (Lines::default(), Instant::now());
+        let logger = Throttled::starting(lines.clone(), 1, start);
+        log(&logger, Level::Error, "Poll failed", start);
+        log(&logger, Level::Error, "Poll failed", start);
+        log(&logger, Level::Warn, "Link is down", start);
+        log(&logger, Level::Error, "Poll failed", start);
+        // disabled levels are not counted
+        log(&logger, Level::Debug, "Sleeping", start);
+        assert_eq!(lines.take(), ["ERROR Poll failed"]);
+
+        logger.flush();
+        assert_eq!(
+            lines.take(),
+            ["ERROR last message repeated 2 times; 1 other log lines suppressed by --max-log-rate"]
+        );
+
+        log(
+            &logger,
+            Level::Warn,
+            "Link is down",
+            start + Duration::from_secs(60),
+        );
+        log(
+            &logger,
+            Level::Error,
+            "Poll failed",
+            start + Duration::from_secs(60),
+        );
+        logger.flush();
+        assert_eq!(
+            lines.take(),
+            [
+                "WARN Link is down",
+                "WARN 1 log lines suppressed by --max-log-rate"
+            ]
+        );
+    }
+}
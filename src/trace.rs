@@ -0,0 +1,139 @@
+//! Traces of the raw counters read by each save.
+//!
+//! A trace holds one JSON object per line with the time of a reading (unix
+//! seconds), the interface, the boot it was taken in and the raw counters,
+//! e.g. `{"time":1700000000,"iface":"wlan0","boot":"...","rx":1234,"tx":567}`.
+//! Lines are appended as readings are taken, so that a trace can be replayed
+//! through the accounting and alerting of a scratch data store later.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::BoxError;
+use crate::traffic::Traffic;
+
+/// Counters read from an interface at a point in time
+#[derive(Debug)]
+pub struct Reading {
+    pub time: u64, // unix seconds
+    pub iface: String,
+    pub boot: Option<String>,
+    pub traffic: Traffic,
+}
+
+impl Reading {
+    /// Line of a trace holding the reading
+    fn to_line(&self) -> String {
+        json!({
+            "time": self.time,
+            "iface": self.iface,
+            "boot": self.boot,
+            "rx": self.traffic.rx,
+            "tx": self.traffic.tx,
+        })
+        .to_string()
+    }
+
+    /// Parse a reading from a line of a trace
+    fn parse(line: &str) -> Result<Reading, String> {
+        let value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+        let uint = |key: &str| {
+            value[key]
+                .as_u64()
+                .ok_or_else(|| format!("missing or invalid {}", key))
+        };
+
+        Ok(Reading {
+            time: uint("time")?,
+            iface: value["iface"]
+                .as_str()
+                .ok_or("missing or invalid iface")?
+                .to_string(),
+            boot: value["boot"].as_str().map(str::to_string),
+            traffic: Traffic {
+                rx: uint("rx")?,
+                tx: uint("tx")?,
+            },
+        })
+    }
+}
+
+/// Append a reading to a trace file, creating it if needed
+pub fn append(path: &Path, reading: &Reading) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", reading.to_line())
+}
+
+/// Load the readings of a trace file, in the order they were taken
+///
+/// Blank lines are skipped; any other line which cannot be parsed fails the
+/// load with its line number.
+pub fn load(path: &Path) -> Result<Vec<Reading>, BoxError> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            Reading::parse(line).map_err(|e| {
+                format!("{}:{}: invalid reading: {}", path.display(), index + 1, e).into()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::scratch_dir;
+
+    #[test]
+    fn readings_round_trip() {
+        let path = scratch_dir("trace").join("trace.jsonl");
+        let reading = |time, boot: Option<&str>, rx| Reading {
+            time,
+            iface: "eth9".to_string(),
+            boot: boot.map(str::to_string),
+            traffic: Traffic { rx, tx: rx / 10 },
+        };
+        append(&path, &reading(1_760_000_000, Some("b1"), 1000)).unwrap();
+        append(&path, &reading(1_760_000_060, None, 5000)).unwrap();
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            (
+                loaded[0].time,
+                loaded[0].boot.as_deref(),
+                loaded[0].traffic.rx
+            ),
+            (1_760_000_000, Some("b1"), 1000)
+        );
+        assert_eq!(
+            (loaded[1].boot.as_deref(), loaded[1].traffic.tx),
+            (None, 500)
+        );
+        assert_eq!(loaded[1].iface, "eth9");
+    }
+
+    #[test]
+    fn invalid_line_is_reported_with_its_number() {
+        let path = scratch_dir("trace-invalid").join("trace.jsonl");
+        fs::write(
+            &path,
+            "{\"time\":1,\"iface\":\"eth9\",\"rx\":1,\"tx\":1}\n\n{\"time\":2,\"iface\":\"eth9\",\"rx\":1}\n",
+        )
+        .unwrap();
+        let e = load(&path).unwrap_err().to_string();
+        assert_eq!(
+            e,
+            format!(
+                "{}:3: invalid reading: missing or invalid tx",
+                path.display()
+            )
+        );
+    }
+}
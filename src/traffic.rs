@@ -0,0 +1,284 @@
+//! Sources of network traffic counters.
+
+use std::fs;
+use std::process::Command;
+use std::str::FromStr;
+
+use log::debug;
+
+use crate::error::NetworkError;
+use crate::nft::Counters;
+use crate::snmp::Agent;
+
+/// Kernel interface statistics
+const NET_DEV: &str = "/proc/net/dev";
+
+/// Network traffic counters (bytes)
+#[derive(Debug)]
+pub struct Traffic {
+    pub rx: u64, // received (bytes)
+    pub tx: u64, // transmitted (bytes)
+}
+
+/// Where network traffic counters are read from
+#[derive(Debug)]
+pub enum Source {
+    /// Kernel interface statistics (`/proc/net/dev`)
+    Proc,
+    /// A user-supplied shell command which prints the counters
+    Command(String),
+    /// The 64-bit interface counters of an SNMP agent (e.g. a separate router)
+    Snmp(Agent),
+    /// The named counters of traffic classes in an nftables table
+    Nft(Counters),
+}
+
+impl Traffic {
+    /// Retrieve latest statistics for traffic
+    pub fn get(iface: &str, source: &Source) -> Result<Traffic, NetworkError> {
+        match source {
+            Source::Proc => Traffic::from_proc(iface),
+            Source::Command(command) => Traffic::from_command(iface, command),
+            Source::Snmp(agent) => {
+                let (rx, tx) = agent.get().map_err(|reason| NetworkError::Snmp {
+                    host: agent.host.clone(),
+                    reason,
+                })?;
+                Ok(Traffic { rx, tx })
+            }
+            Source::Nft(counters) => {
+                let (rx, tx) = counters.get().map_err(|reason| NetworkError::Nft {
+                    table: counters.table.clone(),
+                    reason,
+                })?;
+                Ok(Traffic { rx, tx })
+            }
+        }
+    }
+
+    fn from_proc(iface: &str) -> Result<Traffic, NetworkError> {
+        let net_dev = fs::read_to_string(NET_DEV).map_err(NetworkError::Read)?;
+        Traffic::from_net_dev(iface, &net_dev)
+    }
+
+    /// Parse the counters of an interface from the contents of `/proc/net/dev`
+    ///
+    /// An interface may be listed more than once (e.g. for aliases), in which
+    /// case the counters of all of its entries are summed.
+    fn from_net_dev(iface: &str, net_dev: &str) -> Result<Traffic, NetworkError> {
+        let malformed = |line: &str| NetworkError::Malformed {
+            line: line.to_string(),
+        };
+
+        let mut traffic: Option<Traffic> = None;
+        let mut entries = 0;
+        // the first two lines are headers
+        for line in net_dev.lines().skip(2) {
+            let (name, counters) = line.split_once(':').ok_or_else(|| malformed(line))?;
+            if name.trim() != iface {
+                continue;
+            }
+
+            // received bytes are the first counter and transmitted bytes the ninth
+            let counters: Vec<&str> = counters.split_whitespace().collect();
+            let counter = |i: usize| {
+                counters
+                    .get(i)
+                    .and_then(|counter| u64::from_str(counter).ok())
+                    .ok_or_else(|| malformed(line))
+            };
+            let (rx, tx) = (counter(0)?, counter(8)?);
+
+            let total = traffic.get_or_insert(Traffic { rx: 0, tx: 0 });
+            total.rx = total.rx.saturating_add(rx);
+            total.tx = total.tx.saturating_add(tx);
+            entries += 1;
+        }
+
+        if entries > 1 {
+            debug!("{}: summed {} entries in {}", iface, entries, NET_DEV);
+        }
+        traffic.ok_or_else(|| NetworkError::Interface {
+            iface: iface.to_string(),
+        })
+    }
+
+    /// Run a stats command and parse the counters it prints
+    ///
+    /// The command is run with `sh -c` and the interface name is passed in
+    /// the `PM_IFACE` environment variable.
+    fn from_command(iface: &str, command: &str) -> Result<Traffic, NetworkError> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("PM_IFACE", iface)
+            .output()
+            .map_err(|source| NetworkError::Command {
+                command: command.to_string(),
+                source,
+            })?;
+
+        if !output.status.success() {
+            return Err(NetworkError::Output {
+                command: command.to_string(),
+                reason: format!("exited with {}", output.status),
+            });
+        }
+
+        String::from_utf8(output.stdout)
+            .map_err(|_| "output is not valid UTF-8".to_string())
+            .and_then(|stdout| parse_stats(&stdout))
+            .map_err(|reason| NetworkError::Output {
+                command: command.to_string(),
+                reason,
+            })
+    }
+}
+
+/// Parse the counters printed by a stats command
+///
+/// Two forms are accepted: the received and transmitted byte counts as two
+/// integers (`1234 5678`), or `rx=` and `tx=` pairs separated by whitespace
+/// or newlines (`rx=1234 tx=5678`), in which case other keys are ignored.
+/// Interfaces which only count one direction may print a single pair; the
+/// missing counter then reads as zero.
+fn parse_stats(output: &str) -> Result<Traffic, String> {
+    let tokens: Vec<&str> = output.split_whitespace().collect();
+    if tokens.is_empty() {
+        return Err("no output".to_string());
+    }
+
+    let counter = |name: &str, value: &str| {
+        u64::from_str(value).map_err(|_| format!("invalid {} counter: {}", name, value))
+    };
+
+    if tokens.iter().all(|token| !token.contains('=')) {
+        return match tokens[..] {
+            [rx, tx] => Ok(Traffic {
+                rx: counter("rx", rx)?,
+                tx: counter("tx", tx)?,
+            }),
+            _ => Err(format!("expected two counters, found {}", tokens.len())),
+        };
+    }
+
+    let (mut rx, mut tx) = (None, None);
+    for token in tokens {
+        match token.split_once('=') {
+            Some(("rx", value)) => rx = Some(counter("rx", value)?),
+            Some(("tx", value)) => tx = Some(counter("tx", value)?),
+            Some(_) => (),
+            None => return Err(format!("expected key=value, found {}", token)),
+        }
+    }
+
+    match (rx, tx) {
+        (None, None) => Err("missing rx and tx counters".to_string()),
+        (rx, tx) => Ok(Traffic {
+            rx: rx.unwrap_or(0),
+            tx: tx.unwrap_or(0),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_integers() {
+        let traffic = parse_stats("1234567 89012\n").unwrap();
+        assert_eq!((traffic.rx, traffic.tx), (1234567, 89012));
+    }
+
+    #[test]
+    fn key_value_pairs() {
+        let traffic = parse_stats("iface=wan\nrx=1234567\ntx=89012\n").unwrap();
+        assert_eq!((traffic.rx, traffic.tx), (1234567, 89012));
+        let traffic = parse_stats("tx=5 rx=7").unwrap();
+        assert_eq!((traffic.rx, traffic.tx), (7, 5));
+    }
+
+    #[test]
+    fn single_direction() {
+        let traffic = parse_stats("rx=1000").unwrap();
+        assert_eq!((traffic.rx, traffic.tx), (1000, 0));
+    }
+
+    #[test]
+    fn malformed_output() {
+        assert_eq!(parse_stats("").unwrap_err(), "no output");
+        assert_eq!(parse_stats(" \n").unwrap_err(), "no output");
+        assert_eq!(
+            parse_stats("1234").unwrap_err(),
+            "expected two counters, found 1"
+        );
+        assert_eq!(
+            parse_stats("1 2 3").unwrap_err(),
+            "expected two counters, found 3"
+        );
+        assert_eq!(
+            parse_stats("12ab 5").unwrap_err(),
+            "invalid rx counter: 12ab"
+        );
+        assert_eq!(
+            parse_stats("rx=1 tx=-5").unwrap_err(),
+            "invalid tx counter: -5"
+        );
+        assert_eq!(
+            parse_stats("rx=1 oops").unwrap_err(),
+            "expected key=value, found oops"
+        );
+        assert_eq!(
+            parse_stats("iface=wan").unwrap_err(),
+            "missing rx and tx counters"
+        );
+    }
+
+    #[test]
+    fn command_output() {
+        let traffic = Traffic::get("wan", &Source::Command("echo rx=$PM_IFACE".into()));
+        assert!(matches!(traffic, Err(NetworkError::Output { .. })));
+        let traffic = Traffic::get("wan", &Source::Command("echo 10 20".into())).unwrap();
+        assert_eq!((traffic.rx, traffic.tx), (10, 20));
+        let failed = Traffic::get("wan", &Source::Command("exit 3".into()));
+        assert!(matches!(failed, Err(NetworkError::Output { .. })));
+    }
+
+    const NET_DEV_HEADERS: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+";
+
+    #[test]
+    fn duplicate_entries_are_summed() {
+        let net_dev = format!(
+            "{}    lo: 500 5 0 0 0 0 0 0 500 5 0 0 0 0 0 0\n  \
+             eth0: 1000 10 0 0 0 0 0 0 200 2 0 0 0 0 0 0\n  \
+             eth0: 3000 30 0 0 0 0 0 0 400 4 0 0 0 0 0 0\n",
+            NET_DEV_HEADERS
+        );
+        let traffic = Traffic::from_net_dev("eth0", &net_dev).unwrap();
+        assert_eq!((traffic.rx, traffic.tx), (4000, 600));
+        let traffic = Traffic::from_net_dev("lo", &net_dev).unwrap();
+        assert_eq!((traffic.rx, traffic.tx), (500, 500));
+    }
+
+    #[test]
+    fn missing_interface_and_malformed_entry() {
+        let net_dev = format!(
+            "{}  eth0: 1000 10 0 0 0 0 0 0 200 2 0 0 0 0 0 0\n",
+            NET_DEV_HEADERS
+        );
+        match Traffic::from_net_dev("wlan0", &net_dev) {
+            Err(NetworkError::Interface { iface }) => assert_eq!(iface, "wlan0"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let net_dev = format!("{}  eth0: 1000 10 0\n", NET_DEV_HEADERS);
+        match Traffic::from_net_dev("eth0", &net_dev) {
+            Err(NetworkError::Malformed { line }) => assert_eq!(line, "  eth0: 1000 10 0"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Parsing and formatting of human-readable data sizes.
+
+use std::fmt;
+
+/// Size units and their multipliers (bytes), largest first
+const UNITS: [(&str, u64); 5] = [
+    ("TB", 1024 * 1024 * 1024 * 1024),
+    ("GB", 1024 * 1024 * 1024),
+    ("MB", 1024 * 1024),
+    ("KB", 1024),
+    ("B", 1),
+];
+
+/// Error returned when a size string cannot be parsed
+#[derive(Debug)]
+pub struct ParseSizeError(String);
+
+impl fmt::Display for ParseSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid size: {} (expected a number with an optional B, KB, MB, GB or TB suffix)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseSizeError {}
+
+/// Parse a human-readable size (e.g. `50GB`, `1.5 TB`, `2048`) into bytes
+///
+/// Units are binary multiples (1 GB = 1024 MB) to match the megabyte
+/// thresholds used by the data store. A number without a suffix is taken
+/// to be in bytes.
+pub fn parse_size(input: &str) -> Result<u64, ParseSizeError> {
+    let err = || ParseSizeError(input.to_string());
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let multiplier = parse_unit(unit).map_err(|_| err())?;
+
+    let value: f64 = number.parse().map_err(|_| err())?;
+    let bytes = value * multiplier as f64;
+    if !bytes.is_finite() || bytes < 0.0 || bytes > u64::MAX as f64 {
+        return Err(err());
+    }
+
+    Ok(bytes.round() as u64)
+}
+
+/// Parse a size unit (e.g. `MB` or `GiB`) into its multiplier (bytes)
+pub fn parse_unit(input: &str) -> Result<u64, ParseSizeError> {
+    let unit = input.trim().to_ascii_uppercase();
+    let unit = unit.trim_end_matches("IB").trim_end_matches('B');
+    UNITS
+        .iter()
+        .find(|(name, _)| name.trim_end_matches('B') == unit)
+        .map(|(_, multiplier)| *multiplier)
+        .ok_or_else(|| ParseSizeError(input.to_string()))
+}
+
+/// Round a byte value to the nearest multiple of a unit (bytes)
+pub fn round_bytes(bytes: u64, unit: u64) -> u64 {
+    let rounded = (bytes / unit) * unit;
+    if bytes - rounded >= unit / 2 + unit % 2 {
+        rounded.saturating_add(unit)
+    } else {
+        rounded
+    }
+}
+
+/// Largest unit a byte value fills, and its multiplier (bytes)
+///
+/// Values below 1KB, including zero, are given in bytes.
+fn magnitude(bytes: u64) -> (&'static str, u64) {
+    *UNITS
+        .iter()
+        .find(|(_, multiplier)| bytes >= *multiplier)
+        .unwrap_or(&UNITS[UNITS.len() - 1])
+}
+
+/// Format a byte value in a unit, to one decimal place unless it is a whole number of the unit
+fn format_in(bytes: u64, (unit, multiplier): (&str, u64)) -> String {
+    if bytes.is_multiple_of(multiplier) {
+        format!("{}{}", bytes / multiplier, unit)
+    } else {
+        format!("{:.1}{}", bytes as f64 / multiplier as f64, unit)
+    }
+}
+
+/// Format a byte value using the largest unit it fills (e.g. `23.4GB`)
+pub fn format_bytes(bytes: u64) -> String {
+    format_in(bytes, magnitude(bytes))
+}
+
+/// Format a transfer rate (bytes per second) using the largest unit it fills (e.g. `1.5MB/s`)
+pub fn format_rate(bytes_per_sec: u64) -> String {
+    format!("{}/s", format_in(bytes_per_sec, magnitude(bytes_per_sec)))
+}
+
+/// Group the digits of a number in threes with commas (e.g. `1,234,567`)
+pub fn group_digits(val: u64) -> String {
+    let digits = val.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Width (characters) of the bar drawn by `usage_gauge`, excluding brackets
+const GAUGE_WIDTH: usize = 10;
+
+/// Percentage of a cap used, rounded down and clamped at 100
+///
+/// An empty cap counts as fully used as soon as any data has been used.
+pub fn percent_used(used: u64, cap: u64) -> u64 {
+    if cap == 0 {
+        return if used > 0 { 100 } else { 0 };
+    }
+    (u128::from(used) * 100 / u128::from(cap)).min(100) as u64
+}
+
+/// Draw the share of a cap used as a bar and percentage (e.g. `[####------] 42%`)
+pub fn usage_gauge(used: u64, cap: u64) -> String {
+    let percent = percent_used(used, cap) as usize;
+    let filled = percent * GAUGE_WIDTH / 100;
+    format!(
+        "[{}{}] {}%",
+        "#".repeat(filled),
+        "-".repeat(GAUGE_WIDTH - filled),
+        percent
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_with_units() {
+        assert_eq!(parse_size("2048").unwrap(), 2048);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("50GB").unwrap(), 50 * 1024 * 1024 * 1024);
+        assert_eq!(
+            parse_size("1.5 TB").unwrap(),
+            3 * 1024 * 1024 * 1024 * 1024 / 2
+        );
+        assert_eq!(parse_size("10mb").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("4GiB").unwrap(), 4 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("2k").unwrap(), 2048);
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        for input in &["", "GB", "-5GB", "5XB", "1.2.3MB", "abc"] {
+            assert!(parse_size(input).is_err(), "{:?} parsed", input);
+        }
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(parse_unit("MB").unwrap(), 1024 * 1024);
+        assert_eq!(parse_unit("gib").unwrap(), 1024 * 1024 * 1024);
+        assert_eq!(parse_unit("B").unwrap(), 1);
+        assert!(parse_unit("PB").is_err());
+    }
+
+    #[test]
+    fn rounding_to_the_nearest_unit() {
+        const MB: u64 = 1024 * 1024;
+        assert_eq!(round_bytes(0, MB), 0);
+        assert_eq!(round_bytes(MB / 2 - 1, MB), 0);
+        assert_eq!(round_bytes(MB / 2, MB), MB);
+        assert_eq!(round_bytes(3 * MB + 10, MB), 3 * MB);
+        assert_eq!(round_bytes(3 * MB - 10, MB), 3 * MB);
+        assert_eq!(round_bytes(u64::MAX, MB), u64::MAX);
+        // halfway between, for an odd unit
+        assert_eq!(round_bytes(2, 5), 0);
+        assert_eq!(round_bytes(3, 5), 5);
+    }
+
+    #[test]
+    fn digit_grouping() {
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(7), "7");
+        assert_eq!(group_digits(42), "42");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1000), "1,000");
+        assert_eq!(group_digits(123_456), "123,456");
+        assert_eq!(group_digits(1_234_567_890), "1,234,567,890");
+        assert_eq!(group_digits(u64::MAX), "18,446,744,073,709,551,615");
+    }
+
+    #[test]
+    fn percentages_used() {
+        assert_eq!(percent_used(0, 1000), 0);
+        assert_eq!(percent_used(500, 1000), 50);
+        assert_eq!(percent_used(999, 1000), 99);
+        assert_eq!(percent_used(1000, 1000), 100);
+        assert_eq!(percent_used(2500, 1000), 100);
+        assert_eq!(percent_used(u64::MAX, u64::MAX - 1), 100);
+        assert_eq!(percent_used(0, 0), 0);
+        assert_eq!(percent_used(1, 0), 100);
+    }
+
+    #[test]
+    fn gauges() {
+        assert_eq!(usage_gauge(0, 1000), "[----------] 0%");
+        assert_eq!(usage_gauge(420, 1000), "[####------] 42%");
+        assert_eq!(usage_gauge(500, 1000), "[#####-----] 50%");
+        assert_eq!(usage_gauge(1000, 1000), "[##########] 100%");
+        assert_eq!(usage_gauge(2500, 1000), "[##########] 100%");
+    }
+
+    #[test]
+    fn rates_in_the_largest_unit_they_fill() {
+        for &(rate, formatted) in &[
+            (0, "0B/s"),
+            (1023, "1023B/s"),
+            (1024, "1KB/s"),
+            (1536, "1.5KB/s"),
+            (12 * 1024 * 1024 + 300 * 1024, "12.3MB/s"),
+            (2 * 1024 * 1024 * 1024, "2GB/s"),
+        ] {
+            assert_eq!(format_rate(rate), formatted);
+        }
+        // rates and sizes share their units
+        assert_eq!(format_rate(1536), format!("{}/s", format_bytes(1536)));
+    }
+}
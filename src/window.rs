@@ -0,0 +1,180 @@
+//! Usage samples for evaluating thresholds over a trailing window.
+//!
+//! Each save records a sample of the usage counted since the previous save.
+//! Samples are kept in `net/samples` for as long as the window covers them,
+//! along with the time sampling began so that a window which reaches back
+//! further than the samples can be recognised.
+
+use std::time::Duration;
+
+use nest::{Store, Value};
+
+use crate::error::NestError;
+use crate::keys::{NET, SAMPLES};
+
+/// Key of the time sampling began (unix seconds)
+const SINCE: &str = "since";
+/// Key of the list of samples
+const LIST: &str = "list";
+
+/// Usage counted at a save (bytes)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub time: u64, // unix seconds
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Recorded samples and the time sampling began (unix seconds)
+#[derive(Debug, Default)]
+pub struct Samples {
+    pub since: Option<u64>,
+    pub list: Vec<Sample>,
+}
+
+/// Usage summed over a trailing window (bytes)
+#[derive(Debug, PartialEq)]
+pub struct WindowTotal {
+    pub rx: u64,
+    pub tx: u64,
+    /// Whether the samples cover the whole window
+    pub complete: bool,
+}
+
+impl Samples {
+    /// Retrieve the recorded samples from the data store
+    pub fn load(store: &Store) -> Samples {
+        let since = match store.get(&[NET, SAMPLES, SINCE]) {
+            Ok(Value::Uint(since)) => Some(since),
+            _ => None,
+        };
+        let list = match store.get(&[NET, SAMPLES, LIST]) {
+            Ok(Value::Array(list)) => list
+                .iter()
+                .filter_map(|sample| match sample {
+                    Value::Array(fields) => match fields[..] {
+                        [Value::Uint(time), Value::Uint(rx), Value::Uint(tx)] => {
+                            Some(Sample { time, rx, tx })
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Samples { since, list }
+    }
+
+    /// Add a sample, dropping those which have left the window
+    pub fn record(&mut self, sample: Sample, window: Duration) {
+        self.since.get_or_insert(sample.time);
+        let start = sample.time.saturating_sub(window.as_secs());
+        self.list.retain(|s| s.time > start);
+        self.list.push(sample);
+    }
+
+    /// Write the samples to the data store
+    pub fn save(&self, store: &Store) -> Result<(), NestError> {
+        let list = self
+            .list
+            .iter()
+            .map(|s| {
+                Value::Array(vec![
+                    Value::Uint(s.time),
+                    Value::Uint(s.rx),
+                    Value::Uint(s.tx),
+                ])
+            })
+            .collect();
+        store.set(&[NET, SAMPLES, LIST], &Value::Array(list))?;
+        if let Some(since) = self.since {
+            store.set(&[NET, SAMPLES, SINCE], &Value::Uint(since))?;
+        }
+
+        Ok(())
+    }
+
+    /// Sum the usage of the samples within the window ending at `now` (unix seconds)
+    ///
+    /// When sampling began partway through the window the total is
+    /// incomplete, and only a lower bound on the actual usage.
+    pub fn total(&self, now: u64, window: Duration) -> WindowTotal {
+        let start = now.saturating_sub(window.as_secs());
+        let (rx, tx) = self
+            .list
+            .iter()
+            .filter(|s| s.time > start && s.time <= now)
+            .fold((0, 0), |(rx, tx), s| (rx + s.rx, tx + s.tx));
+
+        WindowTotal {
+            rx,
+            tx,
+            complete: self.since.is_some_and(|since| since <= start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::scratch_store;
+
+    const HOUR: u64 = 60 * 60;
+
+    /// Samples of 100 bytes received and 10 transmitted every hour over two days
+    fn hourly(window: Duration) -> Samples {
+        let mut samples = Samples::default();
+        for hour in 1..=48 {
+            let sample = Sample {
+                time: hour * HOUR,
+                rx: 100,
+                tx: 10,
+            };
+            samples.record(sample, window);
+        }
+        samples
+    }
+
+    #[test]
+    fn rolling_sum() {
+        let day = Duration::from_secs(24 * HOUR);
+        let samples = hourly(day);
+        // samples older than the window are dropped as samples are recorded
+        assert_eq!(samples.list.len(), 24);
+        assert_eq!(
+            samples.total(48 * HOUR, day),
+            WindowTotal {
+                rx: 2400,
+                tx: 240,
+                complete: true,
+            }
+        );
+        // a sample at the start of the window is outside of it
+        let six = Duration::from_secs(6 * HOUR);
+        assert_eq!(samples.total(30 * HOUR, six).rx, 600);
+    }
+
+    #[test]
+    fn incomplete_until_sampling_covers_the_window() {
+        let week = Duration::from_secs(7 * 24 * HOUR);
+        let samples = hourly(week);
+        let total = samples.total(48 * HOUR, week);
+        assert_eq!((total.rx, total.tx), (4800, 480));
+        assert!(!total.complete);
+    }
+
+    #[test]
+    fn round_trip() {
+        let store = scratch_store("window-samples");
+        let day = Duration::from_secs(24 * HOUR);
+        let samples = hourly(day);
+        samples.save(&store).unwrap();
+
+        let loaded = Samples::load(&store);
+        assert_eq!(loaded.since, Some(HOUR));
+        assert_eq!(loaded.list, samples.list);
+    }
+}